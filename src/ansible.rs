@@ -0,0 +1,67 @@
+use crate::Args;
+use serde_json::json;
+use std::io;
+
+/// Prints the outcome of the run to `stdout` as the JSON result of an Ansible module, so that a
+/// playbook can run ranch as one: 'changed' if any package's target changed, or would have with
+/// '--check', and 'failed' with 'msg' saying why if the run stopped with `error`. 'results' has
+/// what happened to each package.
+pub fn print(args: &Args, error: Option<&str>, stdout: &mut dyn io::Write) {
+    let outcomes = args
+        .outcomes
+        .lock()
+        .map(|outcomes| outcomes.clone())
+        .unwrap_or_default();
+    let changed = outcomes.iter().any(|outcome| outcome.summary.changed());
+    let msg = match error {
+        Some(error) => error.to_owned(),
+        None => {
+            let changed = outcomes.iter().filter(|o| o.summary.changed()).count();
+            format!("{} of {} packages changed", changed, outcomes.len())
+        }
+    };
+    let result = json!({
+        "changed": changed,
+        "failed": error.is_some(),
+        "msg": msg,
+        "results": outcomes,
+    });
+    _ = writeln!(stdout, "{}", result);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply::{self, Summary};
+    use clap::Parser;
+    use std::path::PathBuf;
+    use std::time::Instant;
+
+    #[test]
+    fn test_print() {
+        let args = Args::parse_from(["ranch", "--format", "ansible", "home"]);
+        let summary = Summary {
+            created: vec![PathBuf::from("/home/alice/.vimrc")],
+            ..Summary::default()
+        };
+        let started = Instant::now();
+        apply::record(&args, "link", "vim", &[], started, &summary, None);
+        let failed = Summary::default();
+        apply::record(&args, "link", "zsh", &[], started, &failed, Some("boom"));
+        let mut stdout = Vec::new();
+        print(&args, Some("boom"), &mut stdout);
+        let result: serde_json::Value = serde_json::from_slice(&stdout).unwrap();
+        assert_eq!(result["changed"], true);
+        assert_eq!(result["failed"], true);
+        assert_eq!(result["msg"], "boom");
+        assert_eq!(result["results"][0]["package"], "vim");
+        assert_eq!(result["results"][0]["created"][0], "/home/alice/.vimrc");
+        assert_eq!(result["results"][1]["error"], "boom");
+
+        let mut stdout = Vec::new();
+        print(&Args::parse_from(["ranch", "home"]), None, &mut stdout);
+        let result: serde_json::Value = serde_json::from_slice(&stdout).unwrap();
+        assert_eq!(result["changed"], false);
+        assert_eq!(result["msg"], "0 of 0 packages changed");
+    }
+}
@@ -0,0 +1,1357 @@
+use crate::config::Config;
+use crate::diagnostic::{self, Diagnostic};
+use crate::diff;
+use crate::elevate;
+use crate::filesystem::Kind;
+use crate::i18n::tr;
+use crate::merge;
+use crate::paths;
+use crate::plan::{self, Action};
+use crate::state::State;
+use crate::template;
+use crate::users;
+use crate::wsl;
+use crate::{
+    Args, Backend, ConflictResolution, LinkMode, OutputFormat, TypeConflict, XdevFallback,
+    LV_DEBUG, LV_INFO, LV_WARN,
+};
+use serde::Serialize;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+
+/// What an apply or unapply changed in the target, for reporting; nothing is recorded on a dry
+/// run.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Summary {
+    /// Links and generated files written, including ones that replaced an existing file.
+    pub created: Vec<PathBuf>,
+    /// Links deleted, and existing files overwritten.
+    pub removed: Vec<PathBuf>,
+    /// Existing files that were left in place because they conflicted with the plan.
+    pub conflicts: Vec<PathBuf>,
+    /// Package files overwritten with the contents of the target by '--exists adopt'.
+    pub adopted: Vec<PathBuf>,
+    /// Directories created in the target, whether to hold links or mirroring empty package
+    /// directories; remembered in the state so deletes can remove them again.
+    #[serde(skip)]
+    pub created_dirs: Vec<PathBuf>,
+}
+
+impl Summary {
+    /// Whether anything in the target changed.
+    pub fn changed(&self) -> bool {
+        !self.created.is_empty() || !self.removed.is_empty() || !self.adopted.is_empty()
+    }
+}
+
+/// What linking or unlinking one package did, kept for reporting on the whole run.
+#[derive(Serialize, Clone, Debug)]
+pub struct Outcome {
+    pub package: String,
+    /// "link" or "unlink".
+    pub action: &'static str,
+    /// The actions planned for the package.
+    pub plan: Vec<Action>,
+    #[serde(flatten)]
+    pub summary: Summary,
+    /// Why the operation stopped early, if it did.
+    pub error: Option<String>,
+    /// How long the operation took, in milliseconds.
+    pub elapsed_ms: u64,
+}
+
+/// Adds the outcome of linking or unlinking `package`, as planned by `actions` and started at
+/// `started`, to the run's.
+pub fn record(
+    args: &Args,
+    action: &'static str,
+    package: &str,
+    actions: &[Action],
+    started: Instant,
+    summary: &Summary,
+    error: Option<&str>,
+) {
+    // Long-running commands such as 'watch' would otherwise collect outcomes forever.
+    if args.format != OutputFormat::Ansible && args.report.is_none() {
+        return;
+    }
+    if let Ok(mut outcomes) = args.outcomes.lock() {
+        outcomes.push(Outcome {
+            package: package.to_owned(),
+            action,
+            plan: actions.to_vec(),
+            summary: summary.clone(),
+            error: error.map(str::to_owned),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        });
+    }
+}
+
+/// Performs `actions` in order as they come, resolving conflicts with existing target files
+/// according to '--exists', and records what changed in `summary`. The first failure stops the
+/// run, unless '--keep-going' is given, in which case the rest are still deployed and every
+/// failure is reported together at the end.
+pub fn apply<'a>(
+    args: &Args,
+    config: &Config,
+    actions: impl IntoIterator<Item = &'a Action>,
+    summary: &mut Summary,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    // Paths created before this run aren't for '--exists rollback' to undo.
+    let start = summary.created.len();
+    let mut warned_copies = false;
+    let mut failures = Vec::new();
+    for action in actions {
+        let copies = !warned_copies
+            && matches!(action, Action::Link { .. })
+            && args.link_mode == LinkMode::Auto
+            && wsl::link_mode(args, action.dst()) == LinkMode::Copy;
+        if copies && args.verbosity("apply") >= LV_WARN {
+            _ = writeln!(
+                stderr,
+                "{}",
+                tr!("wsl-copying", path = action.dst().display())
+            );
+            warned_copies = true;
+        }
+        if args.verbosity("apply") >= LV_INFO {
+            _ = writeln!(
+                stderr,
+                "{} -> {}",
+                action.src().display(),
+                action.dst().display()
+            );
+        }
+        match deploy(args, config, action, summary, stderr) {
+            Err(e) if args.keep_going => {
+                if args.verbosity("apply") >= LV_WARN {
+                    _ = writeln!(stderr, "{}", tr!("keeping-going", error = e));
+                }
+                failures.push(e);
+            }
+            Err(e) => failures.push(e),
+            Ok(()) => continue,
+        }
+        if !args.keep_going {
+            break;
+        }
+    }
+    if failures.is_empty() {
+        return Ok(());
+    }
+    if let ConflictResolution::Rollback = args.exists {
+        rollback(args, &summary.created[start..], stderr);
+        summary.created.truncate(start);
+    }
+    match failures.len() {
+        1 => Err(failures.remove(0)),
+        n => Err(tr!(
+            "files-not-deployed",
+            count = n,
+            failures = failures.join("\n  ")
+        )),
+    }
+}
+
+fn deploy(
+    args: &Args,
+    config: &Config,
+    action: &Action,
+    summary: &mut Summary,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let dst = action.dst();
+    // Contents of generated (non-link) files; computed even for files that already exist so that
+    // up-to-date files are recognized as such. Runs that write nothing leave them uncomputed,
+    // since that runs age, gpg, and secret commands, unless '--diff' asks to see them; '--backend
+    // sftp' writes them remotely, so it still needs them.
+    let preview = args.dry_run || args.check || args.backend == Backend::Record;
+    let contents = match action {
+        _ if preview && !args.diff => None,
+        Action::Decrypt { src, cipher, .. } => Some(cipher.decrypt(src, args)?),
+        Action::Render { src, .. } => Some(template::render(src, config)?),
+        _ => None,
+    };
+
+    if is_deployed(args, action, contents.as_deref()) {
+        if args.verbosity("apply") >= LV_DEBUG {
+            _ = writeln!(stderr, "{} is already deployed", dst.display());
+        }
+        return Ok(());
+    }
+
+    if let Some(blocking) = plan::blocking_path(args, action) {
+        let problem = plan::describe_type_conflict(args, dst, &blocking);
+        match args.type_conflict {
+            TypeConflict::Stop => {
+                let package = diagnostic::package_of(args, action.src()).unwrap_or_default();
+                let message = tr!("type-conflict-stop", problem = problem);
+                return Err(conflict(args, action, message, &blocking)
+                    .help(tr!("hint-type-conflict-backup", package = &package))
+                    .help(tr!("hint-type-conflict-skip", package = &package))
+                    .into());
+            }
+            TypeConflict::Skip => {
+                if args.verbosity("apply") >= LV_WARN {
+                    _ = writeln!(stderr, "{}", tr!("skipping", problem = problem));
+                }
+                if !args.dry_run {
+                    summary.conflicts.push(dst.to_path_buf());
+                }
+                return Ok(());
+            }
+            TypeConflict::Backup => {
+                let backup = backup_path(args, &blocking);
+                if args.verbosity("apply") >= LV_INFO {
+                    _ = writeln!(stderr, "{}; moving it to {}", problem, backup.display());
+                }
+                if args.dry_run {
+                    return Ok(());
+                }
+                args.fs.rename(&blocking, &backup).map_err(|e| {
+                    tr!("could-not-move-aside", path = blocking.display(), error = e)
+                })?;
+                summary.removed.push(blocking);
+            }
+        }
+    }
+
+    // Whether what's at `dst` is replaced by renaming the new file over it, so that programs
+    // reading it never find it missing.
+    let mut replace = false;
+    if args.fs.kind(dst).is_some() {
+        if args.diff {
+            show_diff(args, action, contents.as_deref(), stderr);
+        }
+        match args.exists {
+            ConflictResolution::Stop | ConflictResolution::Rollback => {
+                if !args.dry_run {
+                    summary.conflicts.push(dst.to_path_buf());
+                }
+                let package = diagnostic::package_of(args, action.src()).unwrap_or_default();
+                let message = tr!("already-exists", path = dst.display());
+                return Err(conflict(args, action, message, dst)
+                    .help(tr!("hint-exists-adopt", package = &package))
+                    .help(tr!("hint-exists-overwrite", package = &package))
+                    .help(tr!("hint-exists-ignore", package = &package))
+                    .into());
+            }
+            ConflictResolution::Ignore => {
+                if args.verbosity("apply") >= LV_WARN {
+                    _ = writeln!(
+                        stderr,
+                        "{}",
+                        tr!("already-exists-ignoring", path = dst.display())
+                    );
+                }
+                if !args.dry_run {
+                    summary.conflicts.push(dst.to_path_buf());
+                }
+                return Ok(());
+            }
+            ConflictResolution::Overwrite => {
+                if args.verbosity("apply") >= LV_INFO {
+                    _ = writeln!(stderr, "Overwriting {}", dst.display());
+                }
+                if !args.dry_run {
+                    replace = replaceable(args, dst)?;
+                    summary.removed.push(dst.to_path_buf());
+                }
+            }
+            ConflictResolution::Adopt if is_identical(args, action) => {
+                if args.verbosity("apply") >= LV_INFO {
+                    _ = writeln!(
+                        stderr,
+                        "{} is identical to {}; relinking",
+                        dst.display(),
+                        action.src().display()
+                    );
+                }
+                if !args.dry_run {
+                    replace = replaceable(args, dst)?;
+                }
+            }
+            ConflictResolution::Adopt => {
+                if !confirm_adopt(args, action, contents.as_deref(), stderr) {
+                    if !args.dry_run {
+                        summary.conflicts.push(dst.to_path_buf());
+                    }
+                    return Ok(());
+                }
+                if args.verbosity("apply") >= LV_INFO {
+                    _ = writeln!(
+                        stderr,
+                        "Adopting {} into {}",
+                        dst.display(),
+                        action.src().display()
+                    );
+                }
+                if args.dry_run {
+                    return Ok(());
+                }
+                match action {
+                    // The target already holds the plaintext; it only needs to flow back.
+                    Action::Decrypt { src, cipher, .. } => {
+                        let contents = args
+                            .fs
+                            .read(dst)
+                            .map_err(|e| tr!("could-not-read", path = dst.display(), error = e))?;
+                        cipher.encrypt(&contents, src, args)?;
+                        summary.adopted.push(src.clone());
+                        return Ok(());
+                    }
+                    Action::Render { src, .. } => {
+                        return Err(tr!(
+                            "cannot-adopt-template",
+                            path = dst.display(),
+                            template = src.display()
+                        ));
+                    }
+                    Action::Link { src, .. } if args.merge => {
+                        merge::adopt(args, src, dst, stderr)?;
+                        summary.adopted.push(src.clone());
+                        replace = replaceable(args, dst)?;
+                    }
+                    // Anything in the way of a directory is a type conflict, handled above.
+                    Action::Mkdir { .. } => {}
+                    Action::Link { src, .. } => {
+                        args.fs
+                            .copy(dst, src)
+                            .and_then(|_| args.fs.copy_times(dst, src))
+                            .map_err(|e| {
+                                tr!(
+                                    "could-not-adopt",
+                                    path = dst.display(),
+                                    package_file = src.display(),
+                                    error = e
+                                )
+                            })?;
+                        summary.adopted.push(src.clone());
+                        replace = replaceable(args, dst)?;
+                    }
+                }
+            }
+        }
+    }
+
+    if args.dry_run {
+        return Ok(());
+    }
+    let dir = match action {
+        Action::Mkdir { .. } => Some(dst),
+        _ => dst.parent(),
+    };
+    if let Some(dir) = dir {
+        create_dirs(args, dir, summary, stderr)?;
+    }
+    let new = match replace {
+        true => replacement_path(dst),
+        false => dst.to_path_buf(),
+    };
+    if replace {
+        // Left over from an interrupted run.
+        _ = args.fs.remove_file(&new);
+    }
+    inject_failure(args).map_err(|e| tr!("could-not-create", path = dst.display(), error = e))?;
+    let result = with_retries(args, dst, stderr, |stderr| match action {
+        Action::Link { src, .. } => match wsl::link_mode(args, dst) {
+            LinkMode::Windows if args.fs.is_real() => wsl::mklink(src, &new),
+            LinkMode::Copy => args.fs.copy(src, &new),
+            LinkMode::Hardlink => args.fs.hard_link(src, &new),
+            _ if args.relative => {
+                let runtime = paths::unroot(args.root.as_deref(), dst);
+                let src = paths::relative(runtime.parent().unwrap_or(&runtime), src);
+                elevate::symlink(args, &src, &new, stderr)
+            }
+            _ => elevate::symlink(args, src, &new, stderr),
+        },
+        Action::Decrypt { .. } | Action::Render { .. } => args
+            .fs
+            .write_private(&new, contents.as_deref().unwrap_or_default()),
+        Action::Mkdir { .. } => Ok(()),
+    });
+    let result = match replace {
+        true => result.and_then(|_| {
+            args.fs
+                .rename(&new, dst)
+                .inspect_err(|_| _ = args.fs.remove_file(&new))
+        }),
+        false => result,
+    };
+    if let (Ok(()), Action::Link { src, .. }) = (&result, action) {
+        if let Err(e) = args.fs.stamp_link(src, dst) {
+            if args.verbosity("apply") >= LV_DEBUG {
+                _ = writeln!(stderr, "Could not set the time of {}: {}", dst.display(), e);
+            }
+        }
+    }
+    result
+        .and_then(|_| users::chown(args, dst, stderr))
+        .map_err(|e| tr!("could-not-create", path = dst.display(), error = e))?;
+    summary.created.push(dst.to_path_buf());
+    Ok(())
+}
+
+/// With '--sync', flushes everything `summary` records to disk: generated files' contents, and
+/// the directories that links, files, and directories were created in or removed from, so that
+/// a crash right afterwards doesn't lose them. Failures are warned about, not fatal, since the
+/// changes themselves were made.
+pub fn sync(args: &Args, summary: &Summary, stderr: &mut dyn io::Write) {
+    if !args.sync || args.dry_run {
+        return;
+    }
+    let mut paths: Vec<&Path> = summary
+        .created
+        .iter()
+        .filter(|p| args.fs.kind(p) == Some(Kind::File))
+        .map(PathBuf::as_path)
+        .collect();
+    let mut dirs: Vec<&Path> = [&summary.created, &summary.removed, &summary.created_dirs]
+        .into_iter()
+        .flatten()
+        .filter_map(|p| p.parent())
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    paths.extend(dirs);
+    for path in paths {
+        if args.verbosity("apply") >= LV_DEBUG {
+            _ = writeln!(stderr, "fsync {}", path.display());
+        }
+        if let Err(e) = args.fs.sync(path) {
+            if args.verbosity("apply") >= LV_WARN {
+                _ = writeln!(
+                    stderr,
+                    "{}",
+                    tr!("could-not-sync", path = path.display(), error = e)
+                );
+            }
+        }
+    }
+}
+
+/// First delay before retrying a transient failure; it doubles with each retry.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Whether `e` is the kind of failure network filesystems such as NFS and SMB return
+/// temporarily, which trying again may get past.
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ResourceBusy
+            | io::ErrorKind::StaleNetworkFileHandle
+    )
+}
+
+/// Runs `op` on `path`, retrying transient failures up to '--retries' times with exponential
+/// backoff before giving up.
+fn with_retries(
+    args: &Args,
+    path: &Path,
+    stderr: &mut dyn io::Write,
+    mut op: impl FnMut(&mut dyn io::Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut delay = RETRY_DELAY;
+    for _ in 0..args.retries {
+        match op(stderr) {
+            Err(e) if is_transient(&e) => {
+                if args.verbosity("apply") >= LV_WARN {
+                    _ = writeln!(
+                        stderr,
+                        "{}",
+                        tr!(
+                            "retrying",
+                            path = path.display(),
+                            error = e,
+                            milliseconds = delay.as_millis()
+                        )
+                    );
+                }
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+    op(stderr)
+}
+
+/// Counts a change to the filesystem, failing it if it's the one '--inject-failure' names.
+/// Rolling back doesn't count, so that it can be tested.
+fn inject_failure(args: &Args) -> io::Result<()> {
+    let Some(n) = args.inject_failure else {
+        return Ok(());
+    };
+    match args.changes.fetch_add(1, Ordering::SeqCst) + 1 == n {
+        true => Err(io::Error::other(tr!("injected-failure", count = n))),
+        false => Ok(()),
+    }
+}
+
+/// Creates `dir` and any missing parents, recording the ones that didn't exist in `summary`.
+fn create_dirs(
+    args: &Args,
+    dir: &Path,
+    summary: &mut Summary,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let missing: Vec<PathBuf> = dir
+        .ancestors()
+        .take_while(|d| args.fs.kind(d).is_none())
+        .map(Path::to_path_buf)
+        .collect();
+    if !missing.is_empty() {
+        inject_failure(args)
+            .map_err(|e| tr!("could-not-create-dir", path = dir.display(), error = e))?;
+    }
+    with_retries(args, dir, stderr, |stderr| {
+        elevate::create_dir_all(args, dir, stderr)
+    })
+    .and_then(|_| {
+        missing
+            .iter()
+            .try_for_each(|d| users::chown(args, d, stderr))
+    })
+    .map_err(|e| tr!("could-not-create-dir", path = dir.display(), error = e))?;
+    summary.created_dirs.extend(missing.into_iter().rev());
+    Ok(())
+}
+
+/// Explains why `action` can't be deployed while `occupant` is in the way: what's there, and
+/// which package wants the target. When the occupant is another package's link, the first hint
+/// is to unlink that package; callers add the options that would resolve the conflict.
+fn conflict(args: &Args, action: &Action, message: String, occupant: &Path) -> Diagnostic {
+    let package = diagnostic::package_of(args, action.src()).unwrap_or_default();
+    let occupant_package = args
+        .fs
+        .resolve_link(occupant)
+        .and_then(|target| diagnostic::package_of(args, &target))
+        .filter(|p| *p != package);
+    let occupied_by = diagnostic::describe_occupant(args, occupant);
+    let wanted_by = tr!(
+        "wanted-by",
+        package = &package,
+        src = action.src().display()
+    );
+    let diagnostic = Diagnostic::new(message)
+        .label(tr!("label-target"), action.dst().display())
+        .label(tr!("label-occupied-by"), occupied_by)
+        .label(tr!("label-wanted-by"), wanted_by);
+    match occupant_package {
+        Some(other) => diagnostic.help(tr!("hint-unlink-other", package = other)),
+        None => diagnostic,
+    }
+}
+
+/// With '--diff', shows how the real file in the way at the target differs from what `action`
+/// deploys: the package file, or the generated `contents` of a secret or template, which are
+/// computed for it even on a dry run.
+fn show_diff(args: &Args, action: &Action, contents: Option<&[u8]>, stderr: &mut dyn io::Write) {
+    let dst = action.dst();
+    if args.fs.kind(dst) != Some(Kind::File) {
+        return;
+    }
+    let read = |path: &Path| {
+        args.fs
+            .read(path)
+            .map_err(|e| tr!("could-not-read", path = path.display(), error = e))
+    };
+    let package = match (action, contents) {
+        (Action::Link { src, .. }, _) => read(src),
+        (_, Some(contents)) => Ok(contents.to_vec()),
+        (_, None) => return,
+    };
+    let diff = package.and_then(|package| {
+        Ok(diff::diff_bytes(
+            &action.src().display().to_string(),
+            &package,
+            &dst.display().to_string(),
+            &read(dst)?,
+            diff::color(),
+        ))
+    });
+    match diff {
+        Ok(diff) => _ = write!(stderr, "{}", diff),
+        Err(e) => _ = writeln!(stderr, "{}", tr!("warning", message = e)),
+    }
+}
+
+/// Removes the links that `actions` created, recording them in `summary`. Anything else found
+/// at a target path, including links pointing elsewhere and generated files, is left alone.
+pub fn unapply(
+    args: &Args,
+    actions: &[Action],
+    summary: &mut Summary,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    for action in actions {
+        let Action::Link { src, dst } = action else {
+            continue;
+        };
+        if !is_linked(args, src, dst) {
+            continue;
+        }
+        if args.verbosity("apply") >= LV_INFO {
+            _ = writeln!(stderr, "Unlinking {}", dst.display());
+        }
+        if !args.dry_run {
+            inject_failure(args)
+                .and_then(|_| elevate::remove_file(args, dst, stderr))
+                .map_err(|e| tr!("could-not-remove", path = dst.display(), error = e))?;
+            summary.removed.push(dst.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Points the existing link at `dst` to `src` instead, replacing it in one step. Relative links
+/// stay relative.
+pub fn repoint(
+    args: &Args,
+    dst: &Path,
+    src: &Path,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let error = |e: io::Error| tr!("could-not-repoint", path = dst.display(), error = e);
+    let target = match args.fs.read_link(dst).map_err(error)?.is_relative() {
+        true => paths::relative(dst.parent().unwrap_or(dst), src),
+        false => src.to_path_buf(),
+    };
+    let new = replacement_path(dst);
+    // Left over from an interrupted run.
+    _ = args.fs.remove_file(&new);
+    inject_failure(args)
+        .and_then(|_| elevate::symlink(args, &target, &new, stderr))
+        .and_then(|_| {
+            args.fs
+                .rename(&new, dst)
+                .inspect_err(|_| _ = args.fs.remove_file(&new))
+        })
+        .map_err(error)
+}
+
+/// Removes the directories ranch created in `state` that were left empty by unapplying
+/// `actions`, recorded in `summary`: the parents of removed links, and directories the actions
+/// created, walking up for as long as each is empty. Directories ranch didn't create are kept.
+pub fn remove_created_dirs(
+    args: &Args,
+    actions: &[Action],
+    state: &mut State,
+    summary: &mut Summary,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let mut dirs: Vec<&Path> = summary
+        .removed
+        .iter()
+        .filter_map(|p| p.parent())
+        .chain(actions.iter().filter_map(|a| match a {
+            Action::Mkdir { dst, .. } => Some(dst.as_path()),
+            _ => None,
+        }))
+        .collect();
+    // Deepest first, so that emptied children are gone before their parents are checked.
+    dirs.sort_by_key(|d| std::cmp::Reverse(d.components().count()));
+    dirs.dedup();
+    let mut removed = Vec::new();
+    for dir in dirs {
+        for dir in dir.ancestors() {
+            if !state.created_dirs.contains(dir) {
+                break;
+            }
+            let empty = args
+                .fs
+                .read_dir(dir)
+                .is_ok_and(|entries| entries.is_empty());
+            if !empty {
+                break;
+            }
+            if args.verbosity("apply") >= LV_INFO {
+                _ = writeln!(stderr, "Removing empty directory {}", dir.display());
+            }
+            if args.dry_run {
+                break;
+            }
+            inject_failure(args)
+                .and_then(|_| elevate::remove_dir(args, dir, stderr))
+                .map_err(|e| tr!("could-not-remove", path = dir.display(), error = e))?;
+            state.created_dirs.remove(dir);
+            removed.push(dir.to_path_buf());
+        }
+    }
+    summary.removed.extend(removed);
+    Ok(())
+}
+
+/// Deletes links in `target_path` that point to files no longer in the package at
+/// `prefix_path`, such as ones removed by a 'git pull'. Only target directories mirroring a
+/// directory that's still in the package are searched, so that the rest of the target isn't
+/// crawled.
+pub fn prune(
+    args: &Args,
+    prefix_path: &Path,
+    target_path: &Path,
+    summary: &mut Summary,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    for dir in WalkDir::new(prefix_path)
+        .into_iter()
+        .filter_map(|r| r.ok())
+        .filter(|e| e.file_type().is_dir())
+    {
+        let Ok(rel_path) = dir.path().strip_prefix(prefix_path) else {
+            continue;
+        };
+        let Ok(entries) = args.fs.read_dir(&target_path.join(rel_path)) else {
+            continue;
+        };
+        for dst in entries {
+            let Some(src) = args.fs.resolve_link(&dst) else {
+                continue;
+            };
+            if !src.starts_with(prefix_path) || args.fs.kind(&src).is_some() {
+                continue;
+            }
+            if args.verbosity("apply") >= LV_INFO {
+                _ = writeln!(stderr, "Pruning {}", dst.display());
+            }
+            if !args.dry_run {
+                args.fs
+                    .remove_file(&dst)
+                    .map_err(|e| tr!("could-not-remove", path = dst.display(), error = e))?;
+                summary.removed.push(dst);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether the target already matches what `action` would produce, making it a no-op. Without
+/// the generated `contents` to compare, any file already there is taken to be up to date.
+fn is_deployed(args: &Args, action: &Action, contents: Option<&[u8]>) -> bool {
+    match action {
+        Action::Link { src, dst } => is_linked(args, src, dst),
+        Action::Decrypt { dst, .. } | Action::Render { dst, .. } => match contents {
+            Some(c) => !args.fs.is_symlink(dst) && args.fs.read(dst).is_ok_and(|d| d == c),
+            None => args.fs.kind(dst) == Some(Kind::File),
+        },
+        Action::Mkdir { dst, .. } => args.fs.is_dir(dst),
+    }
+}
+
+/// With '--link-mode hardlink', checks up front whether the package at `prefix_path` and its
+/// target at `target_path` are on the same filesystem, since hard links can't cross them.
+/// Returns the link mode to use instead, per '--xdev-fallback', if they aren't.
+pub fn xdev_fallback(
+    args: &Args,
+    prefix_path: &Path,
+    target_path: &Path,
+    stderr: &mut dyn io::Write,
+) -> Result<Option<LinkMode>, String> {
+    if args.link_mode != LinkMode::Hardlink
+        || paths::same_device(prefix_path, target_path) != Some(false)
+    {
+        return Ok(None);
+    }
+    let problem = tr!(
+        "different-filesystems",
+        package = prefix_path.display(),
+        target = target_path.display()
+    );
+    let (mode, warning) = match args.xdev_fallback {
+        XdevFallback::Stop => return Err(tr!("xdev-stop", problem = problem)),
+        XdevFallback::Symlink => (LinkMode::Symlink, tr!("xdev-symlinks", problem = problem)),
+        XdevFallback::Copy => (LinkMode::Copy, tr!("xdev-copies", problem = problem)),
+    };
+    if args.verbosity("apply") >= LV_WARN {
+        _ = writeln!(stderr, "{}", warning);
+    }
+    Ok(Some(mode))
+}
+
+/// Whether `dst` is deployed from the package file `src`: a link to it, or, where '--link-mode'
+/// doesn't make Linux symlinks, a real file with the same contents, since copies and Windows
+/// links made from WSL can't be read back as links to `src`.
+pub fn is_linked(args: &Args, src: &Path, dst: &Path) -> bool {
+    // Relative links staged under '--root' resolve to 'DIR' under it too.
+    let points_to_src = |link: PathBuf| {
+        paths::same_path(&link, src)
+            || paths::same_path(&paths::unroot(args.root.as_deref(), &link), src)
+    };
+    if args.fs.resolve_link(dst).is_some_and(points_to_src) {
+        return true;
+    }
+    wsl::link_mode(args, dst) != LinkMode::Symlink
+        && args
+            .fs
+            .read(dst)
+            .is_ok_and(|d| args.fs.read(src).is_ok_and(|s| s == d))
+}
+
+/// A free name beside `path` to move it to: 'NAME.ranch-backup', or 'NAME.ranch-backup.N'.
+fn backup_path(args: &Args, path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".ranch-backup");
+    let backup = PathBuf::from(&name);
+    (1..)
+        .map(|n| match n {
+            1 => backup.clone(),
+            n => PathBuf::from(format!("{}.{}", backup.display(), n - 1)),
+        })
+        .find(|p| args.fs.kind(p).is_none())
+        .unwrap_or(backup)
+}
+
+/// Whether the real file at the target of a link holds exactly what the package file does, so
+/// adopting it would change nothing.
+fn is_identical(args: &Args, action: &Action) -> bool {
+    let Action::Link { src, dst } = action else {
+        return false;
+    };
+    !args.fs.is_symlink(dst)
+        && args
+            .fs
+            .read(dst)
+            .is_ok_and(|d| args.fs.read(src).is_ok_and(|s| s == d))
+}
+
+/// Lists how the target of `action` differs from the package file (or the generated `contents`
+/// of a secret), and asks whether to adopt it, unless '--yes' was given. Without a terminal to
+/// ask on, the file isn't adopted.
+fn confirm_adopt(
+    args: &Args,
+    action: &Action,
+    contents: Option<&[u8]>,
+    stderr: &mut dyn io::Write,
+) -> bool {
+    let (src, dst) = (action.src(), action.dst());
+    let ask = !args.yes && !args.dry_run && !args.check;
+    if ask || args.verbosity("apply") >= LV_INFO {
+        let package = match (action, contents) {
+            (Action::Link { .. }, _) => args.fs.read(src).ok(),
+            (_, contents) => contents.map(<[u8]>::to_vec),
+        };
+        let stat = match (package, args.fs.read(dst)) {
+            (Some(package), Ok(target)) => diff::stat(&package, &target),
+            _ => None,
+        };
+        let stat = match stat {
+            Some((added, removed)) => format!("{} line(s) added, {} removed", added, removed),
+            None => String::from("contents differ"),
+        };
+        _ = writeln!(
+            stderr,
+            "{} differs from {}: {}",
+            dst.display(),
+            src.display(),
+            stat
+        );
+    }
+    if !ask {
+        return true;
+    }
+    if !io::stdin().is_terminal() {
+        if args.verbosity("apply") >= LV_WARN {
+            _ = writeln!(stderr, "{}", tr!("not-adopting", path = dst.display()));
+        }
+        return false;
+    }
+    _ = write!(
+        stderr,
+        "Adopt {} into {}? [y/N] ",
+        dst.display(),
+        src.display()
+    );
+    _ = stderr.flush();
+    let mut answer = String::new();
+    _ = io::stdin().read_line(&mut answer);
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Whether the file or link at `path` can be replaced; directories never are.
+fn replaceable(args: &Args, path: &Path) -> Result<bool, String> {
+    if args.fs.kind(path) == Some(Kind::Dir) {
+        return Err(tr!("refusing-to-replace-dir", path = path.display()));
+    }
+    Ok(true)
+}
+
+/// Where the replacement for `path` is made before being renamed over it: '.NAME.ranch-new'
+/// beside it, so that the rename stays on one filesystem.
+fn replacement_path(path: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".ranch-new");
+    path.with_file_name(name)
+}
+
+fn rollback(args: &Args, created: &[PathBuf], stderr: &mut dyn io::Write) {
+    for path in created.iter().rev() {
+        if args.verbosity("apply") >= LV_INFO {
+            _ = writeln!(stderr, "Rolling back {}", path.display());
+        }
+        let result = match args.fs.kind(path) == Some(Kind::Dir) {
+            true => args.fs.remove_dir(path),
+            false => args.fs.remove_file(path),
+        };
+        if let Err(e) = result {
+            if args.verbosity("apply") >= LV_WARN {
+                _ = writeln!(
+                    stderr,
+                    "{}",
+                    tr!("could-not-roll-back", path = path.display(), error = e)
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_with_retries() {
+        let flaky = |failures: u32| {
+            let mut calls = 0;
+            move |_: &mut dyn io::Write| {
+                calls += 1;
+                match calls > failures {
+                    true => Ok(()),
+                    false => Err(io::Error::from(io::ErrorKind::StaleNetworkFileHandle)),
+                }
+            }
+        };
+        let args = |retries: &str| Args::parse_from(["ranch", "--retries", retries, "home"]);
+        let path = Path::new("/mnt/nfs/.vimrc");
+
+        assert!(with_retries(&args("2"), path, &mut io::sink(), flaky(2)).is_ok());
+        assert!(with_retries(&args("1"), path, &mut io::sink(), flaky(2)).is_err());
+        let denied = |_: &mut dyn io::Write| Err(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(with_retries(&args("2"), path, &mut io::sink(), denied).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_overwrite_replaces_atomically() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        let target = tmp_dir.path().join("home");
+        fs::create_dir_all(dir.join("home")).unwrap();
+        fs::create_dir_all(&target).unwrap();
+        fs::write(dir.join("home/.vimrc"), "").unwrap();
+        std::os::unix::fs::symlink("elsewhere", target.join(".vimrc")).unwrap();
+        let args = Args::parse_from([
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "-e",
+            "overwrite",
+            "home",
+        ]);
+        let actions = [Action::Link {
+            src: dir.join("home/.vimrc"),
+            dst: target.join(".vimrc"),
+        }];
+
+        let mut summary = Summary::default();
+        apply(
+            &args,
+            &Config::default(),
+            &actions,
+            &mut summary,
+            &mut io::sink(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_link(target.join(".vimrc")).unwrap(),
+            dir.join("home/.vimrc")
+        );
+        assert!(!replacement_path(&target.join(".vimrc")).is_symlink());
+        assert_eq!(summary.removed, [target.join(".vimrc")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlink_mode() {
+        use std::os::unix::fs::MetadataExt;
+
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        let target = tmp_dir.path().join("home");
+        fs::create_dir_all(dir.join("home")).unwrap();
+        fs::create_dir_all(&target).unwrap();
+        fs::write(dir.join("home/.vimrc"), "set number").unwrap();
+        let argv = [
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "--link-mode",
+            "hardlink",
+            "home",
+        ];
+        let args = Args::parse_from(argv);
+        let actions = [Action::Link {
+            src: dir.join("home/.vimrc"),
+            dst: target.join(".vimrc"),
+        }];
+
+        let fallback = xdev_fallback(&args, &dir.join("home"), &target, &mut io::sink()).unwrap();
+        let mut summary = Summary::default();
+        apply(
+            &args,
+            &Config::default(),
+            &actions,
+            &mut summary,
+            &mut io::sink(),
+        )
+        .unwrap();
+
+        assert!(fallback.is_none());
+        let ino = |p: &Path| fs::metadata(p).unwrap().ino();
+        assert_eq!(ino(&target.join(".vimrc")), ino(&dir.join("home/.vimrc")));
+        assert!(is_linked(
+            &args,
+            &dir.join("home/.vimrc"),
+            &target.join(".vimrc")
+        ));
+    }
+
+    #[test]
+    fn test_adopt_preserves_times() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        let target = tmp_dir.path().join("home");
+        fs::create_dir_all(dir.join("home")).unwrap();
+        fs::create_dir_all(&target).unwrap();
+        fs::write(dir.join("home/.vimrc"), "").unwrap();
+        fs::write(target.join(".vimrc"), "set number").unwrap();
+        let mtime = std::time::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let times = fs::FileTimes::new().set_modified(mtime);
+        fs::File::options()
+            .write(true)
+            .open(target.join(".vimrc"))
+            .unwrap()
+            .set_times(times)
+            .unwrap();
+        let argv = [
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "-e",
+            "adopt",
+            "-y",
+            "home",
+        ];
+        let actions = [Action::Link {
+            src: dir.join("home/.vimrc"),
+            dst: target.join(".vimrc"),
+        }];
+
+        let mut summary = Summary::default();
+        let args = Args::parse_from(argv);
+        apply(
+            &args,
+            &Config::default(),
+            &actions,
+            &mut summary,
+            &mut io::sink(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("home/.vimrc")).unwrap(),
+            "set number"
+        );
+        assert_eq!(
+            fs::metadata(dir.join("home/.vimrc"))
+                .unwrap()
+                .modified()
+                .unwrap(),
+            mtime
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_link_mtime() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        let target = tmp_dir.path().join("home");
+        fs::create_dir_all(dir.join("home")).unwrap();
+        fs::create_dir_all(&target).unwrap();
+        fs::write(dir.join("home/.vimrc"), "").unwrap();
+        let mtime = std::time::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        fs::File::options()
+            .write(true)
+            .open(dir.join("home/.vimrc"))
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "home"]);
+        let actions = [Action::Link {
+            src: dir.join("home/.vimrc"),
+            dst: target.join(".vimrc"),
+        }];
+
+        let mut summary = Summary::default();
+        apply(
+            &args,
+            &Config::default(),
+            &actions,
+            &mut summary,
+            &mut io::sink(),
+        )
+        .unwrap();
+
+        let link = fs::symlink_metadata(target.join(".vimrc")).unwrap();
+        assert!(link.is_symlink());
+        assert_eq!(link.modified().unwrap(), mtime);
+    }
+
+    #[test]
+    fn test_keep_going() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        let target = tmp_dir.path().join("home");
+        fs::create_dir_all(dir.join("home")).unwrap();
+        fs::create_dir_all(&target).unwrap();
+        for name in [".bashrc", ".vimrc", ".zshrc"] {
+            fs::write(dir.join("home").join(name), "").unwrap();
+        }
+        fs::write(target.join(".bashrc"), "mine").unwrap();
+        fs::write(target.join(".vimrc"), "mine").unwrap();
+        let actions = [".bashrc", ".vimrc", ".zshrc"].map(|name| Action::Link {
+            src: dir.join("home").join(name),
+            dst: target.join(name),
+        });
+        let config = Config::default();
+        let args = |extra: &[&str]| {
+            Args::parse_from([&["ranch", "-C", dir.to_str().unwrap()], extra, &["home"]].concat())
+        };
+
+        let mut summary = Summary::default();
+        let result = apply(&args(&[]), &config, &actions, &mut summary, &mut io::sink());
+        assert!(result.is_err());
+        assert!(summary.created.is_empty());
+
+        let mut summary = Summary::default();
+        let result = apply(
+            &args(&["--keep-going"]),
+            &config,
+            &actions,
+            &mut summary,
+            &mut io::sink(),
+        );
+        let e = result.unwrap_err();
+        assert!(e.starts_with("2 files could not be deployed:"), "{}", e);
+        assert_eq!(summary.created, [target.join(".zshrc")]);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        use crate::filesystem::{Filesystem, Fs, MemoryFs, Node};
+        use std::sync::Arc;
+
+        let src = Path::new("/dotfiles/home/.vimrc");
+        let dst = Path::new("/home/.vimrc");
+        let actions = [Action::Link {
+            src: src.to_path_buf(),
+            dst: dst.to_path_buf(),
+        }];
+        let occupants = ["none", "file", "dir", "linked", "link", "broken"];
+        for (occupant, exists, type_conflict) in occupants.into_iter().flat_map(|o| {
+            ["stop", "ignore", "overwrite", "adopt", "rollback"]
+                .into_iter()
+                .flat_map(move |e| ["stop", "skip", "backup"].map(move |t| (o, e, t)))
+        }) {
+            let fs = Arc::new(MemoryFs::default());
+            fs.file(src, "package").file("/home/other", "other");
+            match occupant {
+                "file" => _ = fs.file(dst, "mine"),
+                "dir" => _ = fs.dir(dst),
+                "linked" => _ = fs.link(dst, src),
+                "link" => _ = fs.link(dst, "other"),
+                "broken" => _ = fs.link(dst, "missing"),
+                _ => {}
+            }
+            let before = fs.nodes();
+            let mut args = Args::parse_from([
+                "ranch",
+                "-C",
+                "/dotfiles",
+                "--exists",
+                exists,
+                "--type-conflict",
+                type_conflict,
+                "--yes",
+                "home",
+            ]);
+            args.fs = Fs(fs.clone());
+            let mut summary = Summary::default();
+            let case = format!(
+                "{} with --exists {} --type-conflict {}",
+                occupant, exists, type_conflict
+            );
+
+            let result = apply(
+                &args,
+                &Config::default(),
+                &actions,
+                &mut summary,
+                &mut io::sink(),
+            );
+
+            let after = fs.nodes();
+            let deployed = fs.resolve_link(dst).as_deref() == Some(src);
+            let fails = match (occupant, exists, type_conflict) {
+                ("none" | "linked", _, _) => false,
+                ("dir", _, t) => t == "stop",
+                (_, "stop" | "rollback", _) => true,
+                (o, e, _) => o == "broken" && e == "adopt",
+            };
+            let left_alone = match (occupant, exists, type_conflict) {
+                ("none" | "linked", _, _) => false,
+                ("dir", _, t) => t == "skip",
+                (_, e, _) => e == "ignore",
+            };
+            assert_eq!(result.is_err(), fails, "{}: {:?}", case, result);
+            if fails || left_alone {
+                assert_eq!(after, before, "{}", case);
+                continue;
+            }
+            assert!(deployed, "{}", case);
+            let package = match (occupant, exists) {
+                ("file", "adopt") => "mine",
+                ("link", "adopt") => "other",
+                _ => "package",
+            };
+            assert_eq!(after[src], Node::File(package.into()), "{}", case);
+            let backup = Path::new("/home/.vimrc.ranch-backup");
+            assert_eq!(after.contains_key(backup), occupant == "dir", "{}", case);
+            assert!(
+                after.keys().all(|p| !p.ends_with(".vimrc.ranch-new")),
+                "{}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn test_owner_set_through_fs() {
+        use crate::filesystem::{Filesystem, Fs, MemoryFs};
+        use std::sync::Arc;
+
+        let src = Path::new("/dotfiles/home/.vimrc");
+        let dst = Path::new("/home/.vimrc");
+        let fs = Arc::new(MemoryFs::default());
+        fs.file(src, "package").dir("/home");
+        let mut args = Args::parse_from(["ranch", "-C", "/dotfiles", "home"]);
+        args.fs = Fs(fs.clone());
+        args.owner = Some((1000, 1000));
+        let actions = [Action::Link {
+            src: src.to_path_buf(),
+            dst: dst.to_path_buf(),
+        }];
+        let mut summary = Summary::default();
+
+        apply(
+            &args,
+            &Config::default(),
+            &actions,
+            &mut summary,
+            &mut io::sink(),
+        )
+        .unwrap();
+
+        assert_eq!(fs.resolve_link(dst).as_deref(), Some(src));
+        assert_eq!(summary.created, [dst]);
+    }
+
+    #[test]
+    fn test_inject_failure() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        let target = tmp_dir.path().join("home");
+        fs::create_dir_all(dir.join("home")).unwrap();
+        fs::create_dir_all(&target).unwrap();
+        for name in [".bashrc", ".vimrc", ".zshrc"] {
+            fs::write(dir.join("home").join(name), "").unwrap();
+        }
+        let actions = [".bashrc", ".vimrc", ".zshrc"].map(|name| Action::Link {
+            src: dir.join("home").join(name),
+            dst: target.join(name),
+        });
+        let args = Args::parse_from([
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "--inject-failure",
+            "2",
+            "--exists",
+            "rollback",
+            "home",
+        ]);
+
+        let mut summary = Summary::default();
+        let result = apply(
+            &args,
+            &Config::default(),
+            &actions,
+            &mut summary,
+            &mut io::sink(),
+        );
+        let e = result.unwrap_err();
+        assert!(e.contains("injected failure of change 2"), "{}", e);
+        assert!(summary.created.is_empty());
+        assert!(!target.join(".bashrc").is_symlink());
+        assert!(!target.join(".vimrc").is_symlink());
+    }
+
+    #[test]
+    fn test_remove_created_dirs() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("home/.config/foo")).unwrap();
+        fs::write(dir.join("home/.config/foo/foo.toml"), "").unwrap();
+        fs::create_dir_all(tmp_dir.path().join("home")).unwrap();
+        let target = tmp_dir.path().join("home");
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "home"]);
+        let actions = [Action::Link {
+            src: dir.join("home/.config/foo/foo.toml"),
+            dst: target.join(".config/foo/foo.toml"),
+        }];
+        let config = Config::default();
+        let mut summary = Summary::default();
+        apply(&args, &config, &actions, &mut summary, &mut io::sink()).unwrap();
+        assert_eq!(
+            summary.created_dirs,
+            [target.join(".config"), target.join(".config/foo")]
+        );
+        let mut state = State::default();
+        state.created_dirs.extend(summary.created_dirs);
+        fs::write(target.join(".config/other"), "").unwrap();
+
+        let mut summary = Summary::default();
+        unapply(&args, &actions, &mut summary, &mut io::sink()).unwrap();
+        remove_created_dirs(&args, &actions, &mut state, &mut summary, &mut io::sink()).unwrap();
+
+        assert!(!target.join(".config/foo").exists());
+        assert!(target.join(".config/other").exists());
+        assert_eq!(
+            state.created_dirs.into_iter().collect::<Vec<_>>(),
+            [target.join(".config")]
+        );
+    }
+}
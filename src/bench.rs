@@ -0,0 +1,127 @@
+use crate::apply::{self, Summary};
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::plan;
+use crate::{Args, LV_INFO};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Name of the synthetic package.
+const PACKAGE: &str = "bench";
+
+/// How many files go in each directory of the synthetic package.
+const FILES_PER_DIR: usize = 100;
+
+/// Generates a synthetic package of `files` files in a temporary directory, plans and deploys it
+/// to an empty target there with the given options, and prints how long each step took and how
+/// many files per second that comes to on `stdout`, for tracking performance across changes.
+/// The user's config is left out, so that runs compare; the temporary directory is removed after.
+pub fn bench(
+    args: &Args,
+    files: usize,
+    stdout: &mut dyn io::Write,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let root = env::temp_dir().join(format!("ranch-bench-{}", std::process::id()));
+    let result = run(args, &root, files, stdout, stderr);
+    _ = fs::remove_dir_all(&root);
+    result
+}
+
+fn run(
+    args: &Args,
+    root: &Path,
+    files: usize,
+    stdout: &mut dyn io::Write,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let started = Instant::now();
+    generate(&root.join("packages").join(PACKAGE), files)?;
+    report(stdout, "Generated", files, "files", started.elapsed());
+
+    let target = root.join("target");
+    fs::create_dir_all(&target)
+        .map_err(|e| tr!("could-not-create-dir", path = target.display(), error = e))?;
+    let args = Args {
+        dir: root.join("packages"),
+        target: Some(target.clone()),
+        ..args.clone()
+    };
+    if args.verbosity("bench") >= LV_INFO {
+        _ = writeln!(stderr, "Deploying {} to {}", PACKAGE, target.display());
+    }
+    let started = Instant::now();
+    let actions = plan::plan(&args, PACKAGE, &args.dir.join(PACKAGE), &target, stderr)?;
+    report(
+        stdout,
+        "Planned",
+        actions.len(),
+        "actions",
+        started.elapsed(),
+    );
+
+    let started = Instant::now();
+    let mut summary = Summary::default();
+    apply::apply(&args, &Config::default(), &actions, &mut summary, stderr)?;
+    report(
+        stdout,
+        "Applied",
+        actions.len(),
+        "actions",
+        started.elapsed(),
+    );
+    Ok(())
+}
+
+/// Writes `files` small files into `dir`, [FILES_PER_DIR] to a subdirectory.
+fn generate(dir: &Path, files: usize) -> Result<(), String> {
+    for i in 0..files {
+        let sub = dir.join(format!("dir{}", i / FILES_PER_DIR));
+        if i % FILES_PER_DIR == 0 {
+            fs::create_dir_all(&sub)
+                .map_err(|e| tr!("could-not-create-dir", path = sub.display(), error = e))?;
+        }
+        let path = sub.join(format!("file{}", i));
+        fs::write(&path, format!("{}\n", i))
+            .map_err(|e| tr!("could-not-write", path = path.display(), error = e))?;
+    }
+    Ok(())
+}
+
+fn report(stdout: &mut dyn io::Write, step: &str, count: usize, what: &str, elapsed: Duration) {
+    _ = writeln!(
+        stdout,
+        "{} {} {} in {:.2?} ({:.0} {}/s)",
+        step,
+        count,
+        what,
+        elapsed,
+        count as f64 / elapsed.as_secs_f64().max(1e-9),
+        what
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_bench() {
+        let args = Args::parse_from(["ranch", "home"]);
+        let mut stdout = Vec::new();
+        bench(&args, 250, &mut stdout, &mut io::sink()).unwrap();
+        let stdout = String::from_utf8(stdout).unwrap();
+        let lines: Vec<_> = stdout.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("Generated 250 files in "));
+        assert!(lines[1].starts_with("Planned 250 actions in "));
+        assert!(lines[2].starts_with("Applied 250 actions in "));
+        assert!(!env::temp_dir()
+            .join(format!("ranch-bench-{}", std::process::id()))
+            .exists());
+    }
+}
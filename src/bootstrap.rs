@@ -0,0 +1,252 @@
+use crate::filesystem::quote;
+use crate::git;
+use crate::paths;
+use crate::plan::{self, Action};
+use crate::{load_package, Args};
+use std::io;
+use std::path::Path;
+
+/// Installs ranch from its own repo, rather than whatever crate of that name crates.io has.
+pub const INSTALL: &str = concat!(
+    "cargo install --quiet --locked --git ",
+    env!("CARGO_PKG_REPOSITORY"),
+    " ranch"
+);
+
+/// Start of the installer; '@REPO@' and '@DIR@' are replaced with the quoted URL of the
+/// dotfiles repo and path of 'DIR' within it, '@INSTALL@' with [INSTALL], and '@PACKAGES@' with
+/// the quoted packages.
+const HEADER: &str = r#"#!/bin/sh
+# Installs the dotfiles; generated by 'ranch bootstrap --emit'. Run it on a new machine, e.g. with
+# 'curl -fsSL https://example.com/install.sh | sh'. Set RANCH_REPO or RANCH_CLONE to override
+# where the repo is cloned from or to.
+set -eu
+repo=${RANCH_REPO:-@REPO@}
+clone=${RANCH_CLONE:-"$HOME/.dotfiles"}
+dir="$clone"/@DIR@
+
+if [ -d "$clone/.git" ]; then
+    git -C "$clone" pull --quiet --ff-only --recurse-submodules
+else
+    git clone --quiet --recurse-submodules -- "$repo" "$clone"
+fi
+
+export PATH="$PATH:$HOME/.cargo/bin:$HOME/.local/bin"
+if ! command -v ranch >/dev/null 2>&1 && command -v cargo >/dev/null 2>&1; then
+    @INSTALL@ || echo "Could not install ranch" >&2
+fi
+if command -v ranch >/dev/null 2>&1; then
+    for package in @PACKAGES@; do
+        ranch -C "$dir" "$package"
+    done
+    exit
+fi
+
+echo "ranch isn't installed, so linking with 'ln' instead" >&2
+# Links the package file $1, relative to $dir, at $2, unless something is there already.
+link() {
+    mkdir -p "$(dirname "$2")"
+    if [ -e "$2" ] || [ -L "$2" ]; then
+        echo "Skipping $2, which already exists" >&2
+    else
+        ln -s "$dir/$1" "$2"
+    fi
+}
+"#;
+
+/// Prints a self-contained POSIX shell script to `stdout` that deploys `packages` on a new
+/// machine: it clones the repo holding 'DIR' from its 'origin', installs ranch with cargo if it
+/// can, and runs it, or else makes the links of today's plan with 'ln'. Targets in the home
+/// directory are made relative to the new machine's. Templates and encrypted files need ranch.
+pub fn emit(
+    args: &Args,
+    packages: &[String],
+    stdout: &mut dyn io::Write,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let (url, prefix) = git::origin(&args.dir)?;
+    let mut actions = Vec::new();
+    for package in packages {
+        let (prefix_path, _, target_path) = load_package(args, package)?;
+        actions.extend(plan::plan(
+            args,
+            package,
+            &prefix_path,
+            &target_path,
+            stderr,
+        )?);
+    }
+    let home = paths::home_dir().unwrap_or_default();
+    _ = write!(
+        stdout,
+        "{}",
+        script(args, &url, &prefix, packages, &actions, &home)
+    );
+    Ok(())
+}
+
+fn script(
+    args: &Args,
+    url: &str,
+    prefix: &Path,
+    packages: &[String],
+    actions: &[Action],
+    home: &Path,
+) -> String {
+    let packages: Vec<String> = packages.iter().map(|p| quote(p)).collect();
+    let mut out = HEADER
+        .replace("@REPO@", &quote(url))
+        .replace(
+            "@DIR@",
+            &quote(prefix.to_string_lossy().trim_end_matches('/')),
+        )
+        .replace("@INSTALL@", INSTALL)
+        .replace("@PACKAGES@", &packages.join(" "));
+    for action in actions {
+        let dst = match action.dst().strip_prefix(home) {
+            Ok(rel) if !home.as_os_str().is_empty() => {
+                format!("\"$HOME\"/{}", quote(&rel.to_string_lossy()))
+            }
+            _ => quote(&action.dst().to_string_lossy()),
+        };
+        let src = action.src().strip_prefix(&args.dir).unwrap_or(action.src());
+        let src = quote(&src.to_string_lossy());
+        out += &match action {
+            Action::Link { .. } => format!("link {} {}\n", src, dst),
+            Action::Mkdir { .. } => format!("mkdir -p {}\n", dst),
+            Action::Render { .. } | Action::Decrypt { .. } => {
+                format!("printf 'Skipping %s, which needs ranch\\n' {} >&2\n", dst)
+            }
+        };
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    #[test]
+    fn test_script() {
+        let args = Args::parse_from(["ranch", "-C", "/home/alice/.dotfiles", "vim"]);
+        let home = PathBuf::from("/home/alice");
+        let actions = [
+            Action::Link {
+                src: PathBuf::from("/home/alice/.dotfiles/vim/my vimrc"),
+                dst: home.join("my vimrc"),
+            },
+            Action::Mkdir {
+                src: PathBuf::from("/home/alice/.dotfiles/vim/.cache"),
+                dst: home.join(".cache"),
+            },
+            Action::Link {
+                src: PathBuf::from("/home/alice/.dotfiles/etc/hosts"),
+                dst: PathBuf::from("/etc/hosts"),
+            },
+        ];
+        let script = script(
+            &args,
+            "https://example.com/alice/dotfiles.git",
+            Path::new(""),
+            &[String::from("vim")],
+            &actions,
+            &home,
+        );
+        assert!(script.contains("repo=${RANCH_REPO:-https://example.com/alice/dotfiles.git}\n"));
+        assert!(script.contains("for package in vim; do\n"));
+        assert!(script.ends_with(
+            "link 'vim/my vimrc' \"$HOME\"/'my vimrc'\n\
+             mkdir -p \"$HOME\"/.cache\n\
+             link etc/hosts /etc/hosts\n"
+        ));
+        let status = Command::new("sh").args(["-n", "-c", &script]).status();
+        assert!(status.is_ok_and(|s| s.success()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_script_runs() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = tempdir::TempDir::new("alice").unwrap();
+        let upstream = tmp_dir.path().join("upstream");
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(&upstream)
+                .args(["-c", "user.name=a", "-c", "user.email=a@example.com"])
+                .args(args)
+                .output()
+                .is_ok_and(|o| o.status.success())
+        };
+        fs::create_dir_all(upstream.join("vim")).unwrap();
+        if !git(&["init"]) {
+            return; // git isn't installed
+        }
+        fs::write(upstream.join("vim/.vimrc"), "").unwrap();
+        assert!(git(&["add", "."]) && git(&["commit", "-m", "1"]));
+        let home = tmp_dir.path().join("home");
+        let args = Args::parse_from(["ranch", "-C", upstream.to_str().unwrap(), "vim"]);
+        let actions = [Action::Render {
+            src: upstream.join("vim/.netrc.tmpl"),
+            dst: home.join("$(touch pwned).netrc"),
+        }];
+        let packages = [String::from("vim"), String::from("my zsh")];
+        let script = script(
+            &args,
+            upstream.to_str().unwrap(),
+            Path::new(""),
+            &packages,
+            &actions,
+            &home,
+        );
+        let run = |path: &str| {
+            Command::new("sh")
+                .arg("-c")
+                .arg(&script)
+                .current_dir(tmp_dir.path())
+                .env("HOME", &home)
+                .env("PATH", path)
+                .output()
+                .unwrap()
+        };
+
+        let output = run("/usr/bin:/bin");
+
+        assert!(output.status.success(), "{:?}", output);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr).lines().last(),
+            Some(
+                format!(
+                    "Skipping {}/$(touch pwned).netrc, which needs ranch",
+                    home.display()
+                )
+                .as_str()
+            )
+        );
+        assert!(!tmp_dir.path().join("pwned").exists());
+
+        let bin = tmp_dir.path().join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        let calls = tmp_dir.path().join("calls");
+        fs::write(
+            bin.join("ranch"),
+            format!("#!/bin/sh\necho \"$*\" >> '{}'\n", calls.display()),
+        )
+        .unwrap();
+        fs::set_permissions(bin.join("ranch"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        let output = run(&format!("{}:/usr/bin:/bin", bin.display()));
+
+        assert!(output.status.success(), "{:?}", output);
+        let dir = home.join(".dotfiles");
+        assert_eq!(
+            fs::read_to_string(&calls).unwrap(),
+            format!("-C {}/ vim\n-C {}/ my zsh\n", dir.display(), dir.display())
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::paths;
+use crate::source;
+use crate::{stow, Args, LV_INFO};
+use std::io;
+use std::path::Path;
+
+/// Sets up a new machine in one go: clones the dotfiles repo at `url` into `into`, or else
+/// '~/.dotfiles', remembers it as 'dir' in the user's config so that later runs find it from
+/// anywhere, and deploys `packages` from it. An existing clone is fast-forwarded instead.
+pub fn clone(
+    args: &Args,
+    url: &str,
+    into: Option<&Path>,
+    packages: &[String],
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let dir = match into {
+        Some(dir) => dir.to_path_buf(),
+        None => paths::home_dir()
+            .ok_or_else(|| tr!("no-home-dir", text = "~/.dotfiles"))?
+            .join(".dotfiles"),
+    };
+    if args.changes_nothing() {
+        if args.verbosity("clone") >= LV_INFO {
+            _ = writeln!(stderr, "Would clone {} into {}", url, dir.display());
+        }
+        return Ok(());
+    }
+    source::clone_or_pull(args, url, &dir, stderr)?;
+    let dir = paths::canonicalize(&dir);
+    let remembered =
+        match paths::home_dir().and_then(|h| dir.strip_prefix(h).ok().map(Path::to_path_buf)) {
+            Some(rel) => format!("~/{}", rel.display()),
+            None => dir.display().to_string(),
+        };
+    Config::remember_dir(&remembered)?;
+    if args.verbosity("clone") >= LV_INFO {
+        _ = writeln!(
+            stderr,
+            "Remembered {} as the dotfiles directory",
+            remembered
+        );
+    }
+    let args = Args {
+        dir,
+        ..args.clone()
+    };
+    for package in packages {
+        stow(&args, package, stderr)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use crate::config::Config;
+use crate::paths;
+use crate::plan;
+use clap_complete::engine::CompletionCandidate;
+use std::env;
+use std::ffi::{OsStr, OsString};
+
+/// Completes a package name from the subdirectories of the 'DIR' on the command line being
+/// completed, or else '$RANCH_DIR' or the default 'DIR', as it would be when run.
+pub fn packages(current: &OsStr) -> Vec<CompletionCandidate> {
+    // The shell passes the words being completed after a '--'.
+    let words: Vec<OsString> = env::args_os().skip_while(|w| w != "--").skip(1).collect();
+    let dir = match dir_option(&words).or_else(|| env::var_os("RANCH_DIR")) {
+        Some(dir) => paths::expand_path(dir),
+        None => env::current_dir()
+            .map_err(|e| e.to_string())
+            .and_then(|cwd| paths::default_dir(&cwd, Config::load()?.dir.as_deref())),
+    };
+    let (Ok(dir), Some(current)) = (dir, current.to_str()) else {
+        return Vec::new();
+    };
+    plan::packages(&dir)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|p| p.starts_with(current))
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// The last '-C'/'--dir' value among `words`.
+fn dir_option(words: &[OsString]) -> Option<OsString> {
+    let mut dir = None;
+    for (i, word) in words.iter().enumerate() {
+        let value = match word.to_str() {
+            Some("-C" | "--dir") => words.get(i + 1).cloned(),
+            Some(w) => w
+                .strip_prefix("--dir=")
+                .or_else(|| w.strip_prefix("-C"))
+                .map(OsString::from),
+            None => None,
+        };
+        if value.as_ref().is_some_and(|v| !v.is_empty()) {
+            dir = value;
+        }
+    }
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dir_option() {
+        let words = |w: &[&str]| w.iter().map(OsString::from).collect::<Vec<_>>();
+        let dir = |d: &str| Some(OsString::from(d));
+        assert_eq!(dir_option(&words(&["ranch", "ho"])), None);
+        assert_eq!(dir_option(&words(&["ranch", "-C", "a", "ho"])), dir("a"));
+        assert_eq!(
+            dir_option(&words(&["ranch", "-Ca", "--dir=b", "ho"])),
+            dir("b")
+        );
+        assert_eq!(
+            dir_option(&words(&["ranch", "--dir", "c", "status", ""])),
+            dir("c")
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use crate::i18n::tr;
+use crate::{paths, ConflictResolution, Elevate, LinkMode, TypeConflict};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::path::PathBuf;
+
+/// User-level settings read from '$RANCH_CONFIG', or else '<config dir>/ranch/config.toml'.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Directory holding the packages, used when neither '-C' nor '$RANCH_DIR' is given and
+    /// no '.ranchrc' marks a repo around the current directory.
+    pub dir: Option<String>,
+
+    /// Where packages deploy to when neither '--target' nor a manifest says otherwise, in place
+    /// of 'DIR/..'.
+    pub target: Option<String>,
+
+    /// Conflict resolution used when '--exists' isn't given.
+    pub exists: Option<ConflictResolution>,
+
+    /// Policy for type conflicts used when '--type-conflict' isn't given.
+    pub type_conflict: Option<TypeConflict>,
+
+    /// How many times transient failures are retried when '--retries' isn't given.
+    pub retries: Option<u32>,
+
+    /// Tool to run changes that need root with when '--elevate' isn't given.
+    pub elevate: Option<Elevate>,
+
+    /// How package files are deployed when '--link-mode' isn't given.
+    pub link_mode: Option<LinkMode>,
+
+    /// Create links with relative paths, as with '--relative'; '--absolute' overrides it.
+    #[serde(default)]
+    pub relative_links: bool,
+
+    /// Named sets of packages deployed together with '--profile', such as a 'work' profile.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Vec<String>>,
+
+    /// Program and leading arguments of a command that prints the secret named by its final
+    /// argument, backing the `secret` template function; e.g. `["bw", "get", "password"]`.
+    pub secret_command: Option<Vec<String>>,
+
+    /// Shell commands run around every link and unlink operation.
+    #[serde(default)]
+    pub hooks: GlobalHooks,
+
+    /// Seconds a hook may run before it is killed and the operation fails; unlimited if unset.
+    pub hook_timeout: Option<u64>,
+
+    /// Always run hooks sandboxed, as with '--sandbox-hooks'.
+    #[serde(default)]
+    pub sandbox_hooks: bool,
+
+    /// Settings for 'ranch daemon'.
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+
+    /// How 'ranch daemon' and 'ranch watch' report failures and drift.
+    #[serde(default)]
+    pub notify: NotifyConfig,
+}
+
+/// `text`, a config file, with its top-level 'dir' set to `dir`: replaced if it's there, and
+/// otherwise added as the first line, ahead of any table.
+fn set_dir(text: &str, dir: &str) -> String {
+    let line = format!("dir = {}", toml::Value::from(dir));
+    let mut lines: Vec<&str> = text.lines().collect();
+    let top_level = lines
+        .iter()
+        .position(|l| l.trim_start().starts_with('['))
+        .unwrap_or(lines.len());
+    let existing = lines[..top_level].iter().position(|l| {
+        l.split_once('=')
+            .is_some_and(|(key, _)| key.trim() == "dir")
+    });
+    match existing {
+        Some(i) => lines[i] = &line,
+        None => lines.insert(0, &line),
+    }
+    lines.join("\n") + "\n"
+}
+
+/// The config's '[daemon]' table.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// Packages the daemon keeps deployed; defaults to every package in 'DIR'.
+    #[serde(default)]
+    pub packages: Vec<String>,
+    /// Path of the control socket.
+    pub socket: Option<String>,
+    /// Loopback address, such as '127.0.0.1:7878', on which to serve the HTTP API; off if unset.
+    pub http: Option<String>,
+    /// How often to check targets for drift: an interval such as '30m', or a cron expression such
+    /// as '0 9 * * 1-5'; off if unset.
+    pub check: Option<String>,
+}
+
+/// The config's '[notify]' table.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// Show a desktop notification when an unattended apply fails or files drift.
+    #[serde(default)]
+    pub desktop: bool,
+    /// URL that receives a JSON summary of every apply and delete via HTTP POST.
+    pub webhook: Option<String>,
+}
+
+/// Global hooks from the config's '[hooks]' table.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct GlobalHooks {
+    /// Run before any package hook or filesystem change.
+    #[serde(default)]
+    pub pre: Vec<String>,
+    /// Run after every filesystem change and package hook.
+    #[serde(default)]
+    pub post: Vec<String>,
+}
+
+impl Config {
+    /// Location of the user's config file, whether or not it exists.
+    pub fn path() -> Option<PathBuf> {
+        match env::var_os("RANCH_CONFIG") {
+            Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+            _ => Some(paths::config_dir()?.join("ranch").join("config.toml")),
+        }
+    }
+
+    /// Reads the user's config file, or returns the default config if there isn't one.
+    pub fn load() -> Result<Config, String> {
+        let Some(path) = Config::path().filter(|p| p.exists()) else {
+            return Ok(Config::default());
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| tr!("could-not-read", path = path.display(), error = e))?;
+        toml::from_str(&text).map_err(|e| tr!("invalid-config", path = path.display(), error = e))
+    }
+
+    /// Sets 'dir' in the user's config file to `dir`, creating the file if there isn't one.
+    /// The file is edited as text, so that its comments and layout survive.
+    pub fn remember_dir(dir: &str) -> Result<(), String> {
+        let path = Config::path().ok_or_else(|| tr!("no-config-path"))?;
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(tr!("could-not-read", path = path.display(), error = e)),
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| tr!("could-not-create-dir", path = parent.display(), error = e))?;
+        }
+        std::fs::write(&path, set_dir(&text, dir))
+            .map_err(|e| tr!("could-not-write", path = path.display(), error = e))
+    }
+
+    /// Packages in the profile `name`.
+    pub fn profile(&self, name: &str) -> Result<&[String], String> {
+        self.profiles
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| tr!("no-such-profile", profile = name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_dir() {
+        assert_eq!(set_dir("", "~/.dotfiles"), "dir = \"~/.dotfiles\"\n");
+        assert_eq!(
+            set_dir("# Mine\nretries = 2\n\n[hooks]\ndir = 1\n", "~/dots"),
+            "dir = \"~/dots\"\n# Mine\nretries = 2\n\n[hooks]\ndir = 1\n"
+        );
+        assert_eq!(
+            set_dir("target = \"~\"\ndir = \"/old\" # moved\n", "/new"),
+            "target = \"~\"\ndir = \"/new\"\n"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use crate::i18n::tr;
+use crate::plan::{self, Action};
+use crate::{load_package, Args, LV_WARN};
+use std::io;
+use std::path::Path;
+
+/// Prints a Dockerfile fragment to `stdout` that puts `package`'s files into an image as they'd
+/// be deployed, under `prefix` in place of the package's target: copies of package files and
+/// empty directories. It's built with 'DIR' as the context. Encrypted files and templates are
+/// left out, since anyone with the image could read the secrets in them.
+pub fn containerize(
+    args: &Args,
+    package: &str,
+    prefix: Option<&Path>,
+    chown: Option<&str>,
+    stdout: &mut dyn io::Write,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let (prefix_path, _, target_path) = load_package(args, package)?;
+    let actions = plan::plan(args, package, &prefix_path, &target_path, stderr)?;
+    let prefix = prefix.unwrap_or(&target_path);
+    let copy = match chown {
+        Some(owner) => format!("COPY --chown={}", owner),
+        None => String::from("COPY"),
+    };
+    let mut lines = vec![format!("# ranch containerize {}", package)];
+    for action in &actions {
+        let rel = action
+            .dst()
+            .strip_prefix(&target_path)
+            .unwrap_or(action.dst());
+        let dst = prefix.join(rel);
+        match action {
+            Action::Link { src, .. } => {
+                let src = src.strip_prefix(&args.dir).unwrap_or(src);
+                let slash = if action.src().is_dir() { "/" } else { "" };
+                lines.push(format!(
+                    "{} [{}, {}]",
+                    copy,
+                    json(&format!("{}{}", src.display(), slash)),
+                    json(&format!("{}{}", dst.display(), slash))
+                ));
+            }
+            Action::Decrypt { src, .. } | Action::Render { src, .. } => {
+                if args.verbosity("containerize") >= LV_WARN {
+                    _ = writeln!(
+                        stderr,
+                        "{}",
+                        tr!("containerize-secret", path = src.display())
+                    );
+                }
+            }
+            Action::Mkdir { .. } => {
+                lines.push(format!(
+                    "RUN [\"mkdir\", \"-p\", {}]",
+                    json(&dst.display().to_string())
+                ));
+                if let Some(owner) = chown {
+                    lines.push(format!(
+                        "RUN [\"chown\", {}, {}]",
+                        json(owner),
+                        json(&dst.display().to_string())
+                    ));
+                }
+            }
+        }
+    }
+    _ = writeln!(stdout, "{}", lines.join("\n"));
+    Ok(())
+}
+
+/// `s` as a JSON string, as Dockerfiles' exec form takes.
+fn json(s: &str) -> String {
+    serde_json::to_string(s).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_containerize() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("vim/.vim/colors")).unwrap();
+        fs::write(dir.join("vim/.vim/colors/dark.vim"), "").unwrap();
+        fs::write(dir.join("vim/my vimrc"), "set nu").unwrap();
+        fs::write(dir.join("vim/.netrc.tmpl"), "password {{ env \"PATH\" }}").unwrap();
+        fs::create_dir_all(dir.join("vim/.cache/vim")).unwrap();
+        fs::write(dir.join("vim/.cache/vim/.keep"), "").unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "vim"]);
+
+        let mut stdout = Vec::new();
+        containerize(
+            &args,
+            "vim",
+            Some(Path::new("/home/dev")),
+            Some("dev"),
+            &mut stdout,
+            &mut io::sink(),
+        )
+        .unwrap();
+        let fragment = String::from_utf8(stdout).unwrap();
+        let mut lines: Vec<_> = fragment.lines().collect();
+        lines.sort();
+        assert_eq!(
+            lines,
+            [
+                "# ranch containerize vim",
+                "COPY --chown=dev [\"vim/.vim/colors/dark.vim\", \"/home/dev/.vim/colors/dark.vim\"]",
+                "COPY --chown=dev [\"vim/my vimrc\", \"/home/dev/my vimrc\"]",
+                "RUN [\"chown\", \"dev\", \"/home/dev/.cache/vim\"]",
+                "RUN [\"mkdir\", \"-p\", \"/home/dev/.cache/vim\"]",
+            ]
+        );
+    }
+}
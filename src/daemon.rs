@@ -0,0 +1,524 @@
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::paths;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Where the daemon listens when neither '--socket' nor the config's 'daemon.socket' is set:
+/// '$XDG_RUNTIME_DIR/ranch.sock', or else 'ranch/ranch.sock' in the state directory.
+pub fn socket_path(args_socket: Option<&Path>, config: &Config) -> Result<PathBuf, String> {
+    if let Some(socket) = args_socket {
+        return Ok(socket.to_path_buf());
+    }
+    if let Some(socket) = &config.daemon.socket {
+        return paths::expand(socket).map(PathBuf::from);
+    }
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty()) {
+        return Ok(Path::new(&dir).join("ranch.sock"));
+    }
+    paths::state_dir()
+        .map(|d| d.join("ranch").join("ranch.sock"))
+        .ok_or_else(|| tr!("no-socket-path"))
+}
+
+#[cfg(not(unix))]
+pub fn daemon(
+    _args: &crate::Args,
+    _packages: &[String],
+    _socket: Option<&Path>,
+    _http: Option<&str>,
+    _stderr: &mut dyn std::io::Write,
+) -> Result<(), String> {
+    Err(tr!("daemon-requires-unix"))
+}
+
+#[cfg(not(unix))]
+pub fn ctl(_command: &str, _socket: Option<&Path>) -> Result<(), String> {
+    Err(tr!("daemon-requires-unix"))
+}
+
+#[cfg(unix)]
+pub use unix::{ctl, daemon};
+
+#[cfg(unix)]
+mod unix {
+    use super::socket_path;
+    use crate::config::Config;
+    use crate::http;
+    use crate::i18n::tr;
+    use crate::metrics::Metrics;
+    use crate::notification::Notifier;
+    use crate::plan::{self, Action};
+    use crate::rpc;
+    use crate::schedule::Schedule;
+    use crate::state::{DriftCheck, State};
+    use crate::status::PackageStatus;
+    use crate::watch::{self, Watched, DEBOUNCE};
+    use crate::{stow, Args, LV_INFO};
+    use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+    use serde_json::json;
+    use std::collections::BTreeSet;
+    use std::io::{self, BufRead, BufReader, Read, Write};
+    use std::net::TcpStream;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// Input to the daemon's main loop, which owns all state and handles one message at a time.
+    enum Msg {
+        Fs(notify::Result<notify::Event>),
+        /// A control command, and where to send its reply.
+        Control(String, Sender<String>),
+        /// An HTTP API request, and where to send its response.
+        Http(http::Request, Sender<http::Response>),
+    }
+
+    struct Daemon<'a> {
+        args: &'a Args,
+        /// Packages named on the command line, which take precedence over the config.
+        requested: &'a [String],
+        config: Config,
+        watcher: RecommendedWatcher,
+        /// Each watched package with its current plan, kept warm for status requests.
+        packages: Vec<(Watched, Vec<Action>)>,
+        metrics: Metrics,
+        notifier: Notifier,
+        /// When to check for drift, from the config's 'daemon.check'.
+        schedule: Option<Schedule>,
+        next_check: Option<SystemTime>,
+    }
+
+    impl Daemon<'_> {
+        /// Re-reads the config and package list, re-plans every package, and re-targets the
+        /// filesystem watches accordingly.
+        fn reload(&mut self) -> Result<(), String> {
+            self.config = Config::load()?;
+            self.notifier = Notifier::new(self.args, &self.config);
+            self.schedule = match &self.config.daemon.check {
+                Some(check) => Some(Schedule::parse(check)?),
+                None => None,
+            };
+            self.next_check = self
+                .schedule
+                .as_ref()
+                .map(|s| s.next_after(SystemTime::now()));
+            let names = if !self.requested.is_empty() {
+                self.requested.to_vec()
+            } else if !self.config.daemon.packages.is_empty() {
+                self.config.daemon.packages.clone()
+            } else {
+                plan::packages(&self.args.dir)?
+            };
+            for (w, _) in self.packages.drain(..) {
+                _ = self.watcher.unwatch(&w.prefix_path);
+            }
+            for name in names {
+                let w = Watched::new(self.args, &name)?;
+                self.watcher
+                    .watch(&w.prefix_path, RecursiveMode::Recursive)
+                    .map_err(|e| {
+                        tr!("could-not-watch", path = w.prefix_path.display(), error = e)
+                    })?;
+                let actions = self.plan(&w)?;
+                self.packages.push((w, actions));
+            }
+            Ok(())
+        }
+
+        fn plan(&self, w: &Watched) -> Result<Vec<Action>, String> {
+            plan::plan(
+                self.args,
+                &w.package,
+                &w.prefix_path,
+                &w.target_path,
+                &mut io::sink(),
+            )
+        }
+
+        fn status(&self) -> Vec<PackageStatus> {
+            self.packages
+                .iter()
+                .map(|(w, actions)| PackageStatus::new(self.args, &w.package, actions))
+                .collect()
+        }
+
+        /// Deploys every package, as a plain 'ranch PACKAGE' would.
+        fn apply(&mut self, out: &mut dyn io::Write) -> Result<(), String> {
+            let result = self.apply_all(out);
+            self.metrics.applied(&result);
+            match &result {
+                Ok(()) => self.notifier.drift(&self.status(), out),
+                Err(e) => self.notifier.failed(e, out),
+            }
+            result
+        }
+
+        fn apply_all(&mut self, out: &mut dyn io::Write) -> Result<(), String> {
+            for i in 0..self.packages.len() {
+                stow(self.args, &self.packages[i].0.package, out)?;
+                self.packages[i].1 = self.plan(&self.packages[i].0)?;
+            }
+            Ok(())
+        }
+
+        fn handle(&mut self, command: &str) -> String {
+            // Lines that look like JSON are JSON-RPC requests, as served by 'ranch rpc'.
+            if command.trim_start().starts_with('{') {
+                return rpc::handle(self.args, command).map_or_else(String::new, |r| r + "\n");
+            }
+            match command.trim() {
+                "status" => self.status().iter().map(|s| s.to_string()).collect(),
+                "apply" => {
+                    let mut out = Vec::new();
+                    let result = self.apply(&mut out);
+                    let out = String::from_utf8_lossy(&out);
+                    match result {
+                        Ok(()) => format!("{}ok\n", out),
+                        Err(e) => format!("{}error: {}\n", out, e),
+                    }
+                }
+                "reload" => match self.reload() {
+                    Ok(()) => String::from("ok\n"),
+                    Err(e) => format!("error: {}\n", e),
+                },
+                other => format!(
+                    "error: {}\n",
+                    tr!("daemon-unknown-command", command = other)
+                ),
+            }
+        }
+
+        /// Compares every package with its target, notifying about and recording any drift.
+        fn check(&mut self, stderr: &mut dyn io::Write) -> Result<(), String> {
+            let now = SystemTime::now();
+            self.next_check = self.schedule.as_ref().map(|s| s.next_after(now));
+            let statuses = self.status();
+            if self.args.verbosity("daemon") >= LV_INFO {
+                let drifted: usize = statuses
+                    .iter()
+                    .map(|s| s.missing.len() + s.conflicts.len())
+                    .sum();
+                _ = writeln!(stderr, "Drift check: {} file(s) drifted", drifted);
+            }
+            self.notifier.drift(&statuses, stderr);
+            let mut state = State::load(self.args)?;
+            state.last_check = Some(DriftCheck {
+                checked_at: now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+                packages: statuses,
+            });
+            state.save(self.args)
+        }
+
+        /// Waits for the next message, running drift checks as they come due.
+        fn recv(
+            &mut self,
+            rx: &mpsc::Receiver<Msg>,
+            stderr: &mut dyn io::Write,
+        ) -> Result<Msg, String> {
+            let shut_down = || tr!("daemon-shut-down");
+            loop {
+                let Some(at) = self.next_check else {
+                    return rx.recv().map_err(|_| shut_down());
+                };
+                let timeout = at.duration_since(SystemTime::now()).unwrap_or_default();
+                match rx.recv_timeout(timeout) {
+                    Ok(msg) => return Ok(msg),
+                    Err(RecvTimeoutError::Timeout) => {
+                        if let Err(e) = self.check(stderr) {
+                            _ = writeln!(stderr, "{}", tr!("error", error = e));
+                        }
+                    }
+                    Err(RecvTimeoutError::Disconnected) => return Err(shut_down()),
+                }
+            }
+        }
+
+        /// Answers a request for the HTTP API.
+        fn route(&mut self, request: &http::Request) -> http::Response {
+            match (request.method.as_str(), request.path.as_str()) {
+                ("GET", "/status") => http::Response::json(200, &self.status()),
+                ("GET", "/metrics") => http::Response {
+                    status: 200,
+                    content_type: "text/plain; version=0.0.4",
+                    body: self.metrics.render(&self.status()),
+                },
+                ("GET", "/drift") => {
+                    let drifted: Vec<_> = self
+                        .status()
+                        .into_iter()
+                        .filter(|s| !s.missing.is_empty() || !s.conflicts.is_empty())
+                        .collect();
+                    http::Response::json(200, &drifted)
+                }
+                ("POST", "/apply") => {
+                    let mut log = Vec::new();
+                    let result = self.apply(&mut log);
+                    let log = String::from_utf8_lossy(&log);
+                    match result {
+                        Ok(()) => http::Response::json(200, &json!({ "log": log })),
+                        Err(e) => http::Response::json(500, &json!({ "log": log, "error": e })),
+                    }
+                }
+                (_, "/status" | "/drift" | "/metrics" | "/apply") => {
+                    http::Response::text(405, "Method not allowed")
+                }
+                _ => http::Response::text(404, "Not found"),
+            }
+        }
+
+        /// Replies to a control command or HTTP request.
+        fn answer(&mut self, msg: Msg) {
+            match msg {
+                Msg::Control(command, reply) => _ = reply.send(self.handle(&command)),
+                Msg::Http(request, reply) => _ = reply.send(self.route(&request)),
+                Msg::Fs(_) => {}
+            }
+        }
+
+        /// Mirrors changed package paths into the target, then re-plans affected packages.
+        fn sync(&mut self, paths: BTreeSet<PathBuf>, stderr: &mut dyn io::Write) {
+            for path in paths {
+                let Some(i) = self
+                    .packages
+                    .iter()
+                    .position(|(w, _)| path.starts_with(&w.prefix_path))
+                else {
+                    continue;
+                };
+                let w = &self.packages[i].0;
+                if let Err(e) = watch::sync_path(self.args, &self.config, w, &path, stderr) {
+                    self.metrics.errors += 1;
+                    _ = writeln!(stderr, "{}", tr!("error", error = e));
+                    self.notifier.failed(&e, stderr);
+                }
+                match self.plan(&self.packages[i].0) {
+                    Ok(actions) => self.packages[i].1 = actions,
+                    Err(e) => _ = writeln!(stderr, "{}", tr!("error", error = e)),
+                }
+            }
+        }
+    }
+
+    /// Runs until killed: watches packages like 'ranch watch', and answers 'status', 'apply', and
+    /// 'reload' commands, or JSON-RPC requests, sent one per connection over a Unix socket.
+    /// Optionally serves the same over a small HTTP API bound to localhost, and checks for drift
+    /// on a schedule. HTTP requests must carry the token the daemon writes next to the socket
+    /// (as 'ranch.token' beside 'ranch.sock'), readable only by its owner, as a bearer token.
+    pub fn daemon(
+        args: &Args,
+        packages: &[String],
+        socket: Option<&Path>,
+        http: Option<&str>,
+        stderr: &mut dyn io::Write,
+    ) -> Result<(), String> {
+        let config = Config::load()?;
+        let socket = socket_path(socket, &config)?;
+        let listener = bind(&socket)?;
+        if args.verbosity("daemon") >= LV_INFO {
+            _ = writeln!(stderr, "Listening on {}", socket.display());
+        }
+        let http_listener = match http.or(config.daemon.http.as_deref()) {
+            Some(addr) => {
+                let listener = http::bind(addr)?;
+                let token_path = socket.with_extension("token");
+                let token = write_token(&token_path)?;
+                if args.verbosity("daemon") >= LV_INFO {
+                    _ = writeln!(
+                        stderr,
+                        "Serving the HTTP API on http://{}, with the token in {}",
+                        addr,
+                        token_path.display()
+                    );
+                }
+                Some((listener, token))
+            }
+            None => None,
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let fs_tx = tx.clone();
+        let watcher = notify::recommended_watcher(move |e| _ = fs_tx.send(Msg::Fs(e)))
+            .map_err(|e| tr!("could-not-start-watching", error = e))?;
+        if let Some((listener, token)) = http_listener {
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming().map_while(Result::ok) {
+                    let (tx, token) = (tx.clone(), token.clone());
+                    std::thread::spawn(move || serve_http(stream, tx, &token));
+                }
+            });
+        }
+        std::thread::spawn(move || {
+            for stream in listener.incoming().map_while(Result::ok) {
+                let tx = tx.clone();
+                std::thread::spawn(move || serve(stream, tx));
+            }
+        });
+
+        let notifier = Notifier::new(args, &config);
+        let mut daemon = Daemon {
+            args,
+            requested: packages,
+            config,
+            watcher,
+            packages: Vec::new(),
+            metrics: Metrics::default(),
+            notifier,
+            schedule: None,
+            next_check: None,
+        };
+        daemon.reload()?;
+        if let Err(e) = daemon.apply(stderr) {
+            _ = writeln!(stderr, "{}", tr!("error", error = e));
+        }
+
+        loop {
+            let msg = daemon.recv(&rx, stderr)?;
+            let Msg::Fs(event) = msg else {
+                daemon.answer(msg);
+                continue;
+            };
+            // Debounce bursts of changes, deferring any commands that arrive meanwhile.
+            let mut paths = BTreeSet::new();
+            let mut deferred = Vec::new();
+            watch::collect(args, event, &mut paths, stderr);
+            while let Ok(msg) = rx.recv_timeout(DEBOUNCE) {
+                match msg {
+                    Msg::Fs(event) => watch::collect(args, event, &mut paths, stderr),
+                    msg => deferred.push(msg),
+                }
+            }
+            daemon.sync(paths, stderr);
+            for msg in deferred {
+                daemon.answer(msg);
+            }
+        }
+    }
+
+    /// Binds the control socket, replacing a stale one left by a daemon that died, but refusing
+    /// to steal the socket of one that's still running.
+    fn bind(socket: &Path) -> Result<UnixListener, String> {
+        if socket.exists() {
+            if UnixStream::connect(socket).is_ok() {
+                return Err(tr!("daemon-already-listening", socket = socket.display()));
+            }
+            std::fs::remove_file(socket)
+                .map_err(|e| tr!("could-not-remove-stale", path = socket.display(), error = e))?;
+        }
+        if let Some(parent) = socket.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| tr!("could-not-create-dir", path = parent.display(), error = e))?;
+        }
+        let listener = UnixListener::bind(socket)
+            .map_err(|e| tr!("could-not-listen", address = socket.display(), error = e))?;
+        // Anyone who can connect can trigger an apply; keep it to the owner.
+        std::fs::set_permissions(socket, std::os::unix::fs::PermissionsExt::from_mode(0o600))
+            .map_err(|e| tr!("could-not-secure", path = socket.display(), error = e))?;
+        Ok(listener)
+    }
+
+    /// Reads one command line from `stream`, hands it to the main loop, and writes back the reply.
+    fn serve(stream: UnixStream, tx: Sender<Msg>) {
+        let mut command = String::new();
+        if BufReader::new(&stream).read_line(&mut command).is_err() {
+            return;
+        }
+        let (reply_tx, reply_rx) = mpsc::channel();
+        if tx.send(Msg::Control(command, reply_tx)).is_err() {
+            return;
+        }
+        if let Ok(reply) = reply_rx.recv() {
+            _ = (&stream).write_all(reply.as_bytes());
+        }
+    }
+
+    /// Writes a new random token for the HTTP API to `path`, readable only by the owner, and
+    /// returns it.
+    fn write_token(path: &Path) -> Result<String, String> {
+        let mut bytes = [0u8; 32];
+        std::fs::File::open("/dev/urandom")
+            .and_then(|mut f| f.read_exact(&mut bytes))
+            .map_err(|e| tr!("could-not-read", path = "/dev/urandom", error = e))?;
+        let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        // Start afresh, so that a file someone else made (or a link) isn't written through.
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(tr!("could-not-remove", path = path.display(), error = e));
+            }
+            _ => {}
+        }
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)
+            .and_then(|mut f| f.write_all(token.as_bytes()))
+            .map_err(|e| tr!("could-not-write", path = path.display(), error = e))?;
+        Ok(token)
+    }
+
+    /// Reads one HTTP request from `stream`, hands it to the main loop if it's addressed to
+    /// loopback and carries `token`, and writes back the response. A client gets [http::TIMEOUT]
+    /// to send its request and to take the response.
+    fn serve_http(mut stream: TcpStream, tx: Sender<Msg>, token: &str) {
+        if stream.set_read_timeout(Some(http::TIMEOUT)).is_err()
+            || stream.set_write_timeout(Some(http::TIMEOUT)).is_err()
+        {
+            return;
+        }
+        let response = match http::read_request(&stream) {
+            Ok(request) if !request.is_to_loopback() => http::Response::text(403, "Forbidden"),
+            Ok(request) if !request.has_token(token) => http::Response::text(401, "Unauthorized"),
+            Ok(request) => {
+                let (reply_tx, reply_rx) = mpsc::channel();
+                if tx.send(Msg::Http(request, reply_tx)).is_err() {
+                    return;
+                }
+                match reply_rx.recv() {
+                    Ok(response) => response,
+                    Err(_) => return,
+                }
+            }
+            Err(e) => http::Response::text(400, &e),
+        };
+        _ = response.write(&mut stream);
+    }
+
+    /// Sends `command` to a running daemon and prints its reply to stdout.
+    pub fn ctl(command: &str, socket: Option<&Path>) -> Result<(), String> {
+        let socket = socket_path(socket, &Config::load()?)?;
+        let mut stream = UnixStream::connect(&socket).map_err(|e| {
+            tr!(
+                "could-not-connect-daemon",
+                socket = socket.display(),
+                error = e
+            )
+        })?;
+        writeln!(stream, "{}", command)
+            .and_then(|_| stream.shutdown(std::net::Shutdown::Write))
+            .map_err(|e| tr!("could-not-send-daemon", command = command, error = e))?;
+        let mut reply = String::new();
+        stream
+            .read_to_string(&mut reply)
+            .map_err(|e| tr!("could-not-read-daemon-reply", error = e))?;
+        // Replies to 'apply' and 'reload' end with an 'ok' or 'error: ...' line.
+        let body = reply.trim_end_matches('\n');
+        let (head, last) = body.rsplit_once('\n').unwrap_or(("", body));
+        let head = if head.is_empty() {
+            head.to_owned()
+        } else {
+            head.to_owned() + "\n"
+        };
+        if let Some(e) = last.strip_prefix("error: ") {
+            print!("{}", head);
+            return Err(String::from(e));
+        }
+        match last == "ok" {
+            true => print!("{}", head),
+            false => print!("{}", reply),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+use crate::filesystem::quote;
+use crate::git;
+use crate::i18n::tr;
+use crate::sftp;
+use crate::Args;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+
+/// Where ranch is commonly installed without being on the PATH of a non-interactive SSH session.
+const REMOTE_PATH: &str = "$PATH:$HOME/.cargo/bin:$HOME/.local/bin:/usr/local/bin";
+
+/// How a host gets the packages.
+enum Transfer {
+    /// Copied from 'DIR' as a tar archive.
+    Copy,
+    /// Cloned from, or pulled from, the URL of the repo holding 'DIR', with 'DIR' at the path
+    /// within it.
+    Clone(String, PathBuf),
+}
+
+/// Deploys `packages` on each of `hosts` in parallel: gets them into `remote_dir` there, relative
+/// to the home directory, by copying them or with `clone` by cloning the repo holding 'DIR',
+/// then runs ranch there to link them. Prints what each host's ranch printed, then one line per
+/// host saying how it went. When this run changes nothing, such as with '--dry-run' or
+/// '--check', the packages aren't sent; ranch there previews what's already in `remote_dir`.
+/// The hosts need ranch, but not the packages; see '--backend sftp' for hosts without ranch.
+pub fn deploy(
+    args: &Args,
+    hosts: &[String],
+    packages: &[String],
+    remote_dir: &str,
+    clone: bool,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    for package in packages {
+        if !args.dir.join(package).is_dir() {
+            return Err(tr!("no-such-package", package = package));
+        }
+    }
+    let transfer = match clone {
+        true => {
+            let (url, prefix) = git::origin(&args.dir)?;
+            Transfer::Clone(url, prefix)
+        }
+        false => Transfer::Copy,
+    };
+    let archive = match transfer {
+        Transfer::Copy if !args.changes_nothing() => archive(args, packages)?,
+        _ => Vec::new(),
+    };
+    let results: Vec<Result<String, String>> = thread::scope(|scope| {
+        let handles: Vec<_> = hosts
+            .iter()
+            .map(|host| {
+                let (transfer, archive) = (&transfer, &archive);
+                scope.spawn(move || deploy_to(args, host, packages, remote_dir, transfer, archive))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    });
+    for (host, result) in hosts.iter().zip(&results) {
+        let output = match result {
+            Ok(output) => output,
+            Err(error) => error,
+        };
+        for line in output.lines() {
+            _ = writeln!(stderr, "{}: {}", host, line);
+        }
+    }
+    let mut failed = 0;
+    for (host, result) in hosts.iter().zip(&results) {
+        match result {
+            Ok(_) => _ = writeln!(stderr, "{}", tr!("deploy-host-ok", host = host)),
+            Err(_) => {
+                failed += 1;
+                _ = writeln!(stderr, "{}", tr!("deploy-host-failed", host = host));
+            }
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(tr!("deploy-failed", failed = failed, hosts = hosts.len())),
+    }
+}
+
+/// A tar archive of `packages` in 'DIR'.
+fn archive(args: &Args, packages: &[String]) -> Result<Vec<u8>, String> {
+    let output = Command::new("tar")
+        .arg("-C")
+        .arg(&args.dir)
+        .arg("-cf")
+        .arg("-")
+        .arg("--")
+        .args(packages)
+        .output()
+        .map_err(|e| tr!("could-not-run", program = "tar", error = e))?;
+    match output.status.success() {
+        true => Ok(output.stdout),
+        false => Err(tr!(
+            "program-failed",
+            program = "tar",
+            output = String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+/// Gets the packages onto `host`, and links them there; returns everything ranch printed there.
+fn deploy_to(
+    args: &Args,
+    host: &str,
+    packages: &[String],
+    remote_dir: &str,
+    transfer: &Transfer,
+    archive: &[u8],
+) -> Result<String, String> {
+    let fail = |e: io::Error| tr!("deploy-could-not-transfer", host = host, error = e);
+    let fetch = transfer_script(remote_dir, transfer);
+    let mut text = match args.changes_nothing() {
+        true => format!("{}\n", tr!("deploy-would-transfer", script = fetch)),
+        false => {
+            sftp::ssh(host, &fetch, archive).map_err(fail)?;
+            String::new()
+        }
+    };
+    let script = link_script(args, remote_dir, transfer, packages);
+    let output = sftp::ssh_command(host, &script)
+        .output()
+        .map_err(|e| tr!("could-not-run", program = "ssh", error = e))?;
+    text.push_str(&String::from_utf8_lossy(&output.stdout));
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    match output.status.success() {
+        true => Ok(text),
+        false => Err(text),
+    }
+}
+
+/// A script that gets the packages into `remote_dir`, reading a tar archive of them from
+/// standard input when copying.
+fn transfer_script(remote_dir: &str, transfer: &Transfer) -> String {
+    let remote_dir = quote(remote_dir);
+    match transfer {
+        Transfer::Copy => format!("mkdir -p {remote_dir} && tar -C {remote_dir} -xf -"),
+        Transfer::Clone(url, _) => format!(
+            "if [ -d {remote_dir}/.git ]; then \
+             git -C {remote_dir} pull --quiet --ff-only --recurse-submodules; \
+             else git clone --quiet --recurse-submodules -- {} {remote_dir}; fi",
+            quote(url)
+        ),
+    }
+}
+
+/// A script that runs ranch on the host once for each of `packages`, linking it from
+/// `remote_dir`, dry or checking if this run is, and stopping at the first that fails.
+fn link_script(args: &Args, remote_dir: &str, transfer: &Transfer, packages: &[String]) -> String {
+    let dir = match transfer {
+        Transfer::Copy => PathBuf::from(remote_dir),
+        Transfer::Clone(_, prefix) => Path::new(remote_dir).join(prefix),
+    };
+    let dir: PathBuf = dir.components().collect();
+    let mut words = vec![
+        String::from("ranch"),
+        String::from("-C"),
+        quote(&dir.to_string_lossy()),
+    ];
+    if args.dry_run {
+        words.push(String::from("--dry-run"));
+    }
+    if args.check {
+        words.push(String::from("--check"));
+    }
+    if args.verbose > 0 {
+        words.push(format!("-{}", "v".repeat(args.verbose.into())));
+    }
+    let ranch = words.join(" ");
+    let commands: Vec<String> = packages
+        .iter()
+        .map(|package| format!("{} {}", ranch, quote(package)))
+        .collect();
+    format!(
+        "export PATH=\"{}\" && {}",
+        REMOTE_PATH,
+        commands.join(" && ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_scripts() {
+        assert_eq!(
+            transfer_script(".dotfiles", &Transfer::Copy),
+            "mkdir -p .dotfiles && tar -C .dotfiles -xf -"
+        );
+        let clone = Transfer::Clone(
+            String::from("https://example.com/alice/dotfiles.git"),
+            PathBuf::from("home/"),
+        );
+        assert!(transfer_script(".dotfiles", &clone).ends_with(
+            "git clone --quiet --recurse-submodules -- \
+             https://example.com/alice/dotfiles.git .dotfiles; fi"
+        ));
+
+        let args = Args::parse_from(["ranch", "--dry-run", "home"]);
+        let packages = [String::from("shell"), String::from("my vim")];
+        assert_eq!(
+            link_script(&args, ".dotfiles", &clone, &packages),
+            format!(
+                "export PATH=\"{}\" && ranch -C .dotfiles/home --dry-run shell && \
+                 ranch -C .dotfiles/home --dry-run 'my vim'",
+                REMOTE_PATH
+            )
+        );
+        let args = Args::parse_from(["ranch", "--check", "home"]);
+        assert!(link_script(&args, ".dotfiles", &clone, &packages[..1])
+            .ends_with("ranch -C .dotfiles/home --check shell"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_link_script_runs_ranch_per_package() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = tempdir::TempDir::new("deploy").unwrap();
+        let ranch = tmp_dir.path().join("ranch");
+        let calls = tmp_dir.path().join("calls");
+        std::fs::write(
+            &ranch,
+            format!("#!/bin/sh\necho \"$*\" >> '{}'\n", calls.display()),
+        )
+        .unwrap();
+        std::fs::set_permissions(&ranch, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let args = Args::parse_from(["ranch", "home"]);
+        let packages = [String::from("shell"), String::from("my vim")];
+        let script = link_script(&args, ".dotfiles", &Transfer::Copy, &packages);
+
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&script)
+            .env(
+                "PATH",
+                format!("{}:/usr/bin:/bin", tmp_dir.path().display()),
+            )
+            .status()
+            .unwrap();
+
+        assert!(status.success());
+        assert_eq!(
+            std::fs::read_to_string(&calls).unwrap(),
+            "-C .dotfiles shell\n-C .dotfiles my vim\n"
+        );
+    }
+}
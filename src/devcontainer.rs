@@ -0,0 +1,250 @@
+use crate::bootstrap::INSTALL;
+use crate::filesystem::quote;
+use crate::git;
+use crate::i18n::tr;
+use crate::{Args, LV_INFO};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Marks bootstrap scripts as ranch's own, so that other scripts are never overwritten or
+/// removed.
+const MARKER: &str = "# Installed by 'ranch devcontainer install'.";
+
+/// Where the bootstrap script goes, relative to the project.
+const BOOTSTRAP: &str = ".devcontainer/ranch-bootstrap.sh";
+
+/// The devcontainer's 'postCreateCommand', which runs in the project directory.
+const POST_CREATE: &str = "\"postCreateCommand\": \"sh .devcontainer/ranch-bootstrap.sh\"";
+
+/// Body of the bootstrap script; '@URL@' is replaced with the quoted URL of the dotfiles repo,
+/// '@DIR@' with the quoted path of 'DIR' in a clone of it, '@INSTALL@' with the command that
+/// installs ranch, and '@PACKAGES@' with the quoted packages.
+const SCRIPT: &str = r#"# Clones the dotfiles repo and deploys its packages when the container is created.
+set -e
+repo="$HOME/.dotfiles"
+[ -d "$repo" ] || git clone --quiet --recurse-submodules -- @URL@ "$repo"
+export PATH="$PATH:$HOME/.cargo/bin:$HOME/.local/bin"
+if ! command -v ranch >/dev/null 2>&1; then
+    command -v cargo >/dev/null 2>&1 || {
+        echo "ranch: neither ranch nor cargo is installed in the container" >&2
+        exit 1
+    }
+    @INSTALL@
+fi
+for package in @PACKAGES@; do
+    ranch -C "$repo"/@DIR@ "$package"
+done
+"#;
+
+/// A devcontainer.json for projects without one.
+const MINIMAL: &str = r#"{
+    "image": "mcr.microsoft.com/devcontainers/base:ubuntu"
+}
+"#;
+
+/// Sets up the devcontainer of the project at `project` to clone the repo holding 'DIR' from
+/// its 'origin' and deploy `packages` from it when the container is created: writes a bootstrap
+/// script, and points the 'postCreateCommand' of the project's devcontainer.json at it, creating
+/// a minimal one if there's none. The file is edited as text, so that its comments survive.
+pub fn install(
+    args: &Args,
+    project: &Path,
+    packages: &[String],
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let (url, prefix) = git::origin(&args.dir)?;
+    let script = script(&url, &prefix, packages);
+    let bootstrap = project.join(BOOTSTRAP);
+    if !is_ours(&bootstrap)? {
+        return Err(tr!(
+            "devcontainer-script-exists",
+            path = bootstrap.display()
+        ));
+    }
+    let config = config_path(project);
+    let text = match fs::read_to_string(&config) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::from(MINIMAL),
+        Err(e) => return Err(tr!("could-not-read", path = config.display(), error = e)),
+    };
+    let text = match text.find("\"postCreateCommand\"") {
+        None => add_post_create(&text)
+            .ok_or_else(|| tr!("devcontainer-not-an-object", path = config.display()))?,
+        Some(_) if text.contains(POST_CREATE) => text,
+        Some(_) => {
+            return Err(tr!(
+                "devcontainer-has-post-create",
+                path = config.display(),
+                script = BOOTSTRAP
+            ))
+        }
+    };
+    for (path, contents) in [(&bootstrap, &script), (&config, &text)] {
+        if args.verbosity("devcontainer") >= LV_INFO {
+            _ = writeln!(stderr, "Writing {}", path.display());
+        }
+        if args.changes_nothing() {
+            continue;
+        }
+        let parent = path.parent().unwrap_or(project);
+        fs::create_dir_all(parent)
+            .map_err(|e| tr!("could-not-create-dir", path = parent.display(), error = e))?;
+        fs::write(path, contents)
+            .map_err(|e| tr!("could-not-write", path = path.display(), error = e))?;
+    }
+    Ok(())
+}
+
+/// Undoes [install]: removes the bootstrap script and the 'postCreateCommand' that runs it,
+/// leaving the rest of the devcontainer alone.
+pub fn uninstall(args: &Args, project: &Path, stderr: &mut dyn io::Write) -> Result<(), String> {
+    let config = config_path(project);
+    if let Ok(text) = fs::read_to_string(&config) {
+        let kept = remove_post_create(&text);
+        if kept != text {
+            if args.verbosity("devcontainer") >= LV_INFO {
+                _ = writeln!(stderr, "Writing {}", config.display());
+            }
+            if !args.changes_nothing() {
+                fs::write(&config, kept)
+                    .map_err(|e| tr!("could-not-write", path = config.display(), error = e))?;
+            }
+        }
+    }
+    let bootstrap = project.join(BOOTSTRAP);
+    if bootstrap.exists() && is_ours(&bootstrap)? {
+        if args.verbosity("devcontainer") >= LV_INFO {
+            _ = writeln!(stderr, "Removing {}", bootstrap.display());
+        }
+        if !args.changes_nothing() {
+            fs::remove_file(&bootstrap)
+                .map_err(|e| tr!("could-not-remove", path = bootstrap.display(), error = e))?;
+        }
+    }
+    Ok(())
+}
+
+/// The bootstrap script, which clones `url` and deploys `packages` from `prefix` within it.
+fn script(url: &str, prefix: &Path, packages: &[String]) -> String {
+    let prefix = prefix.to_string_lossy();
+    let packages: Vec<String> = packages.iter().map(|p| quote(p)).collect();
+    format!(
+        "#!/bin/sh\n{}\n{}",
+        MARKER,
+        SCRIPT
+            .replace("@URL@", &quote(url))
+            .replace("@DIR@", &quote(prefix.trim_end_matches('/')))
+            .replace("@INSTALL@", INSTALL)
+            .replace("@PACKAGES@", &packages.join(" "))
+    )
+}
+
+/// The project's devcontainer.json: '.devcontainer.json' if that's what it uses, and otherwise
+/// '.devcontainer/devcontainer.json'.
+fn config_path(project: &Path) -> PathBuf {
+    let root = project.join(".devcontainer.json");
+    match root.exists() {
+        true => root,
+        false => project.join(".devcontainer/devcontainer.json"),
+    }
+}
+
+fn is_ours(path: &Path) -> Result<bool, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(text.contains(MARKER)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(true),
+        Err(e) => Err(tr!("could-not-read", path = path.display(), error = e)),
+    }
+}
+
+/// `text` with [POST_CREATE] as the first member of its top-level object; None if it has none.
+fn add_post_create(text: &str) -> Option<String> {
+    let brace = text.find('{')?;
+    let (head, tail) = text.split_at(brace + 1);
+    let comma = match tail.trim_start().starts_with('}') {
+        true => "",
+        false => ",",
+    };
+    Some(format!("{}\n    {}{}{}", head, POST_CREATE, comma, tail))
+}
+
+/// `text` without the line [add_post_create] added.
+fn remove_post_create(text: &str) -> String {
+    for line in [
+        format!("\n    {},", POST_CREATE),
+        format!("\n    {}", POST_CREATE),
+    ] {
+        if text.contains(&line) {
+            return text.replacen(&line, "", 1);
+        }
+    }
+    text.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_post_create() {
+        let text = "// Dev image\n{\n    \"image\": \"rust:1\" // pinned\n}\n";
+        let added = add_post_create(text).unwrap();
+        assert_eq!(
+            added,
+            "// Dev image\n{\n    \
+             \"postCreateCommand\": \"sh .devcontainer/ranch-bootstrap.sh\",\n    \
+             \"image\": \"rust:1\" // pinned\n}\n"
+        );
+        assert_eq!(remove_post_create(&added), text);
+        let empty = add_post_create("{}").unwrap();
+        assert!(serde_json::from_str::<serde_json::Value>(&empty).is_ok());
+        assert_eq!(remove_post_create(&empty), "{}");
+        assert_eq!(add_post_create("[]"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_script_deploys_each_package() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::process::Command;
+
+        let tmp_dir = tempdir::TempDir::new("alice").unwrap();
+        let bin = tmp_dir.path().join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        fs::create_dir_all(tmp_dir.path().join(".dotfiles")).unwrap();
+        let calls = tmp_dir.path().join("calls");
+        fs::write(
+            bin.join("ranch"),
+            format!("#!/bin/sh\necho \"$*\" >> '{}'\n", calls.display()),
+        )
+        .unwrap();
+        fs::set_permissions(bin.join("ranch"), fs::Permissions::from_mode(0o755)).unwrap();
+        let packages = [String::from("shell"), String::from("my vim")];
+        let script = script(
+            "https://example.com/alice/dotfiles.git",
+            Path::new("home/"),
+            &packages,
+        );
+
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&script)
+            .env("HOME", tmp_dir.path())
+            .env("PATH", format!("{}:/usr/bin:/bin", bin.display()))
+            .status()
+            .unwrap();
+
+        assert!(status.success());
+        let repo = tmp_dir.path().join(".dotfiles");
+        assert_eq!(
+            fs::read_to_string(&calls).unwrap(),
+            format!(
+                "-C {}/home shell\n-C {}/home my vim\n",
+                repo.display(),
+                repo.display()
+            )
+        );
+        assert!(script.contains(&format!("--git {} ranch", env!("CARGO_PKG_REPOSITORY"))));
+    }
+}
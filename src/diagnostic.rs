@@ -0,0 +1,154 @@
+use crate::filesystem::Kind;
+use crate::i18n::tr;
+use crate::Args;
+use std::fmt;
+use std::path::{Component, Path};
+
+/// An error explained in full, in the manner of miette's reports: what went wrong, the facts
+/// behind it, and how to fix it. It renders into the error message that ranch prints, as
+///
+/// ```text
+/// ~/.vimrc already exists
+///   ├─ target: /home/alice/.vimrc
+///   ╰─ occupied by: a file of 120 bytes
+///   help: run 'ranch --exists adopt vim' to move it into the package
+/// ```
+#[derive(Debug)]
+pub struct Diagnostic {
+    message: String,
+    labels: Vec<(String, String)>,
+    help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(message: String) -> Diagnostic {
+        Diagnostic {
+            message,
+            labels: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    /// Adds the fact that `name` is `value`.
+    pub fn label(mut self, name: String, value: impl fmt::Display) -> Diagnostic {
+        self.labels.push((name, value.to_string()));
+        self
+    }
+
+    /// Adds a way to fix the error.
+    pub fn help(mut self, hint: String) -> Diagnostic {
+        self.help.push(hint);
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for (i, (name, value)) in self.labels.iter().enumerate() {
+            let branch = if i + 1 == self.labels.len() {
+                '╰'
+            } else {
+                '├'
+            };
+            write!(f, "\n  {}─ {}: {}", branch, name, value)?;
+        }
+        for hint in &self.help {
+            write!(f, "\n  {}", tr!("diagnostic-help", hint = hint))?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Diagnostic> for String {
+    fn from(diagnostic: Diagnostic) -> String {
+        diagnostic.to_string()
+    }
+}
+
+/// The package in 'DIR' that `path` belongs to, if any.
+pub fn package_of(args: &Args, path: &Path) -> Option<String> {
+    match path.strip_prefix(&args.dir).ok()?.components().next()? {
+        Component::Normal(package) => Some(package.to_string_lossy().into_owned()),
+        _ => None,
+    }
+}
+
+/// Describes what's at `path`, such as "a directory", or "a link into package vim" when another
+/// package's link is in the way.
+pub fn describe_occupant(args: &Args, path: &Path) -> String {
+    match args.fs.kind(path) {
+        None => tr!("occupant-nothing"),
+        Some(Kind::Symlink) => {
+            let target = args.fs.read_link(path).unwrap_or_default();
+            let resolved = path.parent().unwrap_or(path).join(&target);
+            match package_of(args, &resolved) {
+                Some(package) => tr!(
+                    "occupant-package-link",
+                    package = package,
+                    target = target.display()
+                ),
+                None => tr!("occupant-link", target = target.display()),
+            }
+        }
+        Some(Kind::Dir) => tr!("occupant-dir"),
+        Some(Kind::File) => tr!(
+            "occupant-file",
+            size = args.fs.size(path).unwrap_or_default()
+        ),
+        Some(Kind::Other) => tr!("occupant-special"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_diagnostic() {
+        let diagnostic = Diagnostic::new(String::from("~/.vimrc already exists"))
+            .label(String::from("target"), "/home/alice/.vimrc")
+            .label(String::from("occupied by"), "a directory")
+            .help(String::from("move it"));
+        assert_eq!(
+            diagnostic.to_string(),
+            "~/.vimrc already exists\n  \
+             ├─ target: /home/alice/.vimrc\n  \
+             ╰─ occupied by: a directory\n  \
+             help: move it"
+        );
+    }
+
+    #[test]
+    fn test_describe_occupant() {
+        let tmp = TempDir::new("ranch").unwrap();
+        let dir = tmp.path().join("dotfiles");
+        fs::create_dir_all(dir.join("vim")).unwrap();
+        fs::write(dir.join("vim/.vimrc"), "set nu").unwrap();
+        let args = crate::Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "vim"]);
+        let file = tmp.path().join("file");
+        fs::write(&file, "12345").unwrap();
+        assert_eq!(describe_occupant(&args, &file), "a file of 5 bytes");
+        assert_eq!(describe_occupant(&args, tmp.path()), "a directory");
+        assert_eq!(
+            describe_occupant(&args, &tmp.path().join("missing")),
+            "nothing"
+        );
+        assert_eq!(
+            package_of(&args, &dir.join("vim/.vimrc")).as_deref(),
+            Some("vim")
+        );
+        #[cfg(unix)]
+        {
+            let link = tmp.path().join(".vimrc");
+            std::os::unix::fs::symlink("dotfiles/vim/.vimrc", &link).unwrap();
+            assert_eq!(
+                describe_occupant(&args, &link),
+                "a link into package vim (dotfiles/vim/.vimrc)"
+            );
+        }
+    }
+}
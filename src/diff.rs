@@ -0,0 +1,206 @@
+use std::env;
+use std::io::IsTerminal;
+
+/// Unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+
+/// Largest table of line pairs compared before giving up on a line-by-line diff.
+const MAX_CELLS: usize = 16 * 1024 * 1024;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// One line of an edit script turning one text into another.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Op<'a> {
+    Equal(&'a str),
+    Delete(&'a str),
+    Insert(&'a str),
+}
+
+/// Whether diffs written to stderr should be colored: it's a terminal, and '$NO_COLOR' isn't
+/// set.
+pub fn color() -> bool {
+    std::io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+}
+
+/// The shortest edit script turning `old` into `new`, or None if they're too long to compare.
+pub fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Option<Vec<Op<'a>>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    if (a.len() + 1).saturating_mul(b.len() + 1) > MAX_CELLS {
+        return None;
+    }
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..].
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = match a[i] == b[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+    let mut ops: Vec<Op> = old[..prefix].iter().map(|l| Op::Equal(l)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push(Op::Equal(a[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(Op::Delete(a[i]));
+            i += 1;
+        } else {
+            ops.push(Op::Insert(b[j]));
+            j += 1;
+        }
+    }
+    ops.extend(old[old.len() - suffix..].iter().map(|l| Op::Equal(l)));
+    Some(ops)
+}
+
+/// A unified diff from `old` to `new`, empty if they're the same.
+pub fn unified(old_name: &str, old: &str, new_name: &str, new: &str, color: bool) -> String {
+    if old == new {
+        return String::new();
+    }
+    let paint = |code: &str, line: String| match color {
+        true => format!("{}{}{}\n", code, line, RESET),
+        false => format!("{}\n", line),
+    };
+    let mut out =
+        paint(BOLD, format!("--- {}", old_name)) + &paint(BOLD, format!("+++ {}", new_name));
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let Some(ops) = diff_lines(&old_lines, &new_lines) else {
+        return out + "Files are too large to compare line by line\n";
+    };
+    for hunk in hunks(&ops) {
+        let (old_start, new_start) = hunk.start;
+        let ops = &ops[hunk.range];
+        let old_len = ops.iter().filter(|op| !matches!(op, Op::Insert(_))).count();
+        let new_len = ops.iter().filter(|op| !matches!(op, Op::Delete(_))).count();
+        out += &paint(
+            CYAN,
+            format!(
+                "@@ -{} +{} @@",
+                range(old_start, old_len),
+                range(new_start, new_len)
+            ),
+        );
+        for op in ops {
+            out += &match op {
+                Op::Equal(line) => format!(" {}\n", line),
+                Op::Delete(line) => paint(RED, format!("-{}", line)),
+                Op::Insert(line) => paint(GREEN, format!("+{}", line)),
+            };
+        }
+    }
+    out
+}
+
+/// A unified diff from `old` to `new`, or a note that they differ if either isn't text.
+pub fn diff_bytes(old_name: &str, old: &[u8], new_name: &str, new: &[u8], color: bool) -> String {
+    if old == new {
+        return String::new();
+    }
+    match (std::str::from_utf8(old), std::str::from_utf8(new)) {
+        (Ok(a), Ok(b)) => unified(old_name, a, new_name, b, color),
+        _ => format!("Binary files {} and {} differ\n", old_name, new_name),
+    }
+}
+
+/// How many lines were added and removed going from `old` to `new`, or None if either isn't
+/// text or they're too large to compare.
+pub fn stat(old: &[u8], new: &[u8]) -> Option<(usize, usize)> {
+    let (old, new) = (
+        std::str::from_utf8(old).ok()?,
+        std::str::from_utf8(new).ok()?,
+    );
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let ops = diff_lines(&old_lines, &new_lines)?;
+    let added = ops.iter().filter(|op| matches!(op, Op::Insert(_))).count();
+    let removed = ops.iter().filter(|op| matches!(op, Op::Delete(_))).count();
+    Some((added, removed))
+}
+
+struct Hunk {
+    /// Zero-based line numbers in the old and new text where the hunk starts.
+    start: (usize, usize),
+    range: std::ops::Range<usize>,
+}
+
+/// Groups the changes in `ops` with their surrounding context, merging groups whose context
+/// overlaps.
+fn hunks(ops: &[Op]) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let (mut old_line, mut new_line) = (0, 0);
+    for (i, op) in ops.iter().enumerate() {
+        if !matches!(op, Op::Equal(_)) {
+            let start = i.saturating_sub(CONTEXT);
+            let end = (i + 1 + CONTEXT).min(ops.len());
+            match hunks.last_mut() {
+                Some(hunk) if hunk.range.end >= start => hunk.range.end = end,
+                _ => {
+                    let before = i - start;
+                    hunks.push(Hunk {
+                        start: (old_line - before, new_line - before),
+                        range: start..end,
+                    });
+                }
+            }
+        }
+        match op {
+            Op::Equal(_) => (old_line, new_line) = (old_line + 1, new_line + 1),
+            Op::Delete(_) => old_line += 1,
+            Op::Insert(_) => new_line += 1,
+        }
+    }
+    hunks
+}
+
+/// A hunk header's 'START,LENGTH', with one-based line numbers.
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+
+        assert_eq!(unified("old", old, "new", old, false), "");
+        assert_eq!(
+            unified("old", old, "new", new, false),
+            "--- old\n+++ new\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -8,3 +8,4 @@\n h\n i\n j\n+k\n"
+        );
+        assert_eq!(
+            unified("old", "", "new", "x\n", false),
+            "--- old\n+++ new\n@@ -0,0 +1 @@\n+x\n"
+        );
+        assert!(unified("old", "a\n", "new", "b\n", true).contains("\x1b[31m-a\x1b[0m"));
+    }
+}
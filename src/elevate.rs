@@ -0,0 +1,77 @@
+use crate::i18n::tr;
+use crate::{Args, Elevate, LV_INFO};
+use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// Runs `op`, and if it's denied permission, runs `argv` through '--elevate' instead, so that
+/// only the changes that need root get it while everything else, planning included, runs as the
+/// user. Only changes to the machine ranch runs on fall back; a remote '--backend' has no use
+/// for a local 'sudo'.
+fn with_fallback(
+    args: &Args,
+    op: impl FnOnce() -> io::Result<()>,
+    argv: &[&OsStr],
+    stderr: &mut dyn io::Write,
+) -> io::Result<()> {
+    let e = match op() {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => e,
+        result => return result,
+    };
+    let Some(elevate) = args.elevate.as_ref().filter(|_| args.fs.is_real()) else {
+        return Err(e);
+    };
+    let program = match elevate {
+        Elevate::Sudo => "sudo",
+        Elevate::Doas => "doas",
+        Elevate::Pkexec => "pkexec",
+    };
+    if args.verbosity("elevate") >= LV_INFO {
+        let argv: Vec<_> = argv.iter().map(|a| a.to_string_lossy()).collect();
+        _ = writeln!(stderr, "{} {}", program, argv.join(" "));
+    }
+    let output = Command::new(program).args(argv).output()?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(io::Error::other(tr!(
+            "elevated-command-failed",
+            program = program,
+            command = argv[0].to_string_lossy(),
+            output = String::from_utf8_lossy(&output.stderr).trim()
+        ))),
+    }
+}
+
+/// Creates a symlink at `link` pointing to `target`, as root if need be.
+pub fn symlink(
+    args: &Args,
+    target: &Path,
+    link: &Path,
+    stderr: &mut dyn io::Write,
+) -> io::Result<()> {
+    let argv = ["ln", "-s", "--"].map(OsStr::new);
+    let argv = [&argv[..], &[target.as_os_str(), link.as_os_str()]].concat();
+    with_fallback(args, || args.fs.symlink(target, link), &argv, stderr)
+}
+
+/// Creates `dir` and any missing parents, as root if need be.
+pub fn create_dir_all(args: &Args, dir: &Path, stderr: &mut dyn io::Write) -> io::Result<()> {
+    let argv = ["mkdir", "-p", "--"].map(OsStr::new);
+    let argv = [&argv[..], &[dir.as_os_str()]].concat();
+    with_fallback(args, || args.fs.create_dir_all(dir), &argv, stderr)
+}
+
+/// Removes the file or link at `path`, as root if need be.
+pub fn remove_file(args: &Args, path: &Path, stderr: &mut dyn io::Write) -> io::Result<()> {
+    let argv = ["rm", "-f", "--"].map(OsStr::new);
+    let argv = [&argv[..], &[path.as_os_str()]].concat();
+    with_fallback(args, || args.fs.remove_file(path), &argv, stderr)
+}
+
+/// Removes the empty directory `dir`, as root if need be.
+pub fn remove_dir(args: &Args, dir: &Path, stderr: &mut dyn io::Write) -> io::Result<()> {
+    let argv = ["rmdir", "--"].map(OsStr::new);
+    let argv = [&argv[..], &[dir.as_os_str()]].concat();
+    with_fallback(args, || args.fs.remove_dir(dir), &argv, stderr)
+}
@@ -0,0 +1,137 @@
+use crate::i18n::tr;
+use crate::paths;
+use crate::plan::{self, Action};
+use crate::{load_package, Args, ExportFormat, LV_WARN};
+use std::io;
+use std::path::Path;
+
+/// Prints the plan for `packages`, or every package in 'DIR', to `stdout` as configuration for
+/// another tool, in `format`.
+pub fn export(
+    args: &Args,
+    packages: &[String],
+    format: &ExportFormat,
+    stdout: &mut dyn io::Write,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let packages = match packages {
+        [] => plan::packages(&args.dir)?,
+        _ => packages.to_vec(),
+    };
+    let mut actions = Vec::new();
+    for package in &packages {
+        let (prefix_path, _, target_path) = load_package(args, package)?;
+        actions.extend(plan::plan(
+            args,
+            package,
+            &prefix_path,
+            &target_path,
+            stderr,
+        )?);
+    }
+    let text = match format {
+        ExportFormat::HomeManager => {
+            let home = paths::home_dir().ok_or_else(|| tr!("no-home-dir", text = "~"))?;
+            home_manager(args, &actions, &home, stderr)?
+        }
+    };
+    _ = write!(stdout, "{}", text);
+    Ok(())
+}
+
+/// A home-manager module whose 'home.file' deploys `actions` like ranch: package files as links
+/// out of the Nix store into 'DIR', so that edits show up without a rebuild. Only paths within
+/// `home` can be expressed; others, empty directories, and templates and encrypted files, whose
+/// secrets would end up in the world-readable Nix store, are left out with a warning.
+fn home_manager(
+    args: &Args,
+    actions: &[Action],
+    home: &Path,
+    stderr: &mut dyn io::Write,
+) -> Result<String, String> {
+    let mut out = String::from(
+        "# Generated by 'ranch export --format home-manager'.\n{ config, ... }:\n{\n  home.file = {\n",
+    );
+    for action in actions {
+        let rel = match action.dst().strip_prefix(home) {
+            Ok(rel) if matches!(action, Action::Link { .. }) => rel,
+            _ => {
+                if args.verbosity("export") >= LV_WARN {
+                    _ = writeln!(
+                        stderr,
+                        "{}",
+                        tr!("export-left-out", path = action.dst().display())
+                    );
+                }
+                continue;
+            }
+        };
+        let key = nix_string(&rel.to_string_lossy());
+        out += &format!(
+            "    {}.source = config.lib.file.mkOutOfStoreSymlink {};\n",
+            key,
+            nix_string(&action.src().to_string_lossy())
+        );
+    }
+    out += "  };\n}\n";
+    Ok(out)
+}
+
+/// `s` as a double-quoted Nix string.
+fn nix_string(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("${", "\\${");
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_home_manager() {
+        let args = Args::parse_from(["ranch", "-v", "-C", "/home/alice/.dotfiles", "vim"]);
+        let home = PathBuf::from("/home/alice");
+        let actions = [
+            Action::Link {
+                src: PathBuf::from("/home/alice/.dotfiles/vim/.vimrc"),
+                dst: home.join(".vimrc"),
+            },
+            Action::Link {
+                src: PathBuf::from("/home/alice/.dotfiles/vim/my \"${x}\""),
+                dst: home.join("my \"${x}\""),
+            },
+            Action::Link {
+                src: PathBuf::from("/home/alice/.dotfiles/etc/hosts"),
+                dst: PathBuf::from("/etc/hosts"),
+            },
+            Action::Mkdir {
+                src: PathBuf::from("/home/alice/.dotfiles/vim/.cache"),
+                dst: home.join(".cache"),
+            },
+            Action::Render {
+                src: PathBuf::from("/home/alice/.dotfiles/vim/.netrc.tmpl"),
+                dst: home.join(".netrc"),
+            },
+        ];
+        let mut stderr = Vec::new();
+        assert_eq!(
+            home_manager(&args, &actions, &home, &mut stderr).unwrap(),
+            "# Generated by 'ranch export --format home-manager'.\n\
+             { config, ... }:\n\
+             {\n  \
+             home.file = {\n    \
+             \".vimrc\".source = config.lib.file.mkOutOfStoreSymlink \
+             \"/home/alice/.dotfiles/vim/.vimrc\";\n    \
+             \"my \\\"\\${x}\\\"\".source = config.lib.file.mkOutOfStoreSymlink \
+             \"/home/alice/.dotfiles/vim/my \\\"\\${x}\\\"\";\n  \
+             };\n\
+             }\n"
+        );
+        assert_eq!(String::from_utf8(stderr).unwrap().lines().count(), 3);
+    }
+}
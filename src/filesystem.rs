@@ -0,0 +1,711 @@
+use crate::i18n::tr;
+use crate::paths::{self, MAX_LINKS};
+use crate::sftp::SftpFs;
+use crate::{Args, Backend};
+#[cfg(test)]
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+#[cfg(test)]
+use std::sync::Mutex;
+
+/// What's at a path, without following a link there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Dir,
+    Symlink,
+    /// A socket, FIFO, or device.
+    Other,
+}
+
+/// The operations the applier, and the planner's checks of the target, make on the filesystem,
+/// so that they can run against an in-memory [MemoryFs] in tests, and against remote machines
+/// with [SftpFs]. Walking the package itself still reads the real filesystem.
+pub trait Filesystem: fmt::Debug + Send + Sync {
+    /// What's at `path`, not following a link there; None if there's nothing.
+    fn kind(&self, path: &Path) -> Option<Kind>;
+    /// The size of the file at `path`, following links.
+    fn size(&self, path: &Path) -> io::Result<u64>;
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    /// The paths of the entries in the directory `path`.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Creates a link at `link` pointing to `target`.
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()>;
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()>;
+    /// Writes `contents` to the file at `path`, keeping its mode if it exists; for package files
+    /// that take in a target's changes.
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    /// Writes generated contents (decrypted secrets, rendered templates) to `path`, readable
+    /// only by the current user.
+    fn write_private(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    /// Gives `to` the access and modification times of `from`.
+    fn copy_times(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+    /// Gives the link at `link` the modification time of the file `src` it points to. Does
+    /// nothing if `link` isn't a link.
+    fn stamp_link(&self, src: &Path, link: &Path) -> io::Result<()>;
+    /// Gives the file, directory, or link at `path` to `uid` and `gid`, not following a link.
+    fn chown(&self, path: &Path, uid: u32, gid: u32) -> io::Result<()>;
+    /// Flushes the file or directory at `path` to disk.
+    fn sync(&self, path: &Path) -> io::Result<()>;
+
+    /// Whether this is the filesystem of the machine ranch runs on, which other programs there,
+    /// such as '--elevate' and 'mklink', can change too.
+    fn is_real(&self) -> bool {
+        false
+    }
+
+    /// What's at `path` after following links; None if there's nothing, or the link is broken.
+    fn target_kind(&self, path: &Path) -> Option<Kind> {
+        let mut path = path.to_path_buf();
+        for _ in 0..MAX_LINKS {
+            match self.kind(&path)? {
+                Kind::Symlink => path = self.resolve_link(&path)?,
+                kind => return Some(kind),
+            }
+        }
+        None
+    }
+
+    /// Where the link at `path` points, resolved against its directory if relative; None if it
+    /// isn't a link.
+    fn resolve_link(&self, path: &Path) -> Option<PathBuf> {
+        let link = self.read_link(path).ok()?;
+        Some(paths::normalize(&path.parent()?.join(link)))
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        self.kind(path) == Some(Kind::Symlink)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.target_kind(path) == Some(Kind::Dir)
+    }
+}
+
+/// The filesystem ranch changes, shared by every copy of the arguments: the real one, unless a
+/// test swaps in a [MemoryFs].
+#[derive(Clone, Debug)]
+pub struct Fs(pub Arc<dyn Filesystem>);
+
+impl Default for Fs {
+    fn default() -> Fs {
+        Fs(Arc::new(RealFs))
+    }
+}
+
+impl Deref for Fs {
+    type Target = dyn Filesystem;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+/// The filesystem that '--backend' names.
+pub fn open(args: &Args) -> Result<Fs, String> {
+    if args.check {
+        return Ok(Fs(Arc::new(RecordFs { print: false })));
+    }
+    Ok(match args.backend {
+        Backend::Local => Fs::default(),
+        Backend::Record => Fs(Arc::new(RecordFs { print: true })),
+        Backend::Sftp => {
+            let host = args.host.as_deref().ok_or_else(|| tr!("sftp-needs-host"))?;
+            Fs(Arc::new(SftpFs::new(host, &args.dir)))
+        }
+    })
+}
+
+/// The filesystem of the machine ranch runs on.
+#[derive(Debug)]
+pub struct RealFs;
+
+impl Filesystem for RealFs {
+    fn kind(&self, path: &Path) -> Option<Kind> {
+        let file_type = path.symlink_metadata().ok()?.file_type();
+        Some(match file_type {
+            t if t.is_symlink() => Kind::Symlink,
+            t if t.is_dir() => Kind::Dir,
+            t if t.is_file() => Kind::File,
+            _ => Kind::Other,
+        })
+    }
+
+    // The OS also follows links in the middle of the path, such as '~/.config' linked elsewhere.
+    fn target_kind(&self, path: &Path) -> Option<Kind> {
+        let file_type = path.metadata().ok()?.file_type();
+        Some(match file_type {
+            t if t.is_dir() => Kind::Dir,
+            t if t.is_file() => Kind::File,
+            _ => Kind::Other,
+        })
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(fs::metadata(path)?.len())
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect()
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    #[cfg(unix)]
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        std::os::unix::fs::symlink(target, link)
+    }
+
+    #[cfg(windows)]
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        // Directories folded by '--max-depth' need directory links.
+        match link
+            .parent()
+            .map(|dir| dir.join(target))
+            .is_some_and(|p| p.is_dir())
+        {
+            true => std::os::windows::fs::symlink_dir(target, link),
+            false => std::os::windows::fs::symlink_file(target, link),
+        }
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::copy(from, to).map(|_| ())
+    }
+
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::hard_link(from, to)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn write_private(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        io::Write::write_all(&mut options.open(path)?, contents)
+    }
+
+    fn copy_times(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let metadata = fs::metadata(from)?;
+        let times = fs::FileTimes::new()
+            .set_accessed(metadata.accessed()?)
+            .set_modified(metadata.modified()?);
+        fs::File::options().write(true).open(to)?.set_times(times)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    #[cfg(unix)]
+    fn stamp_link(&self, src: &Path, link: &Path) -> io::Result<()> {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::fs::MetadataExt;
+
+        if !link.is_symlink() {
+            return Ok(());
+        }
+        let metadata = fs::metadata(src)?;
+        let path = std::ffi::CString::new(link.as_os_str().as_bytes())?;
+        let modified = libc::timespec {
+            tv_sec: metadata.mtime() as libc::time_t,
+            tv_nsec: metadata.mtime_nsec() as _,
+        };
+        let times = [modified, modified];
+        // SAFETY: `path` is NUL-terminated and `times` holds the two timespecs utimensat reads,
+        // both outliving the call.
+        match unsafe {
+            libc::utimensat(
+                libc::AT_FDCWD,
+                path.as_ptr(),
+                times.as_ptr(),
+                libc::AT_SYMLINK_NOFOLLOW,
+            )
+        } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn stamp_link(&self, _: &Path, _: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn chown(&self, path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+        std::os::unix::fs::lchown(path, Some(uid), Some(gid))
+    }
+
+    #[cfg(not(unix))]
+    fn chown(&self, _: &Path, _: u32, _: u32) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        fs::File::open(path)?.sync_all()
+    }
+
+    /// Windows can only flush files opened for writing, and not directories at all; NTFS
+    /// journals its directory changes.
+    #[cfg(windows)]
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        match path.is_dir() {
+            true => Ok(()),
+            false => fs::OpenOptions::new().write(true).open(path)?.sync_all(),
+        }
+    }
+
+    fn is_real(&self) -> bool {
+        true
+    }
+}
+
+/// Reads the real filesystem, but prints each change to stdout as the shell command that would
+/// make it if `print` is set, and otherwise acts as though it succeeded. Later reads don't see
+/// the changes.
+#[derive(Debug)]
+pub struct RecordFs {
+    print: bool,
+}
+
+impl RecordFs {
+    fn record(&self, argv: &[&Path]) -> io::Result<()> {
+        if !self.print {
+            return Ok(());
+        }
+        let words: Vec<String> = argv
+            .iter()
+            .map(|word| quote(&word.to_string_lossy()))
+            .collect();
+        println!("{}", words.join(" "));
+        Ok(())
+    }
+}
+
+/// `word` quoted for a POSIX shell where needed.
+pub fn quote(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "/._-+=:@%,~".contains(c);
+    match !word.is_empty() && word.chars().all(safe) {
+        true => word.to_owned(),
+        false => format!("'{}'", word.replace('\'', "'\\''")),
+    }
+}
+
+impl Filesystem for RecordFs {
+    fn kind(&self, path: &Path) -> Option<Kind> {
+        RealFs.kind(path)
+    }
+
+    fn target_kind(&self, path: &Path) -> Option<Kind> {
+        RealFs.target_kind(path)
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        RealFs.size(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        RealFs.read_link(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        RealFs.read(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        RealFs.read_dir(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        self.record(&[Path::new("mkdir"), Path::new("-p"), path])
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        self.record(&[Path::new("ln"), Path::new("-s"), target, link])
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.record(&[Path::new("cp"), from, to])
+    }
+
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.record(&[Path::new("ln"), from, to])
+    }
+
+    fn write(&self, path: &Path, _: &[u8]) -> io::Result<()> {
+        self.record(&[Path::new("cp"), Path::new("/dev/null"), path])
+    }
+
+    // Generated contents are secrets, or rendered from them, so they aren't printed.
+    fn write_private(&self, path: &Path, _: &[u8]) -> io::Result<()> {
+        self.record(&[
+            Path::new("install"),
+            Path::new("-m"),
+            Path::new("600"),
+            Path::new("/dev/null"),
+            path,
+        ])
+    }
+
+    fn copy_times(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let reference = format!("--reference={}", from.display());
+        self.record(&[Path::new("touch"), Path::new(&reference), to])
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.record(&[Path::new("mv"), from, to])
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.record(&[Path::new("rm"), path])
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        self.record(&[Path::new("rmdir"), path])
+    }
+
+    fn stamp_link(&self, src: &Path, link: &Path) -> io::Result<()> {
+        let reference = format!("--reference={}", src.display());
+        self.record(&[
+            Path::new("touch"),
+            Path::new("-h"),
+            Path::new(&reference),
+            link,
+        ])
+    }
+
+    fn chown(&self, path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+        let owner = format!("{}:{}", uid, gid);
+        self.record(&[Path::new("chown"), Path::new("-h"), Path::new(&owner), path])
+    }
+
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        self.record(&[Path::new("sync"), path])
+    }
+}
+
+#[cfg(test)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    File(Vec<u8>),
+    Dir,
+    Symlink(PathBuf),
+}
+
+/// A filesystem held in memory, for tests that would otherwise need a tempdir. Paths are
+/// absolute, '/' always exists, and only a link at the end of a path is followed. Hard links are
+/// copies, and files have no times.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MemoryFs {
+    nodes: Mutex<BTreeMap<PathBuf, Node>>,
+}
+
+#[cfg(test)]
+impl MemoryFs {
+    /// Adds the file `path` holding `contents`, and any missing parent directories.
+    pub fn file(&self, path: impl AsRef<Path>, contents: &str) -> &MemoryFs {
+        let path = path.as_ref();
+        self.create_dir_all(path.parent().unwrap_or(Path::new("/")))
+            .and_then(|_| self.write_private(path, contents.as_bytes()))
+            .expect("a file can be added");
+        self
+    }
+
+    /// Adds the directory `path`, and any missing parents.
+    pub fn dir(&self, path: impl AsRef<Path>) -> &MemoryFs {
+        self.create_dir_all(path.as_ref())
+            .expect("a directory can be added");
+        self
+    }
+
+    /// Adds a link at `link` pointing to `target`, and any missing parent directories.
+    pub fn link(&self, link: impl AsRef<Path>, target: impl AsRef<Path>) -> &MemoryFs {
+        let link = link.as_ref();
+        self.create_dir_all(link.parent().unwrap_or(Path::new("/")))
+            .and_then(|_| self.symlink(target.as_ref(), link))
+            .expect("a link can be added");
+        self
+    }
+
+    /// Everything in the filesystem, by path.
+    pub fn nodes(&self) -> BTreeMap<PathBuf, Node> {
+        self.nodes.lock().unwrap().clone()
+    }
+
+    fn node(&self, path: &Path) -> Option<Node> {
+        match path == Path::new("/") {
+            true => Some(Node::Dir),
+            false => self.nodes.lock().unwrap().get(path).cloned(),
+        }
+    }
+
+    /// Puts `node` at `path`, whose parent must be a directory and which must be free unless
+    /// `replace` is set.
+    fn insert(&self, path: &Path, node: Node, replace: bool) -> io::Result<()> {
+        let parent = path.parent().ok_or(io::ErrorKind::InvalidInput)?;
+        match self.target_kind(parent) {
+            Some(Kind::Dir) => {}
+            Some(_) => return Err(io::ErrorKind::NotADirectory.into()),
+            None => return Err(io::ErrorKind::NotFound.into()),
+        }
+        let mut nodes = self.nodes.lock().unwrap();
+        match nodes.get(path) {
+            Some(Node::Dir) => Err(io::ErrorKind::IsADirectory.into()),
+            Some(_) if !replace => Err(io::ErrorKind::AlreadyExists.into()),
+            _ => {
+                nodes.insert(path.to_path_buf(), node);
+                Ok(())
+            }
+        }
+    }
+
+    /// The file at `path`, following links.
+    fn follow(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut path = path.to_path_buf();
+        for _ in 0..MAX_LINKS {
+            match self.node(&path) {
+                Some(Node::File(contents)) => return Ok(contents),
+                Some(Node::Dir) => return Err(io::ErrorKind::IsADirectory.into()),
+                Some(Node::Symlink(_)) => {
+                    path = self.resolve_link(&path).ok_or(io::ErrorKind::NotFound)?
+                }
+                None => return Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+        Err(io::Error::other("too many levels of symbolic links"))
+    }
+}
+
+#[cfg(test)]
+impl Filesystem for MemoryFs {
+    fn kind(&self, path: &Path) -> Option<Kind> {
+        Some(match self.node(path)? {
+            Node::File(_) => Kind::File,
+            Node::Dir => Kind::Dir,
+            Node::Symlink(_) => Kind::Symlink,
+        })
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(self.follow(path)?.len() as u64)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.node(path) {
+            Some(Node::Symlink(target)) => Ok(target),
+            Some(_) => Err(io::ErrorKind::InvalidInput.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.follow(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        if !self.is_dir(path) {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+        let dir = self.resolve_link(path).unwrap_or(path.to_path_buf());
+        let nodes = self.nodes.lock().unwrap();
+        Ok(nodes
+            .keys()
+            .filter(|p| p.parent() == Some(&dir))
+            .map(|p| path.join(p.file_name().unwrap_or_default()))
+            .collect())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut missing: Vec<&Path> = path
+            .ancestors()
+            .take_while(|d| self.kind(d).is_none())
+            .collect();
+        if missing.is_empty() && !self.is_dir(path) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        missing.reverse();
+        missing
+            .into_iter()
+            .try_for_each(|dir| self.insert(dir, Node::Dir, false))
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        self.insert(link, Node::Symlink(target.to_path_buf()), false)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.write_private(to, &self.follow(from)?)
+    }
+
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.insert(to, Node::File(self.follow(from)?), false)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.write_private(path, contents)
+    }
+
+    fn write_private(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let path = match self.kind(path) {
+            Some(Kind::Symlink) => self.resolve_link(path).ok_or(io::ErrorKind::NotFound)?,
+            _ => path.to_path_buf(),
+        };
+        self.insert(&path, Node::File(contents.to_vec()), true)
+    }
+
+    fn copy_times(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.follow(from).and(self.follow(to)).map(|_| ())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let node = self.node(from).ok_or(io::ErrorKind::NotFound)?;
+        if node == Node::Dir && self.kind(to).is_some() {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        self.insert(to, node, true)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        nodes.remove(from);
+        let children: Vec<PathBuf> = nodes
+            .keys()
+            .filter(|p| p.starts_with(from))
+            .cloned()
+            .collect();
+        for child in children {
+            let node = nodes.remove(&child).expect("listed above");
+            let rest = child.strip_prefix(from).expect("listed above");
+            nodes.insert(to.join(rest), node);
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        match self.node(path) {
+            Some(Node::Dir) => Err(io::ErrorKind::IsADirectory.into()),
+            Some(_) => {
+                self.nodes.lock().unwrap().remove(path);
+                Ok(())
+            }
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        match self.node(path) {
+            Some(Node::Dir) if self.read_dir(path)?.is_empty() => {
+                self.nodes.lock().unwrap().remove(path);
+                Ok(())
+            }
+            Some(Node::Dir) => Err(io::ErrorKind::DirectoryNotEmpty.into()),
+            Some(_) => Err(io::ErrorKind::NotADirectory.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn stamp_link(&self, _: &Path, _: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn chown(&self, path: &Path, _: u32, _: u32) -> io::Result<()> {
+        self.node(path)
+            .map(|_| ())
+            .ok_or(io::ErrorKind::NotFound.into())
+    }
+
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        self.node(path)
+            .map(|_| ())
+            .ok_or(io::ErrorKind::NotFound.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("/home/alice/.vimrc"), "/home/alice/.vimrc");
+        assert_eq!(quote("My Files"), "'My Files'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+        assert_eq!(quote(""), "''");
+    }
+
+    #[test]
+    fn test_memory_fs() {
+        let fs = MemoryFs::default();
+        fs.file("/dotfiles/vim/.vimrc", "set nu")
+            .link("/home/.vimrc", "../dotfiles/vim/.vimrc")
+            .link("/home/broken", "missing")
+            .dir("/home/.config");
+
+        assert_eq!(fs.kind(Path::new("/home/.vimrc")), Some(Kind::Symlink));
+        assert_eq!(fs.target_kind(Path::new("/home/.vimrc")), Some(Kind::File));
+        assert_eq!(fs.read(Path::new("/home/.vimrc")).unwrap(), b"set nu");
+        assert!(fs.is_symlink(Path::new("/home/broken")));
+        assert_eq!(fs.target_kind(Path::new("/home/broken")), None);
+        assert!(fs.is_dir(Path::new("/home/.config")));
+        assert_eq!(
+            fs.read_dir(Path::new("/home")).unwrap(),
+            ["/home/.config", "/home/.vimrc", "/home/broken"].map(PathBuf::from)
+        );
+
+        let kind = |e: io::Error| e.kind();
+        let err = fs.symlink(Path::new("x"), Path::new("/home/.vimrc"));
+        assert_eq!(err.map_err(kind), Err(io::ErrorKind::AlreadyExists));
+        let err = fs.symlink(Path::new("x"), Path::new("/nowhere/x"));
+        assert_eq!(err.map_err(kind), Err(io::ErrorKind::NotFound));
+        let err = fs.remove_dir(Path::new("/home"));
+        assert_eq!(err.map_err(kind), Err(io::ErrorKind::DirectoryNotEmpty));
+        let err = fs.remove_file(Path::new("/home/.config"));
+        assert_eq!(err.map_err(kind), Err(io::ErrorKind::IsADirectory));
+
+        fs.rename(Path::new("/dotfiles/vim"), Path::new("/dotfiles/nvim"))
+            .unwrap();
+        assert!(fs.is_dir(Path::new("/dotfiles/nvim")));
+        assert_eq!(
+            fs.read(Path::new("/dotfiles/nvim/.vimrc")).unwrap(),
+            b"set nu"
+        );
+        assert_eq!(fs.target_kind(Path::new("/home/.vimrc")), None);
+        fs.remove_file(Path::new("/home/.vimrc")).unwrap();
+        assert_eq!(fs.kind(Path::new("/home/.vimrc")), None);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::i18n::tr;
+use crate::pager::Pager;
+use crate::plan::{self, Action};
+use crate::status;
+use crate::{load_package, Args};
+use std::io;
+
+/// Prints every package file matching `pattern`, a file name or glob as in '--only', as
+/// 'PACKAGE: SOURCE -> TARGET (STATE)' lines through the pager.
+pub fn find(args: &Args, pattern: &str, stderr: &mut dyn io::Write) -> Result<(), String> {
+    find_to(args, pattern, &mut Pager::start(args), stderr)
+}
+
+fn find_to(
+    args: &Args,
+    pattern: &str,
+    stdout: &mut dyn io::Write,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let patterns = [pattern.to_owned()];
+    let mut found = false;
+    for package in plan::packages(&args.dir)? {
+        let (prefix_path, _, target_path) = load_package(args, &package)?;
+        for action in plan::stream(args, &package, &prefix_path, &target_path, stderr)? {
+            let action = action?;
+            if matches!(action, Action::Mkdir { .. })
+                || !plan::matches_any(&patterns, &prefix_path, action.src())
+            {
+                continue;
+            }
+            found = true;
+            _ = writeln!(
+                stdout,
+                "{}: {} -> {} ({})",
+                package,
+                action.src().display(),
+                action.dst().display(),
+                status::link_state(args, &action)
+            );
+        }
+    }
+    match found {
+        true => Ok(()),
+        false => Err(tr!("no-files-match", pattern = pattern)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_find() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("kitty/.config/kitty")).unwrap();
+        fs::write(dir.join("kitty/.config/kitty/kitty.conf"), "").unwrap();
+        fs::create_dir_all(dir.join("home")).unwrap();
+        fs::write(dir.join("home/.vimrc"), "").unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "find", "*.conf"]);
+        let mut stdout = Vec::new();
+
+        find_to(&args, "*.conf", &mut stdout, &mut io::sink()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            format!(
+                "kitty: {} -> {} (missing)\n",
+                dir.join("kitty/.config/kitty/kitty.conf").display(),
+                tmp_dir.path().join(".config/kitty/kitty.conf").display()
+            )
+        );
+        assert!(find_to(&args, "init.lua", &mut Vec::new(), &mut io::sink()).is_err());
+    }
+}
@@ -0,0 +1,547 @@
+use crate::i18n::tr;
+use crate::paths;
+use crate::{Args, LV_INFO};
+use std::cmp::Ordering;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+/// Hooks installed by 'ranch git-hook install'; git runs 'post-merge' after a pull, and
+/// 'post-checkout' after switching branches.
+const HOOKS: [&str; 2] = ["post-merge", "post-checkout"];
+
+/// Marks hook scripts as ranch's own, so that other hooks are never overwritten or removed.
+const HOOK_MARKER: &str = "# Installed by 'ranch git-hook install'.";
+
+/// Body of the installed hooks; '@RANCH@' and '@DIR@' are replaced with the quoted path of
+/// ranch and of 'DIR'.
+const HOOK_SCRIPT: &str = r#"# Restows the ranch packages whose files changed.
+ranch=@RANCH@
+dir=@DIR@
+if [ "$#" -ge 3 ]; then
+    # post-checkout: previous HEAD, new HEAD, and whether branches were switched
+    [ "$3" = 1 ] || exit 0
+    old=$1 new=$2
+else
+    old=ORIG_HEAD new=HEAD
+fi
+cd "$dir" || exit 0
+git diff --name-only --relative "$old" "$new" -- . | cut -d/ -f1 | sort -u |
+while IFS= read -r package; do
+    case "$package" in .*) continue ;; esac
+    [ -d "$package" ] || continue
+    "$ranch" -C "$dir" -R "$package" || echo "ranch: could not restow $package" >&2
+done
+"#;
+
+/// Files in git's index under `dir`, read as they're looked up; see [Listing].
+pub fn tracked_files(dir: &Path) -> Result<Listing, String> {
+    // Fail up front outside a repository, rather than with an empty list.
+    run(Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--git-dir"]))?;
+    Listing::spawn(dir, &["ls-files", "-z", "--cached", "--", "."])
+}
+
+/// Paths under `dir` that the repository's '.gitignore' files (and '.git/info/exclude') ignore,
+/// read as they're looked up; see [Listing]. Ignored directories are listed rather than their
+/// contents. Lists nothing if `dir` isn't in a git repository.
+pub fn ignored_files(dir: &Path) -> Listing {
+    let in_repo =
+        fs::canonicalize(dir).is_ok_and(|d| d.ancestors().any(|a| a.join(".git").exists()));
+    let listing = match in_repo {
+        true => Listing::spawn(
+            dir,
+            &[
+                "ls-files",
+                "-z",
+                "--others",
+                "--ignored",
+                "--exclude-standard",
+                "--directory",
+                "--",
+                ".",
+            ],
+        ),
+        false => Err(String::new()),
+    };
+    listing.unwrap_or_else(|_| Listing::empty(dir))
+}
+
+/// Paths under a directory as 'git ls-files' lists them, read from git one at a time. git lists
+/// paths in byte order, so as long as they're looked up in that order (see [git_order]), only
+/// the next path is ever held, however large the repository.
+pub struct Listing {
+    dir: PathBuf,
+    child: Option<Child>,
+    out: Option<io::BufReader<ChildStdout>>,
+    /// The next path in the list, relative to `dir` with '/' separators, or `None` at the end.
+    head: Option<Vec<u8>>,
+}
+
+impl Listing {
+    /// Starts git with `args` in `dir`, and reads the first path.
+    fn spawn(dir: &Path, args: &[&str]) -> Result<Listing, String> {
+        let mut child = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| tr!("could-not-run", program = "git", error = e))?;
+        let out = child.stdout.take().map(io::BufReader::new);
+        let mut listing = Listing {
+            dir: dir.to_path_buf(),
+            child: Some(child),
+            out,
+            head: None,
+        };
+        listing.advance();
+        Ok(listing)
+    }
+
+    /// A listing of nothing.
+    fn empty(dir: &Path) -> Listing {
+        Listing {
+            dir: dir.to_path_buf(),
+            child: None,
+            out: None,
+            head: None,
+        }
+    }
+
+    /// Reads the next path into `head`.
+    fn advance(&mut self) {
+        let mut path = Vec::new();
+        self.head = match self.out.as_mut().map(|o| o.read_until(0, &mut path)) {
+            Some(Ok(n)) if n > 0 => {
+                if path.last() == Some(&0) {
+                    path.pop();
+                }
+                Some(path)
+            }
+            _ => None,
+        };
+    }
+
+    /// Skips the paths that come before `rel`.
+    fn seek(&mut self, rel: &[u8]) {
+        while self.head.as_deref().is_some_and(|h| h < rel) {
+            self.advance();
+        }
+    }
+
+    /// Whether `path`, under the listing's directory, is listed; directories are listed with a
+    /// trailing '/'. Paths looked up before the last one may be reported missing.
+    pub fn contains(&mut self, path: &Path, is_dir: bool) -> bool {
+        let mut rel = relative_bytes(&self.dir, path);
+        if is_dir {
+            rel.push(b'/');
+        }
+        self.seek(&rel);
+        self.head.as_deref() == Some(&rel[..])
+    }
+
+    /// Whether the directory `path`, under the listing's directory, or anything in it is listed.
+    pub fn contains_under(&mut self, path: &Path) -> bool {
+        let mut rel = relative_bytes(&self.dir, path);
+        self.seek(&rel);
+        // A submodule is listed as itself, before anything in the directory.
+        if self.head.as_deref() == Some(&rel[..]) {
+            return true;
+        }
+        rel.push(b'/');
+        self.seek(&rel);
+        self.head.as_deref().is_some_and(|h| h.starts_with(&rel))
+    }
+}
+
+impl Drop for Listing {
+    fn drop(&mut self) {
+        // The rest of the list isn't needed; don't leave git blocked on a full pipe.
+        self.out = None;
+        if let Some(mut child) = self.child.take() {
+            _ = child.kill();
+            _ = child.wait();
+        }
+    }
+}
+
+/// Orders entries of a directory walk the way git lists paths: by the bytes of their names, with
+/// directories as if they ended in '/', so that 'a/x' comes after 'a-b' and 'a.c', as in git.
+pub fn git_order(a: &walkdir::DirEntry, b: &walkdir::DirEntry) -> Ordering {
+    fn key(e: &walkdir::DirEntry) -> impl Iterator<Item = &u8> {
+        let slash = e.file_type().is_dir().then_some(&b'/');
+        e.file_name().as_encoded_bytes().iter().chain(slash)
+    }
+    key(a).cmp(key(b))
+}
+
+/// `path` relative to `dir`, as git lists it: its components' bytes joined by '/'.
+fn relative_bytes(dir: &Path, path: &Path) -> Vec<u8> {
+    let rel = path.strip_prefix(dir).unwrap_or(path);
+    let mut bytes = Vec::new();
+    for (i, component) in rel.iter().enumerate() {
+        if i > 0 {
+            bytes.push(b'/');
+        }
+        bytes.extend_from_slice(component.as_encoded_bytes());
+    }
+    bytes
+}
+
+/// Submodules under `dir` that are registered in '.gitmodules' but not checked out, and so are
+/// empty directories. Returns nothing if `dir` isn't in a git repository with submodules.
+pub fn uninitialized_submodules(dir: &Path) -> Vec<PathBuf> {
+    let has_submodules =
+        fs::canonicalize(dir).is_ok_and(|d| d.ancestors().any(|a| a.join(".gitmodules").is_file()));
+    if !has_submodules {
+        return Vec::new();
+    }
+    let Ok(output) = run(Command::new("git").arg("-C").arg(dir).args([
+        "submodule",
+        "status",
+        "--recursive",
+        "--",
+        ".",
+    ])) else {
+        return Vec::new();
+    };
+    // Each line is a status character, the commit, the path, and maybe a description; '-' means
+    // not initialized.
+    String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|line| line.strip_prefix('-'))
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(|path| dir.join(path))
+        .collect()
+}
+
+/// Clones the repository at `url` into `dir`, with its submodules.
+pub fn clone(url: &str, dir: &Path) -> Result<(), String> {
+    run(Command::new("git")
+        .args(["clone", "--quiet", "--recurse-submodules", "--", url])
+        .arg(dir))
+    .map(|_| ())
+}
+
+/// Fast-forwards the clone at `dir` to its upstream, with its submodules.
+pub fn pull(dir: &Path) -> Result<(), String> {
+    run(Command::new("git").arg("-C").arg(dir).args([
+        "pull",
+        "--quiet",
+        "--ff-only",
+        "--recurse-submodules",
+    ]))
+    .map(|_| ())
+}
+
+/// The URL of the 'origin' remote of the repo holding `dir`, and the path of `dir` within it.
+pub fn origin(dir: &Path) -> Result<(String, PathBuf), String> {
+    let url = run(Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["remote", "get-url", "origin"]))?;
+    let prefix = run(Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--show-prefix"]))?;
+    Ok((
+        String::from_utf8_lossy(&url).trim().to_owned(),
+        PathBuf::from(String::from_utf8_lossy(&prefix).trim()),
+    ))
+}
+
+/// Whether `path` is in git's index.
+pub fn is_tracked(path: &Path) -> bool {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return false;
+    };
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["ls-files", "--error-unmatch", "--"])
+        .arg(name)
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+/// Contents of `path` as of the last commit, or None if it isn't in git's history.
+pub fn committed(path: &Path) -> Option<Vec<u8>> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return None;
+    };
+    let mut spec = std::ffi::OsString::from("HEAD:./");
+    spec.push(name);
+    run(Command::new("git").arg("-C").arg(dir).arg("show").arg(spec)).ok()
+}
+
+/// Commits the package files `adopted` from `package` with '--commit-message', so that files
+/// imported by '--exists adopt' are captured in version control right away. Only those files
+/// are committed; anything else already staged is left alone.
+pub fn commit_adopted(
+    args: &Args,
+    package: &str,
+    adopted: &[PathBuf],
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let dir = &args.dir;
+    let files: Vec<&Path> = adopted
+        .iter()
+        .map(|f| f.strip_prefix(dir).unwrap_or(f))
+        .collect();
+    let message = commit_message(&args.commit_message, package, &files);
+    if args.verbosity("git") >= LV_INFO {
+        _ = writeln!(stderr, "Committing {} adopted file(s)", files.len());
+    }
+    run(Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["add", "--"])
+        .args(&files))?;
+    run(Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["commit", "--quiet", "--message", &message, "--"])
+        .args(&files))?;
+    Ok(())
+}
+
+/// Fills in a commit message template's '{package}', '{files}', and '{count}'.
+fn commit_message(template: &str, package: &str, files: &[&Path]) -> String {
+    let names: Vec<String> = files.iter().map(|f| f.display().to_string()).collect();
+    template
+        .replace("{package}", package)
+        .replace("{files}", &names.join(", "))
+        .replace("{count}", &files.len().to_string())
+}
+
+/// Writes ranch's hooks into the git repository containing 'DIR'.
+pub fn install_hooks(args: &Args, stderr: &mut dyn io::Write) -> Result<(), String> {
+    let ranch = std::env::current_exe().map_err(|e| tr!("could-not-locate-ranch", error = e))?;
+    let dir = paths::canonicalize(&args.dir);
+    let script = format!(
+        "#!/bin/sh\n{}\n{}",
+        HOOK_MARKER,
+        HOOK_SCRIPT
+            .replace("@RANCH@", &shell_quote(&ranch.to_string_lossy()))
+            .replace("@DIR@", &shell_quote(&dir.to_string_lossy()))
+    );
+    let hooks_dir = hooks_dir(&dir)?;
+    fs::create_dir_all(&hooks_dir).map_err(|e| {
+        tr!(
+            "could-not-create-dir",
+            path = hooks_dir.display(),
+            error = e
+        )
+    })?;
+    for name in HOOKS {
+        let path = hooks_dir.join(name);
+        if !is_ours(&path)? {
+            return Err(tr!("git-hook-exists", path = path.display()));
+        }
+    }
+    for name in HOOKS {
+        let path = hooks_dir.join(name);
+        if args.verbosity("git") >= LV_INFO {
+            _ = writeln!(stderr, "Installing {}", path.display());
+        }
+        if args.changes_nothing() {
+            continue;
+        }
+        fs::write(&path, &script)
+            .map_err(|e| tr!("could-not-write", path = path.display(), error = e))?;
+        #[cfg(unix)]
+        fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o755)).map_err(
+            |e| {
+                tr!(
+                    "could-not-make-executable",
+                    path = path.display(),
+                    error = e
+                )
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// Removes hooks installed by [install_hooks], leaving any others alone.
+pub fn uninstall_hooks(args: &Args, stderr: &mut dyn io::Write) -> Result<(), String> {
+    let hooks_dir = hooks_dir(&args.dir)?;
+    for name in HOOKS {
+        let path = hooks_dir.join(name);
+        if !path.exists() || !is_ours(&path)? {
+            continue;
+        }
+        if args.verbosity("git") >= LV_INFO {
+            _ = writeln!(stderr, "Removing {}", path.display());
+        }
+        if !args.changes_nothing() {
+            fs::remove_file(&path)
+                .map_err(|e| tr!("could-not-remove", path = path.display(), error = e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Where git looks for hooks in the repository containing `dir`, honoring 'core.hooksPath'.
+fn hooks_dir(dir: &Path) -> Result<PathBuf, String> {
+    let output =
+        run(Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["rev-parse", "--git-path", "hooks"]))?;
+    let hooks = PathBuf::from(String::from_utf8_lossy(&output).trim_end());
+    Ok(dir.join(hooks))
+}
+
+/// The '.git' directory of the repository containing `dir`.
+pub fn git_dir(dir: &Path) -> Result<PathBuf, String> {
+    let output = run(Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--git-dir"]))?;
+    let git_dir = PathBuf::from(String::from_utf8_lossy(&output).trim_end());
+    Ok(dir.join(git_dir))
+}
+
+/// Whether the hook at `path` is missing or was installed by ranch.
+fn is_ours(path: &Path) -> Result<bool, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(text.contains(HOOK_MARKER)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(true),
+        Err(e) => Err(tr!("could-not-read", path = path.display(), error = e)),
+    }
+}
+
+/// Quotes `s` for a POSIX shell.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Runs a git command, returning its standard output.
+fn run(cmd: &mut Command) -> Result<Vec<u8>, String> {
+    let output = cmd
+        .output()
+        .map_err(|e| tr!("could-not-run", program = "git", error = e))?;
+    if !output.status.success() {
+        return Err(tr!(
+            "program-failed",
+            program = "git",
+            output = String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_tracked_files() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path();
+        if run(Command::new("git").arg("-C").arg(dir).arg("init")).is_err() {
+            return; // git isn't installed
+        }
+        std::fs::create_dir_all(dir.join("home/a")).unwrap();
+        for file in [".vimrc", ".vimrc.swp", "a-b", "a.c", "a/x"] {
+            std::fs::write(dir.join("home").join(file), "").unwrap();
+        }
+        run(Command::new("git").arg("-C").arg(dir).args([
+            "add",
+            "home/.vimrc",
+            "home/a-b",
+            "home/a.c",
+            "home/a/x",
+        ]))
+        .unwrap();
+
+        // Walked in git's order, every tracked file is found, and nothing else.
+        let home = dir.join("home");
+        let mut tracked = tracked_files(&home).unwrap();
+        let found: Vec<PathBuf> = walkdir::WalkDir::new(&home)
+            .min_depth(1)
+            .sort_by(git_order)
+            .into_iter()
+            .map(|e| e.unwrap())
+            .filter(|e| tracked.contains(e.path(), e.file_type().is_dir()))
+            .map(|e| e.into_path())
+            .collect();
+
+        assert_eq!(found, [".vimrc", "a-b", "a.c", "a/x"].map(|f| home.join(f)));
+        assert!(tracked_files(&home)
+            .unwrap()
+            .contains_under(&home.join("a")));
+        assert!(!tracked_files(&home)
+            .unwrap()
+            .contains_under(&home.join("b")));
+        assert!(is_tracked(&dir.join("home/.vimrc")));
+        assert!(!is_tracked(&dir.join("home/.vimrc.swp")));
+    }
+
+    #[test]
+    fn test_ignored_files() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path();
+        if run(Command::new("git").arg("-C").arg(dir).arg("init")).is_err() {
+            return; // git isn't installed
+        }
+        std::fs::create_dir_all(dir.join("home/build")).unwrap();
+        std::fs::write(dir.join(".gitignore"), "*.o\nbuild/\n").unwrap();
+        std::fs::write(dir.join("home/.vimrc"), "").unwrap();
+        std::fs::write(dir.join("home/main.o"), "").unwrap();
+        std::fs::write(dir.join("home/build/out"), "").unwrap();
+
+        let mut ignored = ignored_files(&dir.join("home"));
+
+        assert!(!ignored.contains(&dir.join("home/.vimrc"), false));
+        assert!(ignored.contains(&dir.join("home/build"), true));
+        assert!(ignored.contains(&dir.join("home/main.o"), false));
+    }
+
+    #[test]
+    fn test_install_hooks() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir(&dir).unwrap();
+        if run(Command::new("git").arg("-C").arg(&dir).arg("init")).is_err() {
+            return; // git isn't installed
+        }
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "git-hook", "install"]);
+        fs::write(dir.join(".git/hooks/post-checkout"), "#!/bin/sh\n").unwrap();
+
+        assert!(install_hooks(&args, &mut io::sink()).is_err());
+        assert!(!dir.join(".git/hooks/post-merge").exists());
+
+        fs::remove_file(dir.join(".git/hooks/post-checkout")).unwrap();
+        install_hooks(&args, &mut io::sink()).unwrap();
+        install_hooks(&args, &mut io::sink()).unwrap();
+        let hook = fs::read_to_string(dir.join(".git/hooks/post-merge")).unwrap();
+        assert!(hook.starts_with("#!/bin/sh\n"));
+        assert!(hook.contains(&shell_quote(&dir.canonicalize().unwrap().to_string_lossy())));
+
+        uninstall_hooks(&args, &mut io::sink()).unwrap();
+        assert!(!dir.join(".git/hooks/post-merge").exists());
+        assert!(!dir.join(".git/hooks/post-checkout").exists());
+    }
+
+    #[test]
+    fn test_commit_message() {
+        assert_eq!(
+            commit_message(
+                "Adopt {count} file(s) into {package}: {files}",
+                "home",
+                &[Path::new("home/.vimrc"), Path::new("home/.zshrc")]
+            ),
+            "Adopt 2 file(s) into home: home/.vimrc, home/.zshrc"
+        );
+    }
+}
@@ -0,0 +1,474 @@
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::manifest::Manifest;
+use crate::paths;
+use crate::plan::{self, Action};
+use crate::state::{self, State};
+use crate::{Args, LV_DEBUG, LV_INFO, LV_WARN};
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// Directory in the package root holding executable hooks; it is never linked.
+pub const HOOKS_DIR: &str = "hooks";
+
+/// Environment variables sandboxed hooks keep; everything else (tokens, SSH agents, proxies) is
+/// cleared.
+const SANDBOX_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "LANG",
+    "LC_ALL",
+    "TERM",
+    "TMPDIR",
+    "SYSTEMROOT",
+];
+
+/// Shell script confining a sandboxed hook's filesystem, run in its own user and mount
+/// namespaces: the scratch directory `$0` stays writable and becomes the working directory,
+/// every other mount is made read-only, and then the hook runs as "$@". A mount that can't be
+/// made read-only fails the hook rather than leaving it writable.
+const CONFINE: &str = r#"mount --bind "$0" "$0" || exit 1
+while read -r _ mount _; do
+    [ "$mount" = "$0" ] || mount -o remount,bind,ro "$mount" || exit 1
+done < /proc/self/mounts
+cd "$0" && exec "$@""#;
+
+/// Point in an operation at which hooks run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    PreLink,
+    PostLink,
+    PreUnlink,
+    PostUnlink,
+}
+
+impl Phase {
+    /// The hook's file name in 'hooks/', which is also its key in the manifest's '[hooks]'.
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::PreLink => "pre-link",
+            Phase::PostLink => "post-link",
+            Phase::PreUnlink => "pre-unlink",
+            Phase::PostUnlink => "post-unlink",
+        }
+    }
+
+    /// The operation this phase belongs to, exported to hooks as 'RANCH_ACTION'.
+    pub fn action(self) -> &'static str {
+        match self {
+            Phase::PreLink | Phase::PostLink => "link",
+            Phase::PreUnlink | Phase::PostUnlink => "unlink",
+        }
+    }
+
+    fn is_pre(self) -> bool {
+        matches!(self, Phase::PreLink | Phase::PreUnlink)
+    }
+}
+
+/// The operation that hooks are running around.
+pub struct HookContext<'a> {
+    pub package: &'a str,
+    pub prefix_path: &'a Path,
+    pub target_path: &'a Path,
+    pub manifest: &'a Manifest,
+    pub actions: &'a [Action],
+}
+
+/// Runs every hook for `phase`: the user's global hooks from the config file, and the package's
+/// executable 'hooks/<phase>' followed by its manifest command. Global 'pre' hooks run before the
+/// package's and global 'post' hooks run after them, so they bracket the whole operation.
+///
+/// Hooks run from the package directory with 'RANCH_ACTION', 'RANCH_PACKAGE', 'RANCH_DIR', and
+/// 'RANCH_TARGET' set, and receive the JSON plan on stdin. Any hook failing aborts the operation,
+/// as does a hook outliving '--hook-timeout'. Hook output is relayed to ranch's log prefixed with
+/// the package and phase: stdout at '-vv', stderr at '-v'. With '--dry-run', hooks are only
+/// reported.
+pub fn run_hooks(
+    args: &Args,
+    config: &Config,
+    phase: Phase,
+    ctx: &HookContext,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let mut commands = Vec::new();
+    let global = match phase.is_pre() {
+        true => &config.hooks.pre,
+        false => &config.hooks.post,
+    };
+    if phase.is_pre() {
+        commands.extend(global.iter().map(|c| shell(c)));
+    }
+    let script = ctx.prefix_path.join(HOOKS_DIR).join(phase.name());
+    if script.is_file() {
+        commands.push(Command::new(&script));
+    }
+    let hooks = &ctx.manifest.hooks;
+    let command = match phase {
+        Phase::PreLink => &hooks.pre_link,
+        Phase::PostLink => &hooks.post_link,
+        Phase::PreUnlink => &hooks.pre_unlink,
+        Phase::PostUnlink => &hooks.post_unlink,
+    };
+    commands.extend(command.iter().map(|c| shell(c)));
+    if !phase.is_pre() {
+        commands.extend(global.iter().map(|c| shell(c)));
+    }
+
+    let label = format!("{} {}", ctx.package, phase.name());
+    run_commands(args, config, phase, ctx, &label, commands, stderr)
+}
+
+/// Runs the package's 'run_once_*' scripts that haven't already run on this machine, recording
+/// each one in `state` (by content, so editing a script makes it run again) as soon as it
+/// succeeds. Scripts run like post-link hooks, in path order.
+pub fn run_once(
+    args: &Args,
+    config: &Config,
+    ctx: &HookContext,
+    state: &mut State,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    for script in plan::run_once_scripts(ctx.prefix_path) {
+        let contents = std::fs::read(&script)
+            .map_err(|e| tr!("could-not-read", path = script.display(), error = e))?;
+        let hash = state::sha256_hex(&contents);
+        let name = script.strip_prefix(ctx.prefix_path).unwrap_or(&script);
+        let label = format!("{} {}", ctx.package, name.display());
+        if state.run_once.contains_key(&hash) {
+            if args.verbosity("hooks") >= LV_DEBUG {
+                _ = writeln!(stderr, "Skipping {}; it already ran", label);
+            }
+            continue;
+        }
+        let cmd = Command::new(&script);
+        run_commands(
+            args,
+            config,
+            Phase::PostLink,
+            ctx,
+            &label,
+            vec![cmd],
+            stderr,
+        )?;
+        if !args.changes_nothing() {
+            state.run_once.insert(hash, label);
+            state.save(args)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs `commands` in turn with the hook environment contract, stopping at the first failure.
+fn run_commands(
+    args: &Args,
+    config: &Config,
+    phase: Phase,
+    ctx: &HookContext,
+    label: &str,
+    commands: Vec<Command>,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    if commands.is_empty() {
+        return Ok(());
+    }
+    let plan =
+        serde_json::to_vec(ctx.actions).map_err(|e| tr!("could-not-serialize-plan", error = e))?;
+    let timeout = args
+        .hook_timeout
+        .or(config.hook_timeout)
+        .map(Duration::from_secs);
+    let sandbox = args.sandbox_hooks || config.sandbox_hooks;
+    if sandbox && jail().is_none() && args.verbosity("hooks") >= LV_WARN {
+        _ = writeln!(stderr, "{}", tr!("no-hook-isolation"));
+    }
+    for cmd in commands {
+        // Sandboxed hooks start in an empty, private scratch directory rather than the package;
+        // dry runs only show the command, so they name one without creating it.
+        let tempdir = match sandbox && !args.changes_nothing() {
+            true => Some(paths::private_temp_dir("ranch-hook-")?),
+            false => None,
+        };
+        let scratch = sandbox.then(|| match &tempdir {
+            Some(dir) => dir.path().to_path_buf(),
+            None => env::temp_dir().join("ranch-hook-XXXXXX"),
+        });
+        let mut cmd = match &scratch {
+            Some(scratch) => sandboxed(cmd, scratch),
+            None => cmd,
+        };
+        cmd.current_dir(scratch.as_deref().unwrap_or(ctx.prefix_path))
+            .env("RANCH_ACTION", phase.action())
+            .env("RANCH_PACKAGE", ctx.package)
+            .env("RANCH_DIR", &args.dir)
+            .env("RANCH_TARGET", ctx.target_path);
+        run(args, label, cmd, &plan, timeout, stderr)?;
+    }
+    Ok(())
+}
+
+/// Returns the 'unshare' arguments that put a process in its own user, network, and mount
+/// namespaces, cut off from the network and free to remount the filesystem read-only for
+/// itself, if user namespaces are usable on this machine.
+fn jail() -> Option<&'static [&'static str]> {
+    static JAIL: OnceLock<Option<&'static [&'static str]>> = OnceLock::new();
+    *JAIL.get_or_init(|| {
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+        // Older util-linux lacks '--map-current-user'; mapping to root is the fallback.
+        let candidates: [&'static [&'static str]; 2] = [
+            &["--user", "--map-current-user", "--net", "--mount", "--"],
+            &["--user", "--map-root-user", "--net", "--mount", "--"],
+        ];
+        candidates.into_iter().find(|jail| {
+            Command::new("unshare")
+                .args(&jail[..jail.len() - 1])
+                .arg("true")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success())
+        })
+    })
+}
+
+/// Rebuilds `cmd` to run sandboxed: with only the [`SANDBOX_ENV`] variables, and, where
+/// possible, in its own network namespace with no interfaces but loopback, with the whole
+/// filesystem read-only except for the directory `scratch`, per [`CONFINE`].
+fn sandboxed(cmd: Command, scratch: &Path) -> Command {
+    let mut sandboxed = match jail() {
+        Some(jail) => {
+            let mut unshare = Command::new("unshare");
+            unshare
+                .args(jail)
+                .args(["sh", "-c", CONFINE])
+                .arg(scratch)
+                .arg(cmd.get_program());
+            unshare
+        }
+        None => Command::new(cmd.get_program()),
+    };
+    sandboxed.args(cmd.get_args()).env_clear();
+    for name in SANDBOX_ENV {
+        if let Some(value) = std::env::var_os(name) {
+            sandboxed.env(name, value);
+        }
+    }
+    sandboxed
+}
+
+/// Builds a command running `line` with the platform shell.
+fn shell(line: &str) -> Command {
+    #[cfg(windows)]
+    let mut cmd = Command::new("cmd");
+    #[cfg(windows)]
+    cmd.arg("/C");
+    #[cfg(not(windows))]
+    let mut cmd = Command::new("sh");
+    #[cfg(not(windows))]
+    cmd.arg("-c");
+    cmd.arg(line);
+    cmd
+}
+
+fn run(
+    args: &Args,
+    label: &str,
+    mut cmd: Command,
+    stdin: &[u8],
+    timeout: Option<Duration>,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    if args.changes_nothing() {
+        if args.verbosity("hooks") >= LV_INFO {
+            _ = writeln!(stderr, "Would run {} hook: {:?}", label, cmd);
+        }
+        return Ok(());
+    }
+    if args.verbosity("hooks") >= LV_INFO {
+        _ = writeln!(stderr, "Running {} hook: {:?}", label, cmd);
+    }
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            tr!(
+                "could-not-run-hook",
+                hook = label,
+                command = format!("{:?}", cmd),
+                error = e
+            )
+        })?;
+
+    // Feed the plan from another thread: a hook that never reads stdin must not deadlock ranch
+    // once the plan outgrows the pipe buffer, and it's free to close stdin early.
+    let mut pipe = child.stdin.take().unwrap();
+    let stdin = stdin.to_vec();
+    std::thread::spawn(move || _ = pipe.write_all(&stdin));
+
+    // Relay output line by line from reader threads; `true` marks lines from stderr.
+    let (tx, rx) = mpsc::channel::<(bool, String)>();
+    let out_tx = tx.clone();
+    let out = io::BufReader::new(child.stdout.take().unwrap());
+    std::thread::spawn(move || {
+        for line in out.lines().map_while(Result::ok) {
+            _ = out_tx.send((false, line));
+        }
+    });
+    let err = io::BufReader::new(child.stderr.take().unwrap());
+    std::thread::spawn(move || {
+        for line in err.lines().map_while(Result::ok) {
+            _ = tx.send((true, line));
+        }
+    });
+    let mut relay = |(is_stderr, line): (bool, String)| {
+        if args.verbosity("hooks") >= if is_stderr { LV_WARN } else { LV_INFO } {
+            _ = writeln!(stderr, "[{}] {}", label, line);
+        }
+    };
+
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let status = loop {
+        match rx.recv_timeout(Duration::from_millis(50)) {
+            Ok(line) => {
+                relay(line);
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            // Both pipes are closed, so there's nothing left to wait on but the process.
+            Err(RecvTimeoutError::Disconnected) => std::thread::sleep(Duration::from_millis(50)),
+        }
+        // Stop once the hook itself exits, even if something it spawned (e.g. a tmux server)
+        // still holds the pipes open.
+        if let Some(status) = child.try_wait().map_err(|e| {
+            tr!(
+                "could-not-run-hook",
+                hook = label,
+                command = format!("{:?}", cmd),
+                error = e
+            )
+        })? {
+            rx.try_iter().for_each(&mut relay);
+            break status;
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            _ = child.kill();
+            _ = child.wait();
+            return Err(tr!(
+                "hook-timed-out",
+                hook = label,
+                command = format!("{:?}", cmd),
+                seconds = timeout.unwrap_or_default().as_secs()
+            ));
+        }
+    };
+    if !status.success() {
+        return Err(tr!(
+            "hook-failed",
+            hook = label,
+            command = format!("{:?}", cmd),
+            status = status
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_run_captures_output_and_times_out() {
+        let args = Args::parse_from(["ranch", "-vv", "home"]);
+        let mut stderr = Vec::new();
+
+        run(
+            &args,
+            "home post-link",
+            shell("echo out; echo err >&2; cat"),
+            b"plan",
+            None,
+            &mut stderr,
+        )
+        .unwrap();
+        let stderr = String::from_utf8(stderr).unwrap();
+        assert!(stderr.contains("[home post-link] out\n"));
+        assert!(stderr.contains("[home post-link] err\n"));
+        assert!(stderr.contains("[home post-link] plan\n"));
+
+        let start = Instant::now();
+        let result = run(
+            &args,
+            "home post-link",
+            shell("sleep 10"),
+            b"",
+            Some(Duration::from_millis(200)),
+            &mut io::sink(),
+        );
+        assert!(result.unwrap_err().contains("timed out"));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_sandboxed_clears_environment() {
+        let args = Args::parse_from(["ranch", "-vv", "home"]);
+        // Any inherited variable the sandbox doesn't keep stands in for a token.
+        let Some((name, _)) = env::vars().find(|(name, value)| {
+            !SANDBOX_ENV.contains(&name.as_str())
+                && !value.is_empty()
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }) else {
+            return;
+        };
+        let mut stderr = Vec::new();
+
+        let line = format!("echo token=${}", name);
+        run(
+            &args,
+            "home",
+            sandboxed(shell(&line), &env::temp_dir()),
+            b"",
+            None,
+            &mut stderr,
+        )
+        .unwrap();
+
+        assert!(String::from_utf8(stderr)
+            .unwrap()
+            .contains("[home] token=\n"));
+    }
+
+    #[test]
+    fn test_sandboxed_confines_writes_to_scratch() {
+        if jail().is_none() {
+            return;
+        }
+        let args = Args::parse_from(["ranch", "home"]);
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let scratch = tmp_dir.path().join("scratch");
+        fs::create_dir(&scratch).unwrap();
+        let outside = tmp_dir.path().join("outside");
+
+        let line = format!("touch inside; touch '{}'", outside.display());
+        let mut cmd = sandboxed(shell(&line), &scratch);
+        cmd.current_dir(&scratch);
+        let result = run(&args, "home", cmd, b"", None, &mut io::sink());
+
+        assert!(result.is_err());
+        assert!(scratch.join("inside").exists());
+        assert!(!outside.exists());
+    }
+}
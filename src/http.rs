@@ -0,0 +1,213 @@
+use crate::i18n::tr;
+use std::io::{self, BufRead, BufReader, Read};
+use std::net::{IpAddr, TcpListener, ToSocketAddrs};
+use std::time::Duration;
+
+/// Longest request head (request line plus headers) accepted before giving up on a client.
+const MAX_HEAD: u64 = 16 * 1024;
+
+/// How long a client may take to send its request, or to take the response.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The parts of an HTTP request the daemon's API looks at; request bodies are ignored.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    /// Path without its query string.
+    pub path: String,
+    /// The 'Host' header.
+    pub host: Option<String>,
+    /// The 'Authorization' header.
+    pub authorization: Option<String>,
+}
+
+impl Request {
+    /// Whether the request names a loopback address as its host, as one sent by a browser lured
+    /// through DNS rebinding wouldn't.
+    pub fn is_to_loopback(&self) -> bool {
+        self.host.as_deref().is_some_and(is_loopback_host)
+    }
+
+    /// Whether the request carries `token` as its bearer token.
+    pub fn has_token(&self, token: &str) -> bool {
+        let presented = self
+            .authorization
+            .as_deref()
+            .and_then(|a| a.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        // Compare in constant time, so that the token can't be guessed byte by byte.
+        presented.len() == token.len()
+            && presented
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// Whether `host`, a 'Host' header, names a loopback address: 'localhost', '127.0.0.1', or
+/// '[::1]', with or without a port.
+fn is_loopback_host(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    name.eq_ignore_ascii_case("localhost")
+        || name.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json(status: u16, body: &impl serde::Serialize) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_string(body).unwrap_or_default() + "\n",
+        }
+    }
+
+    pub fn text(status: u16, body: &str) -> Response {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: String::from(body) + "\n",
+        }
+    }
+
+    /// Writes the response and closes the connection.
+    pub fn write(&self, out: &mut impl io::Write) -> io::Result<()> {
+        write!(
+            out,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len(),
+            self.body
+        )?;
+        out.flush()
+    }
+}
+
+/// Listens on `addr`, which must be a loopback address: the API can trigger an apply, so it's
+/// kept off the network, on top of its token.
+pub fn bind(addr: &str) -> Result<TcpListener, String> {
+    let addrs: Vec<_> = addr
+        .to_socket_addrs()
+        .map_err(|e| tr!("invalid-http-address", address = addr, error = e))?
+        .collect();
+    if addrs.is_empty() || addrs.iter().any(|a| !a.ip().is_loopback()) {
+        return Err(tr!("http-not-loopback", address = addr));
+    }
+    TcpListener::bind(&addrs[..]).map_err(|e| tr!("could-not-listen", address = addr, error = e))
+}
+
+/// Reads a request line and headers from `stream`.
+pub fn read_request(stream: impl Read) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream.take(MAX_HEAD));
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|e| tr!("could-not-read-request", error = e))?;
+    let mut words = line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (words.next(), words.next(), words.next())
+    else {
+        return Err(tr!("malformed-request", line = line.trim_end()));
+    };
+    let mut request = Request {
+        method: String::from(method),
+        path: String::from(target.split('?').next().unwrap_or_default()),
+        host: None,
+        authorization: None,
+    };
+    // Read all of the headers, so the client isn't reset while still sending them.
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => return Err(tr!("request-cut-off")),
+            Ok(_) if line.trim_end().is_empty() => return Ok(request),
+            Ok(_) => {}
+            Err(e) => return Err(tr!("could-not-read-request", error = e)),
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = Some(String::from(value.trim()));
+        if name.eq_ignore_ascii_case("host") {
+            request.host = value;
+        } else if name.eq_ignore_ascii_case("authorization") {
+            request.authorization = value;
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_request() {
+        let request = read_request(
+            "POST /apply?dry=1 HTTP/1.1\r\nHost: localhost:7878\r\n\
+             authorization: Bearer s3cret\r\nContent-Length: 0\r\n\r\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            Request {
+                method: String::from("POST"),
+                path: String::from("/apply"),
+                host: Some(String::from("localhost:7878")),
+                authorization: Some(String::from("Bearer s3cret")),
+            }
+        );
+        assert!(read_request("GET /status HTTP/1.1\r\nHost: x\r\n".as_bytes()).is_err());
+        assert!(read_request("hello\r\n\r\n".as_bytes()).is_err());
+        assert!(bind("0.0.0.0:0").is_err());
+        assert!(bind("127.0.0.1:0").is_ok());
+    }
+
+    #[test]
+    fn test_request_checks() {
+        let request = |host: &str, authorization: &str| Request {
+            method: String::from("POST"),
+            path: String::from("/apply"),
+            host: Some(String::from(host)),
+            authorization: Some(String::from(authorization)),
+        };
+        assert!(request("127.0.0.1:7878", "").is_to_loopback());
+        assert!(request("[::1]:7878", "").is_to_loopback());
+        assert!(request("LocalHost", "").is_to_loopback());
+        assert!(!request("evil.example:7878", "").is_to_loopback());
+        assert!(!request("127.0.0.1.evil.example", "").is_to_loopback());
+
+        assert!(request("localhost", "Bearer s3cret").has_token("s3cret"));
+        assert!(!request("localhost", "Bearer s3cre").has_token("s3cret"));
+        assert!(!request("localhost", "s3cret").has_token("s3cret"));
+        let anonymous = Request {
+            authorization: None,
+            ..request("localhost", "")
+        };
+        assert!(!anonymous.has_token("s3cret"));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::paths;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::sync::{LazyLock, OnceLock};
+
+/// Catalogs built into ranch, by language tag; English is the fallback for every message.
+const BUNDLED: &[(&str, &str)] = &[
+    ("en", include_str!("locales/en.ftl")),
+    ("de", include_str!("locales/de.ftl")),
+];
+
+static ENGLISH: LazyLock<Catalog> = LazyLock::new(|| Catalog::parse(BUNDLED[0].1));
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Looks up the message `id` in the chosen language's catalog, filling in its `{ $name }`
+/// placeables from the `name = value` pairs.
+macro_rules! tr {
+    ($id:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::message($id, &[$((stringify!($name), ($value).to_string())),*])
+    };
+}
+pub(crate) use tr;
+
+/// Messages by id, read from a Fluent-style catalog: 'id = text' lines, where text may go on in
+/// lines indented by four spaces, and '{ $name }' stands for an argument; '{ "{" }' writes a
+/// literal brace. Lines starting with '#' are comments, and lines that aren't understood are
+/// skipped, as Fluent skips junk.
+#[derive(Debug, Default)]
+pub struct Catalog {
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    pub fn parse(text: &str) -> Catalog {
+        let mut messages = HashMap::new();
+        let mut last: Option<String> = None;
+        for line in text.lines() {
+            if let (Some(rest), Some(id)) = (line.strip_prefix("    "), &last) {
+                let message: &mut String = messages.get_mut(id).expect("parsed above");
+                message.push('\n');
+                message.push_str(rest);
+                continue;
+            }
+            last = None;
+            let Some((id, text)) = line.split_once('=') else {
+                continue;
+            };
+            let id = id.trim();
+            if line.starts_with('#') || id.is_empty() || id.contains(char::is_whitespace) {
+                continue;
+            }
+            messages.insert(id.to_owned(), text.trim().to_owned());
+            last = Some(id.to_owned());
+        }
+        Catalog { messages }
+    }
+
+    /// The message `id` with its placeables filled in from `args`; None if there isn't one.
+    pub fn format(&self, id: &str, args: &[(&str, String)]) -> Option<String> {
+        let mut template = self.messages.get(id)?.as_str();
+        let mut out = String::new();
+        while let Some(start) = template.find('{') {
+            out.push_str(&template[..start]);
+            let inner = template[start + 1..].trim_start();
+            // A string literal may itself hold braces, so it ends at its closing quote.
+            let (text, rest) = match inner.strip_prefix('"') {
+                Some(literal) => match literal.split_once('"') {
+                    Some((text, rest)) => (text, rest),
+                    None => return Some(out + &template[start..]),
+                },
+                None => ("", inner),
+            };
+            let Some(end) = rest.find('}') else {
+                return Some(out + &template[start..]);
+            };
+            match rest[..end].trim().strip_prefix('$') {
+                Some(name) => match args.iter().find(|(n, _)| *n == name) {
+                    Some((_, value)) => out.push_str(value),
+                    None => out.push_str(&template[start..template.len() - rest.len() + end + 1]),
+                },
+                None => out.push_str(text),
+            }
+            template = &rest[end + 1..];
+        }
+        out.push_str(template);
+        Some(out)
+    }
+}
+
+/// Chooses the language of messages: `lang` from '--lang', or else '$LC_ALL', '$LC_MESSAGES', or
+/// '$LANG', such as 'de_DE.UTF-8'. A catalog in '<config dir>/ranch/locales/TAG.ftl' is used if
+/// there is one, so that users can add or correct translations, and otherwise the bundled one;
+/// 'de-DE' falls back to 'de', and anything missing to English. Only the first call has effect.
+pub fn init(lang: Option<&str>) {
+    let lang = lang.map(String::from).or_else(|| {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+    });
+    let catalog = lang.and_then(|lang| {
+        let tag = lang
+            .split(['.', '@'])
+            .next()
+            .unwrap_or_default()
+            .replace('_', "-");
+        let primary = tag.split('-').next().unwrap_or_default().to_owned();
+        [tag, primary].into_iter().find_map(|tag| load(&tag))
+    });
+    _ = CATALOG.set(catalog.unwrap_or_default());
+}
+
+/// The last '--lang' value in `argv`, found before parsing so that errors from parsing the rest
+/// are translated too.
+pub fn lang_option(argv: &[OsString]) -> Option<String> {
+    let mut lang = None;
+    for (i, word) in argv.iter().enumerate() {
+        match word.to_str() {
+            Some("--") => break,
+            Some("--lang") => lang = argv.get(i + 1).and_then(|w| w.to_str()).map(String::from),
+            Some(w) => {
+                if let Some(value) = w.strip_prefix("--lang=") {
+                    lang = Some(value.to_owned());
+                }
+            }
+            None => {}
+        }
+    }
+    lang
+}
+
+fn load(tag: &str) -> Option<Catalog> {
+    if tag.is_empty() || tag == "C" || tag == "POSIX" {
+        return None;
+    }
+    let user = paths::config_dir()
+        .map(|dir| {
+            dir.join("ranch")
+                .join("locales")
+                .join(format!("{}.ftl", tag))
+        })
+        .and_then(|path| fs::read_to_string(path).ok());
+    let bundled = BUNDLED
+        .iter()
+        .find(|(t, _)| t.eq_ignore_ascii_case(tag))
+        .map(|(_, text)| text.to_string());
+    user.or(bundled).map(|text| Catalog::parse(&text))
+}
+
+/// The message `id` in the chosen language, or else in English; see [tr].
+pub fn message(id: &str, args: &[(&str, String)]) -> String {
+    CATALOG
+        .get()
+        .and_then(|catalog| catalog.format(id, args))
+        .or_else(|| ENGLISH.format(id, args))
+        .unwrap_or_else(|| id.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let catalog = Catalog::parse(
+            "# A comment\n\
+             could-not-read = Could not read { $path }: { $error }\n\
+             stale = { $plan } is stale:\n    { $drift }\n\
+             braces = Use { \"{\" }x{ \"}\" }\n",
+        );
+        let args = [
+            ("path", String::from("~/.vimrc")),
+            ("error", String::from("gone")),
+        ];
+        assert_eq!(
+            catalog.format("could-not-read", &args).unwrap(),
+            "Could not read ~/.vimrc: gone"
+        );
+        assert_eq!(
+            catalog
+                .format("stale", &[("plan", String::from("p.json"))])
+                .unwrap(),
+            "p.json is stale:\n{ $drift }"
+        );
+        assert_eq!(catalog.format("braces", &[]).unwrap(), "Use {x}");
+        assert_eq!(catalog.format("missing", &[]), None);
+    }
+
+    #[test]
+    fn test_catalogs_are_complete() {
+        let english = Catalog::parse(BUNDLED[0].1);
+        for entry in fs::read_dir("src").unwrap() {
+            let source = fs::read_to_string(entry.unwrap().path()).unwrap_or_default();
+            for (at, _) in source.match_indices("tr!(") {
+                // Skips the likes of 'include_str!(', and this search.
+                if source[..at].ends_with(|c: char| c.is_alphanumeric() || c == '_' || c == '"') {
+                    continue;
+                }
+                let Some(id) = source[at + 4..].trim_start().strip_prefix('"') else {
+                    continue;
+                };
+                let id = &id[..id.find('"').unwrap_or(0)];
+                assert!(english.messages.contains_key(id), "{} isn't in en.ftl", id);
+            }
+        }
+        for (tag, text) in &BUNDLED[1..] {
+            let catalog = Catalog::parse(text);
+            for id in english.messages.keys() {
+                assert!(
+                    catalog.messages.contains_key(id),
+                    "{} isn't in {}.ftl",
+                    id,
+                    tag
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::git;
+use crate::i18n::tr;
+use crate::manifest::Manifest;
+use crate::plan::{self, Action};
+use crate::Args;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Editor, OS, and merge droppings that are never deployed, even if present in a package.
+const JUNK_NAMES: &[&str] = &[".DS_Store", "Thumbs.db", "desktop.ini"];
+const JUNK_SUFFIXES: &[&str] = &[".swp", ".swo", "~", ".orig", ".rej"];
+
+/// File names that conventionally hold credentials.
+const SECRET_NAMES: &[&str] = &[
+    "id_rsa",
+    "id_dsa",
+    "id_ecdsa",
+    "id_ed25519",
+    ".netrc",
+    ".pgpass",
+    ".git-credentials",
+    "credentials",
+    "secring.gpg",
+];
+
+/// File extensions that conventionally hold keys or credentials.
+const SECRET_EXTENSIONS: &[&str] = &["pem", "key", "p12", "pfx", "kdbx"];
+
+/// Whether `path` is junk, such as an editor's backup or swap file, that 'ranch lint' reports.
+pub fn is_junk(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    JUNK_NAMES.contains(&name)
+        || JUNK_SUFFIXES.iter().any(|s| name.ends_with(s))
+        || name.starts_with(".#")
+        || (name.len() > 1 && name.starts_with('#') && name.ends_with('#'))
+}
+
+/// How much of a file `is_binary` reads, as git does when deciding whether to diff it.
+const BINARY_SNIFF_LEN: u64 = 8000;
+
+/// Whether the file at `path` looks like a compiled artifact or other binary rather than text:
+/// like git, a NUL byte in its first few kilobytes gives it away. Unreadable files aren't
+/// binary, so that deploying them reports the real problem.
+pub fn is_binary(path: &Path) -> bool {
+    let mut head = Vec::new();
+    File::open(path)
+        .and_then(|f| f.take(BINARY_SNIFF_LEN).read_to_end(&mut head))
+        .is_ok_and(|_| head.contains(&0))
+}
+
+/// Checks `packages` (or every package in 'DIR') for problems that would bite once deployed:
+/// files claimed by more than one package, case-only name collisions, broken symlinks, junk
+/// files, invalid manifests, and secret-looking files readable by other users.
+pub fn lint(args: &Args, packages: &[String], stderr: &mut dyn io::Write) -> Result<(), String> {
+    let dir = &args.dir;
+    let packages = match packages {
+        [] => plan::packages(dir)?,
+        _ => packages.to_vec(),
+    };
+    let mut problems = Vec::new();
+    // Target paths already claimed, both exactly and case-folded, with the package claiming them.
+    let mut claimed: HashMap<PathBuf, (&str, PathBuf)> = HashMap::new();
+    let mut folded: HashMap<String, PathBuf> = HashMap::new();
+    for package in &packages {
+        let prefix_path = dir.join(package);
+        if !prefix_path.is_dir() {
+            problems.push(tr!("lint-no-such-package", package = package));
+            continue;
+        }
+        for entry in WalkDir::new(&prefix_path)
+            .into_iter()
+            .filter_map(|r| r.ok())
+        {
+            let path = entry.path();
+            if entry.path_is_symlink() && !path.exists() {
+                problems.push(tr!(
+                    "lint-broken-symlink",
+                    path = path.display(),
+                    target = std::fs::read_link(path).unwrap_or_default().display()
+                ));
+            }
+            if is_junk(path) {
+                problems.push(tr!("lint-junk", path = path.display()));
+            }
+        }
+        let target_path = match Manifest::load(&prefix_path)
+            .and_then(|manifest| plan::target_path(args, package, &manifest))
+        {
+            Ok(target_path) => target_path,
+            Err(e) => {
+                problems.push(e);
+                continue;
+            }
+        };
+        let actions = match plan::plan(args, package, &prefix_path, &target_path, stderr) {
+            Ok(actions) => actions,
+            Err(e) => {
+                problems.push(e);
+                continue;
+            }
+        };
+        problems.extend(check_secret_permissions(&actions));
+        problems.extend(check_submodules(&prefix_path));
+        for action in actions {
+            // Packages may share directories.
+            if let Action::Mkdir { .. } = action {
+                continue;
+            }
+            let dst = action.dst().to_path_buf();
+            if let Some((owner, src)) = claimed.get(&dst) {
+                problems.push(tr!(
+                    "lint-collision",
+                    path = dst.display(),
+                    package = owner,
+                    package_file = src.display(),
+                    other_package = package,
+                    other_package_file = action.src().display()
+                ));
+                continue;
+            }
+            let key = dst.to_string_lossy().to_lowercase();
+            if let Some(other) = folded.get(&key) {
+                problems.push(tr!(
+                    "lint-case-collision",
+                    path = other.display(),
+                    other_path = dst.display()
+                ));
+            }
+            folded.insert(key, dst.clone());
+            claimed.insert(dst, (package, action.src().to_path_buf()));
+        }
+    }
+
+    for problem in &problems {
+        _ = writeln!(stderr, "{}", problem);
+    }
+    match problems.len() {
+        0 => Ok(()),
+        n => Err(tr!("lint-problems", count = n)),
+    }
+}
+
+/// Whether `path`'s name suggests that it holds a private key or credentials.
+pub fn looks_secret(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    SECRET_NAMES.contains(&name) || SECRET_EXTENSIONS.contains(&extension)
+}
+
+/// Reports secret-looking files that would be readable by other users once deployed.
+///
+/// Links expose the repo copy's permissions, so a private key committed as 0644 is effectively
+/// world readable in $HOME too. Decrypted secrets and rendered templates are always written
+/// owner-only, so only links are checked.
+pub fn check_secret_permissions(actions: &[Action]) -> Vec<String> {
+    let mut findings = Vec::new();
+    for action in actions {
+        let Action::Link { src, dst } = action else {
+            continue;
+        };
+        if !looks_secret(dst) {
+            continue;
+        }
+        #[cfg(unix)]
+        if let Ok(metadata) = std::fs::metadata(src) {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = metadata.permissions().mode() & 0o777;
+            if mode & 0o044 != 0 {
+                findings.push(tr!(
+                    "lint-readable-secret",
+                    path = src.display(),
+                    mode = format!("{:03o}", mode)
+                ));
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = src;
+    }
+    findings
+}
+
+/// Reports git submodules in a package that aren't checked out; the package would deploy
+/// without their files, and nothing else would say so.
+pub fn check_submodules(prefix_path: &Path) -> Vec<String> {
+    git::uninitialized_submodules(prefix_path)
+        .into_iter()
+        .map(|path| tr!("lint-submodule", path = path.display()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint() {
+        use clap::Parser;
+        let tmp_dir = tempdir::TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        for file in [
+            "zsh/.zshrc",
+            "bash/.zshrc",
+            "vim/.vimrc",
+            "vim/.VIMRC",
+            "vim/.vimrc.swp",
+        ] {
+            std::fs::create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        std::fs::write(dir.join("vim/.ranch.toml"), "targett = 1").unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "lint"]);
+        let mut stderr = Vec::new();
+
+        let result = lint(&args, &[], &mut stderr);
+
+        let stderr = String::from_utf8(stderr).unwrap();
+        assert_eq!(result.unwrap_err(), "3 problem(s) found", "{}", stderr);
+        assert!(stderr.contains("is provided by both bash"));
+        assert!(stderr.contains(".vimrc.swp is junk"));
+        assert!(stderr.contains("unknown field `targett`"));
+
+        std::fs::remove_file(dir.join("vim/.ranch.toml")).unwrap();
+        let result = lint(&args, &[String::from("vim")], &mut Vec::new());
+        assert_eq!(result.unwrap_err(), "2 problem(s) found");
+    }
+
+    #[test]
+    fn test_is_binary() {
+        let tmp_dir = tempdir::TempDir::new("alice").unwrap();
+        let text = tmp_dir.path().join("config.toml");
+        let elf = tmp_dir.path().join("a.out");
+        std::fs::write(&text, "key = \"value\"\n").unwrap();
+        std::fs::write(&elf, b"\x7fELF\x02\x01\x01\x00").unwrap();
+
+        assert!(!is_binary(&text));
+        assert!(is_binary(&elf));
+        assert!(!is_binary(&tmp_dir.path().join("missing")));
+    }
+
+    #[test]
+    fn test_looks_secret() {
+        assert!(looks_secret(Path::new(".ssh/id_ed25519")));
+        assert!(looks_secret(Path::new("certs/server.pem")));
+        assert!(looks_secret(Path::new(".aws/credentials")));
+        assert!(!looks_secret(Path::new(".ssh/id_ed25519.pub")));
+        assert!(!looks_secret(Path::new(".vimrc")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_secret_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp_dir = tempdir::TempDir::new("alice").unwrap();
+        let src = tmp_dir.path().join("id_rsa");
+        std::fs::write(&src, "").unwrap();
+        let actions = [Action::Link {
+            src: src.clone(),
+            dst: tmp_dir.path().join(".ssh/id_rsa"),
+        }];
+
+        std::fs::set_permissions(&src, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(check_secret_permissions(&actions).len(), 1);
+        std::fs::set_permissions(&src, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert!(check_secret_permissions(&actions).is_empty());
+    }
+}
@@ -0,0 +1,320 @@
+## Meldungen von ranch auf Deutsch; was fehlt, erscheint auf Englisch.
+
+## Shared
+
+fatal = FEHLER: { $error }
+warning = WARNUNG: { $message }
+error = FEHLER: { $error }
+could-not-read = { $path } konnte nicht gelesen werden: { $error }
+could-not-write = { $path } konnte nicht geschrieben werden: { $error }
+could-not-create = { $path } konnte nicht erstellt werden: { $error }
+could-not-create-dir = Verzeichnis { $path } konnte nicht erstellt werden: { $error }
+could-not-remove = { $path } konnte nicht entfernt werden: { $error }
+
+## Deploying
+
+wsl-copying = WARNUNG: { $path } liegt auf einem Windows-Laufwerk, wo Windows-Programme WSL-Symlinks nicht folgen können und 'cmd.exe' keine Windows-Links anlegen kann; Paketdateien werden stattdessen kopiert
+keeping-going = WARNUNG: { $error }; es wird fortgefahren
+files-not-deployed = { $count } Dateien konnten nicht bereitgestellt werden:
+    { "  " }{ $failures }
+type-conflict-stop = { $problem }
+skipping = WARNUNG: { $problem }; wird übersprungen
+could-not-move-aside = { $path } konnte nicht beiseitegeschoben werden: { $error }
+already-exists = { $path } existiert bereits
+already-exists-ignoring = WARNUNG: { $path } existiert bereits; wird ignoriert
+cannot-adopt-template = { $path } kann nicht in die Vorlage { $template } übernommen werden; bitte von Hand zusammenführen
+could-not-adopt = { $path } konnte nicht in { $package_file } übernommen werden: { $error }
+could-not-sync = WARNUNG: { $path } konnte nicht synchronisiert werden: { $error }
+retrying = WARNUNG: { $path }: { $error }; neuer Versuch in { $milliseconds } ms
+could-not-repoint = { $path } konnte nicht neu verknüpft werden: { $error }
+different-filesystems = { $package } und { $target } liegen auf verschiedenen Dateisystemen, daher sind keine Hardlinks möglich
+xdev-stop = { $problem }; mit --xdev-fallback werden stattdessen Symlinks oder Kopien angelegt
+xdev-symlinks = WARNUNG: { $problem }; stattdessen werden Symlinks angelegt
+xdev-copies = WARNUNG: { $problem }; stattdessen werden Kopien angelegt
+not-adopting = WARNUNG: { $path } wird ohne Bestätigung nicht übernommen; mit --yes wird es übernommen
+refusing-to-replace-dir = { $path } ist ein Verzeichnis und wird nicht ersetzt
+could-not-roll-back = WARNUNG: { $path } konnte nicht zurückgesetzt werden: { $error }
+
+## Command line
+
+invalid-size = Ungültige Größe '{ $size }'; erwartet werden Bytes oder eine Größe wie '100M'
+option-with-subcommand = '{ $option }' kann nicht mit dem Unterbefehl '{ $subcommand }' kombiniert werden
+no-packages-match = Keine Pakete passen zu '{ $pattern }'
+could-not-open-current-dir = Das aktuelle Verzeichnis konnte nicht geöffnet werden: { $error }
+invalid-config-target = Ungültiges Ziel in der Konfiguration: { $error }
+no-such-package = Paket { $package } existiert nicht; Abbruch
+could-not-create-target = Zielverzeichnis { $path } konnte nicht erstellt werden: { $error }
+lint-failed-strict = Prüfung mit --strict fehlgeschlagen; Abbruch
+
+## Config
+
+invalid-config = Ungültige Konfiguration { $path }: { $error }
+no-such-profile = Kein Profil namens { $profile } in der Konfiguration
+
+## Daemon
+
+no-socket-path = Es ließ sich nicht bestimmen, wo der Socket des Daemons liegen soll
+daemon-requires-unix = Der Daemon-Modus erfordert Unix-Domain-Sockets
+could-not-watch = { $path } konnte nicht überwacht werden: { $error }
+daemon-unknown-command = unbekannter Befehl '{ $command }'; erwartet wird status, apply oder reload
+daemon-shut-down = Die Ereignisschleife des Daemons wurde beendet
+could-not-start-watching = Die Überwachung auf Änderungen konnte nicht gestartet werden: { $error }
+daemon-already-listening = Auf { $socket } lauscht bereits ein Daemon
+could-not-remove-stale = Veraltetes { $path } konnte nicht entfernt werden: { $error }
+could-not-listen = Auf { $address } konnte nicht gelauscht werden: { $error }
+could-not-secure = { $path } konnte nicht abgesichert werden: { $error }
+could-not-connect-daemon = Keine Verbindung zum Daemon unter { $socket }: { $error }
+could-not-send-daemon = '{ $command }' konnte nicht an den Daemon gesendet werden: { $error }
+could-not-read-daemon-reply = Die Antwort des Daemons konnte nicht gelesen werden: { $error }
+
+## Programs
+
+could-not-run = { $program } konnte nicht ausgeführt werden: { $error }
+program-failed = { $program } ist fehlgeschlagen: { $output }
+elevated-command-failed = { $program } { $command } ist fehlgeschlagen: { $output }
+could-not-make-executable = { $path } konnte nicht ausführbar gemacht werden: { $error }
+
+## Finding and git
+
+no-files-match = Keine Paketdatei passt zu '{ $pattern }'
+could-not-locate-ranch = Das ranch-Programm wurde nicht gefunden: { $error }
+git-hook-exists = { $path } existiert bereits; bitte einen Aufruf von 'ranch -R' von Hand ergänzen
+
+## Hooks
+
+could-not-serialize-plan = Der Plan für die Hooks konnte nicht serialisiert werden: { $error }
+no-hook-isolation = WARNUNG: Isolation ist hier nicht verfügbar; abgeschottete Hooks haben weiterhin Netzwerkzugriff und Schreibzugriff auf das ganze Dateisystem
+could-not-run-hook = { $hook }-Hook { $command } konnte nicht ausgeführt werden: { $error }
+hook-timed-out = { $hook }-Hook { $command } hat nach { $seconds } s das Zeitlimit überschritten
+hook-failed = { $hook }-Hook { $command } ist fehlgeschlagen mit { $status }
+
+## HTTP API
+
+invalid-http-address = Ungültige HTTP-Adresse '{ $address }': { $error }
+http-not-loopback = Die HTTP-API wird nicht auf '{ $address }' angeboten; nur Loopback-Adressen sind erlaubt
+could-not-read-request = Die Anfrage konnte nicht gelesen werden: { $error }
+malformed-request = Fehlerhafte Anfragezeile '{ $line }'
+request-cut-off = Die Kopfzeilen der Anfrage wurden abgeschnitten
+
+## Lint
+
+lint-no-such-package = Paket { $package } existiert nicht
+lint-broken-symlink = { $path } ist ein defekter Symlink auf { $target }
+lint-junk = { $path } ist Datenmüll; bitte löschen
+lint-collision = { $path } wird sowohl von { $package } ({ $package_file }) als auch von { $other_package } ({ $other_package_file }) bereitgestellt
+lint-case-collision = { $path } und { $other_path } unterscheiden sich nur in der Groß-/Kleinschreibung; auf Dateisystemen ohne diese Unterscheidung kollidieren sie
+lint-problems = { $count } Problem(e) gefunden
+lint-readable-secret = { $path } sieht nach einem Geheimnis aus, ist aber für andere Benutzer lesbar (Modus { $mode }); 'chmod 600' darauf anwenden oder verschlüsselt als '.age'/'.gpg' ablegen
+lint-submodule = { $path } ist ein nicht ausgechecktes Git-Submodul; 'git submodule update --init' ausführen
+
+## Man pages, manifests, and merging
+
+could-not-write-man-pages = Manpages konnten nicht nach { $path } geschrieben werden: { $error }
+invalid-manifest = Ungültiges Manifest { $path }: { $error }
+could-not-merge = { $path } konnte nicht zusammengeführt werden: { $error }
+merge-conflicts = { $package_file } und { $path } haben widersprüchliche Änderungen, die in der Paketdatei markiert sind; diese auflösen und erneut mit '--exists overwrite' ausführen
+
+## Moving files
+
+not-a-package-file = { $path } ist keine Datei in einem Paket
+destination-exists = { $path } existiert bereits
+could-not-move = { $path } konnte nicht nach { $destination } verschoben werden: { $error }
+not-in-a-package = { $path } ist kein Pfad innerhalb eines Pakets, wie etwa 'zsh/.zshrc'
+
+## Notifications
+
+drifted = ranch: { $count } Datei(en) abgewichen
+drifted-more = und { $count } weitere
+could-not-notify = WARNUNG: Die Benachrichtigung konnte nicht gesendet werden: { $error }
+could-not-post = WARNUNG: An { $url } konnte nicht gesendet werden: { $error }
+could-not-write-to = An { $program } konnte nicht geschrieben werden: { $error }
+
+## Paths
+
+no-home-dir = das Home-Verzeichnis ließ sich nicht bestimmen (in '{ $text }')
+unknown-user = unbekannter Benutzer '{ $user }' (in '{ $text }')
+home-not-unicode = das Home-Verzeichnis ist kein gültiges Unicode (in '{ $text }')
+unterminated-variable = nicht abgeschlossenes '${ "{" }' in '{ $text }'
+variable-not-set = Umgebungsvariable '{ $variable }' ist nicht gesetzt (in '{ $text }')
+variable-not-unicode = Umgebungsvariable '{ $variable }' ist kein gültiges Unicode (in '{ $text }')
+invalid-config-dir = Ungültiges Verzeichnis in der Konfiguration: { $error }
+symlink-loop = { $path } führt durch eine Schleife von Symlinks, bei { $at }
+
+## Planning
+
+invalid-package-target = Ungültiges Ziel in Paket { $package }: { $error }
+vcs-only-could-not-list = --vcs-only: Die Dateien in { $path } konnten nicht aufgelistet werden: { $error }
+no-default-target = Auf das Standardziel 'DIR/..' konnte nicht zugegriffen werden
+special-file-skipping = WARNUNG: { $path } ist ein FIFO, Socket oder Gerät; wird übersprungen
+special-file = { $path } ist ein FIFO, Socket oder Gerät; mit --special-files lässt es sich überspringen oder verknüpfen
+too-large-skipping = WARNUNG: { $path } ist { $size } Bytes groß, mehr als --max-file-size; wird übersprungen
+directory-in-the-way = { $path } ist ein Verzeichnis, aber das Paket hat dort eine Datei
+file-in-the-way = { $path } ist eine Datei, aber das Paket hat dort ein Verzeichnis
+in-the-way-for = { $problem } (für { $target })
+type-conflicts = { $problems }; mit --type-conflict lassen sich diese Dateien überspringen oder das Hindernis beiseiteschieben
+leads-through-symlink = { $path } führt über einen Symlink nach { $dir }
+symlinked-parents = { $problems }; diese Links durch echte Verzeichnisse ersetzen
+case-collision = { $package_file } und { $other_package_file } unterscheiden sich nur in der Groß-/Kleinschreibung, daher würden beide nach { $target } bereitgestellt
+case-collisions = { $problems }; dieses Dateisystem ignoriert Groß-/Kleinschreibung, daher jeweils eine umbenennen
+not-a-child = WARNUNG: { $path } liegt nicht unter { $dir }; wird ignoriert
+
+## Plan files
+
+could-not-write-plan = Der Plan konnte nicht geschrieben werden: { $error }
+invalid-plan = Ungültige Plandatei { $path }: { $error }
+plan-version = { $path } ist ein Plan der Version { $version }; dieses ranch liest Version { $supported }
+plan-other-dir = { $path } wurde für { $planned } geplant, nicht für { $dir }
+plan-stale = { $path } ist veraltet; bitte neu planen, da sich seit der Planung Folgendes geändert hat:
+    { "  " }{ $drift }
+drift-contents = { $path }: Inhalt geändert, von sha256 { $old } zu { $new }
+drift-modified = { $path }: seitdem geändert, der Inhalt ist aber gleich
+drift-gone = { $path }: existiert nicht mehr
+drift-target = { $path }: war { $was } ({ $was_state }), jetzt { $now } ({ $now_state })
+
+## Renaming packages
+
+invalid-package-name = '{ $package }' ist kein gültiger Paketname
+package-exists = Paket { $package } existiert bereits; Abbruch
+could-not-rename = { $path } konnte nicht in { $destination } umbenannt werden: { $error }
+links-not-repointed = { $old } wurde in { $new } umbenannt, aber { $count } Links konnten nicht neu verknüpft werden:
+    { "  " }{ $failures }
+
+## JSON-RPC and schedules
+
+could-not-write-response = Die Antwort konnte nicht geschrieben werden: { $error }
+invalid-schedule = Ungültiger Zeitplan '{ $schedule }'; erwartet wird ein Intervall wie '30m' oder ein Cron-Ausdruck
+invalid-cron = Ungültiger Cron-Ausdruck '{ $expression }': { $error }
+cron-out-of-range = '{ $number }' ist keine Zahl von { $min } bis { $max }
+cron-invalid-step = ungültige Schrittweite '{ $step }'
+cron-empty-range = '{ $range }' ist ein leerer Bereich
+
+## Secrets
+
+age-identity-required = Zum erneuten Verschlüsseln von { $path } ist eine age-Identität nötig; --age-identity setzen
+no-windows-credential = keine Anmeldeinformation '{ $credential }' in der Windows-Anmeldeinformationsverwaltung
+no-secret-command = kein secret_command eingerichtet; lege es in der ranch-Konfigurationsdatei fest
+template-line = Zeile { $line }: { $error }
+template-empty-tag = leerer Template-Tag
+template-arity = '{ $function }' erwartet { $expected } Argument(e), erhielt aber { $given }
+template-env-not-set = Umgebungsvariable '{ $variable }' ist nicht gesetzt
+template-unknown-function = unbekannte Template-Funktion '{ $function }'
+template-unterminated-string = nicht abgeschlossene Zeichenkette
+template-unterminated-tag = nicht abgeschlossenes '{ "{{" }'
+could-not-run-installed = '{ $program }' konnte nicht ausgeführt werden (ist es installiert?): { $error }
+program-failed-with = '{ $program }' ist fehlgeschlagen mit { $status }
+
+## Setup, signatures, and snapshots
+
+no-config-path = Es ließ sich nicht bestimmen, wo ranchs Konfiguration gespeichert werden soll
+config-exists = { $path } existiert bereits; bearbeite die Datei von Hand oder entferne sie, um die Einrichtung erneut auszuführen
+could-not-serialize-config = Die Konfiguration konnte nicht serialisiert werden: { $error }
+could-not-read-answer = Die Antwort konnte nicht gelesen werden: { $error }
+setup-cancelled = Die Einrichtung wurde abgebrochen
+not-signed = { $file } ist nicht signiert; unter { $signature } wurde eine Signatur erwartet
+bad-signature = Ungültige Signatur für { $file }: { $error }
+nothing-to-verify = { $package } hat kein { $checksums } zum Prüfen; schreibe es mit 'ranch checksums' und signiere es, oder verteile das Paket als signiertes Archiv
+package-not-as-signed = { $package } ist nicht so, wie es signiert wurde: { $problems }
+file-not-signed = { $file } steht nicht in den signierten Prüfsummen
+file-changed-since-signed = { $file } wurde seit dem Signieren geändert
+signed-file-missing = { $file } wurde signiert, fehlt aber
+unknown-signer = von keinem der zugelassenen Unterzeichner erstellt
+no-btrfs-subvolume = Das btrfs-Subvolume von { $path } wurde nicht gefunden
+cannot-snapshot = --snapshot: { $path } liegt auf { $filesystem }, nicht auf btrfs, ZFS oder APFS
+cannot-snapshot-unknown = --snapshot: { $path } liegt auf einem unbekannten Dateisystem, nicht auf btrfs, ZFS oder APFS
+
+## Sources and state
+
+could-not-update = { $url } konnte nicht aktualisiert werden: { $error }
+could-not-clone = { $url } konnte nicht geklont werden: { $error }
+git-source-pinned = --sha256 und --lockfile gelten nur für Archive; lege eine git-Quelle stattdessen auf einen Commit fest
+checksum-mismatch = Prüfsumme von { $url } stimmt nicht: SHA-256 { $expected } erwartet, { $actual } erhalten; es wird nicht bereitgestellt
+no-checksum = { $lockfile } enthält keine Prüfsumme für { $url }
+could-not-extract = { $path } konnte nicht entpackt werden: { $error }
+no-cache-dir = Das Cache-Verzeichnis ließ sich nicht bestimmen
+invalid-state = Ungültiger Zustand { $path }: { $error }
+no-state-path = Es ließ sich nicht bestimmen, wo ranchs Zustand gespeichert werden soll
+could-not-serialize-state = Der Zustand konnte nicht serialisiert werden: { $error }
+
+## Users, watching, and the TUI
+
+could-not-chown = { $path } konnte nicht an Benutzer { $uid } zurückgegeben werden: { $error }
+for-users-needs-root = --for-users muss als root ausgeführt werden
+for-users-unix-only = --for-users wird nur unter Unix unterstützt
+no-such-user = Benutzer { $user } existiert nicht
+for-user = Für { $user }: { $error }
+could-not-start-watching = Die Überwachung auf Änderungen konnte nicht gestartet werden: { $error }
+could-not-watch = { $path } konnte nicht überwacht werden: { $error }
+notifications-stopped = Es kommen keine Dateisystem-Benachrichtigungen mehr an
+notification-error = WARNUNG: Fehler bei einer Dateisystem-Benachrichtigung: { $error }
+could-not-set-up-terminal = Das Terminal konnte nicht eingerichtet werden: { $error }
+could-not-draw = Zeichnen fehlgeschlagen: { $error }
+could-not-read-input = Die Eingabe konnte nicht gelesen werden: { $error }
+tui-help = ↑/↓ bewegen  Tab wechseln  l verlinken  o überschreiben  a übernehmen  u entfernen  r neu laden  q beenden
+tui-nothing-changed = Nichts geändert (--dry-run)
+tui-summary = { $created } angelegt, { $removed } entfernt, { $adopted } übernommen
+tui-confirm-overwrite = { $count } vorhandene Datei(en) überschreiben? y bestätigt, jede andere Taste bricht ab
+tui-cancelled = Abgebrochen
+
+## WSL
+
+mklink-failed = mklink fehlgeschlagen: { $output }; aktiviere den Entwicklermodus oder gib '--link-mode copy' an
+invalid-path = Ungültiger Pfad { $path }
+
+## Logging
+
+invalid-log-level = Ungültige Protokollstufe '{ $level }'; erwartet wird off, error, warn, info, debug oder trace
+invalid-log-directive = Ungültige Protokollangabe '{ $directive }'; erwartet wird LEVEL oder MODULE=LEVEL
+
+## Diagnostics
+
+diagnostic-help = Hilfe: { $hint }
+label-target = Ziel
+label-occupied-by = belegt von
+label-wanted-by = gewünscht von
+wanted-by = Paket { $package } ({ $src })
+occupant-nothing = nichts
+occupant-dir = einem Verzeichnis
+occupant-file = einer Datei mit { $size } Bytes
+occupant-link = einem Link auf { $target }
+occupant-package-link = einem Link in das Paket { $package } ({ $target })
+occupant-special = einer Spezialdatei
+hint-unlink-other = führe zuerst 'ranch -D { $package }' aus, wenn die Datei nicht mehr aus { $package } kommen soll
+hint-exists-adopt = führe 'ranch --exists adopt { $package }' aus, um die vorhandene Datei ins Paket zu übernehmen
+hint-exists-overwrite = führe 'ranch --exists overwrite { $package }' aus, um sie durch die Datei des Pakets zu ersetzen
+hint-exists-ignore = führe 'ranch --exists ignore { $package }' aus, um sie zu belassen und den Rest bereitzustellen
+hint-type-conflict-backup = führe 'ranch --type-conflict backup { $package }' aus, um das Hindernis beiseitezuschieben
+hint-type-conflict-skip = führe 'ranch --type-conflict skip { $package }' aus, um es zu belassen und den Rest bereitzustellen
+
+## Fault injection
+
+injected-failure = absichtlicher Fehler bei Änderung { $count } (--inject-failure)
+
+## SFTP
+
+sftp-needs-host = '--backend sftp' braucht den Host, auf den verteilt wird; gib ihn mit '--host [BENUTZER@]HOST' an
+
+## Deploying over SSH
+
+deploy-could-not-transfer = Konnte die Pakete nicht auf { $host } bringen: { $error }
+deploy-would-transfer = Würde die Pakete dorthin bringen mit: { $script }
+deploy-host-ok = { $host }: verteilt
+deploy-host-failed = { $host }: fehlgeschlagen
+deploy-failed = Verteilen ist auf { $failed } von { $hosts } Hosts fehlgeschlagen
+
+## Containers
+
+containerize-secret = WARNUNG: { $path } wird ausgelassen, da jeder mit dem Image das Geheimnis lesen könnte
+
+## Devcontainers
+
+devcontainer-script-exists = { $path } existiert bereits und wurde nicht von ranch geschrieben; verschiebe es zuerst
+devcontainer-not-an-object = { $path } enthält kein JSON-Objekt
+devcontainer-has-post-create = { $path } hat bereits ein 'postCreateCommand'; füge 'sh { $script }' selbst hinzu
+
+## Exporting
+
+export-left-out = WARNUNG: { $path } wird ausgelassen, da das Format es nicht ausdrücken kann
+
+## Reports
+
+could-not-write-report = Konnte den Bericht nicht schreiben: { $error }
@@ -0,0 +1,320 @@
+## Messages shown by ranch, in English; every other catalog falls back to these.
+
+## Shared
+
+fatal = FATAL: { $error }
+warning = WARNING: { $message }
+error = ERROR: { $error }
+could-not-read = Could not read { $path }: { $error }
+could-not-write = Could not write { $path }: { $error }
+could-not-create = Could not create { $path }: { $error }
+could-not-create-dir = Could not create directory { $path }: { $error }
+could-not-remove = Could not remove { $path }: { $error }
+
+## Deploying
+
+wsl-copying = WARNING: { $path } is on a Windows drive, where Windows apps can't follow WSL symlinks, and 'cmd.exe' can't make Windows links; copying package files instead
+keeping-going = WARNING: { $error }; keeping going
+files-not-deployed = { $count } files could not be deployed:
+    { "  " }{ $failures }
+type-conflict-stop = { $problem }
+skipping = WARNING: { $problem }; skipping
+could-not-move-aside = Could not move { $path } aside: { $error }
+already-exists = { $path } already exists
+already-exists-ignoring = WARNING: { $path } already exists; ignoring
+cannot-adopt-template = { $path } can't be adopted into the template { $template }; merge it by hand
+could-not-adopt = Could not adopt { $path } into { $package_file }: { $error }
+could-not-sync = WARNING: Could not sync { $path }: { $error }
+retrying = WARNING: { $path }: { $error }; retrying in { $milliseconds }ms
+could-not-repoint = Could not repoint { $path }: { $error }
+different-filesystems = { $package } and { $target } are on different filesystems, so hard links can't be made
+xdev-stop = { $problem }; pass --xdev-fallback to make symlinks or copies instead
+xdev-symlinks = WARNING: { $problem }; making symlinks instead
+xdev-copies = WARNING: { $problem }; making copies instead
+not-adopting = WARNING: Not adopting { $path } without confirmation; pass --yes to adopt it
+refusing-to-replace-dir = { $path } is a directory; refusing to replace it
+could-not-roll-back = WARNING: Could not roll back { $path }: { $error }
+
+## Command line
+
+invalid-size = Invalid size '{ $size }'; expected bytes or a size such as '100M'
+option-with-subcommand = '{ $option }' can't be combined with the '{ $subcommand }' subcommand
+no-packages-match = No packages match '{ $pattern }'
+could-not-open-current-dir = Could not open the current directory: { $error }
+invalid-config-target = Invalid target in config: { $error }
+no-such-package = Package { $package } does not exist; exiting now
+could-not-create-target = Could not create target directory { $path }: { $error }
+lint-failed-strict = Lint failed with --strict; exiting now
+
+## Config
+
+invalid-config = Invalid config { $path }: { $error }
+no-such-profile = No profile named { $profile } in the config
+
+## Daemon
+
+no-socket-path = Could not determine where to put the daemon's socket
+daemon-requires-unix = Daemon mode requires Unix domain sockets
+could-not-watch = Could not watch { $path }: { $error }
+daemon-unknown-command = unknown command '{ $command }'; expected status, apply, or reload
+daemon-shut-down = The daemon's event loop shut down
+could-not-start-watching = Could not start watching for changes: { $error }
+daemon-already-listening = A daemon is already listening on { $socket }
+could-not-remove-stale = Could not remove stale { $path }: { $error }
+could-not-listen = Could not listen on { $address }: { $error }
+could-not-secure = Could not secure { $path }: { $error }
+could-not-connect-daemon = Could not connect to the daemon at { $socket }: { $error }
+could-not-send-daemon = Could not send '{ $command }' to the daemon: { $error }
+could-not-read-daemon-reply = Could not read the daemon's reply: { $error }
+
+## Programs
+
+could-not-run = Could not run { $program }: { $error }
+program-failed = { $program } failed: { $output }
+elevated-command-failed = { $program } { $command } failed: { $output }
+could-not-make-executable = Could not make { $path } executable: { $error }
+
+## Finding and git
+
+no-files-match = No package files match '{ $pattern }'
+could-not-locate-ranch = Could not locate the ranch executable: { $error }
+git-hook-exists = { $path } already exists; add a call to 'ranch -R' to it by hand
+
+## Hooks
+
+could-not-serialize-plan = Could not serialize the plan for hooks: { $error }
+no-hook-isolation = WARNING: Isolation is unavailable here; sandboxed hooks still have network access and can write to the whole filesystem
+could-not-run-hook = Could not run { $hook } hook { $command }: { $error }
+hook-timed-out = { $hook } hook { $command } timed out after { $seconds }s
+hook-failed = { $hook } hook { $command } failed with { $status }
+
+## HTTP API
+
+invalid-http-address = Invalid HTTP address '{ $address }': { $error }
+http-not-loopback = Refusing to serve the HTTP API on '{ $address }'; only loopback addresses are allowed
+could-not-read-request = Could not read request: { $error }
+malformed-request = Malformed request line '{ $line }'
+request-cut-off = Request headers were cut off
+
+## Lint
+
+lint-no-such-package = Package { $package } does not exist
+lint-broken-symlink = { $path } is a broken symlink to { $target }
+lint-junk = { $path } is junk; delete it
+lint-collision = { $path } is provided by both { $package } ({ $package_file }) and { $other_package } ({ $other_package_file })
+lint-case-collision = { $path } and { $other_path } differ only by case; they collide on case-insensitive filesystems
+lint-problems = { $count } problem(s) found
+lint-readable-secret = { $path } looks like a secret but is readable by other users (mode { $mode }); 'chmod 600' it or store it encrypted as '.age'/'.gpg'
+lint-submodule = { $path } is a git submodule that isn't checked out; run 'git submodule update --init'
+
+## Man pages, manifests, and merging
+
+could-not-write-man-pages = Could not write man pages to { $path }: { $error }
+invalid-manifest = Invalid manifest { $path }: { $error }
+could-not-merge = Could not merge { $path }: { $error }
+merge-conflicts = { $package_file } and { $path } have conflicting changes, marked in the package file; resolve them, then run again with '--exists overwrite'
+
+## Moving files
+
+not-a-package-file = { $path } is not a file in a package
+destination-exists = { $path } already exists
+could-not-move = Could not move { $path } to { $destination }: { $error }
+not-in-a-package = { $path } is not a path within a package, such as 'zsh/.zshrc'
+
+## Notifications
+
+drifted = ranch: { $count } file(s) drifted
+drifted-more = and { $count } more
+could-not-notify = WARNING: Could not send a notification: { $error }
+could-not-post = WARNING: Could not post to { $url }: { $error }
+could-not-write-to = Could not write to { $program }: { $error }
+
+## Paths
+
+no-home-dir = could not determine the home directory (in '{ $text }')
+unknown-user = unknown user '{ $user }' (in '{ $text }')
+home-not-unicode = home directory is not valid unicode (in '{ $text }')
+unterminated-variable = unterminated '${ "{" }' in '{ $text }'
+variable-not-set = environment variable '{ $variable }' is not set (in '{ $text }')
+variable-not-unicode = environment variable '{ $variable }' is not valid unicode (in '{ $text }')
+invalid-config-dir = Invalid dir in config: { $error }
+symlink-loop = { $path } goes through a loop of symlinks, at { $at }
+
+## Planning
+
+invalid-package-target = Invalid target in package { $package }: { $error }
+vcs-only-could-not-list = --vcs-only: Could not list the files in { $path }: { $error }
+no-default-target = Could not access default target path 'DIR/..'
+special-file-skipping = WARNING: { $path } is a FIFO, socket, or device; skipping
+special-file = { $path } is a FIFO, socket, or device; use --special-files to skip or link it
+too-large-skipping = WARNING: { $path } is { $size } bytes, more than --max-file-size; skipping
+directory-in-the-way = { $path } is a directory, but the package has a file there
+file-in-the-way = { $path } is a file, but the package has a directory there
+in-the-way-for = { $problem } (for { $target })
+type-conflicts = { $problems }; use --type-conflict to skip these files or move what's in the way aside
+leads-through-symlink = { $path } leads into { $dir } through a symlink
+symlinked-parents = { $problems }; replace these links with real directories
+case-collision = { $package_file } and { $other_package_file } differ only by case, so both would deploy to { $target }
+case-collisions = { $problems }; this filesystem ignores case, so rename one of each
+not-a-child = WARNING: { $path } is not a child of { $dir }; ignoring
+
+## Plan files
+
+could-not-write-plan = Could not write the plan: { $error }
+invalid-plan = Invalid plan file { $path }: { $error }
+plan-version = { $path } is a version { $version } plan; this ranch reads version { $supported }
+plan-other-dir = { $path } was planned for { $planned }, not { $dir }
+plan-stale = { $path } is stale; plan again, since these changed after it was made:
+    { "  " }{ $drift }
+drift-contents = { $path }: contents changed, from sha256 { $old } to { $new }
+drift-modified = { $path }: modified since, though its contents are the same
+drift-gone = { $path }: no longer exists
+drift-target = { $path }: was { $was } ({ $was_state }), now { $now } ({ $now_state })
+
+## Renaming packages
+
+invalid-package-name = '{ $package }' is not a valid package name
+package-exists = Package { $package } already exists; exiting now
+could-not-rename = Could not rename { $path } to { $destination }: { $error }
+links-not-repointed = Renamed { $old } to { $new }, but { $count } links could not be repointed:
+    { "  " }{ $failures }
+
+## JSON-RPC and schedules
+
+could-not-write-response = Could not write response: { $error }
+invalid-schedule = Invalid schedule '{ $schedule }'; expected an interval such as '30m' or a cron expression
+invalid-cron = Invalid cron expression '{ $expression }': { $error }
+cron-out-of-range = '{ $number }' is not a number from { $min } to { $max }
+cron-invalid-step = invalid step '{ $step }'
+cron-empty-range = '{ $range }' is an empty range
+
+## Secrets
+
+age-identity-required = Re-encrypting { $path } requires an age identity; set --age-identity
+no-windows-credential = no credential '{ $credential }' in the Windows Credential Manager
+no-secret-command = no secret_command is configured; set it in the ranch config file
+template-line = line { $line }: { $error }
+template-empty-tag = empty template tag
+template-arity = '{ $function }' takes { $expected } argument(s), but { $given } were given
+template-env-not-set = environment variable '{ $variable }' is not set
+template-unknown-function = unknown template function '{ $function }'
+template-unterminated-string = unterminated string
+template-unterminated-tag = unterminated '{ "{{" }'
+could-not-run-installed = Could not run '{ $program }' (is it installed?): { $error }
+program-failed-with = '{ $program }' failed with { $status }
+
+## Setup, signatures, and snapshots
+
+no-config-path = Could not determine where to store ranch's config
+config-exists = { $path } already exists; edit it by hand, or remove it to run setup again
+could-not-serialize-config = Could not serialize the config: { $error }
+could-not-read-answer = Could not read answer: { $error }
+setup-cancelled = Setup was cancelled
+not-signed = { $file } is not signed; expected a signature at { $signature }
+bad-signature = Bad signature for { $file }: { $error }
+nothing-to-verify = { $package } has no { $checksums } to verify; write it with 'ranch checksums' and sign it, or distribute the package as a signed archive
+package-not-as-signed = { $package } isn't as it was signed: { $problems }
+file-not-signed = { $file } isn't listed in the signed checksums
+file-changed-since-signed = { $file } has changed since it was signed
+signed-file-missing = { $file } was signed but is missing
+unknown-signer = not made by any of the allowed signers
+no-btrfs-subvolume = Could not find the btrfs subvolume of { $path }
+cannot-snapshot = --snapshot: { $path } is on { $filesystem }, not btrfs, ZFS, or APFS
+cannot-snapshot-unknown = --snapshot: { $path } is on an unknown filesystem, not btrfs, ZFS, or APFS
+
+## Sources and state
+
+could-not-update = Could not update { $url }: { $error }
+could-not-clone = Could not clone { $url }: { $error }
+git-source-pinned = --sha256 and --lockfile only apply to archives; pin a git source to a commit instead
+checksum-mismatch = Checksum mismatch for { $url }: expected SHA-256 { $expected }, got { $actual }; refusing to deploy it
+no-checksum = { $lockfile } has no checksum for { $url }
+could-not-extract = Could not extract { $path }: { $error }
+no-cache-dir = Could not determine the cache directory
+invalid-state = Invalid state { $path }: { $error }
+no-state-path = Could not determine where to store ranch's state
+could-not-serialize-state = Could not serialize state: { $error }
+
+## Users, watching, and the TUI
+
+could-not-chown = Could not give { $path } back to user { $uid }: { $error }
+for-users-needs-root = --for-users must be run as root
+for-users-unix-only = --for-users is only supported on Unix
+no-such-user = No such user { $user }
+for-user = For { $user }: { $error }
+could-not-start-watching = Could not start watching for changes: { $error }
+could-not-watch = Could not watch { $path }: { $error }
+notifications-stopped = Stopped receiving filesystem notifications
+notification-error = WARNING: Filesystem notification error: { $error }
+could-not-set-up-terminal = Could not set up the terminal: { $error }
+could-not-draw = Could not draw: { $error }
+could-not-read-input = Could not read input: { $error }
+tui-help = ↑/↓ move  tab switch  l link  o overwrite  a adopt  u unlink  r refresh  q quit
+tui-nothing-changed = Nothing changed (--dry-run)
+tui-summary = { $created } created, { $removed } removed, { $adopted } adopted
+tui-confirm-overwrite = Overwrite { $count } existing file(s)? Press y to confirm, any other key to cancel
+tui-cancelled = Cancelled
+
+## WSL
+
+mklink-failed = mklink failed: { $output }; enable Developer Mode, or pass '--link-mode copy'
+invalid-path = Invalid path { $path }
+
+## Logging
+
+invalid-log-level = Invalid log level '{ $level }'; expected off, error, warn, info, debug, or trace
+invalid-log-directive = Invalid log directive '{ $directive }'; expected LEVEL or MODULE=LEVEL
+
+## Diagnostics
+
+diagnostic-help = help: { $hint }
+label-target = target
+label-occupied-by = occupied by
+label-wanted-by = wanted by
+wanted-by = package { $package } ({ $src })
+occupant-nothing = nothing
+occupant-dir = a directory
+occupant-file = a file of { $size } bytes
+occupant-link = a link to { $target }
+occupant-package-link = a link into package { $package } ({ $target })
+occupant-special = a special file
+hint-unlink-other = run 'ranch -D { $package }' first if the file should no longer come from { $package }
+hint-exists-adopt = run 'ranch --exists adopt { $package }' to move the existing file into the package
+hint-exists-overwrite = run 'ranch --exists overwrite { $package }' to replace it with the package's file
+hint-exists-ignore = run 'ranch --exists ignore { $package }' to leave it alone and deploy the rest
+hint-type-conflict-backup = run 'ranch --type-conflict backup { $package }' to move what's in the way aside
+hint-type-conflict-skip = run 'ranch --type-conflict skip { $package }' to leave it alone and deploy the rest
+
+## Fault injection
+
+injected-failure = injected failure of change { $count } (--inject-failure)
+
+## SFTP
+
+sftp-needs-host = '--backend sftp' needs the host to deploy to; give it with '--host [USER@]HOST'
+
+## Deploying over SSH
+
+deploy-could-not-transfer = Could not get the packages onto { $host }: { $error }
+deploy-would-transfer = Would get the packages there with: { $script }
+deploy-host-ok = { $host }: deployed
+deploy-host-failed = { $host }: failed
+deploy-failed = Deploying failed on { $failed } of { $hosts } hosts
+
+## Containers
+
+containerize-secret = WARNING: leaving out { $path }, since anyone with the image could read the secret
+
+## Devcontainers
+
+devcontainer-script-exists = { $path } already exists, and wasn't written by ranch; move it aside first
+devcontainer-not-an-object = { $path } doesn't hold a JSON object
+devcontainer-has-post-create = { $path } already has a 'postCreateCommand'; add 'sh { $script }' to it yourself
+
+## Exporting
+
+export-left-out = WARNING: leaving out { $path }, which the format can't express
+
+## Reports
+
+could-not-write-report = Could not write the report: { $error }
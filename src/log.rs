@@ -0,0 +1,99 @@
+use crate::i18n::tr;
+use crate::{LV_DEBUG, LV_INFO, LV_WARN};
+use std::fmt;
+
+/// Verbosity by level name; errors are always printed, and nothing logs below 'debug' yet, so
+/// 'trace' means the same.
+const LEVELS: &[(&str, u8)] = &[
+    ("off", 0),
+    ("error", 0),
+    ("warn", LV_WARN),
+    ("info", LV_INFO),
+    ("debug", LV_DEBUG),
+    ("trace", LV_DEBUG),
+];
+
+/// Which messages to print from where, as given to '--log' or '$RANCH_LOG': comma-separated
+/// directives that are either a level, such as 'debug', or 'TARGET=LEVEL', such as 'plan=trace',
+/// where a target is one of ranch's modules.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LogFilter {
+    /// Level of targets without a directive of their own.
+    default: Option<u8>,
+    targets: Vec<(String, u8)>,
+}
+
+impl LogFilter {
+    pub fn parse(s: &str) -> Result<LogFilter, String> {
+        let mut filter = LogFilter::default();
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (target, name) = match directive.split_once('=') {
+                Some((target, name)) => (Some(target.trim()), name.trim()),
+                None => (None, directive),
+            };
+            let level = LEVELS
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|&(_, level)| level)
+                .ok_or_else(|| tr!("invalid-log-level", level = name))?;
+            match target {
+                Some("") => return Err(tr!("invalid-log-directive", directive = directive)),
+                Some(target) => filter.targets.push((target.to_owned(), level)),
+                None => filter.default = Some(level),
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Verbosity of messages from `target`: its own directive if it has one, and otherwise the
+    /// greater of the default level and `verbose`, from '-v'.
+    pub fn verbosity(&self, target: &str, verbose: u8) -> u8 {
+        match self.targets.iter().rev().find(|(t, _)| t == target) {
+            Some(&(_, level)) => level,
+            None => self.default.map_or(verbose, |level| level.max(verbose)),
+        }
+    }
+}
+
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |level: u8| {
+            LEVELS
+                .iter()
+                .find(|&&(_, l)| l == level)
+                .map_or("off", |&(name, _)| name)
+        };
+        let directives = self
+            .default
+            .map(|level| name(level).to_owned())
+            .into_iter()
+            .chain(
+                self.targets
+                    .iter()
+                    .map(|(target, level)| format!("{}={}", target, name(*level))),
+            );
+        write!(f, "{}", directives.collect::<Vec<_>>().join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_filter() {
+        let filter = LogFilter::parse("warn, plan=trace,apply=off").unwrap();
+        assert_eq!(filter.verbosity("plan", 0), LV_DEBUG);
+        assert_eq!(filter.verbosity("apply", LV_DEBUG), 0);
+        assert_eq!(filter.verbosity("hooks", 0), LV_WARN);
+        assert_eq!(filter.verbosity("hooks", LV_INFO), LV_INFO);
+        assert_eq!(filter.to_string(), "warn,plan=debug,apply=off");
+        assert_eq!(
+            LogFilter::parse("").unwrap().verbosity("plan", LV_INFO),
+            LV_INFO
+        );
+        assert_eq!(LogFilter::parse(&filter.to_string()), Ok(filter));
+        assert!(LogFilter::parse("plan=loud").is_err());
+        assert!(LogFilter::parse("=debug").is_err());
+    }
+}
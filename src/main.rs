@@ -1,16 +1,87 @@
-use clap::Parser;
+mod ansible;
+mod apply;
+mod bench;
+mod bootstrap;
+mod clone;
+mod complete;
+mod config;
+mod containerize;
+mod daemon;
+mod deploy;
+mod devcontainer;
+mod diagnostic;
+mod diff;
+mod export;
+mod elevate;
+mod filesystem;
+mod find;
+mod git;
+mod hooks;
+mod i18n;
+mod http;
+mod lint;
+mod log;
+mod man;
+mod manifest;
+mod merge;
+mod metrics;
+mod mv;
+mod notification;
+mod orphans;
+mod pager;
+mod paths;
+mod plan;
+mod planfile;
+mod plugin;
+mod preview;
+mod purge;
+mod rename;
+mod report;
+mod rpc;
+mod schedule;
+mod secrets;
+mod setup;
+mod show;
+mod sftp;
+mod signature;
+mod snapshot;
+mod source;
+mod state;
+mod status;
+mod sudo;
+mod template;
+mod tui;
+mod users;
+mod watch;
+mod wsl;
+
+use clap::parser::ValueSource;
+use clap::builder::{NonEmptyStringValueParser, OsStringValueParser, TypedValueParser};
+use clap::{CommandFactory, FromArgMatches, Parser};
+use clap_complete::engine::ArgValueCompleter;
+use clap_complete::CompleteEnv;
+use config::Config;
+use hooks::{HookContext, Phase};
+use i18n::tr;
+use manifest::Manifest;
+use plan::Action;
+use state::State;
 use std::env;
+use std::ffi::OsString;
 use std::io;
 use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::str;
-use walkdir::WalkDir;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
 
 const LV_WARN: u8 = 1;
 const LV_INFO: u8 = 2;
 const LV_DEBUG: u8 = 3;
 
-#[derive(clap::ValueEnum, Clone, Debug)]
+#[derive(clap::ValueEnum, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
 enum ConflictResolution {
     /// Immediately stop running ranch.
     Stop,
@@ -19,26 +90,139 @@ enum ConflictResolution {
     /// Deletes the existing file, replacing it with the soft link.
     Overwrite,
     /// Overwrites the source file with the contents of the existing file, then
-    /// replaces the existing file with a soft link.
+    /// replaces the existing file with a soft link. Encrypted secrets are re-encrypted into the
+    /// package instead.
     Adopt,
     /// Ranch stops running, instead removing all previously created soft-links.
     Rollback,
 }
 
-fn parse_dir(s: &str) -> Result<String, String> {
-    if s == "." {
-        return Ok(env::var("RANCH_DIR").unwrap_or_else(|_| {
-            env::current_dir()
-                .expect("FATAL: Could not open the current directory.")
-                .into_os_string()
-                .into_string()
-                .expect("FATAL: Could not decode 'dir'; invalid unicode encountered.")
-        }));
+#[derive(clap::ValueEnum, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+enum TypeConflict {
+    /// Stop before deploying anything.
+    Stop,
+    /// Leave what's in the way alone, and skip the file; continue with the rest.
+    Skip,
+    /// Rename what's in the way to 'NAME.ranch-backup', then deploy the file.
+    Backup,
+}
+
+#[derive(clap::ValueEnum, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+enum DeepDirs {
+    /// Link the directory itself, as stow folds trees.
+    Fold,
+    /// Leave the directory out.
+    Skip,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// Messages for people, on standard error.
+    Text,
+    /// A JSON result on standard output as an Ansible module prints it: whether anything
+    /// 'changed', whether the run 'failed', and what happened to each package.
+    Ansible,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Eq)]
+enum DryRunFormat {
+    /// Each file's source and target, as it's deployed.
+    Lines,
+    /// A compact summary grouped by directory: '+' to link, '-' to unlink, '!' in conflict.
+    Diff,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Eq)]
+enum ExportFormat {
+    /// A Nix home-manager module setting 'home.file'.
+    HomeManager,
+}
+
+#[derive(clap::ValueEnum, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+enum SpecialFiles {
+    /// Leave them out, with a warning.
+    SkipWithWarning,
+    /// Stop before deploying anything.
+    Error,
+    /// Link them like regular files.
+    Link,
+}
+
+#[derive(clap::ValueEnum, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+enum Elevate {
+    /// Run privileged changes with 'sudo'.
+    Sudo,
+    /// Run privileged changes with 'doas'.
+    Doas,
+    /// Run privileged changes with polkit's 'pkexec'.
+    Pkexec,
+}
+
+#[derive(clap::ValueEnum, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+enum LinkMode {
+    /// Symlinks, except for targets on a Windows drive under WSL, where Windows apps can't follow
+    /// them: Windows links if they can be made, else copies.
+    Auto,
+    /// Symlinks.
+    Symlink,
+    /// Windows symbolic links made with 'mklink', for WSL targets on a Windows drive.
+    Windows,
+    /// Copies of the package files; edits no longer flow back to the package.
+    Copy,
+    /// Hard links to the package files, which must be on the same filesystem; see
+    /// '--xdev-fallback'.
+    Hardlink,
+}
+
+#[derive(clap::ValueEnum, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+enum XdevFallback {
+    /// Stop before deploying anything.
+    Stop,
+    /// Make symlinks instead.
+    Symlink,
+    /// Make copies instead.
+    Copy,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Eq)]
+enum Backend {
+    /// This machine's filesystem.
+    Local,
+    /// Read this machine's filesystem, but print each change as a shell command instead of
+    /// making it, so that a plan can be reviewed or replayed elsewhere.
+    Record,
+    /// The filesystem of '--host', reached over SSH, to which package files are uploaded over
+    /// SFTP; the host needs neither ranch nor the packages.
+    Sftp,
+}
+
+/// Parses a size in bytes, optionally with a binary 'K', 'M', 'G', or 'T' suffix, such as
+/// '100M'.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let unit = unit.trim_end_matches(['B', 'b']).trim_end_matches('i');
+    let scale: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => 0,
+    };
+    match number.parse::<u64>().ok().and_then(|n| n.checked_mul(scale)) {
+        Some(size) if scale > 0 => Ok(size),
+        _ => Err(tr!("invalid-size", size = s)),
     }
-    return Ok(String::from(s));
 }
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Clone, Debug)]
 #[command(
     author = "Jonathan Povirk",
     version,
@@ -57,7 +241,13 @@ All of these listed files point back to the .dotfiles repo, and updating is as s
 This program implements a subset of stow - notably, '--no-folding' is set as the default. \
 In other words, ranch does not create symlinks of directories - only files. \
 Intermediate directories will be created at the target location.
-"
+
+Tab completion, including the names of the packages in 'DIR', is enabled by adding 'source <(COMPLETE=bash ranch)' to '~/.bashrc' (or likewise for zsh, fish, elvish, or powershell).
+
+Like git, ranch can be extended with plugins: 'ranch foo ARGS...' runs 'ranch-foo ARGS...' from '$PATH' when 'foo' isn't a package in 'DIR'. \
+Options given before 'foo' are exported to it as '$RANCH_DIR', '$RANCH_VERBOSE', '$RANCH_DRY_RUN', and so on, and '$RANCH' is the path of ranch itself.
+",
+    subcommand_negates_reqs = true,
 )]
 struct Args {
     /// Do not perform any operations that modify the filesystem; merely show what would happen
@@ -69,37 +259,122 @@ struct Args {
     )]
     dry_run: bool,
 
-    /// Change directory to 'DIR' to search for packages instead of using the current directory
+    /// How '--dry-run' shows what would happen
+    #[arg(
+        value_enum,
+        long,
+        default_value_t = DryRunFormat::Lines,
+        requires = "dry_run",
+    )]
+    dry_run_format: DryRunFormat,
+
+    /// Go through the whole run without making any change, but report what would have changed,
+    /// like Ansible's check mode; unlike '--dry-run', conflicts are resolved as they would be
+    #[arg(long, conflicts_with = "dry_run")]
+    check: bool,
+
+    /// How the outcome of the run is reported
+    #[arg(
+        value_enum,
+        long,
+        default_value_t = OutputFormat::Text,
+    )]
+    format: OutputFormat,
+
+    /// Also write a JSON report of the run to this file, for auditing: the options and config
+    /// used, and each package's plan, changes, errors, and timing
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_parser = OsStringValueParser::new().try_map(paths::expand_path),
+    )]
+    report: Option<PathBuf>,
+
+    /// Change directory to 'DIR' to search for packages; defaults to the nearest of the current
+    /// directory and its parents holding a '.ranchrc' file, or else the config's 'dir', or else
+    /// '$XDG_DATA_HOME/ranch' or '~/.dotfiles' if either exists, or else the current directory
     #[arg(
         short = 'C',
         long,
+        global = true,
+        env = "RANCH_DIR",
         default_value = ".",
-        value_parser = parse_dir,
+        hide_default_value = true,
+        value_parser = OsStringValueParser::new().try_map(paths::expand_path),
     )]
-    dir: String,
+    dir: PathBuf,
 
-    /// Destination directory where symlinks are deployed; default implies 'DIR/..'
+    /// Destination directory where symlinks are deployed; default implies the package manifest's
+    /// 'target', or else 'DIR/..'. '~' and environment variables such as '$HOME' are expanded
     #[arg(
         short = 't',
         long,
+        value_parser = OsStringValueParser::new().try_map(paths::expand_path),
+    )]
+    target: Option<PathBuf>,
+
+    /// Stage links under this directory, as with 'DESTDIR', for building packages and images:
+    /// every target is created under it, while links still point at the runtime paths
+    #[arg(
+        long,
+        value_name = "ROOT",
+        value_parser = OsStringValueParser::new().try_map(paths::expand_path),
+    )]
+    root: Option<PathBuf>,
+
+    /// Deploy into the home directory of each of these users, owned by them, instead of the
+    /// current user's; for provisioning shared machines as root
+    #[arg(
+        long,
+        value_name = "USERS",
+        value_delimiter = ',',
+        conflicts_with = "target",
     )]
-    target: Option<String>,
+    for_users: Vec<String>,
 
     /// Standard error output verbosity (nothing by default); specify multiple times to print more
     #[arg(
         short = 'v',
         long,
+        global = true,
         action = clap::ArgAction::Count,
     )]
     verbose: u8,
 
-    /// Deletes the package from the target dir; only symlinks are deleted
+    /// What to print per module, as comma-separated 'LEVEL' or 'MODULE=LEVEL' directives, where
+    /// LEVEL is off, error, warn, info, debug, or trace; e.g. 'plan=debug' traces just walking
+    /// packages, and 'apply=debug' just making changes and resolving conflicts. A module's own
+    /// directive overrides '-v'
+    #[arg(
+        long,
+        global = true,
+        env = "RANCH_LOG",
+        value_name = "FILTER",
+        value_parser = log::LogFilter::parse,
+    )]
+    log: Option<log::LogFilter>,
+
+    /// Deletes the packages from the target dir; only symlinks are deleted. Globs such as
+    /// 'work-*' are matched against the packages in 'DIR'
     #[arg(
         short = 'D',
         long,
-        default_value = "",
+        value_name = "PACKAGE",
+        num_args = 1..,
+        conflicts_with_all = ["package", "profile"],
+        value_parser = NonEmptyStringValueParser::new(),
+        add = ArgValueCompleter::new(complete::packages),
+    )]
+    delete: Vec<String>,
+
+    /// Deletes links to files that were removed from the package (e.g. by a 'git pull') before
+    /// deploying it again
+    #[arg(
+        short = 'R',
+        long,
+        default_value_t = false,
     )]
-    delete: String,
+    restow: bool,
 
     /// Determines what ranch should do if it finds an existing file where a softlink will be
     /// created
@@ -111,138 +386,1759 @@ struct Args {
     )]
     exists: ConflictResolution,
 
-    /// Name of a subdirectory of 'DIR' containing files to symlink
-    #[arg()]
-    package: String,
-}
+    /// Determines what ranch should do if it finds a directory where the package has a file, or a
+    /// file where the package has a directory
+    #[arg(
+        value_enum,
+        long,
+        default_value_t = TypeConflict::Stop,
+    )]
+    type_conflict: TypeConflict,
 
-#[cfg(windows)]
-fn soft_link(from: &Path, to: &Path) -> io::Result<()> {
-    std::os::windows::fs::symlink_file(from, to)
-}
+    /// Create the package's empty directories at the target too; normally only directories
+    /// holding files are
+    #[arg(
+        long,
+        default_value_t = false,
+    )]
+    create_empty_dirs: bool,
 
-#[cfg(unix)]
-fn soft_link(from: &Path, to: &Path) -> io::Result<()> {
-    std::os::unix::fs::symlink(from, to)
-}
+    /// Only deploy or delete the package files matching this glob, such as '*.conf' or
+    /// '.config/nvim/**'; may be given more than once
+    #[arg(
+        long,
+        value_name = "GLOB",
+    )]
+    only: Vec<String>,
 
-fn exec(argv: &[String], stderr: &mut impl io::Write) {
-    let args = Args::parse_from(argv);
-    if args.verbose >= LV_DEBUG {
-        _ = writeln!(stderr, "{:?}", &args);
-    }
-    // --target's default is dependent the arg 'dir', so setup default value here.
-    let target_path = match &args.target {
-        Some(target) => PathBuf::from(target),
-        _ => PathBuf::from(&args.dir)
-            .parent()
-            .expect("FATAL: Could not access default target path 'DIR/..'")
-            .to_owned(),
-    };
+    /// Leave out the package files and directories matching this glob, read as in '.gitignore':
+    /// '*.bak', 'cache/', or '/README.md'; may be given more than once
+    #[arg(
+        long,
+        value_name = "GLOB",
+    )]
+    exclude: Vec<String>,
 
-    // Check source path
-    let prefix_path = PathBuf::from(&args.dir).join(&args.package);
-    if args.verbose >= LV_INFO {
-        _ = writeln!(
-            stderr,
-            "Linking... {} => {}",
-            &prefix_path.display(),
-            &target_path.display()
-        );
-    }
-    if !prefix_path.exists() {
-        _ = writeln!(
-            stderr,
-            "FATAL: Package {} does not exist; exiting now",
-            args.package
-        );
-        exit(1);
-    }
+    /// Skip package files larger than this, such as '100M', so that a stray disk image or cache
+    /// isn't deployed everywhere
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_size,
+    )]
+    max_file_size: Option<u64>,
 
-    // Check destination path
-    std::fs::create_dir_all(&target_path).expect("FATAL: Could not create target directory");
+    /// Plan files that the repo's '.gitignore' ignores too, such as build output inside a package
+    #[arg(long)]
+    no_gitignore: bool,
 
-    // Make links
-    for src in WalkDir::new(prefix_path)
-        .follow_links(false)
-        .into_iter()
-        .filter_map(|r| r.ok())
-        .map(|e| e.path().to_path_buf())
-    {
-        if !(src.is_file() || src.is_symlink()) {
-            continue;
-        }
-
-        let rel_path = src.strip_prefix(&args.dir);
-        if rel_path.is_err() {
-            if args.verbose >= LV_WARN {
-                _ = writeln!(
-                    stderr,
-                    "WARNING: {} is not a child of {}; ignoring",
-                    src.display(),
-                    &args.dir
-                );
-            }
-            continue;
-        }
+    /// What to do with FIFOs, sockets, and device nodes in a package
+    #[arg(
+        value_enum,
+        long,
+        default_value_t = SpecialFiles::SkipWithWarning,
+    )]
+    special_files: SpecialFiles,
 
-        let rel_path = rel_path.unwrap();
+    /// Skip package files that look binary, such as compiled artifacts, and only deploy text
+    #[arg(long)]
+    skip_binaries: bool,
 
-        let relative_output = rel_path.strip_prefix(&args.package).unwrap();
-        let output_path = target_path.join(relative_output).to_path_buf();
-        if args.verbose >= LV_INFO {
-            _ = writeln!(
-                stderr,
-                "{} -> {}",
-                &src.to_path_buf().display(),
-                &output_path.display()
-            );
-        }
-        if !args.dry_run {
-            soft_link(&src.to_path_buf(), &output_path).unwrap();
-        }
-    }
-}
+    /// Resolve symlinks inside packages, such as to shared fragments, and link the real files
+    /// instead of making links to links
+    #[arg(long)]
+    follow_source_links: bool,
 
-fn main() {
-    let argv: Vec<String> = std::env::args().collect();
-    exec(&argv, &mut io::stderr());
-}
+    /// Only walk this many levels into a package, such as to keep vendored trees like
+    /// 'node_modules' from being linked file by file; 1 is the package's top level
+    #[arg(
+        long,
+        value_name = "DEPTH",
+    )]
+    max_depth: Option<usize>,
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs::{File,create_dir_all};
-    use tempdir::TempDir;
+    /// What to do with the directories found at '--max-depth'
+    #[arg(
+        value_enum,
+        long,
+        default_value_t = DeepDirs::Fold,
+        requires = "max_depth",
+    )]
+    deep_dirs: DeepDirs,
 
-    fn make_dummy_fs(dir: &Path)
-    {
-        let dotfiles_home = dir.join(".dotfiles/home");
-        create_dir_all(&dotfiles_home).unwrap();
+    /// Create links with paths relative to their own directory, such as '.dotfiles/home/.vimrc',
+    /// so that they survive moving the target together with 'DIR'
+    #[arg(
+        long,
+        conflicts_with = "absolute",
+    )]
+    relative: bool,
 
-        File::create(dotfiles_home.join(".vimrc")).unwrap();
-    }
+    /// Create links with absolute paths; the default, unless the config sets 'relative_links'
+    #[arg(
+        long,
+    )]
+    absolute: bool,
 
-    #[test]
-    fn test_example()
-    {
-        println!("GIVEN");
-        let mut stderr = io::BufWriter::new(Vec::new());
-        let tmp_dir = TempDir::new("alice").unwrap();
-        make_dummy_fs(tmp_dir.path());
+    /// Snapshot the target's btrfs subvolume, ZFS dataset, or APFS volume before deploying, for
+    /// undoing risky runs such as mass overwrites
+    #[arg(long)]
+    snapshot: bool,
 
-        println!("WHEN");
-        exec(&[
-            "ranch",
-            "-vvv",
-            "-C",
-            tmp_dir.path().join(".dotfiles").to_str().unwrap(),
-            "home"
-        ].map(|s| s.to_owned()), &mut stderr);
+    /// Keep deploying the rest of the files after one fails, then report every failure and
+    /// exit with an error
+    #[arg(long)]
+    keep_going: bool,
 
-        println!("THEN");
-        let bytes = stderr.into_inner().unwrap();
-        let string = String::from_utf8(bytes).unwrap();
-        println!("{}", string);
+    /// Retry links and directories that fail transiently, as on NFS or SMB, this many times
+    /// with backoff
+    #[arg(
+        long,
+        value_name = "COUNT",
+        default_value_t = 0,
+    )]
+    retries: u32,
+
+    /// Make the Nth link, file, or directory that ranch creates or removes fail, to test how
+    /// failures are recovered from
+    #[arg(
+        long,
+        hide = true,
+        env = "RANCH_INJECT_FAILURE",
+        value_name = "N",
+    )]
+    inject_failure: Option<usize>,
+
+    /// Changes made to the filesystem so far, counted for '--inject-failure'
+    #[arg(skip)]
+    changes: Arc<AtomicUsize>,
+
+    /// What linking or unlinking each package did so far, for reporting on the whole run
+    #[arg(skip)]
+    outcomes: Arc<Mutex<Vec<apply::Outcome>>>,
+
+    /// The filesystem that packages are deployed to
+    #[arg(skip)]
+    fs: filesystem::Fs,
+
+    /// Flush the changed directories to disk before exiting, so that a crash right afterwards
+    /// can't lose the links
+    #[arg(long)]
+    sync: bool,
+
+    /// How package files are deployed to the target
+    #[arg(
+        value_enum,
+        long,
+        default_value_t = LinkMode::Auto,
+    )]
+    link_mode: LinkMode,
+
+    /// Where the changes are carried out
+    #[arg(
+        value_enum,
+        long,
+        global = true,
+        default_value_t = Backend::Local,
+    )]
+    backend: Backend,
+
+    /// File where ranch remembers what it did on this machine between runs, such as the
+    /// directories it created; defaults to '<state dir>/ranch/state.json'
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        env = "RANCH_STATE",
+        value_parser = OsStringValueParser::new().try_map(paths::expand_path),
+    )]
+    state: Option<PathBuf>,
+
+    /// With '--backend sftp', the host to deploy to, as '[USER@]HOST' or a name from
+    /// '~/.ssh/config'
+    #[arg(long, global = true, value_name = "[USER@]HOST", env = "RANCH_HOST")]
+    host: Option<String>,
+
+    /// With '--link-mode hardlink', what to do when a package and its target are on different
+    /// filesystems, where hard links can't be made
+    #[arg(
+        value_enum,
+        long,
+        default_value_t = XdevFallback::Stop,
+    )]
+    xdev_fallback: XdevFallback,
+
+    /// Run as the user, and make only the changes denied permission, such as links in '/etc', as
+    /// root with this tool
+    #[arg(
+        value_enum,
+        long,
+        value_name = "TOOL",
+    )]
+    elevate: Option<Elevate>,
+
+    /// When a real file is in the way of a link, show how it differs from the package's file
+    #[arg(
+        long,
+        default_value_t = false,
+    )]
+    diff: bool,
+
+    /// With '--exists adopt', adopt files that differ from the package's without asking first
+    #[arg(
+        short = 'y',
+        long,
+        default_value_t = false,
+    )]
+    yes: bool,
+
+    /// With '--exists adopt', merge the target's changes into the package file instead of
+    /// overwriting it, taking the file's last commit as their common ancestor. Conflicts are
+    /// handed to '$MERGETOOL' if set, else marked in the package file
+    #[arg(
+        long,
+        default_value_t = false,
+    )]
+    merge: bool,
+
+    /// With '--exists adopt', commit the adopted files to the repo's git history
+    #[arg(
+        long,
+        default_value_t = false,
+    )]
+    commit: bool,
+
+    /// Message for '--commit'; '{package}', '{files}', and '{count}' are replaced with the
+    /// package, the adopted files, and how many there are
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        default_value = "Adopt {files} into {package}",
+    )]
+    commit_message: String,
+
+    /// age identity file used to decrypt '*.age' secrets, and to re-encrypt them on adopt
+    #[arg(
+        long,
+        env = "RANCH_AGE_IDENTITY",
+        value_parser = OsStringValueParser::new().try_map(paths::expand_path),
+    )]
+    age_identity: Option<PathBuf>,
+
+    /// gpg recipient used to re-encrypt '*.gpg' secrets on adopt; defaults to your own key
+    #[arg(
+        long,
+        env = "RANCH_GPG_RECIPIENT",
+    )]
+    gpg_recipient: Option<String>,
+
+    /// Treat lint findings, such as secret-looking files readable by other users, as errors
+    #[arg(
+        long,
+        global = true,
+        default_value_t = false,
+    )]
+    strict: bool,
+
+    /// Seconds a hook may run before it is killed and ranch fails; overrides the config's
+    /// 'hook_timeout'
+    #[arg(
+        long,
+        global = true,
+        value_name = "SECONDS",
+    )]
+    hook_timeout: Option<u64>,
+
+    /// Run hooks with a cleared environment in an empty scratch directory and, where the OS
+    /// allows (Linux with user namespaces), with no network access and everything but that
+    /// directory read-only; for applying dotfiles you didn't write
+    #[arg(
+        long,
+        global = true,
+        default_value_t = false,
+    )]
+    sandbox_hooks: bool,
+
+    /// Only deploy files tracked by git, skipping untracked ones such as editor swap files and
+    /// local experiments
+    #[arg(
+        long,
+        global = true,
+        default_value_t = false,
+    )]
+    vcs_only: bool,
+
+    /// Fetch packages from this git repository, or '.tar[.gz|.bz2|.xz]'/'.zip' archive (a local
+    /// path or HTTP(S) URL), instead of using 'DIR'. It's kept in ranch's cache and updated on
+    /// every run, and targets default to your home directory
+    #[arg(
+        long,
+        global = true,
+        value_name = "URL",
+    )]
+    from: Option<String>,
+
+    /// Refuse to deploy a '--from' archive unless its SHA-256 matches this hash
+    #[arg(
+        long,
+        global = true,
+        value_name = "HASH",
+        conflicts_with = "lockfile",
+    )]
+    sha256: Option<String>,
+
+    /// Refuse to deploy a '--from' archive unless its SHA-256 matches the one listed for its URL
+    /// in this file, which holds 'HASH  URL' lines as printed by 'sha256sum'
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        value_parser = OsStringValueParser::new().try_map(paths::expand_path),
+    )]
+    lockfile: Option<PathBuf>,
+
+    /// Require packages to be signed with this minisign public key (or key file): a '--from'
+    /// archive by 'ARCHIVE.minisig', otherwise each package by '.ranch.sha256.minisig', over the
+    /// checksums of all its files that 'ranch checksums' writes to '.ranch.sha256'
+    #[arg(
+        long,
+        global = true,
+        env = "RANCH_MINISIGN_KEY",
+        value_name = "KEY",
+        conflicts_with = "allowed_signers",
+    )]
+    minisign_key: Option<String>,
+
+    /// Require packages to be signed by a key in this ssh 'allowed_signers' file, as with
+    /// '--minisign-key' but with '.sig' signatures made by 'ssh-keygen -Y sign -n file'
+    #[arg(
+        long,
+        global = true,
+        env = "RANCH_ALLOWED_SIGNERS",
+        value_name = "FILE",
+        value_parser = OsStringValueParser::new().try_map(paths::expand_path),
+    )]
+    allowed_signers: Option<PathBuf>,
+
+    /// Write reports such as 'status' straight to stdout instead of through '$PAGER'
+    #[arg(
+        long,
+        global = true,
+        default_value_t = false,
+    )]
+    no_pager: bool,
+
+    /// Language of warnings and errors, such as 'de'; defaults to '$LC_ALL', '$LC_MESSAGES', or
+    /// '$LANG'. Translations in '<config dir>/ranch/locales/LANG.ftl' take precedence over
+    /// ranch's own
+    #[arg(
+        long,
+        global = true,
+        env = "RANCH_LANG",
+    )]
+    lang: Option<String>,
+
+    /// Deploy every package in this profile, one of the config's '[profiles]'
+    #[arg(
+        short = 'p',
+        long,
+        value_name = "NAME",
+        conflicts_with = "package",
+    )]
+    profile: Option<String>,
+
+    /// Where packages deploy to by default, in place of 'DIR/..'; from the config's 'target'
+    #[arg(skip)]
+    default_target: Option<PathBuf>,
+
+    /// Set once a signed '--from' archive has been verified, covering the packages inside it
+    #[arg(skip)]
+    verified_source: bool,
+
+    /// The uid and gid that created paths are given to, set for each of '--for-users'
+    #[arg(skip)]
+    owner: Option<(u32, u32)>,
+
+    /// Name of a subdirectory of 'DIR' containing files to symlink
+    #[arg(
+        required_unless_present_any = ["delete", "profile"],
+        value_parser = NonEmptyStringValueParser::new(),
+        add = ArgValueCompleter::new(complete::packages),
+    )]
+    package: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Clone, Debug)]
+enum Command {
+    /// Check packages for problems (collisions, broken links, junk files, bad manifests) without
+    /// touching the target
+    Lint {
+        /// Packages to check; defaults to every package in 'DIR'
+        #[arg(add = ArgValueCompleter::new(complete::packages))]
+        packages: Vec<String>,
+    },
+    /// Deploy packages, then keep re-linking them as files are added to or removed from the
+    /// repo, until interrupted
+    Watch {
+        /// Packages to watch
+        #[arg(required = true, add = ArgValueCompleter::new(complete::packages))]
+        packages: Vec<String>,
+    },
+    /// Show which files of each package are deployed, missing, or conflicting
+    Status {
+        /// Packages to check; defaults to every package in 'DIR'
+        #[arg(add = ArgValueCompleter::new(complete::packages))]
+        packages: Vec<String>,
+    },
+    /// Continuously reconcile packages like 'watch', taking commands over a control socket
+    Daemon {
+        /// Packages to keep deployed; defaults to the config's 'daemon.packages', or else every
+        /// package in 'DIR'
+        #[arg(add = ArgValueCompleter::new(complete::packages))]
+        packages: Vec<String>,
+
+        /// Control socket path; defaults to '$XDG_RUNTIME_DIR/ranch.sock'
+        #[arg(
+            long,
+            value_parser = OsStringValueParser::new().try_map(paths::expand_path),
+        )]
+        socket: Option<PathBuf>,
+
+        /// Also serve an HTTP API ('GET /status', 'GET /drift', 'GET /metrics', 'POST /apply') on
+        /// this loopback address, such as '127.0.0.1:7878'; overrides the config's 'daemon.http'.
+        /// Requests need the token written next to the socket, as 'Authorization: Bearer TOKEN'
+        #[arg(long, value_name = "ADDR")]
+        http: Option<String>,
+    },
+    /// Send a command to a running daemon
+    Ctl {
+        /// Command to send
+        #[arg(value_parser = ["status", "apply", "reload"])]
+        command: String,
+
+        /// Control socket path; defaults to '$XDG_RUNTIME_DIR/ranch.sock'
+        #[arg(
+            long,
+            value_parser = OsStringValueParser::new().try_map(paths::expand_path),
+        )]
+        socket: Option<PathBuf>,
+    },
+    /// Install a git hook into the repo holding 'DIR' that restows the packages changed by each
+    /// 'git pull' or 'git checkout'
+    GitHook {
+        #[arg(value_parser = ["install", "uninstall"])]
+        action: String,
+    },
+    /// Make a project's VS Code devcontainer clone the repo holding 'DIR' and deploy packages
+    /// from it when the container is created, through its 'postCreateCommand'
+    Devcontainer {
+        #[arg(value_parser = ["install", "uninstall"])]
+        action: String,
+
+        /// Packages to deploy in the container
+        #[arg(
+            required_if_eq("action", "install"),
+            add = ArgValueCompleter::new(complete::packages),
+        )]
+        packages: Vec<String>,
+
+        /// Project whose devcontainer to set up
+        #[arg(
+            long,
+            default_value = ".",
+            value_parser = OsStringValueParser::new().try_map(paths::expand_path),
+        )]
+        project: PathBuf,
+    },
+    /// Answer JSON-RPC 2.0 requests, one per line, on standard input and output; for editor
+    /// plugins and GUI frontends
+    Rpc,
+    /// Browse packages and the status of their files, linking, adopting, and unlinking files one
+    /// at a time; for carefully bringing an existing home directory under ranch
+    Tui {
+        /// Packages to show; defaults to every package in 'DIR'
+        #[arg(add = ArgValueCompleter::new(complete::packages))]
+        packages: Vec<String>,
+    },
+    /// Create the config file by answering questions about the dotfiles directory, target,
+    /// conflict policy, and profiles
+    Setup,
+    /// Write roff man pages for ranch and its subcommands into a directory
+    Man {
+        /// Directory to write the pages into, such as '/usr/share/man/man1'
+        #[arg(value_parser = OsStringValueParser::new().try_map(paths::expand_path))]
+        dir: PathBuf,
+    },
+    /// Remove every link in the target that points into 'DIR', whichever package it belongs to,
+    /// such as before re-provisioning a machine
+    Purge,
+    /// Write down the actions that deploying packages would take, and the state of each target
+    /// path, for reviewing before running them with 'apply'
+    Plan {
+        /// Packages to plan; defaults to every package in 'DIR'
+        #[arg(add = ArgValueCompleter::new(complete::packages))]
+        packages: Vec<String>,
+
+        /// File to write the plan to, as JSON; defaults to standard output
+        #[arg(
+            short = 'o',
+            long,
+            value_parser = OsStringValueParser::new().try_map(paths::expand_path),
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Run exactly the actions in a plan written by 'plan', refusing if any target path has
+    /// changed since
+    Apply {
+        /// Plan file written by 'plan'
+        #[arg(value_parser = OsStringValueParser::new().try_map(paths::expand_path))]
+        plan: PathBuf,
+    },
+    /// List links into 'DIR' whose package file no longer exists
+    Orphans {
+        /// Delete the orphaned links too
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Move a file within or between packages, such as 'mv zsh/aliases.zsh shell-common/',
+    /// relinking it if it's deployed
+    Mv {
+        /// File to move, relative to 'DIR'
+        file: PathBuf,
+        /// Where to move it, relative to 'DIR'; a directory keeps the file's name
+        destination: PathBuf,
+    },
+    /// Rename a package, repointing the links into it so that they aren't orphaned
+    Rename {
+        /// Package to rename
+        #[arg(add = ArgValueCompleter::new(complete::packages))]
+        old: String,
+        /// Its new name
+        new: String,
+    },
+    /// Find which packages provide a file, by name or glob such as 'kitty.conf' or '*.lua', and
+    /// whether it's deployed
+    Find {
+        /// File name or glob; globs containing '/' match the path within the package
+        pattern: String,
+    },
+    /// Copy packages, or clone the repo holding them, to other machines over SSH, and link them
+    /// there with their ranch; reports how each host went
+    Deploy {
+        /// Hosts to deploy to, as '[USER@]HOST' or names from '~/.ssh/config'
+        #[arg(required = true, value_name = "[USER@]HOST")]
+        hosts: Vec<String>,
+
+        /// Package to deploy; may be given more than once
+        #[arg(long = "package", required = true, add = ArgValueCompleter::new(complete::packages))]
+        packages: Vec<String>,
+
+        /// Directory on each host to put the packages in, relative to its home directory
+        #[arg(long, default_value = ".dotfiles")]
+        remote_dir: String,
+
+        /// Clone the git repo holding 'DIR' from its 'origin' on each host, or pull it if it's
+        /// already there, instead of copying the packages
+        #[arg(long)]
+        clone: bool,
+    },
+    /// Print a Dockerfile fragment that puts a package's files into an image as they'd be
+    /// deployed; build it with 'DIR' as the context
+    Containerize {
+        /// Package to put into the image
+        #[arg(add = ArgValueCompleter::new(complete::packages))]
+        package: String,
+
+        /// Directory in the image that stands in for the package's target, such as '/home/dev';
+        /// defaults to the target
+        #[arg(long, value_name = "DIR")]
+        prefix: Option<PathBuf>,
+
+        /// Owner of the files in the image, as 'USER[:GROUP]'; root if not given
+        #[arg(long, value_name = "USER[:GROUP]")]
+        chown: Option<String>,
+    },
+    /// Print what deploying packages would do as another tool's configuration, for using both
+    /// or moving between them
+    Export {
+        /// Packages to export; defaults to every package in 'DIR'
+        #[arg(add = ArgValueCompleter::new(complete::packages))]
+        packages: Vec<String>,
+
+        /// Tool to configure
+        #[arg(value_enum, long)]
+        format: ExportFormat,
+    },
+    /// Generate an installer for new machines that clones the repo holding 'DIR' and deploys
+    /// packages from it, with ranch if it can be installed, and with 'ln' otherwise
+    Bootstrap {
+        /// Packages the installer deploys
+        #[arg(required = true, add = ArgValueCompleter::new(complete::packages))]
+        packages: Vec<String>,
+
+        /// Print the installer, a POSIX shell script, to standard output
+        #[arg(long, required = true)]
+        emit: bool,
+    },
+    /// Set up a new machine: clone a dotfiles repo to '~/.dotfiles', remember it in the config
+    /// as 'dir', and deploy packages from it
+    Clone {
+        /// URL of the repo
+        url: String,
+
+        /// Packages to deploy, separated by commas, such as 'home,zsh'
+        #[arg(long, value_delimiter = ',')]
+        packages: Vec<String>,
+
+        /// Where to clone the repo instead of '~/.dotfiles'
+        #[arg(
+            long,
+            value_name = "DIR",
+            value_parser = OsStringValueParser::new().try_map(paths::expand_path),
+        )]
+        into: Option<PathBuf>,
+    },
+    /// Write a single static HTML page on how deployed packages are, with a table of each
+    /// package's files and the broken links into 'DIR' highlighted, for dashboards and tickets
+    Report {
+        /// Packages to report on; defaults to every package in 'DIR'
+        #[arg(add = ArgValueCompleter::new(complete::packages))]
+        packages: Vec<String>,
+
+        /// Write the report as HTML
+        #[arg(long, required = true)]
+        html: bool,
+
+        /// File to write the report to; defaults to standard output
+        #[arg(
+            short = 'o',
+            long,
+            value_parser = OsStringValueParser::new().try_map(paths::expand_path),
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Generate a synthetic package of many files, deploy it to an empty temporary target with
+    /// the given options, and report how fast planning and applying went
+    Bench {
+        /// How many files the package has
+        #[arg(short = 'n', long, value_name = "COUNT", default_value_t = 10000)]
+        files: usize,
+    },
+    /// Write the checksums of every file in a package to its '.ranch.sha256', to be signed for
+    /// '--minisign-key' or '--allowed-signers'
+    Checksums {
+        /// Package to write the checksums of
+        #[arg(add = ArgValueCompleter::new(complete::packages))]
+        package: String,
+    },
+    /// Describe a package: its manifest's description, homepage, and platforms, its target, and
+    /// how many of its files are deployed
+    Show {
+        /// Package to describe
+        #[arg(add = ArgValueCompleter::new(complete::packages))]
+        package: String,
+    },
+}
+
+impl Args {
+    /// Whether nothing is to be changed on this machine: a dry run or check, or a backend that
+    /// makes its changes elsewhere, if at all, such that local state, hooks, and snapshots don't
+    /// apply.
+    fn changes_nothing(&self) -> bool {
+        self.dry_run || self.check || self.backend != Backend::Local
+    }
+
+    /// Verbosity of messages from the module `target`, such as 'plan', after '--log'.
+    fn verbosity(&self, target: &str) -> u8 {
+        match &self.log {
+            Some(filter) => filter.verbosity(target, self.verbose),
+            None => self.verbose,
+        }
+    }
+
+    /// Builds the arguments from parsed `matches`, filling in options that weren't given on the
+    /// command line from the user's config.
+    fn from_matches(matches: &clap::ArgMatches) -> Result<Args, String> {
+        let mut args = Args::from_arg_matches(matches).map_err(|e| e.to_string())?;
+        let config = Config::load()?;
+        let defaulted = |id| matches.value_source(id) == Some(ValueSource::DefaultValue);
+        if let Some(name) = matches.subcommand_name() {
+            let given = |id| matches.value_source(id) == Some(ValueSource::CommandLine);
+            let options = [
+                ("package", "PACKAGE"),
+                ("delete", "--delete"),
+                ("profile", "--profile"),
+            ];
+            if let Some((_, option)) = options.into_iter().find(|&(id, _)| given(id)) {
+                return Err(tr!("option-with-subcommand", option = option, subcommand = name));
+            }
+        }
+        if defaulted("dir") {
+            let cwd = env::current_dir().map_err(|e| tr!("could-not-open-current-dir", error = e))?;
+            args.dir = paths::default_dir(&cwd, config.dir.as_deref())?;
+        }
+        // Symlinked parents, such as '/home' -> '/var/home', would otherwise make paths found
+        // by walking or watching 'DIR' disagree with it.
+        args.dir = paths::canonicalize(&args.dir);
+        if config.relative_links && !args.absolute {
+            args.relative = true;
+        }
+        if let Some(target) = &config.target {
+            let target =
+                paths::expand(target).map_err(|e| tr!("invalid-config-target", error = e))?;
+            args.default_target = Some(PathBuf::from(target));
+        }
+        if let Some(exists) = config.exists.clone().filter(|_| defaulted("exists")) {
+            args.exists = exists;
+        }
+        if let Some(policy) = config.type_conflict.clone().filter(|_| defaulted("type_conflict")) {
+            args.type_conflict = policy;
+        }
+        if let Some(mode) = config.link_mode.clone().filter(|_| defaulted("link_mode")) {
+            args.link_mode = mode;
+        }
+        if let Some(retries) = config.retries.filter(|_| defaulted("retries")) {
+            args.retries = retries;
+        }
+        if args.elevate.is_none() {
+            args.elevate = config.elevate.clone();
+        }
+        args.fs = filesystem::open(&args)?;
+        Ok(args)
+    }
+}
+
+fn exec(argv: &[impl Into<OsString> + Clone], stderr: &mut impl io::Write) {
+    let argv: Vec<OsString> = argv.iter().cloned().map(Into::into).collect();
+    i18n::init(i18n::lang_option(&argv).or_else(|| env::var("RANCH_LANG").ok()).as_deref());
+    match plugin::dispatch(&argv, stderr) {
+        Some(Ok(code)) => exit(code),
+        Some(Err(e)) => {
+            _ = writeln!(stderr, "{}", tr!("fatal", error = e));
+            exit(1);
+        }
+        None => {}
+    }
+    let started = SystemTime::now();
+    let matches = Args::command().get_matches_from(argv);
+    let result = Args::from_matches(&matches).and_then(|mut args| {
+        if args.verbosity("ranch") >= LV_DEBUG {
+            _ = writeln!(stderr, "{:?}", &args);
+        }
+        let result = match args.from.clone() {
+            Some(url) => source::fetch(&mut args, &url, stderr).and_then(|_| run(&args, stderr)),
+            None => run(&args, stderr),
+        };
+        if args.format == OutputFormat::Ansible {
+            ansible::print(&args, result.as_ref().err().map(String::as_str), &mut io::stdout());
+        }
+        if let Some(path) = &args.report {
+            report::json(&args, started, result.as_ref().err().map(String::as_str), path, stderr);
+        }
+        result
+    });
+    if let Err(e) = result {
+        _ = writeln!(stderr, "{}", tr!("fatal", error = e));
+        exit(1);
+    }
+}
+
+/// Runs the subcommand, or else deploys or deletes the package.
+fn run(args: &Args, stderr: &mut dyn io::Write) -> Result<(), String> {
+    match &args.command {
+        Some(Command::Lint { packages }) => lint::lint(args, packages, stderr),
+        Some(Command::Watch { packages }) => watch::watch(args, packages, stderr),
+        Some(Command::Status { packages }) => status::status(args, packages, stderr),
+        Some(Command::Daemon { packages, socket, http }) => {
+            daemon::daemon(args, packages, socket.as_deref(), http.as_deref(), stderr)
+        }
+        Some(Command::Ctl { command, socket }) => daemon::ctl(command, socket.as_deref()),
+        Some(Command::GitHook { action }) => match action.as_str() {
+            "install" => git::install_hooks(args, stderr),
+            _ => git::uninstall_hooks(args, stderr),
+        },
+        Some(Command::Devcontainer { action, packages, project }) => match action.as_str() {
+            "install" => devcontainer::install(args, project, packages, stderr),
+            _ => devcontainer::uninstall(args, project, stderr),
+        },
+        Some(Command::Rpc) => rpc::serve(args, io::stdin().lock(), &mut io::stdout()),
+        Some(Command::Tui { packages }) => tui::tui(args, packages),
+        Some(Command::Setup) => setup::setup(args, io::stdin().lock(), &mut io::stdout()),
+        Some(Command::Man { dir }) => man::generate(args, dir, stderr),
+        Some(Command::Purge) => purge::purge(args, stderr),
+        Some(Command::Plan { packages, output }) => {
+            planfile::write(args, packages, output.as_deref(), stderr)
+        }
+        Some(Command::Apply { plan }) => planfile::apply(args, plan, stderr),
+        Some(Command::Orphans { remove }) => {
+            orphans::orphans(args, *remove, &mut io::stdout(), stderr)
+        }
+        Some(Command::Mv { file, destination }) => mv::mv(args, file, destination, stderr),
+        Some(Command::Rename { old, new }) => rename::rename(args, old, new, stderr),
+        Some(Command::Find { pattern }) => find::find(args, pattern, stderr),
+        Some(Command::Deploy { hosts, packages, remote_dir, clone }) => {
+            deploy::deploy(args, hosts, packages, remote_dir, *clone, stderr)
+        }
+        Some(Command::Containerize { package, prefix, chown }) => containerize::containerize(
+            args,
+            package,
+            prefix.as_deref(),
+            chown.as_deref(),
+            &mut io::stdout(),
+            stderr,
+        ),
+        Some(Command::Export { packages, format }) => {
+            export::export(args, packages, format, &mut io::stdout(), stderr)
+        }
+        Some(Command::Bootstrap { packages, .. }) => {
+            bootstrap::emit(args, packages, &mut io::stdout(), stderr)
+        }
+        Some(Command::Clone { url, packages, into }) => {
+            clone::clone(args, url, into.as_deref(), packages, stderr)
+        }
+        Some(Command::Report { packages, output, .. }) => {
+            report::html(args, packages, output.as_deref(), stderr)
+        }
+        Some(Command::Show { package }) => show::show(args, package, &mut io::stdout(), stderr),
+        Some(Command::Checksums { package }) => signature::write_checksums(args, package, stderr),
+        Some(Command::Bench { files }) => bench::bench(args, *files, &mut io::stdout(), stderr),
+        None if !args.for_users.is_empty() => users::for_users(args, stderr),
+        None if !args.delete.is_empty() => unstow_all(args, &args.delete, stderr),
+        None => match &args.profile {
+            Some(profile) => {
+                for package in Config::load()?.profile(profile)? {
+                    stow(args, package, stderr)?;
+                }
+                Ok(())
+            }
+            None => stow(args, args.package.as_deref().unwrap_or_default(), stderr),
+        },
+    }
+}
+
+/// Locates `package`, returning its path, manifest, and resolved target directory.
+fn load_package(args: &Args, package: &str) -> Result<(PathBuf, Manifest, PathBuf), String> {
+    // Check source path
+    let prefix_path = args.dir.join(package);
+    if !prefix_path.exists() {
+        return Err(tr!("no-such-package", package = package));
+    }
+    signature::verify_package(args, &prefix_path)?;
+    let manifest = Manifest::load(&prefix_path)?;
+    let target_path = plan::target_path(args, package, &manifest)?;
+    Ok((prefix_path, manifest, target_path))
+}
+
+fn stow(args: &Args, package: &str, stderr: &mut dyn io::Write) -> Result<(), String> {
+    let (prefix_path, manifest, target_path) = load_package(args, package)?;
+    if args.verbosity("ranch") >= LV_INFO {
+        _ = writeln!(
+            stderr,
+            "Linking... {} => {}",
+            &prefix_path.display(),
+            &target_path.display()
+        );
+    }
+
+    // Check destination path
+    elevate::create_dir_all(args, &target_path, stderr).map_err(|e| {
+        tr!("could-not-create-target", path = target_path.display(), error = e)
+    })?;
+
+    // Make links
+    let planning = Instant::now();
+    let actions = plan::plan(args, package, &prefix_path, &target_path, stderr)?;
+    print_planned(args, package, &actions, planning, stderr);
+    stow_actions(args, package, &prefix_path, &manifest, &target_path, &actions, stderr)
+}
+
+/// Checks and deploys the planned `actions` of `package`, running its hooks around them.
+fn stow_actions(
+    args: &Args,
+    package: &str,
+    prefix_path: &Path,
+    manifest: &Manifest,
+    target_path: &Path,
+    actions: &[Action],
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let started = Instant::now();
+    let config = Config::load()?;
+    let fallback_args;
+    let args = match apply::xdev_fallback(args, prefix_path, target_path, stderr)? {
+        Some(link_mode) => {
+            fallback_args = Args { link_mode, ..args.clone() };
+            &fallback_args
+        }
+        None => args,
+    };
+
+    plan::check_links(args, actions)?;
+    plan::check_case(actions)?;
+    plan::check_types(args, actions)?;
+    let mut findings = lint::check_secret_permissions(actions);
+    findings.extend(lint::check_submodules(prefix_path));
+    for finding in &findings {
+        if args.strict {
+            _ = writeln!(stderr, "{}", tr!("error", error = finding));
+        } else if args.verbosity("ranch") >= LV_WARN {
+            _ = writeln!(stderr, "{}", tr!("warning", message = finding));
+        }
+    }
+    if args.strict && !findings.is_empty() {
+        return Err(tr!("lint-failed-strict"));
+    }
+    if args.dry_run_format == DryRunFormat::Diff {
+        preview::print(args, actions, false, &mut io::stdout());
+        return Ok(());
+    }
+    let ctx = HookContext {
+        package,
+        prefix_path,
+        target_path,
+        manifest,
+        actions,
+    };
+    hooks::run_hooks(args, &config, Phase::PreLink, &ctx, stderr)?;
+    snapshot::snapshot(args, package, target_path, stderr)?;
+    let mut summary = apply::Summary::default();
+    let result = match args.restow {
+        true => apply::prune(args, prefix_path, target_path, &mut summary, stderr),
+        false => Ok(()),
+    };
+    let result = result.and_then(|_| apply::apply(args, &config, actions, &mut summary, stderr));
+    apply::sync(args, &summary, stderr);
+    let error = result.as_ref().err().map(String::as_str);
+    apply::record(args, "link", package, actions, started, &summary, error);
+    print_applied(args, package, &summary, started, stderr);
+    notification::webhook(
+        args,
+        &config,
+        "link",
+        package,
+        &summary,
+        result.as_ref().err().map(String::as_str),
+        stderr,
+    );
+    if !summary.created_dirs.is_empty() && !args.changes_nothing() {
+        let mut state = State::load(args)?;
+        state.created_dirs.extend(summary.created_dirs.iter().cloned());
+        state.save(args)?;
+    }
+    sudo::restore_ownership(args, &summary.adopted, stderr);
+    result?;
+    if args.commit && !args.changes_nothing() && !summary.adopted.is_empty() {
+        let committed = git::commit_adopted(args, package, &summary.adopted, stderr);
+        if let Ok(git_dir) = git::git_dir(&args.dir) {
+            sudo::restore_ownership(args, &[git_dir], stderr);
+        }
+        committed?;
+    }
+    let mut state = State::load(args)?;
+    hooks::run_once(args, &config, &ctx, &mut state, stderr)?;
+    hooks::run_hooks(args, &config, Phase::PostLink, &ctx, stderr)
+}
+
+/// Deletes every package named by `patterns`, then reports what was removed across all of
+/// them.
+fn unstow_all(args: &Args, patterns: &[String], stderr: &mut dyn io::Write) -> Result<(), String> {
+    let mut packages: Vec<String> = Vec::new();
+    for pattern in patterns {
+        let matched: Vec<String> = match paths::is_glob(pattern) {
+            true => plan::packages(&args.dir)?
+                .into_iter()
+                .filter(|p| paths::glob_match(pattern, p))
+                .collect(),
+            false => vec![pattern.clone()],
+        };
+        if matched.is_empty() {
+            return Err(tr!("no-packages-match", pattern = pattern));
+        }
+        for package in matched {
+            if !packages.contains(&package) {
+                packages.push(package);
+            }
+        }
+    }
+    let mut removed = 0;
+    for package in &packages {
+        removed += unstow(args, package, stderr)?.removed.len();
+    }
+    if args.verbosity("ranch") >= LV_INFO && packages.len() > 1 {
+        _ = writeln!(
+            stderr,
+            "Deleted {} packages ({}); {} paths removed",
+            packages.len(),
+            packages.join(", "),
+            removed
+        );
+    }
+    Ok(())
+}
+
+fn unstow(
+    args: &Args,
+    package: &str,
+    stderr: &mut dyn io::Write,
+) -> Result<apply::Summary, String> {
+    let started = Instant::now();
+    let config = Config::load()?;
+    let (prefix_path, manifest, target_path) = load_package(args, package)?;
+    if args.verbosity("ranch") >= LV_INFO {
+        _ = writeln!(
+            stderr,
+            "Unlinking... {} => {}",
+            &prefix_path.display(),
+            &target_path.display()
+        );
+    }
+    let planning = Instant::now();
+    let actions = plan::plan(args, package, &prefix_path, &target_path, stderr)?;
+    print_planned(args, package, &actions, planning, stderr);
+    if args.dry_run_format == DryRunFormat::Diff {
+        preview::print(args, &actions, true, &mut io::stdout());
+        return Ok(apply::Summary::default());
+    }
+    let ctx = HookContext {
+        package,
+        prefix_path: &prefix_path,
+        target_path: &target_path,
+        manifest: &manifest,
+        actions: &actions,
+    };
+    hooks::run_hooks(args, &config, Phase::PreUnlink, &ctx, stderr)?;
+    let mut summary = apply::Summary::default();
+    let result = apply::unapply(args, &actions, &mut summary, stderr).and_then(|_| {
+        let mut state = State::load(args)?;
+        let tracked = state.created_dirs.len();
+        apply::remove_created_dirs(args, &actions, &mut state, &mut summary, stderr)?;
+        match state.created_dirs.len() == tracked || args.changes_nothing() {
+            true => Ok(()),
+            false => state.save(args),
+        }
+    });
+    apply::sync(args, &summary, stderr);
+    let error = result.as_ref().err().map(String::as_str);
+    apply::record(args, "unlink", package, &actions, started, &summary, error);
+    print_applied(args, package, &summary, started, stderr);
+    notification::webhook(
+        args,
+        &config,
+        "unlink",
+        package,
+        &summary,
+        result.as_ref().err().map(String::as_str),
+        stderr,
+    );
+    result?;
+    hooks::run_hooks(args, &config, Phase::PostUnlink, &ctx, stderr)?;
+    Ok(summary)
+}
+
+/// At '-vv', prints how many `actions` planning `package` from `started` came to, and how long
+/// it took; walking packages or checking targets on a slow network home shows up here.
+fn print_planned(
+    args: &Args,
+    package: &str,
+    actions: &[Action],
+    started: Instant,
+    stderr: &mut dyn io::Write,
+) {
+    if args.verbosity("ranch") >= LV_INFO {
+        _ = writeln!(
+            stderr,
+            "{}: planned {} actions in {:.2?}",
+            package,
+            actions.len(),
+            started.elapsed()
+        );
+    }
+}
+
+/// At '-vv', prints how much applying or unapplying `package` from `started` changed, and how
+/// long it took.
+fn print_applied(
+    args: &Args,
+    package: &str,
+    summary: &apply::Summary,
+    started: Instant,
+    stderr: &mut dyn io::Write,
+) {
+    if args.verbosity("ranch") >= LV_INFO {
+        _ = writeln!(
+            stderr,
+            "{}: {} created, {} removed, {} adopted, {} conflicting in {:.2?}",
+            package,
+            summary.created.len(),
+            summary.removed.len(),
+            summary.adopted.len(),
+            summary.conflicts.len(),
+            started.elapsed()
+        );
+    }
+}
+
+fn main() {
+    CompleteEnv::with_factory(Args::command).complete();
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    exec(&argv, &mut io::stderr());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{File,create_dir_all};
+    use std::path::Path;
+    use tempdir::TempDir;
+
+    fn make_dummy_fs(dir: &Path)
+    {
+        let dotfiles_home = dir.join(".dotfiles/home");
+        create_dir_all(&dotfiles_home).unwrap();
+
+        File::create(dotfiles_home.join(".vimrc")).unwrap();
+    }
+
+    #[test]
+    fn test_example()
+    {
+        println!("GIVEN");
+        let mut stderr = io::BufWriter::new(Vec::new());
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+
+        println!("WHEN");
+        exec(&[
+            "ranch",
+            "-vvv",
+            "-C",
+            tmp_dir.path().join(".dotfiles").to_str().unwrap(),
+            "home"
+        ].map(|s| s.to_owned()), &mut stderr);
+
+        println!("THEN");
+        let bytes = stderr.into_inner().unwrap();
+        let string = String::from_utf8(bytes).unwrap();
+        println!("{}", string);
+    }
+
+    #[test]
+    fn test_manifest_target_expands_env()
+    {
+        println!("GIVEN");
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        // Expansion itself is tested in 'paths'; an unset variable shows that it happens here,
+        // without changing the environment other tests read.
+        std::fs::write(
+            tmp_dir.path().join(".dotfiles/home").join(manifest::MANIFEST_NAME),
+            "target = \"$RANCH_TEST_UNSET_XDG_CONFIG_HOME/nvim\"",
+        ).unwrap();
+
+        println!("WHEN");
+        let dir = tmp_dir.path().join(".dotfiles");
+        let matches =
+            Args::command().get_matches_from(["ranch", "-C", dir.to_str().unwrap(), "home"]);
+        let result = run(&Args::from_matches(&matches).unwrap(), &mut io::sink());
+
+        println!("THEN");
+        assert!(result
+            .unwrap_err()
+            .contains("'RANCH_TEST_UNSET_XDG_CONFIG_HOME' is not set"));
+        assert!(!tmp_dir.path().join(".vimrc").exists());
+    }
+
+    #[test]
+    fn test_adopt_nested_file()
+    {
+        println!("GIVEN");
+        let mut stderr = io::BufWriter::new(Vec::new());
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        let repo_rc = tmp_dir.path().join(".dotfiles/home/.config/foo/rc");
+        create_dir_all(repo_rc.parent().unwrap()).unwrap();
+        std::fs::write(&repo_rc, "repo").unwrap();
+        let home_rc = tmp_dir.path().join(".config/foo/rc");
+        create_dir_all(home_rc.parent().unwrap()).unwrap();
+        std::fs::write(&home_rc, "local").unwrap();
+        let argv = [
+            "ranch",
+            "-e",
+            "adopt",
+            "--yes",
+            "-C",
+            tmp_dir.path().join(".dotfiles").to_str().unwrap(),
+            "home"
+        ].map(|s| s.to_owned());
+
+        println!("WHEN");
+        exec(&argv, &mut stderr);
+        exec(&argv, &mut stderr);
+
+        println!("THEN");
+        assert_eq!(std::fs::read_link(&home_rc).unwrap(), repo_rc);
+        assert_eq!(std::fs::read_to_string(&repo_rc).unwrap(), "local");
+        assert!(tmp_dir.path().join(".vimrc").is_symlink());
+    }
+
+    #[test]
+    fn test_adopt_relinks_identical_file()
+    {
+        println!("GIVEN");
+        let mut stderr = io::BufWriter::new(Vec::new());
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        let repo_rc = tmp_dir.path().join(".dotfiles/home/.zshrc");
+        std::fs::write(&repo_rc, "same").unwrap();
+        let home_rc = tmp_dir.path().join(".zshrc");
+        std::fs::write(&home_rc, "same").unwrap();
+
+        println!("WHEN");
+        exec(&[
+            "ranch",
+            "-vv",
+            "-e",
+            "adopt",
+            "-C",
+            tmp_dir.path().join(".dotfiles").to_str().unwrap(),
+            "home"
+        ].map(|s| s.to_owned()), &mut stderr);
+
+        println!("THEN");
+        assert_eq!(std::fs::read_link(&home_rc).unwrap(), repo_rc);
+        let string = String::from_utf8(stderr.into_inner().unwrap()).unwrap();
+        assert!(string.contains("is identical to"));
+        assert!(!string.contains("Adopting"));
+    }
+
+    #[test]
+    fn test_type_conflicts()
+    {
+        println!("GIVEN");
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        let dir = tmp_dir.path().join(".dotfiles");
+        create_dir_all(dir.join("home/.config")).unwrap();
+        File::create(dir.join("home/.config/app.toml")).unwrap();
+        create_dir_all(tmp_dir.path().join(".vimrc")).unwrap();
+        std::fs::write(tmp_dir.path().join(".config"), "file").unwrap();
+        let run = |policy: &str, stderr: &mut io::BufWriter<Vec<u8>>| exec(&[
+            "ranch",
+            "--type-conflict",
+            policy,
+            "-C",
+            dir.to_str().unwrap(),
+            "home"
+        ].map(|s| s.to_owned()), stderr);
+
+        println!("WHEN");
+        run("skip", &mut io::BufWriter::new(Vec::new()));
+
+        println!("THEN");
+        assert!(tmp_dir.path().join(".vimrc").is_dir());
+        assert!(tmp_dir.path().join(".config").is_file());
+
+        println!("WHEN");
+        run("backup", &mut io::BufWriter::new(Vec::new()));
+
+        println!("THEN");
+        assert!(tmp_dir.path().join(".vimrc").is_symlink());
+        assert!(tmp_dir.path().join(".vimrc.ranch-backup").is_dir());
+        assert!(tmp_dir.path().join(".config/app.toml").is_symlink());
+        assert!(tmp_dir.path().join(".config.ranch-backup").is_file());
+    }
+
+    #[test]
+    fn test_invalid_combinations()
+    {
+        let parse = |argv: &[&str]| {
+            Args::command()
+                .try_get_matches_from(argv)
+                .map_err(|e| e.to_string())
+                .and_then(|matches| Args::from_matches(&matches))
+        };
+        assert!(parse(&["ranch", "-C", ".", ""]).is_err());
+        assert!(parse(&["ranch", "-C", ".", "home", "-D", "work"]).is_err());
+        assert!(parse(&["ranch", "-C", ".", "-D", "work", "-p", "laptop"]).is_err());
+        let e = parse(&["ranch", "-C", ".", "home", "status"]).unwrap_err();
+        assert_eq!(e, "'PACKAGE' can't be combined with the 'status' subcommand");
+        assert!(parse(&["ranch", "-C", ".", "status", "home"]).is_ok());
+    }
+
+    #[test]
+    fn test_delete_multiple_packages()
+    {
+        println!("GIVEN");
+        let mut stderr = io::BufWriter::new(Vec::new());
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        let dir = tmp_dir.path().join(".dotfiles");
+        for package in ["work-git", "work-ssh"] {
+            create_dir_all(dir.join(package)).unwrap();
+            File::create(dir.join(package).join(format!(".{}", package))).unwrap();
+        }
+        for package in ["home", "work-git", "work-ssh"] {
+            exec(&[
+                "ranch",
+                "-C",
+                dir.to_str().unwrap(),
+                package
+            ].map(|s| s.to_owned()), &mut io::sink());
+        }
+
+        println!("WHEN");
+        exec(&[
+            "ranch",
+            "-vv",
+            "-C",
+            dir.to_str().unwrap(),
+            "-D",
+            "work-*",
+            "home"
+        ].map(|s| s.to_owned()), &mut stderr);
+
+        println!("THEN");
+        assert!(!tmp_dir.path().join(".vimrc").exists());
+        assert!(!tmp_dir.path().join(".work-git").exists());
+        assert!(!tmp_dir.path().join(".work-ssh").exists());
+        let string = String::from_utf8(stderr.into_inner().unwrap()).unwrap();
+        assert!(string.contains("Deleted 3 packages (work-git, work-ssh, home); 3 paths removed"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_relative_links_through_symlinked_dir()
+    {
+        println!("GIVEN");
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let home = tmp_dir.path().join("var/home");
+        make_dummy_fs(&home);
+        create_dir_all(home.join(".dotfiles/home/.config/nvim")).unwrap();
+        File::create(home.join(".dotfiles/home/.config/nvim/init.lua")).unwrap();
+        std::os::unix::fs::symlink(&home, tmp_dir.path().join("home")).unwrap();
+        let dir = tmp_dir.path().join("home/.dotfiles");
+
+        println!("WHEN");
+        exec(&[
+            "ranch",
+            "--relative",
+            "-C",
+            dir.to_str().unwrap(),
+            "home"
+        ].map(|s| s.to_owned()), &mut io::sink());
+
+        println!("THEN");
+        assert_eq!(
+            std::fs::read_link(home.join(".vimrc")).unwrap(),
+            Path::new(".dotfiles/home/.vimrc")
+        );
+        assert_eq!(
+            std::fs::read_link(home.join(".config/nvim/init.lua")).unwrap(),
+            Path::new("../../.dotfiles/home/.config/nvim/init.lua")
+        );
+        assert!(home.join(".config/nvim/init.lua").exists());
+    }
+
+    #[test]
+    fn test_sync()
+    {
+        println!("GIVEN");
+        let mut stderr = io::BufWriter::new(Vec::new());
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+
+        println!("WHEN");
+        exec(&[
+            "ranch",
+            "-vvv",
+            "--sync",
+            "-C",
+            tmp_dir.path().join(".dotfiles").to_str().unwrap(),
+            "home"
+        ].map(|s| s.to_owned()), &mut stderr);
+
+        println!("THEN");
+        let string = String::from_utf8(stderr.into_inner().unwrap()).unwrap();
+        assert!(string.contains(&format!("fsync {}\n", tmp_dir.path().display())));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_root_staging()
+    {
+        println!("GIVEN");
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let home = tmp_dir.path().canonicalize().unwrap().join("home");
+        make_dummy_fs(&home);
+        let root = tmp_dir.path().join("stage");
+        let staged = paths::reroot(
+            &root.canonicalize().unwrap_or(root.clone()),
+            &home.join(".vimrc"),
+        );
+        let dir = home.join(".dotfiles");
+
+        println!("WHEN");
+        exec(&[
+            "ranch",
+            "--root",
+            root.to_str().unwrap(),
+            "-C",
+            dir.to_str().unwrap(),
+            "home"
+        ].map(|s| s.to_owned()), &mut io::sink());
+
+        println!("THEN");
+        assert_eq!(std::fs::read_link(&staged).unwrap(), home.join(".dotfiles/home/.vimrc"));
+        assert!(!home.join(".vimrc").exists());
+
+        println!("WHEN");
+        exec(&[
+            "ranch",
+            "--root",
+            root.to_str().unwrap(),
+            "-C",
+            dir.to_str().unwrap(),
+            "-D",
+            "home"
+        ].map(|s| s.to_owned()), &mut io::sink());
+
+        println!("THEN");
+        assert!(!staged.is_symlink());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_unicode_paths()
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        println!("GIVEN");
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let home = tmp_dir.path().join(std::ffi::OsStr::from_bytes(b"caf\xe9"));
+        make_dummy_fs(&home);
+        let name = std::ffi::OsStr::from_bytes(b".r\xe9sum\xe9");
+        File::create(home.join(".dotfiles/home").join(name)).unwrap();
+        let argv = [
+            OsString::from("ranch"),
+            OsString::from("-C"),
+            home.join(".dotfiles").into_os_string(),
+            OsString::from("home"),
+        ];
+
+        println!("WHEN");
+        exec(&argv, &mut io::sink());
+
+        println!("THEN");
+        assert_eq!(
+            std::fs::read_link(home.join(name)).unwrap(),
+            home.join(".dotfiles/home").join(name)
+        );
+        assert!(home.join(".vimrc").is_symlink());
+    }
+
+    #[test]
+    fn test_hooks_and_delete()
+    {
+        println!("GIVEN");
+        let mut stderr = io::BufWriter::new(Vec::new());
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        let dir = tmp_dir.path().join(".dotfiles");
+        create_dir_all(dir.join("home/hooks")).unwrap();
+        std::fs::write(dir.join("home/hooks/post-link"), "#!/bin/sh\ntouch linked\n").unwrap();
+        #[cfg(unix)]
+        std::fs::set_permissions(
+            dir.join("home/hooks/post-link"),
+            std::os::unix::fs::PermissionsExt::from_mode(0o755),
+        ).unwrap();
+        std::fs::write(
+            dir.join("home").join(manifest::MANIFEST_NAME),
+            "[hooks]\npost-unlink = \"echo $RANCH_ACTION $RANCH_PACKAGE > unlinked; \
+             cat > plan.json\"",
+        ).unwrap();
+
+        println!("WHEN");
+        exec(&["ranch", "-C", dir.to_str().unwrap(), "home"].map(|s| s.to_owned()), &mut stderr);
+
+        println!("THEN");
+        assert!(tmp_dir.path().join(".vimrc").is_symlink());
+        assert!(!tmp_dir.path().join("hooks").exists());
+        #[cfg(unix)]
+        assert!(dir.join("home/linked").exists());
+
+        println!("WHEN");
+        exec(&[
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "-D",
+            "home"
+        ].map(|s| s.to_owned()), &mut stderr);
+
+        println!("THEN");
+        assert!(!tmp_dir.path().join(".vimrc").is_symlink());
+        assert!(dir.join("home/.vimrc").exists());
+        #[cfg(unix)]
+        assert_eq!(std::fs::read_to_string(dir.join("home/unlinked")).unwrap(), "unlink home\n");
+        #[cfg(unix)]
+        assert!(std::fs::read_to_string(dir.join("home/plan.json"))
+            .unwrap()
+            .contains("\"action\":\"link\""));
+    }
+
+    #[test]
+    fn test_restow_prunes_removed_files()
+    {
+        println!("GIVEN");
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        let dir = tmp_dir.path().join(".dotfiles");
+        File::create(dir.join("home/.zshrc")).unwrap();
+        File::create(tmp_dir.path().join("unrelated")).unwrap();
+        exec(&[
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "home"
+        ].map(|s| s.to_owned()), &mut io::sink());
+        std::fs::remove_file(dir.join("home/.zshrc")).unwrap();
+
+        println!("WHEN");
+        exec(&[
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "-R",
+            "home"
+        ].map(|s| s.to_owned()), &mut io::sink());
+
+        println!("THEN");
+        assert!(!tmp_dir.path().join(".zshrc").is_symlink());
+        assert!(tmp_dir.path().join(".vimrc").is_symlink());
+        assert!(tmp_dir.path().join("unrelated").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_once_scripts_run_once()
+    {
+        println!("GIVEN");
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        let dir = tmp_dir.path().join(".dotfiles");
+        let script = dir.join("home/run_once_install.sh");
+        std::fs::write(&script, "#!/bin/sh\necho ran >> \"$RANCH_TARGET/count\"\n").unwrap();
+        let mode = std::os::unix::fs::PermissionsExt::from_mode(0o755);
+        std::fs::set_permissions(&script, mode).unwrap();
+        let state = tmp_dir.path().join("state.json");
+        let argv = [
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "--state",
+            state.to_str().unwrap(),
+            "home",
+        ]
+        .map(|s| s.to_owned());
+
+        println!("WHEN");
+        exec(&argv, &mut io::sink());
+        exec(&argv, &mut io::sink());
+
+        println!("THEN");
+        assert_eq!(std::fs::read_to_string(tmp_dir.path().join("count")).unwrap(), "ran\n");
+        assert!(!tmp_dir.path().join("run_once_install.sh").exists());
+    }
+
+    #[test]
+    fn test_record_backend_changes_nothing()
+    {
+        println!("GIVEN");
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        let dir = tmp_dir.path().join(".dotfiles");
+
+        println!("WHEN");
+        exec(&[
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "--backend",
+            "record",
+            "home"
+        ].map(|s| s.to_owned()), &mut io::sink());
+
+        println!("THEN");
+        assert!(!tmp_dir.path().join(".vimrc").is_symlink());
+    }
+
+    #[test]
+    fn test_check_adopts_nothing() {
+        println!("GIVEN");
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        let dir = tmp_dir.path().join(".dotfiles");
+        std::fs::write(dir.join("home/.vimrc"), "set nu\n").unwrap();
+        std::fs::write(tmp_dir.path().join(".vimrc"), "set nu\nset et\n").unwrap();
+
+        println!("WHEN");
+        // Without a common base the merge conflicts, which fails the run; only what it changed
+        // matters here.
+        let matches = Args::command().get_matches_from([
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "--check",
+            "--exists",
+            "adopt",
+            "--merge",
+            "--yes",
+            "home",
+        ]);
+        _ = run(&Args::from_matches(&matches).unwrap(), &mut io::sink());
+
+        println!("THEN");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("home/.vimrc")).unwrap(),
+            "set nu\n"
+        );
+        assert!(!tmp_dir.path().join(".vimrc").is_symlink());
+    }
+
+    #[test]
+    fn test_report_records_the_run()
+    {
+        println!("GIVEN");
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        let dir = tmp_dir.path().join(".dotfiles");
+        let report = tmp_dir.path().join("report.json");
+
+        println!("WHEN");
+        exec(&[
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "--report",
+            report.to_str().unwrap(),
+            "home"
+        ].map(|s| s.to_owned()), &mut io::sink());
+
+        println!("THEN");
+        let report: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(report).unwrap()).unwrap();
+        assert_eq!(report["options"]["exists"], "stop");
+        assert_eq!(report["packages"][0]["package"], "home");
+        assert_eq!(report["packages"][0]["plan"][0]["action"], "link");
+        let created = report["packages"][0]["created"].as_array().unwrap();
+        let vimrc = tmp_dir.path().join(".vimrc");
+        assert!(created.contains(&vimrc.to_str().unwrap().into()));
+        assert!(report["error"].is_null());
+    }
+
+    #[test]
+    fn test_dry_run_sees_deployed_templates() {
+        println!("GIVEN");
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        let dir = tmp_dir.path().join(".dotfiles");
+        std::fs::write(dir.join("home/.gitconfig.tmpl"), "[user]\n").unwrap();
+        let args_for = |extra: &[&str]| {
+            let argv = ["ranch", "-C", dir.to_str().unwrap()].into_iter();
+            let argv = argv.chain(extra.iter().copied()).chain(["home"]);
+            Args::from_matches(&Args::command().get_matches_from(argv)).unwrap()
+        };
+        run(&args_for(&[]), &mut io::sink()).unwrap();
+
+        println!("WHEN");
+        let result = run(&args_for(&["--dry-run"]), &mut io::sink());
+
+        println!("THEN");
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn test_dry_run_renders_templates_only_for_diff() {
+        println!("GIVEN");
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        let dir = tmp_dir.path().join(".dotfiles");
+        let template = "{{ env \"RANCH_TEST_UNSET_VARIABLE\" }}\n";
+        std::fs::write(dir.join("home/.gitconfig.tmpl"), template).unwrap();
+        std::fs::write(tmp_dir.path().join(".gitconfig"), "[user]\n").unwrap();
+        let args_for = |extra: &[&str]| {
+            let argv = ["ranch", "-C", dir.to_str().unwrap()].into_iter();
+            let argv = argv.chain(extra.iter().copied()).chain(["home"]);
+            Args::from_matches(&Args::command().get_matches_from(argv)).unwrap()
+        };
+
+        println!("WHEN");
+        let dry_run = run(&args_for(&["--dry-run"]), &mut io::sink());
+        let diff = run(&args_for(&["--dry-run", "--diff"]), &mut io::sink());
+
+        println!("THEN");
+        assert_eq!(dry_run, Ok(()));
+        assert!(diff.unwrap_err().contains("RANCH_TEST_UNSET_VARIABLE"));
+    }
+
+    #[test]
+    fn test_check_reports_changes_without_making_them()
+    {
+        println!("GIVEN");
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        let dir = tmp_dir.path().join(".dotfiles");
+        let matches = Args::command().get_matches_from([
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "--check",
+            "--format",
+            "ansible",
+            "home"
+        ]);
+        let args = Args::from_matches(&matches).unwrap();
+
+        println!("WHEN");
+        run(&args, &mut io::sink()).unwrap();
+
+        println!("THEN");
+        assert!(!tmp_dir.path().join(".vimrc").is_symlink());
+        let outcomes = args.outcomes.lock().unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert!(outcomes[0].summary.created.contains(&tmp_dir.path().join(".vimrc")));
+    }
+
+    #[test]
+    fn test_very_verbose_times_each_package()
+    {
+        println!("GIVEN");
+        let tmp_dir = TempDir::new("alice").unwrap();
+        make_dummy_fs(tmp_dir.path());
+        let dir = tmp_dir.path().join(".dotfiles");
+        let matches = Args::command().get_matches_from([
+            "ranch",
+            "-vv",
+            "-C",
+            dir.to_str().unwrap(),
+            "home"
+        ]);
+        let args = Args::from_matches(&matches).unwrap();
+        let mut stderr = Vec::new();
+
+        println!("WHEN");
+        run(&args, &mut stderr).unwrap();
+
+        println!("THEN");
+        let stderr = String::from_utf8(stderr).unwrap();
+        let has_line = |prefix: &str, middle: &str| {
+            stderr.lines().any(|line| line.starts_with(prefix) && line.contains(middle))
+        };
+        assert!(has_line("home: planned ", " actions in "));
+        assert!(has_line("home: ", " created, 0 removed, 0 adopted, 0 conflicting in "));
+        assert!(args.outcomes.lock().unwrap().is_empty());
     }
 }
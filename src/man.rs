@@ -0,0 +1,42 @@
+use crate::i18n::tr;
+use crate::{Args, LV_INFO};
+use clap::CommandFactory;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Writes roff man pages for ranch and each of its subcommands into `dir`, as 'ranch.1',
+/// 'ranch-status.1', and so on, for packagers to install into 'man1'.
+pub fn generate(args: &Args, dir: &Path, stderr: &mut dyn io::Write) -> Result<(), String> {
+    if args.verbosity("man") >= LV_INFO {
+        _ = writeln!(stderr, "Writing man pages to {}", dir.display());
+    }
+    if args.changes_nothing() {
+        return Ok(());
+    }
+    fs::create_dir_all(dir)
+        .map_err(|e| tr!("could-not-create-dir", path = dir.display(), error = e))?;
+    clap_mangen::generate_to(Args::command(), dir)
+        .map_err(|e| tr!("could-not-write-man-pages", path = dir.display(), error = e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_generate() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join("man1");
+        let args = Args::parse_from(["ranch", "man", dir.to_str().unwrap()]);
+
+        generate(&args, &dir, &mut io::sink()).unwrap();
+
+        let page = fs::read_to_string(dir.join("ranch.1")).unwrap();
+        assert!(page.starts_with(".ie"));
+        assert!(page.contains("stow"));
+        assert!(dir.join("ranch-status.1").exists());
+    }
+}
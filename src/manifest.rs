@@ -0,0 +1,70 @@
+use crate::i18n::tr;
+use crate::signature;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Name of the optional per-package manifest; it lives in the package root and is never linked.
+pub const MANIFEST_NAME: &str = ".ranch.toml";
+
+/// Whether `path`, relative to a package's root, is its manifest, its signed checksums, or a
+/// detached signature of either; none of these are linked.
+pub fn is_manifest(path: &Path) -> bool {
+    path.to_str().is_some_and(|p| {
+        signature::is_checksums(p)
+            || p.strip_prefix(MANIFEST_NAME)
+                .is_some_and(|rest| ["", ".minisig", ".sig"].contains(&rest))
+    })
+}
+
+/// Optional per-package settings read from 'DIR/PACKAGE/.ranch.toml'.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Where this package deploys to when '--target' isn't given; environment variables are
+    /// expanded, and relative paths are resolved against the default target 'DIR/..'.
+    pub target: Option<String>,
+
+    /// Create the package's empty directories at the target, as with '--create-empty-dirs'.
+    #[serde(default)]
+    pub create_empty_dirs: bool,
+
+    /// Shell commands run around deploying and deleting the package.
+    #[serde(default)]
+    pub hooks: Hooks,
+
+    /// What the package is for, shown by 'ranch show'.
+    pub description: Option<String>,
+
+    /// Where the configured program lives, shown by 'ranch show'.
+    pub homepage: Option<String>,
+
+    /// Operating systems the package is meant for, as named by Rust's `std::env::consts::OS`
+    /// ('linux', 'macos', 'windows', ...); empty means any.
+    #[serde(default)]
+    pub platforms: Vec<String>,
+}
+
+/// Shell commands from a manifest's '[hooks]' table; each runs after the package's executable
+/// hook of the same name in 'hooks/', if any.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Hooks {
+    pub pre_link: Option<String>,
+    pub post_link: Option<String>,
+    pub pre_unlink: Option<String>,
+    pub post_unlink: Option<String>,
+}
+
+impl Manifest {
+    /// Reads the manifest of the package rooted at `package_dir`, or returns the default manifest
+    /// if the package doesn't have one.
+    pub fn load(package_dir: &Path) -> Result<Manifest, String> {
+        let path = package_dir.join(MANIFEST_NAME);
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| tr!("could-not-read", path = path.display(), error = e))?;
+        toml::from_str(&text).map_err(|e| tr!("invalid-manifest", path = path.display(), error = e))
+    }
+}
@@ -0,0 +1,230 @@
+use crate::diff::{self, Op};
+use crate::i18n::tr;
+use crate::{git, paths, Args, LV_INFO};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const OURS_MARKER: &str = "<<<<<<< package";
+const BASE_MARKER: &str = "||||||| committed";
+const SEPARATOR: &str = "=======";
+const THEIRS_MARKER: &str = ">>>>>>> target";
+
+/// Result of merging two versions of a file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Merged {
+    pub text: String,
+    /// Whether some changes overlapped and were left between conflict markers.
+    pub conflicts: bool,
+}
+
+/// Adopts the target file `dst` into the package file `src` by merging the two, with the last
+/// committed version of `src` as their common ancestor, rather than overwriting `src`. With no
+/// committed version, every difference is a conflict. Conflicts go to '$MERGETOOL' if it's set;
+/// otherwise, or if they're left unresolved, `src` is written with conflict markers and an error
+/// is returned, leaving `dst` in place.
+pub fn adopt(
+    args: &Args,
+    src: &Path,
+    dst: &Path,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let read = |path: &Path| {
+        fs::read_to_string(path)
+            .map_err(|e| tr!("could-not-merge", path = path.display(), error = e))
+    };
+    let (ours, theirs) = (read(src)?, read(dst)?);
+    let base = git::committed(src).and_then(|b| String::from_utf8(b).ok());
+    let mut merged = merge(base.as_deref(), &ours, &theirs);
+    // A merge tool can't be run without changing anything.
+    if merged.conflicts && !args.changes_nothing() {
+        if let Some(tool) = env::var("MERGETOOL").ok().filter(|t| !t.trim().is_empty()) {
+            if args.verbosity("merge") >= LV_INFO {
+                _ = writeln!(stderr, "Running {} on {}", tool, src.display());
+            }
+            let files = [
+                ("LOCAL", ours.as_str()),
+                ("BASE", base.as_deref().unwrap_or_default()),
+                ("REMOTE", theirs.as_str()),
+                ("MERGED", merged.text.as_str()),
+            ];
+            let resolved = run_tool(&tool, src, &files)?;
+            if !resolved.conflicts {
+                merged = resolved;
+            }
+        }
+    }
+    args.fs
+        .write(src, merged.text.as_bytes())
+        .map_err(|e| tr!("could-not-write", path = src.display(), error = e))?;
+    if merged.conflicts {
+        return Err(tr!(
+            "merge-conflicts",
+            package_file = src.display(),
+            path = dst.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Merges the changes `ours` and `theirs` each made to `base`. Without a `base`, only the lines
+/// they have in common are kept without conflict.
+pub fn merge(base: Option<&str>, ours: &str, theirs: &str) -> Merged {
+    let ours_lines: Vec<&str> = ours.lines().collect();
+    let theirs_lines: Vec<&str> = theirs.lines().collect();
+    let base_lines: Vec<&str> = match base {
+        Some(base) => base.lines().collect(),
+        None => diff::diff_lines(&ours_lines, &theirs_lines)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|op| match op {
+                Op::Equal(line) => Some(line),
+                _ => None,
+            })
+            .collect(),
+    };
+    let (Some(ours_at), Some(theirs_at)) = (
+        matches(&base_lines, &ours_lines),
+        matches(&base_lines, &theirs_lines),
+    ) else {
+        // Too large to compare; keep both sides whole.
+        return Merged {
+            text: conflict(&[], &ours_lines, &theirs_lines, base.is_some()),
+            conflicts: true,
+        };
+    };
+
+    let mut text = String::new();
+    let mut conflicts = false;
+    let (mut b, mut o, mut t) = (0, 0, 0);
+    loop {
+        // The next base line that both sides kept unchanged ends the current chunk.
+        let stable = (b..base_lines.len()).find_map(|i| Some((i, ours_at[i]?, theirs_at[i]?)));
+        let (bs, os, ts) =
+            stable.unwrap_or((base_lines.len(), ours_lines.len(), theirs_lines.len()));
+        let (base_chunk, ours_chunk, theirs_chunk) =
+            (&base_lines[b..bs], &ours_lines[o..os], &theirs_lines[t..ts]);
+        if ours_chunk == theirs_chunk || (base.is_some() && theirs_chunk == base_chunk) {
+            push_lines(&mut text, ours_chunk);
+        } else if base.is_some() && ours_chunk == base_chunk {
+            push_lines(&mut text, theirs_chunk);
+        } else {
+            text += &conflict(base_chunk, ours_chunk, theirs_chunk, base.is_some());
+            conflicts = true;
+        }
+        let Some((bs, os, ts)) = stable else {
+            break;
+        };
+        push_lines(&mut text, &[base_lines[bs]]);
+        (b, o, t) = (bs + 1, os + 1, ts + 1);
+    }
+    Merged { text, conflicts }
+}
+
+/// For each line of `base`, the index of the line it's kept as in `other`, if it's kept.
+fn matches(base: &[&str], other: &[&str]) -> Option<Vec<Option<usize>>> {
+    let mut at = vec![None; base.len()];
+    let (mut b, mut o) = (0, 0);
+    for op in diff::diff_lines(base, other)? {
+        match op {
+            Op::Equal(_) => {
+                at[b] = Some(o);
+                (b, o) = (b + 1, o + 1);
+            }
+            Op::Delete(_) => b += 1,
+            Op::Insert(_) => o += 1,
+        }
+    }
+    Some(at)
+}
+
+fn push_lines(text: &mut String, lines: &[&str]) {
+    for line in lines {
+        *text += line;
+        text.push('\n');
+    }
+}
+
+fn conflict(base: &[&str], ours: &[&str], theirs: &[&str], with_base: bool) -> String {
+    let mut text = format!("{}\n", OURS_MARKER);
+    push_lines(&mut text, ours);
+    if with_base {
+        text += BASE_MARKER;
+        text.push('\n');
+        push_lines(&mut text, base);
+    }
+    text += SEPARATOR;
+    text.push('\n');
+    push_lines(&mut text, theirs);
+    text + THEIRS_MARKER + "\n"
+}
+
+/// Runs the merge tool on copies of the versions of `src`, as '$MERGETOOL LOCAL BASE REMOTE
+/// MERGED' with the same paths in the environment under those names. Its result only counts if
+/// it exits successfully without leaving conflict markers.
+fn run_tool(tool: &str, src: &Path, files: &[(&str, &str)]) -> Result<Merged, String> {
+    let temp = paths::private_temp_dir("ranch-merge-")?;
+    let dir = temp.path();
+    let name = src.file_name().unwrap_or_default().to_string_lossy();
+    let mut paths: Vec<(&str, PathBuf)> = Vec::new();
+    for (role, contents) in files {
+        let path = dir.join(format!("{}.{}", name, role));
+        fs::write(&path, contents)
+            .map_err(|e| tr!("could-not-write", path = path.display(), error = e))?;
+        paths.push((role, path));
+    }
+    let mut words = tool.split_whitespace();
+    let mut cmd = Command::new(words.next().unwrap_or_default());
+    cmd.args(words).args(paths.iter().map(|(_, path)| path));
+    for (role, path) in &paths {
+        cmd.env(role, path);
+    }
+    let status = cmd.status();
+    let merged_path = &paths[paths.len() - 1].1;
+    let text = fs::read_to_string(merged_path);
+    drop(temp);
+    let status = status.map_err(|e| tr!("could-not-run", program = tool, error = e))?;
+    let text = text.map_err(|e| tr!("could-not-read", path = merged_path.display(), error = e))?;
+    let conflicts = !status.success()
+        || text
+            .lines()
+            .any(|l| l.starts_with(OURS_MARKER) || l.starts_with(THEIRS_MARKER));
+    Ok(Merged { text, conflicts })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let base = "a\nb\nc\nd\ne\n";
+        let ours = "a\nB\nc\nd\ne\n";
+        let theirs = "a\nb\nc\nd\nE\n";
+        assert_eq!(
+            merge(Some(base), ours, theirs),
+            Merged {
+                text: String::from("a\nB\nc\nd\nE\n"),
+                conflicts: false
+            }
+        );
+        assert_eq!(merge(Some(base), base, theirs).text, theirs);
+
+        let merged = merge(Some(base), ours, "a\nX\nc\nd\ne\n");
+        assert!(merged.conflicts);
+        assert_eq!(
+            merged.text,
+            "a\n<<<<<<< package\nB\n||||||| committed\nb\n=======\nX\n>>>>>>> target\nc\nd\ne\n"
+        );
+
+        let merged = merge(None, "a\nb\n", "a\nc\n");
+        assert!(merged.conflicts);
+        assert_eq!(
+            merged.text,
+            "a\n<<<<<<< package\nb\n=======\nc\n>>>>>>> target\n"
+        );
+        assert!(!merge(None, "a\n", "a\n").conflicts);
+    }
+}
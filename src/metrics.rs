@@ -0,0 +1,128 @@
+use crate::status::PackageStatus;
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Counters the daemon keeps for its '/metrics' endpoint.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// When an apply last finished, successfully or not.
+    pub last_apply: Option<SystemTime>,
+    pub last_successful_apply: Option<SystemTime>,
+    /// Failed applies and failed attempts to mirror a changed file, since the daemon started.
+    pub errors: u64,
+}
+
+impl Metrics {
+    /// Records the outcome of an apply.
+    pub fn applied<T, E>(&mut self, result: &Result<T, E>) {
+        let now = SystemTime::now();
+        self.last_apply = Some(now);
+        match result {
+            Ok(_) => self.last_successful_apply = Some(now),
+            Err(_) => self.errors += 1,
+        }
+    }
+
+    /// Renders these metrics and the drift of `statuses` in the Prometheus text format.
+    pub fn render(&self, statuses: &[PackageStatus]) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, f64)]| {
+            _ = writeln!(out, "# HELP {} {}", name, help);
+            _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples {
+                _ = writeln!(out, "{}{} {}", name, labels, value);
+            }
+        };
+        let per_package = |value: &dyn Fn(&PackageStatus) -> usize| -> Vec<(String, f64)> {
+            statuses
+                .iter()
+                .map(|s| {
+                    (
+                        format!("{{package=\"{}\"}}", escape(&s.package)),
+                        value(s) as f64,
+                    )
+                })
+                .collect()
+        };
+        metric(
+            "ranch_managed_files",
+            "gauge",
+            "Files each package deploys.",
+            &per_package(&|s| s.deployed + s.missing.len() + s.conflicts.len()),
+        );
+        metric(
+            "ranch_deployed_files",
+            "gauge",
+            "Files of each package that are deployed as planned.",
+            &per_package(&|s| s.deployed),
+        );
+        metric(
+            "ranch_drifted_files",
+            "gauge",
+            "Files of each package that are missing from or replaced in the target.",
+            &per_package(&|s| s.missing.len() + s.conflicts.len()),
+        );
+        let timestamp = |t: Option<SystemTime>| {
+            t.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| vec![(String::new(), d.as_secs_f64())])
+                .unwrap_or_default()
+        };
+        metric(
+            "ranch_last_apply_timestamp_seconds",
+            "gauge",
+            "When the last apply finished.",
+            &timestamp(self.last_apply),
+        );
+        metric(
+            "ranch_last_successful_apply_timestamp_seconds",
+            "gauge",
+            "When the last successful apply finished.",
+            &timestamp(self.last_successful_apply),
+        );
+        metric(
+            "ranch_errors_total",
+            "counter",
+            "Failed applies and file syncs.",
+            &[(String::new(), self.errors as f64)],
+        );
+        out
+    }
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    #[test]
+    fn test_render() {
+        let mut metrics = Metrics::default();
+        metrics.applied::<(), ()>(&Err(()));
+        metrics.last_apply = Some(UNIX_EPOCH + Duration::from_secs(1700000000));
+        let status = PackageStatus {
+            package: String::from("ho\"me"),
+            deployed: 2,
+            missing: vec![PathBuf::from("a")],
+            conflicts: Vec::new(),
+        };
+
+        let text = metrics.render(&[status]);
+
+        assert!(text.contains("ranch_managed_files{package=\"ho\\\"me\"} 3\n"));
+        assert!(text.contains("ranch_drifted_files{package=\"ho\\\"me\"} 1\n"));
+        assert!(text.contains("ranch_last_apply_timestamp_seconds 1700000000\n"));
+        assert!(text.contains(
+            "# TYPE ranch_last_successful_apply_timestamp_seconds gauge\n# HELP ranch_errors_total"
+        ));
+        assert!(text.contains("ranch_errors_total 1\n"));
+    }
+}
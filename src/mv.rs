@@ -0,0 +1,171 @@
+use crate::apply::{self, Summary};
+use crate::config::Config;
+use crate::filesystem::Kind;
+use crate::i18n::tr;
+use crate::plan::{self, Action};
+use crate::purge;
+use crate::state::State;
+use crate::{load_package, Args, LV_INFO};
+use std::io;
+use std::path::{Component, Path};
+
+/// Moves the package file `from` to `to`, both relative to 'DIR', such as
+/// 'ranch mv zsh/aliases.zsh shell-common/'. A `to` that ends in '/' or names a directory keeps
+/// the file's name. If the file was deployed, its link is replaced by one to the new location,
+/// and if that can't be done the file is moved back and relinked, so the move either happens
+/// entirely or not at all.
+pub fn mv(args: &Args, from: &Path, to: &Path, stderr: &mut dyn io::Write) -> Result<(), String> {
+    let src = args.dir.join(from);
+    let from_package = package_of(from)?;
+    if args.fs.target_kind(&src) != Some(Kind::File) {
+        return Err(tr!("not-a-package-file", path = src.display()));
+    }
+    let mut dst = args.dir.join(to);
+    if args.fs.is_dir(&dst) || to.as_os_str().to_string_lossy().ends_with('/') {
+        dst = dst.join(src.file_name().unwrap_or_default());
+    }
+    let to_package = package_of(dst.strip_prefix(&args.dir).unwrap_or(to))?;
+    if args.fs.kind(&dst).is_some() {
+        return Err(tr!("destination-exists", path = dst.display()));
+    }
+    load_package(args, &to_package)?;
+
+    let old = deployed_link(args, &from_package, &src, stderr)?;
+    if args.verbosity("mv") >= LV_INFO || args.dry_run {
+        _ = writeln!(stderr, "Moving {} to {}", src.display(), dst.display());
+    }
+    if args.dry_run {
+        return Ok(());
+    }
+
+    let config = Config::load()?;
+    let mut summary = Summary::default();
+    apply::unapply(args, &old, &mut summary, stderr)?;
+    let moved = dst
+        .parent()
+        .map_or(Ok(()), |dir| args.fs.create_dir_all(dir))
+        .and_then(|_| args.fs.rename(&src, &dst))
+        .map_err(|e| {
+            tr!(
+                "could-not-move",
+                path = src.display(),
+                destination = dst.display(),
+                error = e
+            )
+        });
+    let result = moved.clone().and_then(|_| match old.is_empty() {
+        true => Ok(()),
+        false => {
+            let new = link_for(args, &to_package, &dst, stderr)?;
+            apply::apply(args, &config, &new, &mut summary, stderr)
+        }
+    });
+    if let Err(e) = result {
+        // Put everything back the way it was.
+        if moved.is_ok() {
+            _ = args.fs.rename(&dst, &src);
+        }
+        _ = apply::apply(args, &config, &old, &mut Summary::default(), stderr);
+        return Err(e);
+    }
+
+    if !summary.created_dirs.is_empty() && !args.changes_nothing() {
+        let mut state = State::load(args)?;
+        state
+            .created_dirs
+            .extend(summary.created_dirs.iter().cloned());
+        state.save(args)?;
+    }
+    purge::remove_created_dirs(args, &mut summary, stderr)
+}
+
+/// The package holding `path`, relative to 'DIR': its first component.
+fn package_of(path: &Path) -> Result<String, String> {
+    match path.components().next() {
+        Some(Component::Normal(package)) if path.components().count() > 1 => {
+            Ok(package.to_string_lossy().into_owned())
+        }
+        _ => Err(tr!("not-in-a-package", path = path.display())),
+    }
+}
+
+/// The link action `package` plans for its file `src`, if it's a link that's deployed.
+fn deployed_link(
+    args: &Args,
+    package: &str,
+    src: &Path,
+    stderr: &mut dyn io::Write,
+) -> Result<Vec<Action>, String> {
+    Ok(link_for(args, package, src, stderr)?
+        .into_iter()
+        .filter(|a| apply::is_linked(args, a.src(), a.dst()))
+        .collect())
+}
+
+/// The link action `package` plans for its file `src`, if it plans one at all.
+fn link_for(
+    args: &Args,
+    package: &str,
+    src: &Path,
+    stderr: &mut dyn io::Write,
+) -> Result<Vec<Action>, String> {
+    let (prefix_path, _, target_path) = load_package(args, package)?;
+    Ok(
+        plan::plan(args, package, &prefix_path, &target_path, stderr)?
+            .into_iter()
+            .filter(|a| matches!(a, Action::Link { src: s, .. } if s == src))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mv() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("zsh")).unwrap();
+        fs::create_dir_all(dir.join("shell")).unwrap();
+        fs::write(dir.join("zsh/.aliases"), "alias ll='ls -l'").unwrap();
+        let link = tmp_dir.path().join(".aliases");
+        std::os::unix::fs::symlink(dir.join("zsh/.aliases"), &link).unwrap();
+        let args = |to: &str| {
+            Args::parse_from([
+                "ranch",
+                "-C",
+                dir.to_str().unwrap(),
+                "--link-mode",
+                "symlink",
+                "mv",
+                "zsh/.aliases",
+                to,
+            ])
+        };
+
+        let e = mv(
+            &args("nope/"),
+            Path::new("zsh/.aliases"),
+            Path::new("nope/"),
+            &mut io::sink(),
+        );
+        assert!(e.unwrap_err().contains("nope does not exist"));
+
+        mv(
+            &args("shell/"),
+            Path::new("zsh/.aliases"),
+            Path::new("shell/"),
+            &mut io::sink(),
+        )
+        .unwrap();
+
+        assert!(!dir.join("zsh/.aliases").exists());
+        assert_eq!(fs::read_link(&link).unwrap(), dir.join("shell/.aliases"));
+        assert_eq!(fs::read_to_string(&link).unwrap(), "alias ll='ls -l'");
+    }
+}
@@ -0,0 +1,261 @@
+use crate::apply::Summary;
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::status::PackageStatus;
+use crate::{Args, LV_DEBUG, LV_WARN};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::env;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Most drifted paths listed in one notification.
+const MAX_LISTED: usize = 3;
+
+/// Tells the user when unattended work (the daemon, 'ranch watch') fails or finds drift, which
+/// would otherwise only show up in a log nobody reads.
+pub struct Notifier {
+    verbose: u8,
+    desktop: bool,
+    /// Paths that were drifted as of the last check, so each drift is only reported once.
+    drifted: BTreeSet<PathBuf>,
+}
+
+impl Notifier {
+    pub fn new(args: &Args, config: &Config) -> Notifier {
+        Notifier {
+            verbose: args.verbosity("notification"),
+            desktop: config.notify.desktop,
+            drifted: BTreeSet::new(),
+        }
+    }
+
+    /// Reports a failed apply or sync.
+    pub fn failed(&self, error: &str, stderr: &mut dyn io::Write) {
+        self.send("ranch: apply failed", error, stderr);
+    }
+
+    /// Reports target paths that have drifted from their plan since the last check.
+    pub fn drift(&mut self, statuses: &[PackageStatus], stderr: &mut dyn io::Write) {
+        let new = self.newly_drifted(statuses);
+        if new.is_empty() {
+            return;
+        }
+        let mut body: Vec<String> = new
+            .iter()
+            .take(MAX_LISTED)
+            .map(|p| p.display().to_string())
+            .collect();
+        if new.len() > MAX_LISTED {
+            body.push(tr!("drifted-more", count = new.len() - MAX_LISTED));
+        }
+        let summary = tr!("drifted", count = new.len());
+        self.send(&summary, &body.join("\n"), stderr);
+    }
+
+    fn newly_drifted(&mut self, statuses: &[PackageStatus]) -> Vec<PathBuf> {
+        let drifted: BTreeSet<PathBuf> = statuses
+            .iter()
+            .flat_map(|s| s.missing.iter().chain(&s.conflicts))
+            .cloned()
+            .collect();
+        let new = drifted.difference(&self.drifted).cloned().collect();
+        self.drifted = drifted;
+        new
+    }
+
+    fn send(&self, summary: &str, body: &str, stderr: &mut dyn io::Write) {
+        if !self.desktop {
+            return;
+        }
+        if let Err(e) = desktop(summary, body) {
+            if self.verbose >= LV_WARN {
+                _ = writeln!(stderr, "{}", tr!("could-not-notify", error = e));
+            }
+        }
+    }
+}
+
+/// Body of a webhook request.
+#[derive(Serialize, Debug)]
+struct Payload<'a> {
+    host: String,
+    /// "link" or "unlink".
+    action: &'a str,
+    packages: Vec<PackageReport<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+struct PackageReport<'a> {
+    package: &'a str,
+    #[serde(flatten)]
+    summary: &'a Summary,
+    /// Why the operation stopped early, if it did.
+    error: Option<&'a str>,
+}
+
+/// Posts the outcome of linking or unlinking `package` to the config's webhook, if there is one.
+/// A webhook that can't be reached is only a warning; the files are already in place.
+pub fn webhook(
+    args: &Args,
+    config: &Config,
+    action: &str,
+    package: &str,
+    summary: &Summary,
+    error: Option<&str>,
+    stderr: &mut dyn io::Write,
+) {
+    let Some(url) = &config.notify.webhook else {
+        return;
+    };
+    if args.changes_nothing() {
+        return;
+    }
+    let payload = Payload {
+        host: hostname(),
+        action,
+        packages: vec![PackageReport {
+            package,
+            summary,
+            error,
+        }],
+    };
+    if args.verbosity("notification") >= LV_DEBUG {
+        _ = writeln!(stderr, "Posting to {}", url);
+    }
+    if let Err(e) = post(url, &serde_json::to_vec(&payload).unwrap_or_default()) {
+        if args.verbosity("notification") >= LV_WARN {
+            _ = writeln!(stderr, "{}", tr!("could-not-post", url = url, error = e));
+        }
+    }
+}
+
+/// POSTs a JSON `body` to `url` with curl.
+fn post(url: &str, body: &[u8]) -> Result<(), String> {
+    let mut child = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--max-time",
+            "10",
+            "--header",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+            "--",
+            url,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| tr!("could-not-run", program = "curl", error = e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(body)
+            .map_err(|e| tr!("could-not-write-to", program = "curl", error = e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| tr!("could-not-run", program = "curl", error = e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_owned());
+    }
+    Ok(())
+}
+
+/// Name of this machine, for telling reports from many machines apart.
+pub fn hostname() -> String {
+    #[cfg(unix)]
+    {
+        let mut buf = [0u8; 256];
+        // SAFETY: the buffer outlives the call, and its last byte is never written, so the name
+        // is always NUL-terminated.
+        if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len() - 1) } == 0 {
+            let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+            return String::from_utf8_lossy(&buf[..len]).into_owned();
+        }
+    }
+    env::var("COMPUTERNAME")
+        .or_else(|_| env::var("HOSTNAME"))
+        .unwrap_or_default()
+}
+
+/// Shows a desktop notification: through D-Bus on Linux and the BSDs, Notification Center on
+/// macOS, and toasts on Windows.
+fn desktop(summary: &str, body: &str) -> Result<(), String> {
+    notify_rust::Notification::new()
+        .appname("ranch")
+        .summary(summary)
+        .body(body)
+        .show()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_newly_drifted() {
+        let args = Args::parse_from(["ranch", "status"]);
+        let mut notifier = Notifier::new(&args, &Config::default());
+        let status = |missing: &[&str]| PackageStatus {
+            package: String::from("home"),
+            missing: missing.iter().map(PathBuf::from).collect(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            notifier.newly_drifted(&[status(&["a"])]),
+            [PathBuf::from("a")]
+        );
+        assert!(notifier.newly_drifted(&[status(&["a"])]).is_empty());
+        assert_eq!(
+            notifier.newly_drifted(&[status(&["a", "b"])]),
+            [PathBuf::from("b")]
+        );
+        assert!(notifier.newly_drifted(&[status(&[])]).is_empty());
+        assert_eq!(
+            notifier.newly_drifted(&[status(&["a"])]),
+            [PathBuf::from("a")]
+        );
+    }
+
+    #[test]
+    fn test_payload() {
+        let summary = Summary {
+            created: vec![PathBuf::from("/home/alice/.vimrc")],
+            conflicts: vec![PathBuf::from("/home/alice/.zshrc")],
+            ..Default::default()
+        };
+        let payload = Payload {
+            host: hostname(),
+            action: "link",
+            packages: vec![PackageReport {
+                package: "home",
+                summary: &summary,
+                error: Some("stopped"),
+            }],
+        };
+
+        let json = serde_json::to_value(&payload).unwrap();
+
+        assert!(!json["host"].as_str().unwrap().is_empty());
+        assert_eq!(
+            json["packages"][0],
+            serde_json::json!({
+                "package": "home",
+                "created": ["/home/alice/.vimrc"],
+                "removed": [],
+                "conflicts": ["/home/alice/.zshrc"],
+                "adopted": [],
+                "error": "stopped",
+            })
+        );
+    }
+}
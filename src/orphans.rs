@@ -0,0 +1,76 @@
+use crate::apply::Summary;
+use crate::i18n::tr;
+use crate::purge;
+use crate::{Args, LV_INFO};
+use std::io;
+
+/// Lists the links into 'DIR' whose package file no longer exists, as 'LINK -> SOURCE' lines on
+/// `stdout`; with `remove`, deletes them as well.
+pub fn orphans(
+    args: &Args,
+    remove: bool,
+    stdout: &mut dyn io::Write,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let mut summary = Summary::default();
+    for (dst, src) in purge::links(args, stderr)? {
+        if args.fs.kind(&src).is_some() {
+            continue;
+        }
+        _ = writeln!(stdout, "{} -> {}", dst.display(), src.display());
+        if !remove {
+            continue;
+        }
+        if args.verbosity("orphans") >= LV_INFO {
+            _ = writeln!(stderr, "Unlinking {}", dst.display());
+        }
+        if !args.dry_run {
+            args.fs
+                .remove_file(&dst)
+                .map_err(|e| tr!("could-not-remove", path = dst.display(), error = e))?;
+            summary.removed.push(dst);
+        }
+    }
+    purge::remove_created_dirs(args, &mut summary, stderr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_orphans() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("home")).unwrap();
+        fs::write(dir.join("home/.vimrc"), "").unwrap();
+        let link = |src: &str, dst: &str| {
+            std::os::unix::fs::symlink(dir.join(src), tmp_dir.path().join(dst)).unwrap()
+        };
+        link("home/.vimrc", ".vimrc");
+        link("home/.zshrc", ".zshrc");
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "orphans"]);
+        let mut stdout = Vec::new();
+
+        orphans(&args, false, &mut stdout, &mut io::sink()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            format!(
+                "{} -> {}\n",
+                tmp_dir.path().join(".zshrc").display(),
+                dir.join("home/.zshrc").display()
+            )
+        );
+        assert!(tmp_dir.path().join(".zshrc").is_symlink());
+
+        orphans(&args, true, &mut Vec::new(), &mut io::sink()).unwrap();
+
+        assert!(!tmp_dir.path().join(".zshrc").is_symlink());
+        assert!(tmp_dir.path().join(".vimrc").is_symlink());
+    }
+}
@@ -0,0 +1,97 @@
+use crate::Args;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Child, Command, Stdio};
+
+/// Where long reports, such as 'ranch status', are written: through a pager when stdout is a
+/// terminal, as git does, or else straight to stdout. Output that fits on one screen isn't
+/// paged, since 'less' is told to quit right away.
+pub struct Pager {
+    child: Option<Child>,
+    stdout: io::Stdout,
+}
+
+impl Pager {
+    /// Starts '$RANCH_PAGER', '$PAGER', or else 'less', unless '--no-pager' was given or stdout
+    /// isn't a terminal. Setting the pager to '' or 'cat' turns paging off.
+    pub fn start(args: &Args) -> Pager {
+        let stdout = io::stdout();
+        let child = match args.no_pager || !stdout.is_terminal() {
+            true => None,
+            false => spawn(&pager_command()),
+        };
+        Pager { child, stdout }
+    }
+}
+
+/// The configured pager command line.
+fn pager_command() -> String {
+    env::var("RANCH_PAGER")
+        .or_else(|_| env::var("PAGER"))
+        .unwrap_or_else(|_| String::from("less"))
+}
+
+fn spawn(pager: &str) -> Option<Child> {
+    let pager = pager.trim();
+    if pager.is_empty() || pager == "cat" {
+        return None;
+    }
+    #[cfg(unix)]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(pager);
+        cmd
+    };
+    #[cfg(not(unix))]
+    let mut cmd = {
+        let mut words = pager.split_whitespace();
+        let mut cmd = Command::new(words.next()?);
+        cmd.args(words);
+        cmd
+    };
+    // Quit if the output fits on one screen, pass colors through, and leave the output on screen.
+    if env::var_os("LESS").is_none() {
+        cmd.env("LESS", "FRX");
+    }
+    if env::var_os("LV").is_none() {
+        cmd.env("LV", "-c");
+    }
+    cmd.stdin(Stdio::piped()).spawn().ok()
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.child.as_mut().and_then(|c| c.stdin.as_mut()) {
+            Some(stdin) => stdin.write(buf),
+            None => self.stdout.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.child.as_mut().and_then(|c| c.stdin.as_mut()) {
+            Some(stdin) => stdin.flush(),
+            None => self.stdout.flush(),
+        }
+    }
+}
+
+impl Drop for Pager {
+    /// Waits for the user to quit the pager, so that the shell prompt doesn't return under it.
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            drop(child.stdin.take());
+            _ = child.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spawn() {
+        assert!(spawn("").is_none());
+        assert!(spawn(" cat ").is_none());
+    }
+}
@@ -0,0 +1,714 @@
+use crate::i18n::tr;
+use std::collections::VecDeque;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
+
+/// Like `expand`, for a path that may not be valid unicode; such paths are taken as they are,
+/// since '~' and '$' can't be expanded within them.
+pub fn expand_path(s: OsString) -> Result<PathBuf, String> {
+    match s.into_string() {
+        Ok(s) => expand(&s).map(PathBuf::from),
+        Err(s) => Ok(PathBuf::from(s)),
+    }
+}
+
+/// Expands a leading `~` or `~user`, then any environment variables, in a user-supplied path.
+///
+/// Shells only expand `~` in unquoted words, so paths coming from scripts, config files, or
+/// `--target=~/x` often reach ranch verbatim; without this, ranch would create a literal '~'.
+/// On Windows, '/' separators are turned into '\', so that paths print consistently.
+pub fn expand(s: &str) -> Result<String, String> {
+    let expanded = expand_env(&expand_tilde(s)?)?;
+    #[cfg(windows)]
+    let expanded = expanded.replace('/', "\\");
+    Ok(expanded)
+}
+
+/// Expands a leading `~` (the current user's home) or `~user` (that user's home) in `s`.
+pub fn expand_tilde(s: &str) -> Result<String, String> {
+    let Some(rest) = s.strip_prefix('~') else {
+        return Ok(String::from(s));
+    };
+    let end = rest
+        .find(['/', std::path::MAIN_SEPARATOR])
+        .unwrap_or(rest.len());
+    let (user, tail) = rest.split_at(end);
+    let home = if user.is_empty() {
+        home_dir().ok_or_else(|| tr!("no-home-dir", text = s))?
+    } else {
+        user_home(user).ok_or_else(|| tr!("unknown-user", user = user, text = s))?
+    };
+    let home = home
+        .into_os_string()
+        .into_string()
+        .map_err(|_| tr!("home-not-unicode", text = s))?;
+    Ok(home + tail)
+}
+
+/// The current user's home directory.
+pub fn home_dir() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let var = env::var_os("USERPROFILE");
+    #[cfg(not(windows))]
+    let var = env::var_os("HOME");
+    match var {
+        Some(home) if !home.is_empty() => Some(PathBuf::from(home)),
+        #[cfg(unix)]
+        _ => passwd(None).map(|(home, _, _)| home),
+        #[cfg(not(unix))]
+        _ => None,
+    }
+}
+
+/// The per-user configuration directory: '$XDG_CONFIG_HOME', '%APPDATA%' on Windows, or else
+/// '~/.config'.
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    #[cfg(windows)]
+    if let Some(dir) = env::var_os("APPDATA").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    Some(home_dir()?.join(".config"))
+}
+
+/// The per-user data directory: '$XDG_DATA_HOME', '%LOCALAPPDATA%' on Windows, or else
+/// '~/.local/share'.
+pub fn data_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    #[cfg(windows)]
+    if let Some(dir) = env::var_os("LOCALAPPDATA").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    Some(home_dir()?.join(".local").join("share"))
+}
+
+/// The per-user state directory: '$XDG_STATE_HOME', '%LOCALAPPDATA%' on Windows, or else
+/// '~/.local/state'.
+pub fn state_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_STATE_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    #[cfg(windows)]
+    if let Some(dir) = env::var_os("LOCALAPPDATA").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    Some(home_dir()?.join(".local").join("state"))
+}
+
+/// The per-user cache directory: '$XDG_CACHE_HOME', '%LOCALAPPDATA%' on Windows, or else
+/// '~/.cache'.
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CACHE_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    #[cfg(windows)]
+    if let Some(dir) = env::var_os("LOCALAPPDATA").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    Some(home_dir()?.join(".cache"))
+}
+
+/// Creates a uniquely named directory under the system's temporary directory that only the
+/// current user can enter, removed when the returned handle is dropped.
+pub fn private_temp_dir(prefix: &str) -> Result<tempfile::TempDir, String> {
+    let parent = env::temp_dir();
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(prefix);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(fs::Permissions::from_mode(0o700));
+    }
+    builder
+        .tempdir_in(&parent)
+        .map_err(|e| tr!("could-not-create-dir", path = parent.display(), error = e))
+}
+
+/// The home directory of the user named `user`.
+pub fn user_home(user: &str) -> Option<PathBuf> {
+    #[cfg(unix)]
+    return passwd(Some(user)).map(|(home, _, _)| home);
+    // Windows has no cheap lookup by name; profiles conventionally live side by side.
+    #[cfg(not(unix))]
+    return home_dir()?.parent().map(|p| p.join(user));
+}
+
+/// The uid and gid of the user named `user`.
+#[cfg(unix)]
+pub fn user_ids(user: &str) -> Option<(u32, u32)> {
+    passwd(Some(user)).map(|(_, uid, gid)| (uid, gid))
+}
+
+/// Looks up the home directory, uid, and gid of `user` (or of the current uid) in the user
+/// database.
+#[cfg(unix)]
+fn passwd(user: Option<&str>) -> Option<(PathBuf, u32, u32)> {
+    use std::ffi::{CStr, CString, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+
+    let name = match user {
+        Some(user) => Some(CString::new(user).ok()?),
+        None => None,
+    };
+    let mut buf: Vec<libc::c_char> = vec![0; 1024];
+    loop {
+        // SAFETY: every pointer handed to getpw*_r is valid for the duration of the call, and
+        // `pwd`'s string fields point into `buf`, which outlives their use below.
+        unsafe {
+            let mut pwd: libc::passwd = std::mem::zeroed();
+            let mut result = std::ptr::null_mut();
+            let rc = match &name {
+                Some(name) => libc::getpwnam_r(
+                    name.as_ptr(),
+                    &mut pwd,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut result,
+                ),
+                None => libc::getpwuid_r(
+                    libc::getuid(),
+                    &mut pwd,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut result,
+                ),
+            };
+            if rc == libc::ERANGE && buf.len() < 1 << 20 {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            if rc != 0 || result.is_null() || pwd.pw_dir.is_null() {
+                return None;
+            }
+            let dir = CStr::from_ptr(pwd.pw_dir);
+            let home = PathBuf::from(OsStr::from_bytes(dir.to_bytes()));
+            return Some((home, pwd.pw_uid, pwd.pw_gid));
+        }
+    }
+}
+
+/// Expands `$VAR` and `${VAR}` references in `s` using the process environment.
+///
+/// `$$` produces a literal `$`, and a `$` that isn't followed by a variable name is kept as-is.
+/// Referencing a variable that isn't set (or isn't valid unicode) is an error rather than
+/// silently expanding to an empty string, since that would quietly redirect links into '/'.
+pub fn expand_env(s: &str) -> Result<String, String> {
+    expand_env_with(s, |name| env::var(name))
+}
+
+/// [expand_env], looking variables up with `var` rather than in the process environment.
+fn expand_env_with(
+    s: &str,
+    var: impl Fn(&str) -> Result<String, env::VarError>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            out.push('$');
+            rest = after;
+            continue;
+        }
+        let (name, after) = if let Some(braced) = rest.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => return Err(tr!("unterminated-variable", text = s)),
+            }
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+        if name.is_empty() {
+            out.push('$');
+            continue;
+        }
+        match var(name) {
+            Ok(value) => out.push_str(&value),
+            Err(env::VarError::NotPresent) => {
+                return Err(tr!("variable-not-set", variable = name, text = s))
+            }
+            Err(env::VarError::NotUnicode(_)) => {
+                return Err(tr!("variable-not-unicode", variable = name, text = s))
+            }
+        }
+        rest = after;
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// `path` with symlinks resolved, like `fs::canonicalize`, except that it needn't exist: its
+/// deepest existing ancestor is resolved, and the rest appended. Falls back to `path` as is.
+pub fn canonicalize(path: &Path) -> PathBuf {
+    let mut rest = Vec::new();
+    let mut ancestor = path;
+    loop {
+        if let Ok(real) = fs::canonicalize(ancestor) {
+            let real = strip_verbatim(&real).unwrap_or(real);
+            return rest.iter().rev().fold(real, |p, name| p.join(name));
+        }
+        match (ancestor.parent(), ancestor.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                ancestor = match parent.as_os_str().is_empty() {
+                    true => Path::new("."),
+                    false => parent,
+                };
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+/// Name of the file marking a dotfiles repo's root, so that ranch finds 'DIR' when run from
+/// anywhere inside it, as git finds '.git'. Its contents don't matter.
+pub const REPO_MARKER: &str = ".ranchrc";
+
+/// The nearest of `dir` and its parents holding a [REPO_MARKER]; None if none do.
+pub fn find_repo(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|d| d.join(REPO_MARKER).is_file())
+        .map(Path::to_path_buf)
+}
+
+/// 'DIR' when neither '-C' nor '$RANCH_DIR' gives it: the repo holding `cwd` (see
+/// [find_repo]), or else `config_dir` from the config, or else the first of
+/// '$XDG_DATA_HOME/ranch' and '~/.dotfiles' that exists, or else `cwd` itself.
+pub fn default_dir(cwd: &Path, config_dir: Option<&str>) -> Result<PathBuf, String> {
+    let conventional = [
+        data_dir().map(|d| d.join("ranch")),
+        home_dir().map(|h| h.join(".dotfiles")),
+    ];
+    resolve_dir(
+        cwd,
+        config_dir,
+        &conventional.into_iter().flatten().collect::<Vec<_>>(),
+    )
+}
+
+fn resolve_dir(
+    cwd: &Path,
+    config_dir: Option<&str>,
+    conventional: &[PathBuf],
+) -> Result<PathBuf, String> {
+    if let Some(repo) = find_repo(cwd) {
+        return Ok(repo);
+    }
+    if let Some(dir) = config_dir {
+        return expand(dir)
+            .map(PathBuf::from)
+            .map_err(|e| tr!("invalid-config-dir", error = e));
+    }
+    Ok(conventional
+        .iter()
+        .find(|d| d.is_dir())
+        .cloned()
+        .unwrap_or_else(|| cwd.to_path_buf()))
+}
+
+/// `path` without the verbatim '\\?\' prefix `fs::canonicalize` adds on Windows, if it means
+/// the same without it, so that it prints the way users write paths; None if there's no prefix
+/// or the path needs it, such as one longer than 'MAX_PATH'.
+fn strip_verbatim(path: &Path) -> Option<PathBuf> {
+    const MAX_PATH: usize = 260;
+    let s = path.to_str()?;
+    let plain = match s.strip_prefix(r"\\?\UNC\") {
+        Some(share) => format!(r"\\{}", share),
+        None => {
+            let rest = s.strip_prefix(r"\\?\")?;
+            match rest.as_bytes() {
+                [drive, b':', b'\\', ..] if drive.is_ascii_alphabetic() => rest.to_owned(),
+                _ => return None,
+            }
+        }
+    };
+    (plain.len() < MAX_PATH).then(|| PathBuf::from(plain))
+}
+
+/// Most links followed when resolving a path before it's deemed a loop, as on Linux.
+pub const MAX_LINKS: usize = 40;
+
+/// `path` with every symlink along it resolved, following at most `MAX_LINKS` links so that
+/// loops are reported rather than followed forever. Unlike `canonicalize`, parts that don't
+/// exist are simply kept.
+pub fn resolve(path: &Path) -> Result<PathBuf, String> {
+    let parts = |path: &Path| -> Vec<PathBuf> {
+        path.components()
+            .map(|c| PathBuf::from(c.as_os_str()))
+            .collect()
+    };
+    let mut rest: VecDeque<PathBuf> = parts(path).into();
+    let mut out = PathBuf::new();
+    let mut hops = 0;
+    while let Some(part) = rest.pop_front() {
+        let next = match part.components().next() {
+            Some(Component::Normal(_)) => out.join(&part),
+            Some(Component::ParentDir) => {
+                out.pop();
+                continue;
+            }
+            Some(Component::CurDir) | None => continue,
+            Some(_) => {
+                out.push(&part);
+                continue;
+            }
+        };
+        let Ok(link) = fs::read_link(&next) else {
+            out = next;
+            continue;
+        };
+        hops += 1;
+        if hops > MAX_LINKS {
+            return Err(tr!(
+                "symlink-loop",
+                path = path.display(),
+                at = next.display()
+            ));
+        }
+        for part in parts(&link).into_iter().rev() {
+            rest.push_front(part);
+        }
+    }
+    Ok(out)
+}
+
+/// Whether `a` and `b` are on the same filesystem; None if that can't be told, such as when
+/// either is missing, or on Windows.
+pub fn same_device(a: &Path, b: &Path) -> Option<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(fs::metadata(a).ok()?.dev() == fs::metadata(b).ok()?.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        _ = (a, b);
+        None
+    }
+}
+
+/// Removes '.' and '..' components from `path` without touching the filesystem.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(out.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                out.pop();
+            }
+            component => out.push(component),
+        }
+    }
+    out
+}
+
+/// Where the link at `path` points, resolved against its directory if relative; None if it
+/// isn't a link.
+pub fn resolve_link(path: &Path) -> Option<PathBuf> {
+    let link = fs::read_link(path).ok()?;
+    Some(normalize(&path.parent()?.join(link)))
+}
+
+/// The path of `to` relative to the directory `from`, both absolute; `to` itself if they share
+/// no root, such as on different Windows drives.
+pub fn relative(from: &Path, to: &Path) -> PathBuf {
+    let (from, to) = (normalize(from), normalize(to));
+    let common = from
+        .components()
+        .zip(to.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return to;
+    }
+    let mut out = PathBuf::new();
+    for _ in from.components().skip(common) {
+        out.push("..");
+    }
+    for component in to.components().skip(common) {
+        out.push(component);
+    }
+    out
+}
+
+/// Whether the filesystem treats names that differ only in Unicode normalization as the same
+/// file, as APFS and HFS+ do on macOS. There, a name git wrote precomposed (NFC), as Linux
+/// tools do, can be read back decomposed (NFD), so paths must be compared normalized.
+const NORMALIZING_FS: bool = cfg!(target_os = "macos");
+
+/// Whether `a` and `b` name the same file, allowing for Unicode normalization where the
+/// filesystem ignores it.
+pub fn same_path(a: &Path, b: &Path) -> bool {
+    a == b || NORMALIZING_FS && nfc(a) == nfc(b)
+}
+
+/// `path` relative to `prefix`, allowing for Unicode normalization where the filesystem ignores
+/// it; None if it's not within `prefix`.
+pub fn strip_prefix(path: &Path, prefix: &Path) -> Option<PathBuf> {
+    strip_prefix_normalized(path, prefix, NORMALIZING_FS)
+}
+
+fn strip_prefix_normalized(path: &Path, prefix: &Path, normalize: bool) -> Option<PathBuf> {
+    if let Ok(rest) = path.strip_prefix(prefix) {
+        return Some(rest.to_path_buf());
+    }
+    match normalize {
+        true => nfc(path)
+            .strip_prefix(nfc(prefix))
+            .ok()
+            .map(Path::to_path_buf),
+        false => None,
+    }
+}
+
+/// Where the absolute `path` is staged under `root`, as with 'DESTDIR': '/home/alice' under
+/// '/tmp/stage' is '/tmp/stage/home/alice'. As in a chroot, '..' never climbs above `root`.
+pub fn reroot(root: &Path, path: &Path) -> PathBuf {
+    let mut rest = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => rest.push(name),
+            Component::ParentDir => _ = rest.pop(),
+            _ => {}
+        }
+    }
+    root.join(rest)
+}
+
+/// The runtime path that `path` is staged for under `root`; the inverse of `reroot`. Paths
+/// outside `root` are returned as is.
+pub fn unroot(root: Option<&Path>, path: &Path) -> PathBuf {
+    match root.and_then(|root| path.strip_prefix(root).ok()) {
+        Some(rest) => Path::new(std::path::MAIN_SEPARATOR_STR).join(rest),
+        None => path.to_path_buf(),
+    }
+}
+
+/// `path` in Unicode normalization form C, if it's valid unicode.
+fn nfc(path: &Path) -> PathBuf {
+    match path.to_str() {
+        Some(s) => PathBuf::from(s.nfc().collect::<String>()),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Whether `s` contains glob wildcards, as opposed to being a literal name.
+pub fn is_glob(s: &str) -> bool {
+    s.contains(['*', '?'])
+}
+
+/// Whether `name` matches the glob `pattern`: '*' matches any run of characters other than
+/// '/', '**' any run including '/' (so '**/' also matches no directories at all), and '?' any
+/// one character other than '/'.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    fn matches(p: &[char], n: &[char]) -> bool {
+        match p {
+            [] => n.is_empty(),
+            ['*', '*', rest @ ..] => {
+                (0..=n.len()).any(|i| matches(rest, &n[i..]))
+                    || rest.first() == Some(&'/') && matches(&rest[1..], n)
+            }
+            ['*', rest @ ..] => (0..=n.len())
+                .take_while(|&i| !n[..i].contains(&'/'))
+                .any(|i| matches(rest, &n[i..])),
+            ['?', rest @ ..] => n.first().is_some_and(|c| *c != '/') && matches(rest, &n[1..]),
+            [c, rest @ ..] => n.first() == Some(c) && matches(rest, &n[1..]),
+        }
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches(&pattern, &name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_env() {
+        let expand = |s| {
+            expand_env_with(s, |name| match name {
+                "XDG_CONFIG_HOME" => Ok(String::from("/home/alice/.config")),
+                _ => Err(env::VarError::NotPresent),
+            })
+        };
+        assert_eq!(
+            expand("$XDG_CONFIG_HOME/nvim").unwrap(),
+            "/home/alice/.config/nvim"
+        );
+        assert_eq!(
+            expand("${XDG_CONFIG_HOME}nvim").unwrap(),
+            "/home/alice/.confignvim"
+        );
+        assert_eq!(expand("a$$b$").unwrap(), "a$b$");
+        assert!(expand("$RANCH_TEST_UNSET_VARIABLE/nvim")
+            .unwrap_err()
+            .contains("'RANCH_TEST_UNSET_VARIABLE' is not set"));
+        assert!(expand("${XDG_CONFIG_HOME").is_err());
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        let home = home_dir().unwrap().into_os_string().into_string().unwrap();
+        assert_eq!(expand_tilde("~").unwrap(), home);
+        assert_eq!(
+            expand_tilde("~/.config").unwrap(),
+            home.clone() + "/.config"
+        );
+        assert_eq!(expand_tilde("a/~").unwrap(), "a/~");
+        assert!(expand_tilde("~ranch-test-no-such-user/x")
+            .unwrap_err()
+            .contains("unknown user"));
+        #[cfg(unix)]
+        assert_eq!(
+            expand_tilde("~root").unwrap(),
+            user_home("root").unwrap().to_str().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_private_temp_dir() {
+        let a = private_temp_dir("ranch-test-").unwrap();
+        let b = private_temp_dir("ranch-test-").unwrap();
+        assert_ne!(a.path(), b.path());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(a.path()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        let path = a.path().to_path_buf();
+        drop(a);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("vim*", "vim"));
+        assert!(glob_match("vim*", "vim-plugins"));
+        assert!(!glob_match("vim*", "neovim"));
+        assert!(glob_match("?sh", "zsh"));
+        assert!(!glob_match("*.toml", ".config/app.toml"));
+        assert!(glob_match("**/*.toml", ".config/app.toml"));
+        assert!(glob_match("**/*.toml", "app.toml"));
+        assert!(glob_match(".config/**", ".config/nvim/init.lua"));
+        assert!(!is_glob("home"));
+        assert!(is_glob("work-*"));
+    }
+
+    #[test]
+    fn test_find_repo() {
+        let tmp_dir = tempdir::TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("nvim/.config/nvim")).unwrap();
+        assert_eq!(find_repo(&dir.join("nvim/.config/nvim")), None);
+
+        fs::write(dir.join(REPO_MARKER), "").unwrap();
+        assert_eq!(find_repo(&dir.join("nvim/.config/nvim")), Some(dir.clone()));
+        assert_eq!(find_repo(&dir), Some(dir));
+    }
+
+    #[test]
+    fn test_resolve_dir() {
+        let tmp_dir = tempdir::TempDir::new("alice").unwrap();
+        let cwd = tmp_dir.path().join("src");
+        let data = tmp_dir.path().join(".local/share/ranch");
+        let dotfiles = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(&cwd).unwrap();
+        let conventional = [data.clone(), dotfiles.clone()];
+        let resolve = |config_dir| resolve_dir(&cwd, config_dir, &conventional).unwrap();
+
+        assert_eq!(resolve(None), cwd);
+        fs::create_dir_all(&dotfiles).unwrap();
+        assert_eq!(resolve(None), dotfiles);
+        fs::create_dir_all(&data).unwrap();
+        assert_eq!(resolve(None), data);
+        assert_eq!(resolve(Some("/srv/dotfiles")), Path::new("/srv/dotfiles"));
+        fs::write(tmp_dir.path().join(REPO_MARKER), "").unwrap();
+        assert_eq!(resolve(Some("/srv/dotfiles")), tmp_dir.path());
+    }
+
+    #[test]
+    fn test_relative() {
+        assert_eq!(
+            relative(
+                Path::new("/home/alice"),
+                Path::new("/home/alice/.dotfiles/home/.vimrc")
+            ),
+            Path::new(".dotfiles/home/.vimrc")
+        );
+        assert_eq!(
+            relative(
+                Path::new("/home/alice/.config/nvim"),
+                Path::new("/home/alice/.dotfiles/nvim/init.lua")
+            ),
+            Path::new("../../.dotfiles/nvim/init.lua")
+        );
+        assert_eq!(
+            normalize(Path::new("/home/alice/.config/nvim/../../.dotfiles/./x")),
+            Path::new("/home/alice/.dotfiles/x")
+        );
+    }
+
+    #[test]
+    fn test_strip_prefix_normalized() {
+        let nfc = Path::new("/home/alice/.dotfiles/caf\u{e9}");
+        let nfd = Path::new("/home/alice/.dotfiles/cafe\u{301}/.vimrc");
+        assert_eq!(strip_prefix_normalized(nfd, nfc, false), None);
+        assert_eq!(
+            strip_prefix_normalized(nfd, nfc, true),
+            Some(PathBuf::from(".vimrc"))
+        );
+        assert_eq!(
+            strip_prefix_normalized(nfd, Path::new("/home/alice"), false),
+            Some(PathBuf::from(".dotfiles/cafe\u{301}/.vimrc"))
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_reroot() {
+        let root = Path::new("/tmp/stage");
+        let staged = reroot(root, Path::new("/home/alice/.bashrc"));
+        assert_eq!(staged, Path::new("/tmp/stage/home/alice/.bashrc"));
+        assert_eq!(
+            reroot(root, Path::new("/home/alice/../../../etc/x")),
+            Path::new("/tmp/stage/etc/x")
+        );
+        assert_eq!(
+            unroot(Some(root), &staged),
+            Path::new("/home/alice/.bashrc")
+        );
+        assert_eq!(unroot(Some(root), Path::new("/etc")), Path::new("/etc"));
+        assert_eq!(unroot(None, &staged), staged);
+    }
+
+    #[test]
+    fn test_strip_verbatim() {
+        assert_eq!(
+            strip_verbatim(Path::new(r"\\?\C:\Users\alice")),
+            Some(PathBuf::from(r"C:\Users\alice"))
+        );
+        assert_eq!(
+            strip_verbatim(Path::new(r"\\?\UNC\server\share\x")),
+            Some(PathBuf::from(r"\\server\share\x"))
+        );
+        assert_eq!(strip_verbatim(Path::new(r"\\?\GLOBALROOT\Device")), None);
+        assert_eq!(strip_verbatim(Path::new("/home/alice")), None);
+        let long = format!(r"\\?\C:\{}", "a".repeat(300));
+        assert_eq!(strip_verbatim(Path::new(&long)), None);
+    }
+}
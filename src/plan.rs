@@ -0,0 +1,1025 @@
+use crate::filesystem::Kind;
+use crate::git;
+use crate::hooks::HOOKS_DIR;
+use crate::i18n::tr;
+use crate::lint;
+use crate::manifest::{self, Manifest};
+use crate::paths;
+use crate::secrets::Cipher;
+use crate::template::TEMPLATE_EXTENSION;
+use crate::{Args, DeepDirs, SpecialFiles, TypeConflict, LV_DEBUG, LV_INFO, LV_WARN};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+/// A single change ranch intends to make at the target.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum Action {
+    /// Soft-link `dst` to `src`.
+    Link { src: PathBuf, dst: PathBuf },
+    /// Decrypt `src` into a real file at `dst`; secrets are never deployed as links, since the
+    /// plaintext must not live in the repository.
+    Decrypt {
+        src: PathBuf,
+        dst: PathBuf,
+        cipher: Cipher,
+    },
+    /// Render the template `src` into a real file at `dst`.
+    Render { src: PathBuf, dst: PathBuf },
+    /// Create the directory `dst`, mirroring the package directory `src`, which is empty or
+    /// marked with a '.keep' file.
+    Mkdir { src: PathBuf, dst: PathBuf },
+}
+
+impl Action {
+    /// The package file this action deploys.
+    pub fn src(&self) -> &Path {
+        match self {
+            Action::Link { src, .. }
+            | Action::Decrypt { src, .. }
+            | Action::Render { src, .. }
+            | Action::Mkdir { src, .. } => src,
+        }
+    }
+
+    /// The target path this action creates.
+    pub fn dst(&self) -> &Path {
+        match self {
+            Action::Link { dst, .. }
+            | Action::Decrypt { dst, .. }
+            | Action::Render { dst, .. }
+            | Action::Mkdir { dst, .. } => dst,
+        }
+    }
+}
+
+/// Lists the packages in 'DIR': its subdirectories, other than hidden ones such as '.git'.
+pub fn packages(dir: &Path) -> Result<Vec<String>, String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| tr!("could-not-read", path = dir.display(), error = e))?;
+    let mut packages: Vec<String> = entries
+        .filter_map(|r| r.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    packages.sort();
+    Ok(packages)
+}
+
+/// Prefix of package files that are executed once per machine after linking, instead of linked.
+pub const RUN_ONCE_PREFIX: &str = "run_once_";
+
+fn is_run_once(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with(RUN_ONCE_PREFIX))
+}
+
+/// Lists the 'run_once_*' scripts anywhere in the package at `prefix_path`, in path order.
+pub fn run_once_scripts(prefix_path: &Path) -> Vec<PathBuf> {
+    WalkDir::new(prefix_path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|r| r.ok())
+        .filter(|e| e.file_type().is_file() && is_run_once(e.path()))
+        .map(|e| e.into_path())
+        .collect()
+}
+
+/// Resolves where `package` deploys to: '--target' if given, otherwise the manifest's 'target'
+/// relative to 'DIR/..', otherwise 'DIR/..' itself. The config's 'target' stands in for
+/// 'DIR/..' if set; packages fetched with '--from' live in a cache, so the home directory does
+/// otherwise. Symlinks in the result are resolved, so that links land where 'DIR' is compared
+/// against. With '--root', the target is staged under it.
+pub fn target_path(args: &Args, package: &str, manifest: &Manifest) -> Result<PathBuf, String> {
+    let stage = |target: PathBuf| match &args.root {
+        Some(root) => paths::reroot(&paths::canonicalize(root), &target),
+        None => target,
+    };
+    if let Some(target) = &args.target {
+        return Ok(stage(paths::canonicalize(target)));
+    }
+    let default_target = match (&args.default_target, &args.from) {
+        (Some(target), _) => target.clone(),
+        (None, Some(_)) => paths::home_dir().ok_or_else(|| tr!("no-home-dir", text = "~"))?,
+        (None, None) => args
+            .dir
+            .parent()
+            .ok_or_else(|| tr!("no-default-target"))?
+            .to_owned(),
+    };
+    let target_path = match &manifest.target {
+        Some(target) => {
+            let target = paths::expand(target)
+                .map_err(|e| tr!("invalid-package-target", package = package, error = e))?;
+            default_target.join(target)
+        }
+        None => default_target,
+    };
+    Ok(stage(paths::canonicalize(&target_path)))
+}
+
+/// Walks the package at `prefix_path` and returns the actions needed to deploy it into
+/// `target_path`, as [stream] yields them, for the checks that need the whole plan before
+/// anything is deployed.
+pub fn plan(
+    args: &Args,
+    package: &str,
+    prefix_path: &Path,
+    target_path: &Path,
+    stderr: &mut dyn io::Write,
+) -> Result<Vec<Action>, String> {
+    stream(args, package, prefix_path, target_path, stderr)?.collect()
+}
+
+/// Walks the package at `prefix_path`, yielding the actions needed to deploy it into
+/// `target_path` one at a time, so that memory doesn't grow with the size of the package; only
+/// the walk's open directories are held, and git's lists of tracked and ignored files are read
+/// alongside the walk rather than loaded. Empty directories are only created with
+/// '--create-empty-dirs', or if the package's manifest sets 'create_empty_dirs'. With
+/// '--max-depth', directories at that depth are linked whole or skipped, according to
+/// '--deep-dirs', rather than walked. With '--follow-source-links',
+/// symlinks inside the package are walked through, and links point at the real files rather
+/// than at the package's own links. With '--only', just the files matching one of its globs are
+/// planned, and '--exclude' leaves out what matches its globs. Files larger than
+/// '--max-file-size' are skipped with a warning, as are binary files with '--skip-binaries'.
+/// FIFOs, sockets, and devices are handled according to '--special-files'. Whatever the
+/// repository's '.gitignore' ignores is never planned, unless '--no-gitignore' is given. The
+/// stream ends after the first error.
+pub fn stream<'a>(
+    args: &'a Args,
+    package: &'a str,
+    prefix_path: &'a Path,
+    target_path: &'a Path,
+    stderr: &'a mut dyn io::Write,
+) -> Result<Plan<'a>, String> {
+    let tracked = match args.vcs_only {
+        true => Some(git::tracked_files(prefix_path).map_err(|e| {
+            tr!(
+                "vcs-only-could-not-list",
+                path = prefix_path.display(),
+                error = e
+            )
+        })?),
+        false => None,
+    };
+    let create_empty_dirs =
+        args.create_empty_dirs || Manifest::load(prefix_path)?.create_empty_dirs;
+    let ignored = match args.no_gitignore {
+        true => None,
+        false => Some(git::ignored_files(prefix_path)),
+    };
+    // Walked in git's order, so that its lists can be read alongside the walk.
+    let walk = WalkDir::new(prefix_path)
+        .follow_links(args.follow_source_links)
+        .max_depth(args.max_depth.unwrap_or(usize::MAX))
+        .sort_by(git::git_order)
+        .into_iter();
+    Ok(Plan {
+        args,
+        package,
+        prefix_path,
+        target_path,
+        stderr,
+        walk,
+        tracked,
+        ignored,
+        create_empty_dirs,
+        done: false,
+    })
+}
+
+/// The actions deploying a package, walked lazily; see [stream].
+pub struct Plan<'a> {
+    args: &'a Args,
+    package: &'a str,
+    prefix_path: &'a Path,
+    target_path: &'a Path,
+    stderr: &'a mut dyn io::Write,
+    walk: walkdir::IntoIter,
+    tracked: Option<git::Listing>,
+    ignored: Option<git::Listing>,
+    create_empty_dirs: bool,
+    done: bool,
+}
+
+impl Iterator for Plan<'_> {
+    type Item = Result<Action, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let Ok(entry) = self.walk.next()? else {
+                continue;
+            };
+            let is_dir = entry.file_type().is_dir();
+            let is_ignored = |ignored: &mut git::Listing| ignored.contains(entry.path(), is_dir);
+            if is_excluded(&self.args.exclude, self.prefix_path, entry.path(), is_dir)
+                || (entry.depth() > 0 && self.ignored.as_mut().is_some_and(is_ignored))
+            {
+                if is_dir {
+                    self.walk.skip_current_dir();
+                }
+                continue;
+            }
+            match self.plan_entry(&entry) {
+                Ok(None) => {}
+                Ok(Some(action)) => return Some(Ok(action)),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Plan<'_> {
+    /// The action deploying the walked package file or directory `entry`, if any.
+    fn plan_entry(&mut self, entry: &DirEntry) -> Result<Option<Action>, String> {
+        let args = self.args;
+        let stderr = &mut *self.stderr;
+        let src = entry.path().to_path_buf();
+        if !args.only.is_empty() && !matches_any(&args.only, self.prefix_path, &src) {
+            return Ok(None);
+        }
+        if Some(entry.depth()) == args.max_depth && entry.file_type().is_dir() && entry.depth() > 0
+        {
+            match args.deep_dirs {
+                DeepDirs::Fold if self.tracked.as_mut().is_none_or(|t| t.contains_under(&src)) => {
+                    return Ok(plan_path(args, self.package, self.target_path, src, stderr)
+                        .filter(|a| matches!(a, Action::Link { .. })));
+                }
+                DeepDirs::Fold => {}
+                DeepDirs::Skip => {
+                    if args.verbosity("plan") >= LV_DEBUG {
+                        _ = writeln!(stderr, "{} is beyond --max-depth; skipping", src.display());
+                    }
+                }
+            }
+            return Ok(None);
+        }
+        if self.create_empty_dirs && !args.vcs_only && is_empty_dir(&src) {
+            return Ok(plan_dir(self.prefix_path, self.target_path, src));
+        }
+        if entry.file_type().is_dir() {
+            return Ok(None);
+        }
+        if !(src.is_file() || src.is_symlink()) {
+            match args.special_files {
+                SpecialFiles::SkipWithWarning => {
+                    if args.verbosity("plan") >= LV_WARN {
+                        _ = writeln!(
+                            stderr,
+                            "{}",
+                            tr!("special-file-skipping", path = src.display())
+                        );
+                    }
+                    return Ok(None);
+                }
+                SpecialFiles::Error => return Err(tr!("special-file", path = src.display())),
+                SpecialFiles::Link => {}
+            }
+        }
+        if let Some(max) = args.max_file_size {
+            let size = src.metadata().map(|m| m.len()).unwrap_or_default();
+            if size > max {
+                if args.verbosity("plan") >= LV_WARN {
+                    _ = writeln!(
+                        stderr,
+                        "{}",
+                        tr!("too-large-skipping", path = src.display(), size = size)
+                    );
+                }
+                return Ok(None);
+            }
+        }
+        if self
+            .tracked
+            .as_mut()
+            .is_some_and(|t| !t.contains(&src, false))
+        {
+            if args.verbosity("plan") >= LV_DEBUG {
+                _ = writeln!(stderr, "{} is not tracked by git; skipping", src.display());
+            }
+            return Ok(None);
+        }
+        let action = plan_path(args, self.package, self.target_path, src, stderr);
+        let action = action.filter(|action| match action {
+            Action::Link { src, .. } if args.skip_binaries && lint::is_binary(src) => {
+                if args.verbosity("plan") >= LV_INFO {
+                    _ = writeln!(stderr, "Skipping binary file {}", src.display());
+                }
+                false
+            }
+            _ => true,
+        });
+        Ok(action.map(|action| match action {
+            Action::Link { src, dst } if args.follow_source_links => Action::Link {
+                src: paths::canonicalize(&src),
+                dst,
+            },
+            action => action,
+        }))
+    }
+}
+
+/// Whether the package file `src` matches one of the globs in `patterns`. Globs containing '/'
+/// match the path within the package at `prefix_path`, such as '.config/*/init.lua', and others
+/// also match just the file name, such as '*.conf'.
+pub fn matches_any(patterns: &[String], prefix_path: &Path, src: &Path) -> bool {
+    let Ok(rel_path) = src.strip_prefix(prefix_path) else {
+        return false;
+    };
+    let rel_path: Vec<_> = rel_path.iter().map(|c| c.to_string_lossy()).collect();
+    let name = rel_path.last().cloned().unwrap_or_default();
+    let rel_path = rel_path.join("/");
+    patterns.iter().any(|pattern| {
+        paths::glob_match(pattern, &rel_path)
+            || !pattern.contains('/') && paths::glob_match(pattern, &name)
+    })
+}
+
+/// Whether the package file or directory `path` matches one of the '--exclude' globs in
+/// `patterns`, read as in '.gitignore': a leading '/' anchors a glob to the package root at
+/// `prefix_path`, a trailing '/' matches only directories, and globs without a '/' match names
+/// at any depth. Excluding a directory excludes everything in it.
+pub fn is_excluded(patterns: &[String], prefix_path: &Path, path: &Path, is_dir: bool) -> bool {
+    let Some(rel_path) = path
+        .strip_prefix(prefix_path)
+        .ok()
+        .filter(|p| !p.as_os_str().is_empty())
+    else {
+        return false;
+    };
+    let rel_path: Vec<_> = rel_path.iter().map(|c| c.to_string_lossy()).collect();
+    let name = rel_path.last().cloned().unwrap_or_default();
+    let rel_path = rel_path.join("/");
+    patterns.iter().any(|pattern| {
+        let (pattern, dir_only) = match pattern.strip_suffix('/') {
+            Some(pattern) => (pattern, true),
+            None => (pattern.as_str(), false),
+        };
+        if dir_only && !is_dir {
+            return false;
+        }
+        match pattern.strip_prefix('/') {
+            Some(anchored) => paths::glob_match(anchored, &rel_path),
+            None if pattern.contains('/') => paths::glob_match(pattern, &rel_path),
+            None => paths::glob_match(pattern, &name),
+        }
+    })
+}
+
+/// Names of placeholder files that keep otherwise empty directories in git; their directory is
+/// created at the target, but they aren't linked themselves.
+pub const KEEP_NAMES: [&str; 2] = [".keep", ".gitkeep"];
+
+fn is_empty_dir(path: &Path) -> bool {
+    !path.is_symlink() && std::fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_none())
+}
+
+/// Maps the empty package directory `src` to the action creating it at the target, unless it's
+/// the package itself or its hooks directory.
+fn plan_dir(prefix_path: &Path, target_path: &Path, src: PathBuf) -> Option<Action> {
+    let rel_path = src.strip_prefix(prefix_path).ok()?;
+    if rel_path.as_os_str().is_empty() || rel_path.starts_with(HOOKS_DIR) {
+        return None;
+    }
+    Some(Action::Mkdir {
+        dst: target_path.join(rel_path),
+        src,
+    })
+}
+
+/// What's in the way of deploying `action` when it's the wrong type: a real directory where a
+/// file goes, a file where a directory goes, or a file (or broken link) where one of the parent
+/// directories should be.
+pub fn blocking_path(args: &Args, action: &Action) -> Option<PathBuf> {
+    let dst = action.dst();
+    let wrong_type = match action {
+        Action::Mkdir { .. } => args.fs.kind(dst).is_some() && !args.fs.is_dir(dst),
+        _ => args.fs.kind(dst) == Some(Kind::Dir),
+    };
+    if wrong_type {
+        return Some(dst.to_path_buf());
+    }
+    dst.ancestors()
+        .skip(1)
+        .find(|a| args.fs.kind(a).is_some())
+        .filter(|a| !args.fs.is_dir(a))
+        .map(Path::to_path_buf)
+}
+
+/// Describes the type conflict of deploying to `dst`, where `blocking` is in the way.
+pub fn describe_type_conflict(args: &Args, dst: &Path, blocking: &Path) -> String {
+    let problem = match args.fs.kind(blocking) == Some(Kind::Dir) {
+        true => tr!("directory-in-the-way", path = blocking.display()),
+        false => tr!("file-in-the-way", path = blocking.display()),
+    };
+    match dst == blocking {
+        true => problem,
+        false => tr!("in-the-way-for", problem = problem, target = dst.display()),
+    }
+}
+
+/// With '--type-conflict stop', fails before anything is deployed if any of `actions` would
+/// have to replace a directory with a file or a file with a directory, listing all of them.
+pub fn check_types(args: &Args, actions: &[Action]) -> Result<(), String> {
+    if !matches!(args.type_conflict, TypeConflict::Stop) {
+        return Ok(());
+    }
+    let problems: Vec<String> = actions
+        .iter()
+        .filter_map(|a| {
+            Some(describe_type_conflict(
+                args,
+                a.dst(),
+                &blocking_path(args, a)?,
+            ))
+        })
+        .collect();
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(tr!("type-conflicts", problems = problems.join("; "))),
+    }
+}
+
+/// Fails before anything is deployed if the target path of any of `actions` can't be resolved
+/// because its symlinks loop, or leads back into 'DIR' through a symlinked directory (such as a
+/// '~/.config' linked into the package), where links would be created inside the package.
+pub fn check_links(args: &Args, actions: &[Action]) -> Result<(), String> {
+    let dir = &args.dir;
+    let mut problems: Vec<String> = Vec::new();
+    for action in actions {
+        let dst = action.dst();
+        let Some(parent) = dst.parent() else {
+            continue;
+        };
+        let resolved = paths::resolve(parent).and_then(|real| match real.starts_with(dir) {
+            true => Err(tr!(
+                "leads-through-symlink",
+                path = parent.display(),
+                dir = dir.display()
+            )),
+            false => paths::resolve(dst).map(|_| ()),
+        });
+        if let Err(problem) = resolved {
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+    }
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(tr!("symlinked-parents", problems = problems.join("; "))),
+    }
+}
+
+/// Whether the filesystem ignores case in names, as it does by default on Windows and macOS.
+const CASE_INSENSITIVE_FS: bool = cfg!(any(windows, target_os = "macos"));
+
+/// Fails before anything is deployed if two of `actions` target paths that differ only in case,
+/// where the filesystem ignores case: both would be deployed to the same file, and the last one
+/// would silently win.
+pub fn check_case(actions: &[Action]) -> Result<(), String> {
+    match CASE_INSENSITIVE_FS {
+        true => check_case_collisions(actions),
+        false => Ok(()),
+    }
+}
+
+fn check_case_collisions(actions: &[Action]) -> Result<(), String> {
+    let mut seen: HashMap<String, &Action> = HashMap::new();
+    let mut problems: Vec<String> = Vec::new();
+    for action in actions {
+        // Directories may coincide; it's the files in them that would collide.
+        if let Action::Mkdir { .. } = action {
+            continue;
+        }
+        let key = action.dst().to_string_lossy().to_lowercase();
+        match seen.get(&key) {
+            Some(other) => problems.push(tr!(
+                "case-collision",
+                package_file = other.src().display(),
+                other_package_file = action.src().display(),
+                target = other.dst().display()
+            )),
+            None => _ = seen.insert(key, action),
+        }
+    }
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(tr!("case-collisions", problems = problems.join("; "))),
+    }
+}
+
+/// Maps the package file `src` to the action deploying it, or `None` if ranch doesn't deploy it
+/// (manifests, hooks, ...). `src` needn't exist, so that removed files can be mapped to
+/// the links they leave behind.
+pub fn plan_path(
+    args: &Args,
+    package: &str,
+    target_path: &Path,
+    src: PathBuf,
+    stderr: &mut dyn io::Write,
+) -> Option<Action> {
+    let rel_path = paths::strip_prefix(&src, &args.dir);
+    if rel_path.is_none() {
+        if args.verbosity("plan") >= LV_WARN {
+            _ = writeln!(
+                stderr,
+                "{}",
+                tr!(
+                    "not-a-child",
+                    path = src.display(),
+                    dir = args.dir.display()
+                )
+            );
+        }
+        return None;
+    }
+
+    let rel_path = rel_path.unwrap();
+
+    let relative_output = paths::strip_prefix(&rel_path, Path::new(package))?;
+    if manifest::is_manifest(&relative_output)
+        || relative_output.starts_with(HOOKS_DIR)
+        || is_run_once(&src)
+    {
+        return None;
+    }
+    let output_path = target_path.join(relative_output);
+    if src
+        .file_name()
+        .is_some_and(|n| KEEP_NAMES.iter().any(|k| n == *k))
+    {
+        return Some(Action::Mkdir {
+            src: src.parent()?.to_path_buf(),
+            dst: output_path.parent()?.to_path_buf(),
+        });
+    }
+    if let Some(cipher) = Cipher::from_path(&src) {
+        Some(Action::Decrypt {
+            dst: output_path.with_extension(""),
+            src,
+            cipher,
+        })
+    } else if src.extension().is_some_and(|e| e == TEMPLATE_EXTENSION) {
+        Some(Action::Render {
+            dst: output_path.with_extension(""),
+            src,
+        })
+    } else {
+        Some(Action::Link {
+            src,
+            dst: output_path,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs::{create_dir_all, File};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_plan_decrypts_secrets() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        create_dir_all(dir.join("home/.ssh")).unwrap();
+        File::create(dir.join("home/.ssh/id_ed25519.age")).unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "home"]);
+
+        let actions = plan(
+            &args,
+            "home",
+            &dir.join("home"),
+            tmp_dir.path(),
+            &mut io::sink(),
+        )
+        .unwrap();
+
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(
+            actions[0],
+            Action::Decrypt {
+                cipher: Cipher::Age,
+                ..
+            }
+        ));
+        assert_eq!(actions[0].dst(), tmp_dir.path().join(".ssh/id_ed25519"));
+    }
+
+    #[test]
+    fn test_check_types() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        create_dir_all(dir.join("home/.config")).unwrap();
+        File::create(dir.join("home/.vimrc")).unwrap();
+        File::create(dir.join("home/.config/app.toml")).unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "home"]);
+        let actions = plan(
+            &args,
+            "home",
+            &dir.join("home"),
+            tmp_dir.path(),
+            &mut io::sink(),
+        )
+        .unwrap();
+        assert!(check_types(&args, &actions).is_ok());
+
+        create_dir_all(tmp_dir.path().join(".vimrc")).unwrap();
+        File::create(tmp_dir.path().join(".config")).unwrap();
+
+        let err = check_types(&args, &actions).unwrap_err();
+        assert!(err.contains(".vimrc is a directory"));
+        assert!(err.contains(".config is a file"));
+        let args = Args::parse_from(["ranch", "--type-conflict", "skip", "home"]);
+        assert!(check_types(&args, &actions).is_ok());
+    }
+
+    #[test]
+    fn test_plan_creates_empty_dirs() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        create_dir_all(dir.join("home/.cache/app")).unwrap();
+        create_dir_all(dir.join("home/hooks")).unwrap();
+        File::create(dir.join("home/.vimrc")).unwrap();
+        let plan_home = |args: &Args| {
+            plan(
+                args,
+                "home",
+                &dir.join("home"),
+                tmp_dir.path(),
+                &mut io::sink(),
+            )
+            .unwrap()
+        };
+
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "home"]);
+        assert_eq!(plan_home(&args).len(), 1);
+
+        let args = Args::parse_from([
+            "ranch",
+            "--create-empty-dirs",
+            "-C",
+            dir.to_str().unwrap(),
+            "home",
+        ]);
+        let actions = plan_home(&args);
+        assert_eq!(actions.len(), 2);
+        assert!(actions
+            .iter()
+            .any(|a| matches!(a, Action::Mkdir { .. })
+                && a.dst() == tmp_dir.path().join(".cache/app")));
+
+        std::fs::write(
+            dir.join("home").join(manifest::MANIFEST_NAME),
+            "create_empty_dirs = true\n",
+        )
+        .unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "home"]);
+        assert_eq!(plan_home(&args).len(), 2);
+    }
+
+    #[test]
+    fn test_plan_keep_files() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        create_dir_all(dir.join("home/.cache/app")).unwrap();
+        create_dir_all(dir.join("home/.local/state")).unwrap();
+        File::create(dir.join("home/.cache/app/.keep")).unwrap();
+        File::create(dir.join("home/.local/state/.gitkeep")).unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "home"]);
+
+        let mut actions = plan(
+            &args,
+            "home",
+            &dir.join("home"),
+            tmp_dir.path(),
+            &mut io::sink(),
+        )
+        .unwrap();
+        actions.sort_by(|a, b| a.dst().cmp(b.dst()));
+
+        assert_eq!(actions.len(), 2);
+        assert!(actions.iter().all(|a| matches!(a, Action::Mkdir { .. })));
+        assert_eq!(actions[0].dst(), tmp_dir.path().join(".cache/app"));
+        assert_eq!(actions[0].src(), dir.join("home/.cache/app"));
+        assert_eq!(actions[1].dst(), tmp_dir.path().join(".local/state"));
+    }
+
+    #[test]
+    fn test_plan_max_depth() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        create_dir_all(dir.join("home/.config/app/node_modules/left-pad")).unwrap();
+        File::create(dir.join("home/.config/app/node_modules/left-pad/index.js")).unwrap();
+        File::create(dir.join("home/.config/app/init.js")).unwrap();
+        File::create(dir.join("home/.vimrc")).unwrap();
+        let plan_with = |extra: &[&str]| {
+            let argv = [
+                &["ranch", "-C", dir.to_str().unwrap(), "--max-depth", "3"],
+                extra,
+                &["home"],
+            ];
+            let args = Args::parse_from(argv.concat());
+            let mut actions = plan(
+                &args,
+                "home",
+                &dir.join("home"),
+                tmp_dir.path(),
+                &mut io::sink(),
+            )
+            .unwrap();
+            actions.sort_by(|a, b| a.dst().cmp(b.dst()));
+            actions
+        };
+
+        let folded = plan_with(&[]);
+        let skipped = plan_with(&["--deep-dirs", "skip"]);
+
+        let dsts = |actions: &[Action]| -> Vec<PathBuf> {
+            actions
+                .iter()
+                .map(|a| a.dst().strip_prefix(tmp_dir.path()).unwrap().to_owned())
+                .collect()
+        };
+        assert_eq!(
+            dsts(&folded),
+            [".config/app/init.js", ".config/app/node_modules", ".vimrc"].map(PathBuf::from)
+        );
+        assert_eq!(
+            dsts(&skipped),
+            [".config/app/init.js", ".vimrc"].map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn test_plan_only() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        create_dir_all(dir.join("home/.config/nvim")).unwrap();
+        File::create(dir.join("home/.config/nvim/init.lua")).unwrap();
+        File::create(dir.join("home/.config/app.conf")).unwrap();
+        File::create(dir.join("home/.vimrc")).unwrap();
+        let argv = [
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "--only",
+            "*.conf",
+            "--only",
+            ".config/nvim/**",
+            "home",
+        ];
+        let args = Args::parse_from(argv);
+
+        let mut actions = plan(
+            &args,
+            "home",
+            &dir.join("home"),
+            tmp_dir.path(),
+            &mut io::sink(),
+        )
+        .unwrap();
+        actions.sort_by(|a, b| a.dst().cmp(b.dst()));
+
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].dst(), tmp_dir.path().join(".config/app.conf"));
+        assert_eq!(
+            actions[1].dst(),
+            tmp_dir.path().join(".config/nvim/init.lua")
+        );
+    }
+
+    #[test]
+    fn test_plan_max_file_size() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        create_dir_all(dir.join("home")).unwrap();
+        std::fs::write(dir.join("home/.vimrc"), [0; 1024]).unwrap();
+        std::fs::write(dir.join("home/disk.iso"), [0; 1025]).unwrap();
+        let argv = [
+            "ranch",
+            "-v",
+            "-C",
+            dir.to_str().unwrap(),
+            "--max-file-size",
+            "1K",
+            "home",
+        ];
+        let args = Args::parse_from(argv);
+        let mut stderr = Vec::new();
+
+        let actions = plan(
+            &args,
+            "home",
+            &dir.join("home"),
+            tmp_dir.path(),
+            &mut stderr,
+        )
+        .unwrap();
+
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].dst(), tmp_dir.path().join(".vimrc"));
+        assert!(String::from_utf8(stderr)
+            .unwrap()
+            .contains("disk.iso is 1025 bytes"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_plan_special_files() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        create_dir_all(dir.join("home")).unwrap();
+        let fifo = std::ffi::CString::new(dir.join("home/fifo").to_str().unwrap()).unwrap();
+        // SAFETY: `fifo` is a NUL-terminated path that outlives the call.
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+        let plan_with = |policy: &str| {
+            let argv = [
+                "ranch",
+                "-C",
+                dir.to_str().unwrap(),
+                "--special-files",
+                policy,
+                "home",
+            ];
+            let args = Args::parse_from(argv);
+            plan(
+                &args,
+                "home",
+                &dir.join("home"),
+                tmp_dir.path(),
+                &mut io::sink(),
+            )
+        };
+
+        assert!(plan_with("skip-with-warning").unwrap().is_empty());
+        assert!(plan_with("error").unwrap_err().contains("is a FIFO"));
+        assert_eq!(plan_with("link").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_stream() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        create_dir_all(dir.join("home/.config/skipped")).unwrap();
+        for name in ["a", "b", ".config/c", ".config/skipped/d"] {
+            File::create(dir.join("home").join(name)).unwrap();
+        }
+        let argv = [
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "--exclude",
+            "skipped/",
+        ];
+        let args = Args::parse_from(argv.into_iter().chain(["home"]));
+        let prefix_path = dir.join("home");
+        let mut stderr = io::sink();
+
+        let mut stream = stream(&args, "home", &prefix_path, tmp_dir.path(), &mut stderr).unwrap();
+        let first = stream.next().unwrap().unwrap();
+        let rest: Vec<_> = stream.map(Result::unwrap).collect();
+
+        let mut dsts: Vec<_> = [first]
+            .iter()
+            .chain(&rest)
+            .map(|a| a.dst().to_owned())
+            .collect();
+        dsts.sort();
+        assert_eq!(
+            dsts,
+            [".config/c", "a", "b"].map(|name| tmp_dir.path().join(name))
+        );
+    }
+
+    #[test]
+    fn test_is_excluded() {
+        let prefix = Path::new("home");
+        let excluded = |pattern: &str, path: &str, is_dir: bool| {
+            is_excluded(&[pattern.to_owned()], prefix, &prefix.join(path), is_dir)
+        };
+
+        assert!(excluded("*.bak", ".config/app.bak", false));
+        assert!(excluded("cache/", ".config/cache", true));
+        assert!(!excluded("cache/", ".config/cache", false));
+        assert!(excluded("/README.md", "README.md", false));
+        assert!(!excluded("/README.md", "docs/README.md", false));
+        assert!(excluded(".config/*.bak", ".config/app.bak", false));
+        assert!(!excluded(".config/*.bak", ".local/.config/app.bak", false));
+        assert!(!excluded("*", "", true));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_plan_follow_source_links() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().canonicalize().unwrap().join(".dotfiles");
+        create_dir_all(dir.join("home")).unwrap();
+        create_dir_all(dir.join("shared/git")).unwrap();
+        File::create(dir.join("shared/bashrc")).unwrap();
+        File::create(dir.join("shared/git/config")).unwrap();
+        std::os::unix::fs::symlink("../shared/bashrc", dir.join("home/.bashrc")).unwrap();
+        std::os::unix::fs::symlink("../shared/git", dir.join("home/.gitconf")).unwrap();
+        let args = Args::parse_from([
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "--follow-source-links",
+            "home",
+        ]);
+
+        let mut actions = plan(
+            &args,
+            "home",
+            &dir.join("home"),
+            tmp_dir.path(),
+            &mut io::sink(),
+        )
+        .unwrap();
+        actions.sort_by(|a, b| a.dst().cmp(b.dst()));
+
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].src(), dir.join("shared/bashrc"));
+        assert_eq!(actions[0].dst(), tmp_dir.path().join(".bashrc"));
+        assert_eq!(actions[1].src(), dir.join("shared/git/config"));
+        assert_eq!(actions[1].dst(), tmp_dir.path().join(".gitconf/config"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_links() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        create_dir_all(dir.join("home/.config/nvim")).unwrap();
+        create_dir_all(dir.join("home/.local")).unwrap();
+        File::create(dir.join("home/.config/nvim/init.lua")).unwrap();
+        File::create(dir.join("home/.local/a")).unwrap();
+        File::create(dir.join("home/.vimrc")).unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "home"]);
+        let actions = plan(
+            &args,
+            "home",
+            &dir.join("home"),
+            tmp_dir.path(),
+            &mut io::sink(),
+        )
+        .unwrap();
+        assert!(check_links(&args, &actions).is_ok());
+
+        let symlink = std::os::unix::fs::symlink;
+        symlink(dir.join("home/.config"), tmp_dir.path().join(".config")).unwrap();
+        symlink(
+            tmp_dir.path().join(".local2"),
+            tmp_dir.path().join(".local"),
+        )
+        .unwrap();
+        symlink(
+            tmp_dir.path().join(".local"),
+            tmp_dir.path().join(".local2"),
+        )
+        .unwrap();
+        symlink(tmp_dir.path().join(".vimrc"), tmp_dir.path().join(".vimrc")).unwrap();
+
+        let err = check_links(&args, &actions).unwrap_err();
+        assert!(err.contains(".config/nvim leads into"));
+        assert!(err.contains(".local goes through a loop of symlinks"));
+        assert!(err.contains(".vimrc goes through a loop of symlinks"));
+    }
+
+    #[test]
+    fn test_check_case_collisions() {
+        let link = |src: &str, dst: &str| Action::Link {
+            src: PathBuf::from(src),
+            dst: PathBuf::from(dst),
+        };
+        let actions = [
+            link("/d/home/.vimrc", "/home/.vimrc"),
+            link("/d/home/README", "/home/README"),
+            link("/d/home/ReadMe", "/home/ReadMe"),
+        ];
+
+        assert!(check_case_collisions(&actions[..2]).is_ok());
+        let err = check_case_collisions(&actions).unwrap_err();
+        assert!(err.starts_with("/d/home/README and /d/home/ReadMe differ only by case"));
+
+        // Files in directories differing only by case collide too, even with others between.
+        let actions = [
+            link("/d/home/.Config/a", "/home/.Config/a"),
+            link("/d/home/.Config/b/c", "/home/.Config/b/c"),
+            link("/d/home/.config/a", "/home/.config/a"),
+        ];
+        let err = check_case_collisions(&actions).unwrap_err();
+        assert!(err.starts_with("/d/home/.Config/a and /d/home/.config/a differ only by case"));
+    }
+}
@@ -0,0 +1,271 @@
+use crate::i18n::tr;
+use crate::paths;
+use crate::plan::{self, Action};
+use crate::state::sha256_hex;
+use crate::status::{self, LinkState};
+use crate::{load_package, stow_actions, Args};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Format of plan files; bumped whenever old plans can no longer be read correctly.
+const VERSION: u32 = 2;
+
+/// Actions planned for one package, and what each target path held at the time.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PackagePlan {
+    pub package: String,
+    pub target: PathBuf,
+    pub steps: Vec<Step>,
+}
+
+/// A planned action, and what its package file and target path held when it was planned.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Step {
+    #[serde(flatten)]
+    pub action: Action,
+    pub state: LinkState,
+    /// The package file's contents and modification time; None for directories.
+    pub source: Option<Source>,
+    /// What was at the target path, as described by [describe_target]; None if nothing was.
+    pub target: Option<String>,
+}
+
+/// Fingerprint of a package file, for telling whether it changed after planning.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Source {
+    pub sha256: String,
+    pub modified: SystemTime,
+}
+
+impl Source {
+    fn read(path: &Path) -> Option<Source> {
+        Some(Source {
+            sha256: sha256_hex(&fs::read(path).ok()?),
+            modified: fs::metadata(path).ok()?.modified().ok()?,
+        })
+    }
+}
+
+/// What's at `path`: where a link points, a file's hash, or a directory; None if nothing is.
+fn describe_target(path: &Path) -> Option<String> {
+    let metadata = path.symlink_metadata().ok()?;
+    Some(match () {
+        _ if metadata.is_symlink() => match paths::resolve_link(path) {
+            Some(link) => format!("a link to {}", link.display()),
+            None => String::from("a link"),
+        },
+        _ if metadata.is_dir() => String::from("a directory"),
+        _ => match fs::read(path) {
+            Ok(contents) => format!("a file with sha256 {}", sha256_hex(&contents)),
+            Err(_) => String::from("an unreadable file"),
+        },
+    })
+}
+
+/// Everything 'ranch plan' intends to do, as written to a plan file.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PlanFile {
+    pub version: u32,
+    pub dir: PathBuf,
+    pub packages: Vec<PackagePlan>,
+}
+
+/// Plans `packages` (or every package in 'DIR') and writes the plan as JSON to `output`, or to
+/// stdout.
+pub fn write(
+    args: &Args,
+    packages: &[String],
+    output: Option<&Path>,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let plan = make(args, packages, stderr)?;
+    let json = serde_json::to_string_pretty(&plan).map_err(|e| e.to_string())? + "\n";
+    match output {
+        Some(path) => fs::write(path, json)
+            .map_err(|e| tr!("could-not-write", path = path.display(), error = e)),
+        None => io::stdout()
+            .write_all(json.as_bytes())
+            .map_err(|e| tr!("could-not-write-plan", error = e)),
+    }
+}
+
+/// Plans `packages` (or every package in 'DIR'), recording the state of each target path.
+pub fn make(
+    args: &Args,
+    packages: &[String],
+    stderr: &mut dyn io::Write,
+) -> Result<PlanFile, String> {
+    let packages = match packages {
+        [] => plan::packages(&args.dir)?,
+        _ => packages.to_vec(),
+    };
+    let mut plans = Vec::new();
+    for package in packages {
+        let (prefix_path, _, target_path) = load_package(args, &package)?;
+        let actions = plan::plan(args, &package, &prefix_path, &target_path, stderr)?;
+        plans.push(PackagePlan {
+            steps: actions
+                .into_iter()
+                .map(|action| Step {
+                    state: status::link_state(args, &action),
+                    source: Source::read(action.src()),
+                    target: describe_target(action.dst()),
+                    action,
+                })
+                .collect(),
+            target: target_path,
+            package,
+        });
+    }
+    Ok(PlanFile {
+        version: VERSION,
+        dir: args.dir.clone(),
+        packages: plans,
+    })
+}
+
+/// Runs the plan in the file at `path`, exactly as planned. Nothing is deployed if the plan
+/// was made for another 'DIR', or if any package file or target path has changed since, so
+/// that what was reviewed is what runs.
+pub fn apply(args: &Args, path: &Path, stderr: &mut dyn io::Write) -> Result<(), String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| tr!("could-not-read", path = path.display(), error = e))?;
+    let plan: PlanFile = serde_json::from_str(&text)
+        .map_err(|e| tr!("invalid-plan", path = path.display(), error = e))?;
+    if plan.version != VERSION {
+        return Err(tr!(
+            "plan-version",
+            path = path.display(),
+            version = plan.version,
+            supported = VERSION
+        ));
+    }
+    if plan.dir != args.dir {
+        return Err(tr!(
+            "plan-other-dir",
+            path = path.display(),
+            planned = plan.dir.display(),
+            dir = args.dir.display()
+        ));
+    }
+    let drift = drift(args, &plan);
+    if !drift.is_empty() {
+        return Err(tr!(
+            "plan-stale",
+            path = path.display(),
+            drift = drift.join("\n  ")
+        ));
+    }
+    for package in &plan.packages {
+        let (prefix_path, manifest, _) = load_package(args, &package.package)?;
+        let actions: Vec<Action> = package.steps.iter().map(|s| s.action.clone()).collect();
+        stow_actions(
+            args,
+            &package.package,
+            &prefix_path,
+            &manifest,
+            &package.target,
+            &actions,
+            stderr,
+        )?;
+    }
+    Ok(())
+}
+
+/// How the package files and target paths in `plan` differ from when it was made, one line
+/// each.
+fn drift(args: &Args, plan: &PlanFile) -> Vec<String> {
+    let mut drift = Vec::new();
+    for package in &plan.packages {
+        for step in &package.steps {
+            let src = step.action.src();
+            let source = Source::read(src);
+            match (&step.source, &source) {
+                (Some(old), Some(new)) if old.sha256 != new.sha256 => drift.push(tr!(
+                    "drift-contents",
+                    path = src.display(),
+                    old = old.sha256,
+                    new = new.sha256
+                )),
+                (Some(old), Some(new)) if old.modified != new.modified => {
+                    drift.push(tr!("drift-modified", path = src.display()))
+                }
+                (Some(_), None) => drift.push(tr!("drift-gone", path = src.display())),
+                _ => {}
+            }
+            let dst = step.action.dst();
+            let target = describe_target(dst);
+            let state = status::link_state(args, &step.action);
+            if target != step.target || state != step.state {
+                let describe = |t: &Option<String>| t.clone().unwrap_or(String::from("nothing"));
+                drift.push(tr!(
+                    "drift-target",
+                    path = dst.display(),
+                    was = describe(&step.target),
+                    was_state = step.state,
+                    now = describe(&target),
+                    now_state = state
+                ));
+            }
+        }
+    }
+    drift
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_plan_file() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("home")).unwrap();
+        fs::write(dir.join("home/.vimrc"), "").unwrap();
+        fs::write(dir.join("home/.zshrc"), "").unwrap();
+        let plan_path = tmp_dir.path().join("plan.json");
+        let args = |command: &[&str]| {
+            Args::parse_from([&["ranch", "-C", dir.to_str().unwrap()], command].concat())
+        };
+        let plan_args = args(&["plan", "home"]);
+        let apply_args = args(&["apply", plan_path.to_str().unwrap()]);
+        write(
+            &plan_args,
+            &["home".to_owned()],
+            Some(&plan_path),
+            &mut io::sink(),
+        )
+        .unwrap();
+
+        fs::write(tmp_dir.path().join(".zshrc"), "").unwrap();
+        let e = apply(&apply_args, &plan_path, &mut io::sink()).unwrap_err();
+        assert!(
+            e.contains(".zshrc: was nothing (missing), now a file with sha256"),
+            "{}",
+            e
+        );
+        assert!(!tmp_dir.path().join(".vimrc").exists());
+
+        fs::remove_file(tmp_dir.path().join(".zshrc")).unwrap();
+        fs::write(dir.join("home/.vimrc"), "set number").unwrap();
+        let e = apply(&apply_args, &plan_path, &mut io::sink()).unwrap_err();
+        assert!(e.contains(".vimrc: contents changed, from sha256"), "{}", e);
+        assert!(!e.contains(".zshrc"), "{}", e);
+
+        write(
+            &plan_args,
+            &["home".to_owned()],
+            Some(&plan_path),
+            &mut io::sink(),
+        )
+        .unwrap();
+        apply(&apply_args, &plan_path, &mut io::sink()).unwrap();
+        assert!(tmp_dir.path().join(".vimrc").is_symlink());
+        assert!(tmp_dir.path().join(".zshrc").is_symlink());
+    }
+}
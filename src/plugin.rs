@@ -0,0 +1,207 @@
+use crate::i18n::tr;
+use crate::{source, Args};
+use clap::CommandFactory;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Prefix of the executables on 'PATH' that extend ranch, as 'ranch-foo' provides 'ranch foo'.
+const PREFIX: &str = "ranch-";
+
+/// Runs the plugin named by `argv`, if its first positional argument isn't a built-in subcommand
+/// or a package in 'DIR' but a 'ranch-NAME' executable is on 'PATH'. Options before the name are
+/// parsed as usual and exported to the plugin's environment; everything after it is passed on
+/// untouched. Returns the plugin's exit code, or None if `argv` doesn't name a plugin.
+pub fn dispatch(argv: &[OsString], stderr: &mut dyn io::Write) -> Option<Result<i32, String>> {
+    let i = command_index(argv)?;
+    let program = search(argv[i].to_str()?, &env::var_os("PATH")?)?;
+    let matches = Args::command().try_get_matches_from(&argv[..=i]).ok()?;
+    let mut args = Args::from_matches(&matches).ok()?;
+    if let Some(url) = args.from.clone() {
+        if let Err(e) = source::fetch(&mut args, &url, stderr) {
+            return Some(Err(e));
+        }
+    }
+    if args.dir.join(&argv[i]).exists() {
+        return None;
+    }
+    Some(run(&args, &program, &argv[i + 1..]))
+}
+
+/// Index of the first positional argument in `argv`, unless it's a built-in subcommand. Words
+/// that aren't valid unicode can't name a plugin.
+fn command_index(argv: &[OsString]) -> Option<usize> {
+    let mut cmd = Args::command();
+    cmd.build();
+    let takes_value = |arg: Option<&clap::Arg>| arg.is_some_and(|a| a.get_action().takes_values());
+    let mut i = 1;
+    while i < argv.len() {
+        let word = argv[i].to_str()?;
+        if word == "--" {
+            return None;
+        } else if let Some(long) = word.strip_prefix("--") {
+            let arg = cmd.get_arguments().find(|a| {
+                a.get_long_and_visible_aliases()
+                    .is_some_and(|l| l.contains(&long))
+            });
+            if !long.contains('=') && takes_value(arg) {
+                i += 1;
+            }
+        } else if let Some(shorts) = word.strip_prefix('-').filter(|s| !s.is_empty()) {
+            // In a cluster such as '-vC DIR', an option taking a value ends it.
+            for (j, c) in shorts.char_indices() {
+                let arg = cmd.get_arguments().find(|a| a.get_short() == Some(c));
+                if takes_value(arg) {
+                    if j + c.len_utf8() == shorts.len() {
+                        i += 1;
+                    }
+                    break;
+                }
+            }
+        } else if cmd.find_subcommand(word).is_some() {
+            return None;
+        } else {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Finds the executable providing the plugin `name` in the directories of `path`.
+fn search(name: &str, path: &OsStr) -> Option<PathBuf> {
+    if name.is_empty() || name.contains(['/', std::path::MAIN_SEPARATOR]) {
+        return None;
+    }
+    let file = format!("{}{}{}", PREFIX, name, env::consts::EXE_SUFFIX);
+    env::split_paths(path)
+        .map(|dir| dir.join(&file))
+        .find(|p| is_executable(p))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Runs `program` with `plugin_args`, exporting the global options so that it can act on the
+/// same packages, or call '$RANCH' back, the way ranch itself was asked to.
+fn run(args: &Args, program: &Path, plugin_args: &[OsString]) -> Result<i32, String> {
+    let flag = |set: bool| if set { "1" } else { "0" };
+    let mut cmd = Command::new(program);
+    cmd.args(plugin_args)
+        .env("RANCH_DIR", &args.dir)
+        .env("RANCH_VERBOSE", args.verbose.to_string())
+        .env("RANCH_DRY_RUN", flag(args.dry_run))
+        .env("RANCH_STRICT", flag(args.strict))
+        .env("RANCH_VCS_ONLY", flag(args.vcs_only))
+        .env("RANCH_SANDBOX_HOOKS", flag(args.sandbox_hooks));
+    if let Ok(ranch) = env::current_exe() {
+        cmd.env("RANCH", ranch);
+    }
+    let optional: [(&str, Option<OsString>); 7] = [
+        (
+            "RANCH_AGE_IDENTITY",
+            args.age_identity.clone().map(Into::into),
+        ),
+        (
+            "RANCH_GPG_RECIPIENT",
+            args.gpg_recipient.clone().map(Into::into),
+        ),
+        (
+            "RANCH_MINISIGN_KEY",
+            args.minisign_key.clone().map(Into::into),
+        ),
+        (
+            "RANCH_ALLOWED_SIGNERS",
+            args.allowed_signers.clone().map(Into::into),
+        ),
+        ("RANCH_LANG", args.lang.clone().map(Into::into)),
+        ("RANCH_LOG", args.log.as_ref().map(|f| f.to_string().into())),
+        (
+            "RANCH_HOOK_TIMEOUT",
+            args.hook_timeout.map(|t| t.to_string().into()),
+        ),
+    ];
+    match &args.target {
+        Some(target) => cmd.env("RANCH_TARGET", target),
+        None => cmd.env_remove("RANCH_TARGET"),
+    };
+    for (name, value) in optional {
+        match value {
+            Some(value) => cmd.env(name, value),
+            None => cmd.env_remove(name),
+        };
+    }
+    let status = cmd
+        .status()
+        .map_err(|e| tr!("could-not-run", program = program.display(), error = e))?;
+    // A plugin killed by a signal has no exit code; report it as a failure.
+    Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    fn argv(words: &[&str]) -> Vec<OsString> {
+        words.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_command_index() {
+        assert_eq!(command_index(&argv(&["ranch", "foo", "-x"])), Some(1));
+        assert_eq!(
+            command_index(&argv(&["ranch", "-vC", "dir", "foo"])),
+            Some(3)
+        );
+        assert_eq!(command_index(&argv(&["ranch", "-Cdir", "foo"])), Some(2));
+        assert_eq!(
+            command_index(&argv(&["ranch", "--dir", "dir", "-n", "foo"])),
+            Some(4)
+        );
+        assert_eq!(
+            command_index(&argv(&["ranch", "--dir=dir", "foo"])),
+            Some(2)
+        );
+        assert_eq!(command_index(&argv(&["ranch", "-v", "status"])), None);
+        assert_eq!(command_index(&argv(&["ranch", "-D", "home"])), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let plugin = tmp_dir.path().join("ranch-env");
+        let out = tmp_dir.path().join("out");
+        fs::write(
+            &plugin,
+            format!(
+                "#!/bin/sh\necho \"$RANCH_DIR $RANCH_VERBOSE $RANCH_DRY_RUN $*\" > '{}'\nexit 3\n",
+                out.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&plugin, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+        let found = search("env", tmp_dir.path().as_os_str()).unwrap();
+        assert!(search("missing", tmp_dir.path().as_os_str()).is_none());
+        let args = Args::parse_from(["ranch", "-n", "-vv", "-C", "/dotfiles", "env"]);
+
+        let code = run(&args, &found, &argv(&["a", "--b"])).unwrap();
+
+        assert_eq!(code, 3);
+        assert_eq!(fs::read_to_string(out).unwrap(), "/dotfiles 2 1 a --b\n");
+    }
+}
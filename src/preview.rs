@@ -0,0 +1,83 @@
+use crate::plan::Action;
+use crate::status::{self, LinkState};
+use crate::Args;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+/// Prints what deploying (or with `delete`, deleting) `actions` would change, for
+/// '--dry-run-format diff': '+' for paths that would be linked or created, '-' for links that
+/// would be removed, and '!' for conflicts, grouped by directory. Paths that are already as
+/// they should be aren't listed.
+pub fn print(args: &Args, actions: &[Action], delete: bool, out: &mut dyn io::Write) {
+    let mut dirs: BTreeMap<&Path, Vec<String>> = BTreeMap::new();
+    for action in actions {
+        let dst = action.dst();
+        let line = match (status::link_state(args, action), delete, action) {
+            (LinkState::Missing, false, Action::Mkdir { .. }) => String::from("+ ./"),
+            (LinkState::Missing, false, Action::Link { src, .. }) => {
+                format!("+ {} -> {}", name(dst), src.display())
+            }
+            (LinkState::Missing, false, _) => format!("+ {}", name(dst)),
+            (LinkState::Conflict, false, _) => format!("! {}", name(dst)),
+            (LinkState::Deployed, true, Action::Link { .. }) => format!("- {}", name(dst)),
+            _ => continue,
+        };
+        let dir = match action {
+            Action::Mkdir { .. } => dst,
+            _ => dst.parent().unwrap_or(dst),
+        };
+        dirs.entry(dir).or_default().push(line);
+    }
+    for (dir, lines) in dirs {
+        _ = writeln!(out, "{}/", dir.display());
+        for line in lines {
+            _ = writeln!(out, "  {}", line);
+        }
+    }
+}
+
+fn name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_print() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let src = tmp_dir.path().join("src");
+        fs::write(&src, "").unwrap();
+        fs::create_dir(tmp_dir.path().join("nvim")).unwrap();
+        fs::write(tmp_dir.path().join("nvim/init.lua"), "").unwrap();
+        let link = |name: &str| Action::Link {
+            src: src.clone(),
+            dst: tmp_dir.path().join(name),
+        };
+        let args = Args::parse_from(["ranch", "-n", "home"]);
+        let mut out = Vec::new();
+
+        print(
+            &args,
+            &[link(".vimrc"), link("nvim/init.lua")],
+            false,
+            &mut out,
+        );
+
+        let expected = format!(
+            "{}/\n  + .vimrc -> {}\n{}/\n  ! init.lua\n",
+            tmp_dir.path().display(),
+            src.display(),
+            tmp_dir.path().join("nvim").display()
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+}
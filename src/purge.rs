@@ -0,0 +1,169 @@
+use crate::apply::{self, Summary};
+use crate::filesystem::Kind;
+use crate::i18n::tr;
+use crate::manifest::Manifest;
+use crate::plan;
+use crate::snapshot;
+use crate::state::State;
+use crate::{Args, LV_DEBUG, LV_INFO};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Removes every link in the target tree that resolves into 'DIR', whichever package it came
+/// from, including packages that have since been renamed or removed. The tree searched is
+/// '--target' if given, otherwise the targets of all packages in 'DIR'. 'DIR' itself is
+/// skipped, and directories ranch created are removed once they're left empty.
+pub fn purge(args: &Args, stderr: &mut dyn io::Write) -> Result<(), String> {
+    let mut summary = Summary::default();
+    for (dst, _) in links(args, stderr)? {
+        if args.verbosity("purge") >= LV_INFO {
+            _ = writeln!(stderr, "Unlinking {}", dst.display());
+        }
+        if !args.dry_run {
+            args.fs
+                .remove_file(&dst)
+                .map_err(|e| tr!("could-not-remove", path = dst.display(), error = e))?;
+            summary.removed.push(dst);
+        }
+    }
+    remove_created_dirs(args, &mut summary, stderr)
+}
+
+/// Every link in the target tree that resolves into 'DIR', as its path and where it points.
+pub fn links(args: &Args, stderr: &mut dyn io::Write) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let mut links = Vec::new();
+    for root in roots(args)? {
+        if args.verbosity("purge") >= LV_DEBUG {
+            _ = writeln!(stderr, "Searching {}", root.display());
+        }
+        search(args, &root, &mut links);
+    }
+    Ok(links)
+}
+
+/// Adds the links into 'DIR' under `path` to `links`, not following links to directories, and
+/// skipping 'DIR' itself and snapshots.
+fn search(args: &Args, path: &Path, links: &mut Vec<(PathBuf, PathBuf)>) {
+    match args.fs.kind(path) {
+        Some(Kind::Symlink) => {
+            if let Some(src) = args.fs.resolve_link(path) {
+                if src.starts_with(&args.dir) {
+                    links.push((path.to_path_buf(), src));
+                }
+            }
+        }
+        Some(Kind::Dir) if path != args.dir => {
+            if path.file_name() == Some(snapshot::BTRFS_SNAPSHOTS.as_ref()) {
+                return;
+            }
+            let mut entries = args.fs.read_dir(path).unwrap_or_default();
+            entries.sort();
+            for entry in entries {
+                search(args, &entry, links);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Removes the directories ranch created that the links removed in `summary` left empty.
+pub fn remove_created_dirs(
+    args: &Args,
+    summary: &mut Summary,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    if summary.removed.is_empty() {
+        return Ok(());
+    }
+    let mut state = State::load(args)?;
+    let tracked = state.created_dirs.len();
+    apply::remove_created_dirs(args, &[], &mut state, summary, stderr)?;
+    match state.created_dirs.len() == tracked || args.changes_nothing() {
+        true => Ok(()),
+        false => state.save(args),
+    }
+}
+
+/// The directories to search: '--target', or else the target of every package, leaving out
+/// ones inside another.
+fn roots(args: &Args) -> Result<Vec<PathBuf>, String> {
+    if let Some(target) = &args.target {
+        return Ok(vec![PathBuf::from(target)]);
+    }
+    let mut targets = vec![plan::target_path(args, "", &Manifest::default())?];
+    for package in plan::packages(&args.dir)? {
+        let manifest = Manifest::load(&args.dir.join(&package))?;
+        targets.push(plan::target_path(args, &package, &manifest)?);
+    }
+    targets.sort();
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for target in targets {
+        if !roots.iter().any(|r| target.starts_with(r)) {
+            roots.push(target);
+        }
+    }
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_purge() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("home")).unwrap();
+        fs::write(dir.join("home/.vimrc"), "").unwrap();
+        fs::create_dir_all(dir.join("nvim")).unwrap();
+        fs::write(
+            dir.join("nvim/.ranch.toml"),
+            format!("target = '{}'", tmp_dir.path().join("xdg").display()),
+        )
+        .unwrap();
+        fs::create_dir_all(tmp_dir.path().join("xdg/nvim")).unwrap();
+        let link = |src: &Path, dst: &Path| std::os::unix::fs::symlink(src, dst).unwrap();
+        link(&dir.join("home/.vimrc"), &tmp_dir.path().join(".vimrc"));
+        link(&dir.join("gone/.zshrc"), &tmp_dir.path().join(".zshrc"));
+        link(
+            &dir.join("nvim/init.lua"),
+            &tmp_dir.path().join("xdg/nvim/init.lua"),
+        );
+        link(Path::new("/etc/hosts"), &tmp_dir.path().join("hosts"));
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "purge"]);
+
+        purge(&args, &mut io::sink()).unwrap();
+
+        assert!(!tmp_dir.path().join(".vimrc").is_symlink());
+        assert!(!tmp_dir.path().join(".zshrc").is_symlink());
+        assert!(!tmp_dir.path().join("xdg/nvim/init.lua").is_symlink());
+        assert!(tmp_dir.path().join("hosts").is_symlink());
+        assert!(dir.join("home/.vimrc").exists());
+    }
+
+    #[test]
+    fn test_purge_through_fs() {
+        use crate::filesystem::{Filesystem, Fs, MemoryFs};
+        use std::sync::Arc;
+
+        let fs = Arc::new(MemoryFs::default());
+        fs.file("/dotfiles/home/.vimrc", "")
+            .link("/home/.vimrc", "/dotfiles/home/.vimrc")
+            .link("/home/.config/zsh", "../../dotfiles/zsh")
+            .link("/home/hosts", "/etc/hosts")
+            .link("/home/.ranch-snapshots/.vimrc", "/dotfiles/home/.vimrc");
+        let mut args = Args::parse_from(["ranch", "-C", "/dotfiles", "-t", "/home", "purge"]);
+        args.fs = Fs(fs.clone());
+
+        purge(&args, &mut io::sink()).unwrap();
+
+        assert_eq!(fs.kind(Path::new("/home/.vimrc")), None);
+        assert_eq!(fs.kind(Path::new("/home/.config/zsh")), None);
+        assert!(fs.is_symlink(Path::new("/home/hosts")));
+        assert!(fs.is_symlink(Path::new("/home/.ranch-snapshots/.vimrc")));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::apply;
+use crate::i18n::tr;
+use crate::purge;
+use crate::{load_package, Args, LV_INFO};
+use std::io;
+use std::path::Path;
+
+/// Renames the package `old` to `new`, repointing every link in the target tree that resolved
+/// into it, so that no machine is left with orphaned links. On failure, links already
+/// repointed are reported so they can be fixed by hand or by restowing.
+pub fn rename(args: &Args, old: &str, new: &str, stderr: &mut dyn io::Write) -> Result<(), String> {
+    load_package(args, old)?;
+    if new.is_empty() || Path::new(new).components().count() != 1 || new.starts_with('.') {
+        return Err(tr!("invalid-package-name", package = new));
+    }
+    let from = args.dir.join(old);
+    let to = args.dir.join(new);
+    if args.fs.kind(&to).is_some() {
+        return Err(tr!("package-exists", package = new));
+    }
+    // Found before renaming, while the package's own target is still known.
+    let links: Vec<_> = purge::links(args, stderr)?
+        .into_iter()
+        .filter(|(_, src)| src.starts_with(&from))
+        .collect();
+
+    if args.verbosity("rename") >= LV_INFO || args.dry_run {
+        _ = writeln!(
+            stderr,
+            "Renaming {} to {}; repointing {} links",
+            from.display(),
+            to.display(),
+            links.len()
+        );
+    }
+    if args.dry_run {
+        return Ok(());
+    }
+    args.fs.rename(&from, &to).map_err(|e| {
+        tr!(
+            "could-not-rename",
+            path = from.display(),
+            destination = to.display(),
+            error = e
+        )
+    })?;
+    let mut failed = Vec::new();
+    for (dst, src) in &links {
+        let src = to.join(src.strip_prefix(&from).unwrap_or(src));
+        if args.verbosity("rename") >= LV_INFO {
+            _ = writeln!(stderr, "Repointing {} -> {}", dst.display(), src.display());
+        }
+        if let Err(e) = apply::repoint(args, dst, &src, stderr) {
+            failed.push(e);
+        }
+    }
+    match failed.is_empty() {
+        true => Ok(()),
+        false => Err(tr!(
+            "links-not-repointed",
+            old = old,
+            new = new,
+            count = failed.len(),
+            failures = failed.join("\n  ")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rename() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("zsh/.config")).unwrap();
+        fs::write(dir.join("zsh/.zshrc"), "").unwrap();
+        fs::write(dir.join("zsh/.config/starship.toml"), "").unwrap();
+        fs::create_dir_all(tmp_dir.path().join(".config")).unwrap();
+        std::os::unix::fs::symlink(dir.join("zsh/.zshrc"), tmp_dir.path().join(".zshrc")).unwrap();
+        std::os::unix::fs::symlink(
+            "../.dotfiles/zsh/.config/starship.toml",
+            tmp_dir.path().join(".config/starship.toml"),
+        )
+        .unwrap();
+        let args = Args::parse_from([
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "rename",
+            "zsh",
+            "shell",
+        ]);
+
+        assert!(rename(&args, "zsh", "a/b", &mut io::sink()).is_err());
+        rename(&args, "zsh", "shell", &mut io::sink()).unwrap();
+
+        assert!(!dir.join("zsh").exists());
+        assert_eq!(
+            fs::read_link(tmp_dir.path().join(".zshrc")).unwrap(),
+            dir.join("shell/.zshrc")
+        );
+        assert_eq!(
+            fs::read_link(tmp_dir.path().join(".config/starship.toml")).unwrap(),
+            Path::new("../.dotfiles/shell/.config/starship.toml")
+        );
+        assert!(tmp_dir.path().join(".config/starship.toml").exists());
+    }
+}
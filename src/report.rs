@@ -0,0 +1,258 @@
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::notification::hostname;
+use crate::plan::{self, Action};
+use crate::purge;
+use crate::status::{self, LinkState};
+use crate::{load_package, Args, LV_WARN};
+use clap::ValueEnum;
+use serde_json::json;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Format of JSON run reports; bumped whenever a field changes meaning or goes away.
+const VERSION: u32 = 1;
+
+/// Styles the page; rows in trouble are tinted, so that they stand out when skimming.
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+th { background: #eee; }
+code { font-size: 0.9em; }
+tr.missing { background: #fff4cc; }
+tr.conflicting, tr.broken { background: #fdd; }";
+
+/// Writes a self-contained HTML page to `output`, or standard output, on how deployed
+/// `packages` (or every package in 'DIR') are: a summary table, a table of each package's files
+/// and their state, and the links into 'DIR' that are broken because their package file is gone.
+pub fn html(
+    args: &Args,
+    packages: &[String],
+    output: Option<&Path>,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let packages = match packages {
+        [] => plan::packages(&args.dir)?,
+        _ => packages.to_vec(),
+    };
+    let mut planned = Vec::new();
+    for package in packages {
+        let (prefix_path, _, target_path) = load_package(args, &package)?;
+        let actions = plan::plan(args, &package, &prefix_path, &target_path, stderr)?;
+        planned.push((package, actions));
+    }
+    let broken: Vec<_> = purge::links(args, stderr)?
+        .into_iter()
+        .filter(|(_, src)| !(src.exists() || src.is_symlink()))
+        .collect();
+    let page = render(args, &planned, &broken);
+    match output {
+        Some(path) => fs::write(path, page)
+            .map_err(|e| tr!("could-not-write", path = path.display(), error = e)),
+        None => io::stdout()
+            .write_all(page.as_bytes())
+            .map_err(|e| tr!("could-not-write-report", error = e)),
+    }
+}
+
+fn render(args: &Args, planned: &[(String, Vec<Action>)], broken: &[(PathBuf, PathBuf)]) -> String {
+    let host = escape(&hostname());
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>ranch report: {host}</title>\n<style>\n{STYLE}\n</style>\n</head>\n<body>\n\
+         <h1>ranch report: {host}</h1>\n<p>Packages in <code>{}</code></p>\n",
+        escape(&args.dir.display().to_string())
+    );
+    let states: Vec<Vec<LinkState>> = planned
+        .iter()
+        .map(|(_, actions)| {
+            actions
+                .iter()
+                .map(|a| status::link_state(args, a))
+                .collect()
+        })
+        .collect();
+
+    page += "<h2>Summary</h2>\n<table>\n\
+             <tr><th>Package</th><th>Deployed</th><th>Missing</th><th>Conflicting</th></tr>\n";
+    for ((package, _), states) in planned.iter().zip(&states) {
+        let count = |state| states.iter().filter(|&&s| s == state).count();
+        _ = writeln!(
+            page,
+            "<tr><td><a href=\"#{0}\">{0}</a></td><td>{1}</td><td>{2}</td><td>{3}</td></tr>",
+            escape(package),
+            count(LinkState::Deployed),
+            count(LinkState::Missing),
+            count(LinkState::Conflict)
+        );
+    }
+    page += "</table>\n";
+
+    for ((package, actions), states) in planned.iter().zip(&states) {
+        _ = writeln!(
+            page,
+            "<h2 id=\"{0}\">{0}</h2>\n<table>\n\
+             <tr><th>Target</th><th>Package file</th><th>State</th></tr>",
+            escape(package)
+        );
+        for (action, state) in actions.iter().zip(states) {
+            _ = writeln!(
+                page,
+                "<tr class=\"{2}\"><td><code>{0}</code></td><td><code>{1}</code></td>\
+                 <td>{2}</td></tr>",
+                escape(&action.dst().display().to_string()),
+                escape(&action.src().display().to_string()),
+                state
+            );
+        }
+        page += "</table>\n";
+    }
+
+    _ = writeln!(page, "<h2>Broken links</h2>");
+    match broken.is_empty() {
+        true => page += "<p>None</p>\n",
+        false => {
+            page += "<table>\n<tr><th>Link</th><th>Missing package file</th></tr>\n";
+            for (dst, src) in broken {
+                _ = writeln!(
+                    page,
+                    "<tr class=\"broken\"><td><code>{}</code></td><td><code>{}</code></td></tr>",
+                    escape(&dst.display().to_string()),
+                    escape(&src.display().to_string())
+                );
+            }
+            page += "</table>\n";
+        }
+    }
+    page += "</body>\n</html>\n";
+    page
+}
+
+/// Writes a JSON report of the run that began at `started` to `path`, for '--report': the
+/// options and config it ran with, less the webhook's URL, which often carries a token, each
+/// package's plan, changes, error, and timing, and the
+/// `error` that stopped the run, if one did. Failing to write it is only a warning, since the
+/// run itself is over.
+pub fn json(
+    args: &Args,
+    started: SystemTime,
+    error: Option<&str>,
+    path: &Path,
+    stderr: &mut dyn io::Write,
+) {
+    let config_path = Config::path();
+    let settings = config_path
+        .as_ref()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|text| text.parse::<toml::Table>().ok())
+        .map(redact);
+    let outcomes = args
+        .outcomes
+        .lock()
+        .map(|outcomes| outcomes.clone())
+        .unwrap_or_default();
+    let report = json!({
+        "version": VERSION,
+        "host": hostname(),
+        "started": started.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        "elapsed_ms": started.elapsed().unwrap_or_default().as_millis() as u64,
+        "options": {
+            "dir": args.dir,
+            "target": args.target,
+            "dry_run": args.dry_run,
+            "check": args.check,
+            "exists": name(&args.exists),
+            "type_conflict": name(&args.type_conflict),
+            "link_mode": name(&args.link_mode),
+            "backend": name(&args.backend),
+            "relative": args.relative,
+        },
+        "config": {
+            "path": config_path,
+            "settings": settings,
+        },
+        "packages": outcomes,
+        "error": error,
+    });
+    let text = serde_json::to_string_pretty(&report).unwrap_or_default() + "\n";
+    if let Err(e) = fs::write(path, text) {
+        if args.verbosity("report") >= LV_WARN {
+            let error = tr!("could-not-write", path = path.display(), error = e);
+            _ = writeln!(stderr, "{}", tr!("warning", message = error));
+        }
+    }
+}
+
+/// `settings` with the webhook's URL replaced by a placeholder.
+fn redact(mut settings: toml::Table) -> toml::Table {
+    let notify = settings.get_mut("notify").and_then(|n| n.as_table_mut());
+    if let Some(webhook) = notify.and_then(|n| n.get_mut("webhook")) {
+        *webhook = toml::Value::from("<redacted>");
+    }
+    settings
+}
+
+/// The name by which `value` is given on the command line.
+fn name(value: &impl ValueEnum) -> Option<String> {
+    value.to_possible_value().map(|v| v.get_name().to_owned())
+}
+
+/// `s` escaped for HTML text and attribute values.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_render() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("vim")).unwrap();
+        fs::write(dir.join("vim/.vimrc"), "").unwrap();
+        fs::write(tmp_dir.path().join(".vimrc"), "").unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "vim"]);
+        let actions = vec![
+            Action::Link {
+                src: dir.join("vim/.vimrc"),
+                dst: tmp_dir.path().join(".vimrc"),
+            },
+            Action::Link {
+                src: dir.join("vim/<b>"),
+                dst: tmp_dir.path().join("<b>"),
+            },
+        ];
+        let broken = [(PathBuf::from("/home/alice/.zshrc"), dir.join("zsh/.zshrc"))];
+
+        let page = render(&args, &[(String::from("vim"), actions)], &broken);
+
+        assert!(page
+            .contains("<tr><td><a href=\"#vim\">vim</a></td><td>0</td><td>1</td><td>1</td></tr>"));
+        assert!(page.contains("<tr class=\"conflicting\">"));
+        assert!(page.contains("&lt;b&gt;</code></td><td>missing</td>"));
+        assert!(page.contains("<tr class=\"broken\"><td><code>/home/alice/.zshrc</code>"));
+        assert!(!page.contains("<b>"));
+    }
+
+    #[test]
+    fn test_redact() {
+        let settings: toml::Table = "target = '~'\n[notify]\nwebhook = 'https://x/?token=hunter2'"
+            .parse()
+            .unwrap();
+
+        let settings = redact(settings);
+
+        assert_eq!(settings["notify"]["webhook"].as_str(), Some("<redacted>"));
+        assert_eq!(settings["target"].as_str(), Some("~"));
+    }
+}
@@ -0,0 +1,212 @@
+use crate::i18n::tr;
+use crate::plan;
+use crate::status;
+use crate::{load_package, stow, unstow, Args};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, BufRead};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Code for requests that were understood but failed, such as 'apply' stopping on a conflict.
+const FAILED: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    /// Absent for notifications, which get no response.
+    id: Option<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PackageParams {
+    package: String,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StatusParams {
+    /// Defaults to every package in 'DIR'.
+    #[serde(default)]
+    packages: Vec<String>,
+}
+
+/// A JSON-RPC error object: code, message, and optional data.
+type Error = (i64, String, Option<Value>);
+
+/// Answers JSON-RPC 2.0 requests read one per line from `input`, writing one response line per
+/// request to `output`, until `input` ends.
+pub fn serve(args: &Args, input: impl BufRead, output: &mut dyn io::Write) -> Result<(), String> {
+    for line in input.lines() {
+        let line = line.map_err(|e| tr!("could-not-read-request", error = e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle(args, &line) {
+            writeln!(output, "{}", response)
+                .and_then(|_| output.flush())
+                .map_err(|e| tr!("could-not-write-response", error = e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles a single request, returning its response, or nothing for a notification.
+///
+/// Methods:
+///
+/// - `packages`: the names of every package in 'DIR'.
+/// - `plan {package}`: the actions deploying `package` would take.
+/// - `status {packages?}`: the deployed, missing, and conflicting paths of each package.
+/// - `apply {package, dry_run?}` and `delete {package, dry_run?}`: deploy or delete `package`,
+///   returning what would otherwise be printed to standard error as `log`.
+pub fn handle(args: &Args, line: &str) -> Option<String> {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => {
+            return Some(response(
+                Value::Null,
+                Err((PARSE_ERROR, e.to_string(), None)),
+            ))
+        }
+    };
+    let result = match request.jsonrpc.as_str() {
+        "2.0" => call(args, &request.method, request.params),
+        _ => Err((
+            INVALID_REQUEST,
+            String::from("'jsonrpc' must be \"2.0\""),
+            None,
+        )),
+    };
+    request.id.map(|id| response(id, result))
+}
+
+fn response(id: Value, result: Result<Value, Error>) -> String {
+    let response = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err((code, message, data)) => {
+            let mut error = json!({ "code": code, "message": message });
+            if let Some(data) = data {
+                error["data"] = data;
+            }
+            json!({ "jsonrpc": "2.0", "error": error, "id": id })
+        }
+    };
+    response.to_string()
+}
+
+fn call(args: &Args, method: &str, params: Value) -> Result<Value, Error> {
+    let failed = |e: String| (FAILED, e, None);
+    match method {
+        "packages" => plan::packages(&args.dir).map(|p| json!(p)).map_err(failed),
+        "plan" => {
+            let params: PackageParams = parse(params)?;
+            let (prefix_path, _, target_path) =
+                load_package(args, &params.package).map_err(failed)?;
+            let actions = plan::plan(
+                args,
+                &params.package,
+                &prefix_path,
+                &target_path,
+                &mut io::sink(),
+            )
+            .map_err(failed)?;
+            Ok(json!(actions))
+        }
+        "status" => {
+            let params: StatusParams = parse(params)?;
+            let packages = match params.packages.as_slice() {
+                [] => plan::packages(&args.dir).map_err(failed)?,
+                packages => packages.to_vec(),
+            };
+            let statuses = packages
+                .iter()
+                .map(|p| status::package_status(args, p, &mut io::sink()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(failed)?;
+            Ok(json!(statuses))
+        }
+        "apply" | "delete" => {
+            let params: PackageParams = parse(params)?;
+            let mut args = args.clone();
+            args.dry_run |= params.dry_run;
+            let mut log = Vec::new();
+            let result = match method {
+                "apply" => stow(&args, &params.package, &mut log),
+                _ => unstow(&args, &params.package, &mut log).map(|_| ()),
+            };
+            let log = json!({ "log": String::from_utf8_lossy(&log) });
+            match result {
+                Ok(()) => Ok(log),
+                Err(e) => Err((FAILED, e, Some(log))),
+            }
+        }
+        _ => Err((
+            METHOD_NOT_FOUND,
+            format!("Unknown method '{}'", method),
+            None,
+        )),
+    }
+}
+
+fn parse<T: DeserializeOwned>(params: Value) -> Result<T, Error> {
+    let params = match params {
+        Value::Null => json!({}),
+        params => params,
+    };
+    serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string(), None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_serve() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("home")).unwrap();
+        fs::write(dir.join("home/.vimrc"), "").unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "rpc"]);
+        let input = [
+            r#"{"jsonrpc": "2.0", "method": "plan", "params": {"package": "home"}, "id": 1}"#,
+            r#"{"jsonrpc": "2.0", "method": "apply", "params": {"package": "home", "dry_run": true}}"#,
+            r#"{"jsonrpc": "2.0", "method": "status", "id": 2}"#,
+            r#"{"jsonrpc": "2.0", "method": "nope", "id": 3}"#,
+            "{",
+        ]
+        .join("\n");
+
+        let mut output = Vec::new();
+        serve(&args, input.as_bytes(), &mut output).unwrap();
+
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["result"][0]["action"], "link");
+        assert_eq!(responses[1]["result"][0]["package"], "home");
+        assert_eq!(
+            responses[1]["result"][0]["missing"][0],
+            json!(tmp_dir.path().join(".vimrc"))
+        );
+        assert_eq!(responses[2]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses[3]["error"]["code"], PARSE_ERROR);
+        assert!(!tmp_dir.path().join(".vimrc").exists());
+    }
+}
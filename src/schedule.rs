@@ -0,0 +1,268 @@
+use crate::i18n::tr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// When something recurring, such as the daemon's drift check, should run.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Schedule {
+    /// A fixed interval, such as '30m'.
+    Every(Duration),
+    /// A five-field cron expression, in local time.
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// Parses an interval (a number with an optional 's', 'm', 'h', or 'd' suffix; seconds by
+    /// default), a cron expression such as '0 9 * * 1-5', or one of '@hourly', '@daily', and
+    /// '@weekly'.
+    pub fn parse(s: &str) -> Result<Schedule, String> {
+        let s = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            s => s,
+        };
+        if s.split_whitespace().count() == 5 {
+            return Cron::parse(s).map(Schedule::Cron);
+        }
+        parse_interval(s).map(Schedule::Every)
+    }
+
+    /// The first time after `now` that the schedule fires.
+    pub fn next_after(&self, now: SystemTime) -> SystemTime {
+        match self {
+            Schedule::Every(interval) => now + *interval,
+            Schedule::Cron(cron) => cron.next_after(now),
+        }
+    }
+}
+
+fn parse_interval(s: &str) -> Result<Duration, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let scale = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => 0,
+    };
+    match number.parse::<u64>().ok().and_then(|n| n.checked_mul(scale)) {
+        Some(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+        _ => Err(tr!("invalid-schedule", schedule = s)),
+    }
+}
+
+/// The minutes, hours, days of the month, months, and days of the week a cron expression
+/// matches, as bitmasks.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day-of-month and day-of-week fields were '*'; as in cron, when both are
+    /// restricted, a day matching either one matches.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// The parts of a local time that cron expressions match against.
+#[derive(Debug)]
+struct Tm {
+    minute: u32,
+    hour: u32,
+    day: u32,
+    month: u32,
+    weekday: u32,
+}
+
+impl Cron {
+    fn parse(s: &str) -> Result<Cron, String> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let parse = |i: usize, min: u32, max: u32| {
+            field(fields[i], min, max).map_err(|e| tr!("invalid-cron", expression = s, error = e))
+        };
+        let mut weekdays = parse(4, 0, 7)?;
+        // Both 0 and 7 are Sunday.
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Cron {
+            minutes: parse(0, 0, 59)?,
+            hours: parse(1, 0, 23)?,
+            days: parse(2, 1, 31)?,
+            months: parse(3, 1, 12)?,
+            weekdays,
+            any_day: fields[2].starts_with('*'),
+            any_weekday: fields[4].starts_with('*'),
+        })
+    }
+
+    fn matches(&self, tm: &Tm) -> bool {
+        let bit = |mask: u64, n: u32| mask & (1 << n) != 0;
+        let day = bit(self.days, tm.day);
+        let weekday = bit(self.weekdays, tm.weekday);
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        };
+        bit(self.minutes, tm.minute)
+            && bit(self.hours, tm.hour)
+            && bit(self.months, tm.month)
+            && day_matches
+    }
+
+    fn next_after(&self, now: SystemTime) -> SystemTime {
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let mut t = now - now % 60 + 60;
+        // Any satisfiable expression matches within four years (the span between leap days);
+        // one that never matches, such as '0 0 30 2 *', just waits that long.
+        for _ in 0..4 * 366 * 24 * 60 {
+            if self.matches(&local_time(t)) {
+                break;
+            }
+            t += 60;
+        }
+        UNIX_EPOCH + Duration::from_secs(t)
+    }
+}
+
+/// Parses one cron field: a comma-separated list of '*', 'N', or 'N-M', each optionally
+/// followed by '/STEP'.
+fn field(s: &str, min: u32, max: u32) -> Result<u64, String> {
+    let number = |n: &str| {
+        n.parse::<u32>()
+            .ok()
+            .filter(|n| (min..=max).contains(n))
+            .ok_or_else(|| tr!("cron-out-of-range", number = n, min = min, max = max))
+    };
+    let mut mask = 0;
+    for part in s.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<usize>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(tr!("cron-invalid-step", step = step)),
+            },
+            None => (part, 1),
+        };
+        let (lo, hi) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((lo, hi)) => (number(lo)?, number(hi)?),
+            // 'N/STEP' means every STEP from N on.
+            None if step > 1 => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+        if lo > hi {
+            return Err(tr!("cron-empty-range", range = range));
+        }
+        for n in (lo..=hi).step_by(step) {
+            mask |= 1 << n;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(unix)]
+fn local_time(t: u64) -> Tm {
+    let time = t as libc::time_t;
+    // SAFETY: both pointers are valid for the duration of the call, and an all-zero 'tm' is a
+    // valid value for localtime_r to overwrite.
+    let tm = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&time, &mut tm);
+        tm
+    };
+    Tm {
+        minute: tm.tm_min as u32,
+        hour: tm.tm_hour as u32,
+        day: tm.tm_mday as u32,
+        month: tm.tm_mon as u32 + 1,
+        weekday: tm.tm_wday as u32,
+    }
+}
+
+/// UTC, where there's no portable way to ask for local time.
+#[cfg(not(unix))]
+fn local_time(t: u64) -> Tm {
+    let days = t / 86400;
+    let secs = t % 86400;
+    // Converts days since 1970-01-01 to a civil date; see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    Tm {
+        minute: (secs / 60 % 60) as u32,
+        hour: (secs / 3600) as u32,
+        day: (doy - (153 * mp + 2) / 5 + 1) as u32,
+        month: (if mp < 10 { mp + 3 } else { mp - 9 }) as u32,
+        // 1970-01-01 was a Thursday.
+        weekday: ((days + 4) % 7) as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Schedule::parse("90").unwrap(),
+            Schedule::Every(Duration::from_secs(90))
+        );
+        assert_eq!(
+            Schedule::parse("30m").unwrap(),
+            Schedule::Every(Duration::from_secs(1800))
+        );
+        assert!(Schedule::parse("0").is_err());
+        assert!(Schedule::parse("5y").is_err());
+        assert!(Schedule::parse("18446744073709551615d").is_err());
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("* * * * */0").is_err());
+        assert!(Schedule::parse("5-1 * * * *").is_err());
+
+        let Schedule::Cron(cron) = Schedule::parse("*/15 9-17 * * 1-5,7").unwrap() else {
+            panic!("expected a cron expression");
+        };
+        let tm = |minute, hour, day, weekday| Tm {
+            minute,
+            hour,
+            day,
+            month: 6,
+            weekday,
+        };
+        assert!(cron.matches(&tm(45, 9, 3, 1)));
+        assert!(cron.matches(&tm(0, 17, 3, 0)));
+        assert!(!cron.matches(&tm(10, 9, 3, 1)));
+        assert!(!cron.matches(&tm(0, 18, 3, 1)));
+        assert!(!cron.matches(&tm(0, 9, 3, 6)));
+
+        let Schedule::Cron(cron) = Schedule::parse("0 0 1 * 1").unwrap() else {
+            panic!("expected a cron expression");
+        };
+        assert!(cron.matches(&tm(0, 0, 1, 3)));
+        assert!(cron.matches(&tm(0, 0, 9, 1)));
+        assert!(!cron.matches(&tm(0, 0, 9, 2)));
+    }
+
+    #[test]
+    fn test_next_after() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(
+            Schedule::parse("1h").unwrap().next_after(now),
+            now + Duration::from_secs(3600)
+        );
+        // Every time zone is offset from UTC by a multiple of 15 minutes.
+        let next = Schedule::parse("*/15 * * * *").unwrap().next_after(now);
+        let secs = next.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        assert_eq!(secs % 900, 0);
+        assert!(next > now && next <= now + Duration::from_secs(900));
+    }
+}
@@ -0,0 +1,249 @@
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::Args;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Encryption tool used for a secret stored in a package, chosen by its file extension.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Cipher {
+    /// '*.age' files, handled by the 'age' CLI.
+    Age,
+    /// '*.gpg' files, handled by the 'gpg' CLI.
+    Gpg,
+}
+
+impl Cipher {
+    /// Returns the cipher for a package file, or `None` if the file isn't an encrypted secret.
+    pub fn from_path(path: &Path) -> Option<Cipher> {
+        match path.extension()?.to_str()? {
+            "age" => Some(Cipher::Age),
+            "gpg" => Some(Cipher::Gpg),
+            _ => None,
+        }
+    }
+
+    /// Decrypts `src`, returning the plaintext.
+    pub fn decrypt(self, src: &Path, args: &Args) -> Result<Vec<u8>, String> {
+        let mut cmd = match self {
+            Cipher::Age => {
+                let mut cmd = Command::new("age");
+                cmd.arg("--decrypt");
+                if let Some(identity) = &args.age_identity {
+                    cmd.arg("--identity").arg(identity);
+                }
+                cmd
+            }
+            Cipher::Gpg => {
+                let mut cmd = Command::new("gpg");
+                cmd.args(["--quiet", "--batch", "--decrypt"]);
+                cmd
+            }
+        };
+        cmd.arg(src);
+        run(&mut cmd, None)
+    }
+
+    /// Encrypts `plaintext` into `dst`, replacing it.
+    pub fn encrypt(self, plaintext: &[u8], dst: &Path, args: &Args) -> Result<(), String> {
+        let mut cmd = match self {
+            Cipher::Age => {
+                let identity = args
+                    .age_identity
+                    .as_ref()
+                    .ok_or_else(|| tr!("age-identity-required", path = dst.display()))?;
+                // Encrypting with an identity targets the recipient belonging to that identity.
+                let mut cmd = Command::new("age");
+                cmd.arg("--encrypt").arg("--identity").arg(identity);
+                cmd
+            }
+            Cipher::Gpg => {
+                let mut cmd = Command::new("gpg");
+                cmd.args(["--quiet", "--batch", "--yes", "--encrypt"]);
+                match &args.gpg_recipient {
+                    Some(recipient) => cmd.arg("--recipient").arg(recipient),
+                    None => cmd.arg("--default-recipient-self"),
+                };
+                cmd
+            }
+        };
+        let ciphertext = run(&mut cmd, Some(plaintext))?;
+        args.fs
+            .write(dst, &ciphertext)
+            .map_err(|e| tr!("could-not-write", path = dst.display(), error = e))
+    }
+}
+
+/// Looks up `name` in the standard unix password manager, returning the first line of the entry.
+pub fn pass(name: &str) -> Result<String, String> {
+    let output = run(Command::new("pass").arg("show").arg(name), None)?;
+    let output = String::from_utf8_lossy(&output);
+    Ok(String::from(output.lines().next().unwrap_or_default()))
+}
+
+/// Looks up the password stored for `user` under `service` in the platform keyring: the Secret
+/// Service (via 'secret-tool') on Linux and the BSDs, or the login Keychain on macOS.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn keyring(service: &str, user: &str) -> Result<String, String> {
+    let output = run(
+        Command::new("secret-tool").args(["lookup", "service", service, "username", user]),
+        None,
+    )?;
+    let output = String::from_utf8_lossy(&output);
+    Ok(String::from(output.trim_end_matches('\n')))
+}
+
+/// Looks up the password stored for `user` under `service` in the platform keyring: the Secret
+/// Service (via 'secret-tool') on Linux and the BSDs, or the login Keychain on macOS.
+#[cfg(target_os = "macos")]
+pub fn keyring(service: &str, user: &str) -> Result<String, String> {
+    let output = run(
+        Command::new("security").args(["find-generic-password", "-s", service, "-a", user, "-w"]),
+        None,
+    )?;
+    let output = String::from_utf8_lossy(&output);
+    Ok(String::from(output.trim_end_matches('\n')))
+}
+
+/// Looks up the password stored for `user` under `service` in the Windows Credential Manager,
+/// as the generic credential named 'user.service'.
+#[cfg(windows)]
+pub fn keyring(service: &str, user: &str) -> Result<String, String> {
+    use std::ffi::{c_void, OsStr};
+    use std::os::windows::ffi::OsStrExt;
+
+    #[repr(C)]
+    struct Credential {
+        flags: u32,
+        kind: u32,
+        target_name: *mut u16,
+        comment: *mut u16,
+        last_written: [u32; 2],
+        credential_blob_size: u32,
+        credential_blob: *mut u8,
+        persist: u32,
+        attribute_count: u32,
+        attributes: *mut c_void,
+        target_alias: *mut u16,
+        user_name: *mut u16,
+    }
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn CredReadW(target: *const u16, kind: u32, flags: u32, out: *mut *mut Credential) -> i32;
+        fn CredFree(buffer: *mut c_void);
+    }
+    const CRED_TYPE_GENERIC: u32 = 1;
+
+    let target = format!("{}.{}", user, service);
+    let wide: Vec<u16> = OsStr::new(&target).encode_wide().chain([0]).collect();
+    let mut credential: *mut Credential = std::ptr::null_mut();
+    // SAFETY: `wide` is NUL-terminated, and on success `credential` points to a buffer owned by
+    // the OS that stays valid until the CredFree call below.
+    unsafe {
+        if CredReadW(wide.as_ptr(), CRED_TYPE_GENERIC, 0, &mut credential) == 0 {
+            return Err(tr!("no-windows-credential", credential = target));
+        }
+        let blob = std::slice::from_raw_parts(
+            (*credential).credential_blob,
+            (*credential).credential_blob_size as usize,
+        );
+        // Credentials stored by most tools are UTF-16; fall back to UTF-8 for the rest.
+        let secret = match blob.len() % 2 {
+            0 => String::from_utf16(
+                &blob
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect::<Vec<u16>>(),
+            )
+            .unwrap_or_else(|_| String::from_utf8_lossy(blob).into_owned()),
+            _ => String::from_utf8_lossy(blob).into_owned(),
+        };
+        CredFree(credential as *mut c_void);
+        Ok(secret)
+    }
+}
+
+/// Looks up `key` using the user's configured 'secret_command' (Bitwarden, 1Password, ...).
+pub fn lookup(config: &Config, key: &str) -> Result<String, String> {
+    let Some((program, leading)) = config
+        .secret_command
+        .as_deref()
+        .and_then(|c| c.split_first())
+    else {
+        return Err(tr!("no-secret-command"));
+    };
+    let output = run(Command::new(program).args(leading).arg(key), None)?;
+    let output = String::from_utf8_lossy(&output);
+    Ok(String::from(output.trim_end_matches(['\r', '\n'])))
+}
+
+/// Runs `cmd`, feeding it `stdin` if given, and returns its stdout.
+fn run(cmd: &mut Command, stdin: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    // stderr stays attached to the terminal so passphrase prompts and tool diagnostics show.
+    let mut child = cmd
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| tr!("could-not-run-installed", program = program, error = e))?;
+    // Feed stdin from another thread while stdout is read, so that neither side blocks on a
+    // full pipe once the input or output outgrows its buffer. Dropping the handle closes the
+    // pipe so the tool sees end-of-file.
+    let writer = stdin.map(|input| {
+        let mut pipe = child.stdin.take().unwrap();
+        let input = input.to_vec();
+        std::thread::spawn(move || pipe.write_all(&input))
+    });
+    let output = child.wait_with_output().map_err(|e| {
+        tr!(
+            "could-not-run",
+            program = format!("'{}'", program),
+            error = e
+        )
+    })?;
+    if let Some(Ok(Err(e))) = writer.map(|w| w.join()) {
+        return Err(tr!(
+            "could-not-write-to",
+            program = format!("'{}'", program),
+            error = e
+        ));
+    }
+    if !output.status.success() {
+        return Err(tr!(
+            "program-failed-with",
+            program = program,
+            status = output.status
+        ));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            Cipher::from_path(Path::new(".ssh/id_ed25519.age")),
+            Some(Cipher::Age)
+        );
+        assert_eq!(Cipher::from_path(Path::new("token.gpg")), Some(Cipher::Gpg));
+        assert_eq!(Cipher::from_path(Path::new(".vimrc")), None);
+        assert_eq!(Cipher::from_path(Path::new(".age")), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_outgrows_pipe_buffers() {
+        let input = vec![b'x'; 1 << 20];
+        assert_eq!(run(&mut Command::new("cat"), Some(&input)), Ok(input));
+    }
+}
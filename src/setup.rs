@@ -0,0 +1,164 @@
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::{paths, plan, Args, ConflictResolution};
+use clap::ValueEnum;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+/// Walks the user through choosing the dotfiles directory, default target, conflict policy, and
+/// profiles, reading answers from `input` and prompting on `output`, then writes the config
+/// file. On a dry run or check, the config is printed instead. An existing config is never
+/// replaced.
+pub fn setup(args: &Args, input: impl BufRead, output: &mut dyn io::Write) -> Result<(), String> {
+    let path = Config::path().ok_or_else(|| tr!("no-config-path"))?;
+    if path.exists() {
+        return Err(tr!("config-exists", path = path.display()));
+    }
+    let mut wizard = Wizard {
+        lines: input.lines(),
+        output,
+    };
+    let text = wizard.run(args)?;
+    if args.changes_nothing() {
+        _ = write!(wizard.output, "\n{}", text);
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| tr!("could-not-create-dir", path = parent.display(), error = e))?;
+    }
+    fs::write(&path, text).map_err(|e| tr!("could-not-write", path = path.display(), error = e))?;
+    _ = writeln!(wizard.output, "\nWrote {}", path.display());
+    Ok(())
+}
+
+struct Wizard<'a, L> {
+    lines: L,
+    output: &'a mut dyn io::Write,
+}
+
+impl<L: Iterator<Item = io::Result<String>>> Wizard<'_, L> {
+    /// Asks each question in turn, returning the config as TOML.
+    fn run(&mut self, args: &Args) -> Result<String, String> {
+        let mut config = toml::Table::new();
+
+        let default_dir = match plan::packages(&args.dir) {
+            Ok(packages) if !packages.is_empty() => args.dir.clone(),
+            _ => paths::home_dir()
+                .ok_or_else(|| tr!("no-home-dir", text = "~/.dotfiles"))?
+                .join(".dotfiles"),
+        };
+        let dir = loop {
+            let answer = self.ask("Dotfiles directory", &default_dir.to_string_lossy())?;
+            match paths::expand(&answer) {
+                Ok(dir) => break dir,
+                Err(e) => self.say(&format!("Invalid directory: {}", e)),
+            }
+        };
+        config.insert(String::from("dir"), dir.clone().into());
+
+        let default_target = Path::new(&dir)
+            .parent()
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let target = self.ask("Target directory", &default_target)?;
+        if target != default_target {
+            config.insert(String::from("target"), target.into());
+        }
+
+        let policies: Vec<String> = ConflictResolution::value_variants()
+            .iter()
+            .filter_map(|v| v.to_possible_value())
+            .map(|v| v.get_name().to_owned())
+            .collect();
+        self.say(&format!(
+            "When a file already exists where a link goes, ranch can: {}",
+            policies.join(", ")
+        ));
+        let exists = loop {
+            let answer = self.ask("Default conflict policy", "stop")?;
+            if policies.contains(&answer) {
+                break answer;
+            }
+            self.say(&format!("Choose one of: {}", policies.join(", ")));
+        };
+        config.insert(String::from("exists"), exists.into());
+
+        let packages = plan::packages(Path::new(&dir)).unwrap_or_default();
+        if !packages.is_empty() {
+            self.say(&format!("Packages in {}: {}", dir, packages.join(", ")));
+        }
+        let mut profiles = toml::Table::new();
+        loop {
+            let name = self.ask("Name a profile to create (empty to finish)", "")?;
+            if name.is_empty() {
+                break;
+            }
+            let members = self.ask(
+                &format!("Packages in profile {}", name),
+                &packages.join(" "),
+            )?;
+            let members: Vec<toml::Value> = members.split_whitespace().map(Into::into).collect();
+            profiles.insert(name, members.into());
+        }
+        if !profiles.is_empty() {
+            config.insert(String::from("profiles"), profiles.into());
+        }
+        toml::to_string(&config).map_err(|e| tr!("could-not-serialize-config", error = e))
+    }
+
+    /// Prompts with `question`, returning the trimmed answer, or `default` if it's empty.
+    fn ask(&mut self, question: &str, default: &str) -> Result<String, String> {
+        match default.is_empty() {
+            true => _ = write!(self.output, "{}: ", question),
+            false => _ = write!(self.output, "{} [{}]: ", question, default),
+        }
+        _ = self.output.flush();
+        let line = match self.lines.next() {
+            Some(line) => line.map_err(|e| tr!("could-not-read-answer", error = e))?,
+            None => return Err(tr!("setup-cancelled")),
+        };
+        match line.trim() {
+            "" => Ok(String::from(default)),
+            answer => Ok(String::from(answer)),
+        }
+    }
+
+    fn say(&mut self, message: &str) {
+        _ = writeln!(self.output, "{}", message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_wizard() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("home")).unwrap();
+        fs::create_dir_all(dir.join("work")).unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "setup"]);
+        let answers = "\n\nyes\nadopt\nlaptop\n\nwork\nwork home\n\n";
+        let mut output = Vec::new();
+        let mut wizard = Wizard {
+            lines: answers.as_bytes().lines(),
+            output: &mut output,
+        };
+
+        let text = wizard.run(&args).unwrap();
+
+        let config: Config = toml::from_str(&text).unwrap();
+        assert_eq!(config.dir.as_deref(), dir.to_str());
+        assert_eq!(config.target, None);
+        assert!(matches!(config.exists, Some(ConflictResolution::Adopt)));
+        assert_eq!(config.profile("laptop").unwrap(), ["home", "work"]);
+        assert_eq!(config.profile("work").unwrap(), ["work", "home"]);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Choose one of: stop, ignore, overwrite, adopt, rollback"));
+    }
+}
@@ -0,0 +1,356 @@
+use crate::filesystem::{quote, Filesystem, Kind, RealFs};
+use crate::paths;
+use std::env;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Deploys to another host over SSH, for '--backend sftp': paths in 'DIR' are the local
+/// packages, and every other path is on the host. Package files are uploaded over SFTP to the
+/// same path on the host before links to them are made there, so the host needs neither ranch
+/// nor the repo, only a POSIX shell. One SSH connection is shared by every operation.
+#[derive(Debug)]
+pub struct SftpFs {
+    /// '[USER@]HOST', as given to ssh.
+    host: String,
+    /// 'DIR', whose files are read locally.
+    dir: PathBuf,
+}
+
+impl SftpFs {
+    pub fn new(host: &str, dir: &Path) -> SftpFs {
+        SftpFs {
+            host: host.to_owned(),
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    fn is_local(&self, path: &Path) -> bool {
+        path.starts_with(&self.dir)
+    }
+
+    fn sh(&self, script: &str, stdin: &[u8]) -> io::Result<Vec<u8>> {
+        ssh(&self.host, script, stdin)
+    }
+
+    /// Runs the sftp `batch` of commands against the host.
+    fn sftp(&self, batch: &str) -> io::Result<()> {
+        let mut cmd = Command::new("sftp");
+        cmd.args(options()).args(["-q", "-b", "-"]).arg(&self.host);
+        run(&mut cmd, batch.as_bytes()).map(|_| ())
+    }
+
+    /// Uploads the package file or directory `src` to the same path on the host.
+    fn upload(&self, src: &Path) -> io::Result<()> {
+        let parent = src.parent().unwrap_or(src);
+        self.sh(&format!("mkdir -p {}", quote_path(parent)), &[])?;
+        self.sftp(&format!(
+            "put -pr {} {}\n",
+            sftp_quote(src),
+            sftp_quote(src)
+        ))
+    }
+}
+
+/// Options that make ssh and sftp share one connection per host, which stays open for a minute
+/// so that each operation doesn't have to log in again.
+fn options() -> Vec<String> {
+    let control = env::temp_dir().join("ranch-ssh-%C");
+    vec![
+        String::from("-o"),
+        String::from("ControlMaster=auto"),
+        String::from("-o"),
+        format!("ControlPath={}", control.display()),
+        String::from("-o"),
+        String::from("ControlPersist=60"),
+    ]
+}
+
+/// Runs the POSIX shell `script` on `host`, whatever its login shell, feeding it `stdin`, and
+/// returns its output.
+pub fn ssh(host: &str, script: &str, stdin: &[u8]) -> io::Result<Vec<u8>> {
+    run(&mut ssh_command(host, script), stdin)
+}
+
+/// The command that runs the POSIX shell `script` on `host`.
+pub fn ssh_command(host: &str, script: &str) -> Command {
+    let mut cmd = Command::new("ssh");
+    cmd.args(options())
+        .arg(host)
+        .arg("--")
+        .arg(format!("sh -c {}", quote(script)));
+    cmd
+}
+
+/// Runs `cmd`, feeding it `stdin`; a failure carries its standard error, and is NotFound if
+/// that says so, as a local operation's would.
+fn run(cmd: &mut Command, stdin: &[u8]) -> io::Result<Vec<u8>> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut pipe) = child.stdin.take() {
+        pipe.write_all(stdin)?;
+    }
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        return Ok(output.stdout);
+    }
+    let error = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+    let kind = match error.contains("No such file") {
+        true => io::ErrorKind::NotFound,
+        false => io::ErrorKind::Other,
+    };
+    Err(io::Error::new(kind, error))
+}
+
+fn quote_path(path: &Path) -> String {
+    quote(&path.to_string_lossy())
+}
+
+/// `path` quoted for an sftp batch file.
+fn sftp_quote(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A script that prints the kind of thing at `path`, not following a link there unless
+/// `follow` is set, and nothing if there's nothing.
+fn kind_script(path: &Path, follow: bool) -> String {
+    let p = quote_path(path);
+    let link = match follow {
+        true => String::new(),
+        false => format!("if [ -L {p} ]; then echo symlink; el"),
+    };
+    format!(
+        "{link}if [ -d {p} ]; then echo dir; elif [ -f {p} ]; then echo file; \
+         elif [ -e {p} ]; then echo other; fi"
+    )
+}
+
+fn parse_kind(output: &[u8]) -> Option<Kind> {
+    match String::from_utf8_lossy(output).trim() {
+        "symlink" => Some(Kind::Symlink),
+        "dir" => Some(Kind::Dir),
+        "file" => Some(Kind::File),
+        "other" => Some(Kind::Other),
+        _ => None,
+    }
+}
+
+impl Filesystem for SftpFs {
+    fn kind(&self, path: &Path) -> Option<Kind> {
+        match self.is_local(path) {
+            true => RealFs.kind(path),
+            false => parse_kind(&self.sh(&kind_script(path, false), &[]).ok()?),
+        }
+    }
+
+    fn target_kind(&self, path: &Path) -> Option<Kind> {
+        match self.is_local(path) {
+            true => RealFs.target_kind(path),
+            false => parse_kind(&self.sh(&kind_script(path, true), &[]).ok()?),
+        }
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        if self.is_local(path) {
+            return RealFs.size(path);
+        }
+        let output = self.sh(&format!("wc -c < {}", quote_path(path)), &[])?;
+        String::from_utf8_lossy(&output)
+            .trim()
+            .parse()
+            .map_err(io::Error::other)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        if self.is_local(path) {
+            return RealFs.read_link(path);
+        }
+        let output = self.sh(&format!("readlink {}", quote_path(path)), &[])?;
+        let target = String::from_utf8_lossy(&output);
+        Ok(PathBuf::from(target.strip_suffix('\n').unwrap_or(&target)))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.is_local(path) {
+            true => RealFs.read(path),
+            false => self.sh(&format!("cat {}", quote_path(path)), &[]),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        if self.is_local(path) {
+            return RealFs.read_dir(path);
+        }
+        let output = self.sh(&format!("ls -A {}", quote_path(path)), &[])?;
+        Ok(String::from_utf8_lossy(&output)
+            .lines()
+            .map(|name| path.join(name))
+            .collect())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        match self.is_local(path) {
+            true => RealFs.create_dir_all(path),
+            false => self
+                .sh(&format!("mkdir -p {}", quote_path(path)), &[])
+                .map(|_| ()),
+        }
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        let src = paths::normalize(&link.parent().unwrap_or(link).join(target));
+        if self.is_local(&src) && !self.is_local(link) {
+            self.upload(&src)?;
+        }
+        let script = format!("ln -s {} {}", quote_path(target), quote_path(link));
+        self.sh(&script, &[]).map(|_| ())
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        match (self.is_local(from), self.is_local(to)) {
+            (true, true) => RealFs.copy(from, to),
+            (true, false) => {
+                self.sftp(&format!("put -p {} {}\n", sftp_quote(from), sftp_quote(to)))
+            }
+            (false, true) => {
+                self.sftp(&format!("get -p {} {}\n", sftp_quote(from), sftp_quote(to)))
+            }
+            (false, false) => {
+                let script = format!("cp -p {} {}", quote_path(from), quote_path(to));
+                self.sh(&script, &[]).map(|_| ())
+            }
+        }
+    }
+
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        match (self.is_local(from), self.is_local(to)) {
+            (true, true) => RealFs.hard_link(from, to),
+            (false, false) => {
+                let script = format!("ln {} {}", quote_path(from), quote_path(to));
+                self.sh(&script, &[]).map(|_| ())
+            }
+            _ => Err(io::Error::from(io::ErrorKind::CrossesDevices)),
+        }
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        match self.is_local(path) {
+            true => RealFs.write(path, contents),
+            false => self
+                .sh(&format!("cat > {}", quote_path(path)), contents)
+                .map(|_| ()),
+        }
+    }
+
+    fn write_private(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        match self.is_local(path) {
+            true => RealFs.write_private(path, contents),
+            false => self
+                .sh(
+                    &format!("umask 077 && cat > {}", quote_path(path)),
+                    contents,
+                )
+                .map(|_| ()),
+        }
+    }
+
+    // Files copied between the machines keep their times already.
+    fn copy_times(&self, from: &Path, to: &Path) -> io::Result<()> {
+        match (self.is_local(from), self.is_local(to)) {
+            (true, true) => RealFs.copy_times(from, to),
+            (false, false) => {
+                let script = format!("touch -r {} {}", quote_path(from), quote_path(to));
+                self.sh(&script, &[]).map(|_| ())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        if self.is_local(from) && self.is_local(to) {
+            return RealFs.rename(from, to);
+        }
+        // 'mv' would move into a directory at `to` rather than fail like rename(2).
+        let (from, to) = (quote_path(from), quote_path(to));
+        let script = format!(
+            "if [ -d {to} ] && [ ! -L {to} ]; then echo {to} is a directory >&2; exit 1; fi; \
+             mv -f {from} {to}"
+        );
+        self.sh(&script, &[]).map(|_| ())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        match self.is_local(path) {
+            true => RealFs.remove_file(path),
+            false => self
+                .sh(&format!("rm -f {}", quote_path(path)), &[])
+                .map(|_| ()),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        match self.is_local(path) {
+            true => RealFs.remove_dir(path),
+            false => self
+                .sh(&format!("rmdir {}", quote_path(path)), &[])
+                .map(|_| ()),
+        }
+    }
+
+    // Package files are uploaded to the same path on the host, so `src` is there too.
+    fn stamp_link(&self, src: &Path, link: &Path) -> io::Result<()> {
+        match self.is_local(link) {
+            true => RealFs.stamp_link(src, link),
+            false => {
+                let script = format!("touch -h -r {} {}", quote_path(src), quote_path(link));
+                self.sh(&script, &[]).map(|_| ())
+            }
+        }
+    }
+
+    fn chown(&self, path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+        match self.is_local(path) {
+            true => RealFs.chown(path, uid, gid),
+            false => {
+                let script = format!("chown -h {}:{} {}", uid, gid, quote_path(path));
+                self.sh(&script, &[]).map(|_| ())
+            }
+        }
+    }
+
+    // POSIX 'sync' flushes everything; it takes no paths.
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        match self.is_local(path) {
+            true => RealFs.sync(path),
+            false => self.sh("sync", &[]).map(|_| ()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts() {
+        let path = Path::new("/home/bob/My Files");
+        assert_eq!(
+            kind_script(path, true),
+            "if [ -d '/home/bob/My Files' ]; then echo dir; \
+             elif [ -f '/home/bob/My Files' ]; then echo file; \
+             elif [ -e '/home/bob/My Files' ]; then echo other; fi"
+        );
+        assert!(kind_script(path, false).starts_with("if [ -L '/home/bob/My Files' ]; then"));
+        assert_eq!(parse_kind(b"symlink\n"), Some(Kind::Symlink));
+        assert_eq!(parse_kind(b""), None);
+        assert_eq!(sftp_quote(Path::new("/a \"b\"")), "\"/a \\\"b\\\"\"");
+
+        let fs = SftpFs::new("bob@server", Path::new("/home/alice/.dotfiles"));
+        assert!(fs.is_local(Path::new("/home/alice/.dotfiles/vim/.vimrc")));
+        assert!(!fs.is_local(Path::new("/home/bob/.vimrc")));
+    }
+}
@@ -0,0 +1,88 @@
+use crate::plan::{self, Action};
+use crate::status::PackageStatus;
+use crate::{load_package, Args};
+use std::env::consts::OS;
+use std::io;
+
+/// Prints what `package`'s manifest says about it, where it deploys to, and how many of its
+/// files are deployed, to `stdout`.
+pub fn show(
+    args: &Args,
+    package: &str,
+    stdout: &mut dyn io::Write,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let (prefix_path, manifest, target_path) = load_package(args, package)?;
+    let actions = plan::plan(args, package, &prefix_path, &target_path, stderr)?;
+    let files = actions
+        .iter()
+        .filter(|a| !matches!(a, Action::Mkdir { .. }))
+        .count();
+    let status = PackageStatus::new(args, package, &actions);
+
+    _ = writeln!(stdout, "{}", package);
+    if let Some(description) = &manifest.description {
+        _ = writeln!(stdout, "  {}", description);
+    }
+    if let Some(homepage) = &manifest.homepage {
+        _ = writeln!(stdout, "  homepage:  {}", homepage);
+    }
+    if !manifest.platforms.is_empty() {
+        let note = match manifest.platforms.iter().any(|p| p == OS) {
+            true => "",
+            false => " (not this machine)",
+        };
+        _ = writeln!(
+            stdout,
+            "  platforms: {}{}",
+            manifest.platforms.join(", "),
+            note
+        );
+    }
+    _ = writeln!(stdout, "  source:    {}", prefix_path.display());
+    _ = writeln!(stdout, "  target:    {}", target_path.display());
+    _ = writeln!(
+        stdout,
+        "  files:     {} ({} deployed, {} missing, {} conflicting)",
+        files,
+        status.deployed,
+        status.missing.len(),
+        status.conflicts.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_show() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("kitty/.config/kitty")).unwrap();
+        fs::write(dir.join("kitty/.config/kitty/kitty.conf"), "").unwrap();
+        fs::write(
+            dir.join("kitty/.ranch.toml"),
+            "description = 'Terminal emulator'\nhomepage = 'https://sw.kovidgoyal.net/kitty'\nplatforms = ['plan9']\n",
+        )
+        .unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "show", "kitty"]);
+        let mut stdout = Vec::new();
+
+        show(&args, "kitty", &mut stdout, &mut io::sink()).unwrap();
+
+        let stdout = String::from_utf8(stdout).unwrap();
+        assert!(
+            stdout.starts_with("kitty\n  Terminal emulator\n"),
+            "{}",
+            stdout
+        );
+        assert!(stdout.contains("  homepage:  https://sw.kovidgoyal.net/kitty\n"));
+        assert!(stdout.contains("  platforms: plan9 (not this machine)\n"));
+        assert!(stdout.contains("  files:     1 (0 deployed, 1 missing, 0 conflicting)\n"));
+    }
+}
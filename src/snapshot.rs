@@ -0,0 +1,123 @@
+use crate::i18n::tr;
+use crate::{Args, LV_INFO};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory inside a btrfs subvolume that its snapshots are kept in.
+pub const BTRFS_SNAPSHOTS: &str = ".ranch-snapshots";
+
+/// With '--snapshot', snapshots the filesystem holding `target_path` before `package` is
+/// deployed there, for a complete undo: a read-only snapshot of its btrfs subvolume (in
+/// '.ranch-snapshots'), a snapshot of its ZFS dataset, or an APFS local snapshot through Time
+/// Machine. Nothing is deployed if the snapshot can't be taken.
+pub fn snapshot(
+    args: &Args,
+    package: &str,
+    target_path: &Path,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    if !args.snapshot || args.changes_nothing() {
+        return Ok(());
+    }
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let name = format!("{}-{}", package, secs);
+    let taken = match filesystem(target_path).as_deref() {
+        Some("btrfs") => {
+            let subvolume = btrfs_subvolume(target_path)
+                .ok_or_else(|| tr!("no-btrfs-subvolume", path = target_path.display()))?;
+            let dir = subvolume.join(BTRFS_SNAPSHOTS);
+            std::fs::create_dir_all(&dir)
+                .map_err(|e| tr!("could-not-create", path = dir.display(), error = e))?;
+            let snapshot = dir.join(name);
+            run(Command::new("btrfs")
+                .args(["subvolume", "snapshot", "-r"])
+                .arg(&subvolume)
+                .arg(&snapshot))?;
+            snapshot.display().to_string()
+        }
+        Some("zfs") => {
+            let output = run(Command::new("zfs")
+                .args(["list", "-H", "-o", "name"])
+                .arg(target_path))?;
+            let dataset = String::from_utf8_lossy(&output).trim().to_owned();
+            let snapshot = format!("{}@ranch-{}", dataset, name);
+            run(Command::new("zfs").args(["snapshot", &snapshot]))?;
+            snapshot
+        }
+        _ if cfg!(target_os = "macos") => {
+            let output = run(Command::new("tmutil").arg("localsnapshot"))?;
+            String::from_utf8_lossy(&output).trim().to_owned()
+        }
+        other => {
+            return Err(match other {
+                Some(filesystem) => tr!(
+                    "cannot-snapshot",
+                    path = target_path.display(),
+                    filesystem = filesystem
+                ),
+                None => tr!("cannot-snapshot-unknown", path = target_path.display()),
+            })
+        }
+    };
+    if args.verbosity("snapshot") >= LV_INFO {
+        _ = writeln!(
+            stderr,
+            "Snapshot of {} taken: {}",
+            target_path.display(),
+            taken
+        );
+    }
+    Ok(())
+}
+
+/// The type of the filesystem holding `path`, such as 'btrfs' or 'zfs', from 'stat'.
+fn filesystem(path: &Path) -> Option<String> {
+    let output = Command::new("stat")
+        .args(["-f", "-c", "%T"])
+        .arg(path)
+        .output()
+        .ok()?;
+    match output.status.success() {
+        true => Some(String::from_utf8_lossy(&output.stdout).trim().to_owned()),
+        false => None,
+    }
+}
+
+/// The root of the btrfs subvolume holding `path`: its nearest ancestor with inode 256, which
+/// btrfs gives every subvolume root.
+#[cfg(unix)]
+fn btrfs_subvolume(path: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    const SUBVOLUME_INO: u64 = 256;
+    let dev = path.metadata().ok()?.dev();
+    path.ancestors()
+        .take_while(|a| a.metadata().is_ok_and(|m| m.dev() == dev))
+        .find(|a| a.metadata().is_ok_and(|m| m.ino() == SUBVOLUME_INO))
+        .map(Path::to_path_buf)
+}
+
+#[cfg(not(unix))]
+fn btrfs_subvolume(_: &Path) -> Option<PathBuf> {
+    None
+}
+
+fn run(cmd: &mut Command) -> Result<Vec<u8>, String> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let output = cmd
+        .output()
+        .map_err(|e| tr!("could-not-run", program = program, error = e))?;
+    if !output.status.success() {
+        return Err(tr!(
+            "program-failed",
+            program = program,
+            output = String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
@@ -0,0 +1,415 @@
+use crate::git;
+use crate::i18n::tr;
+use crate::paths;
+use crate::signature::Verifier;
+use crate::state::sha256_hex;
+use crate::{Args, LV_INFO};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The kinds of package source '--from' accepts, told apart by file extension.
+#[derive(Debug, PartialEq, Eq)]
+enum Kind {
+    Git,
+    Tar,
+    Zip,
+}
+
+impl Kind {
+    fn of(url: &str) -> Kind {
+        let url = url.to_lowercase();
+        const TARS: [&str; 7] = [
+            ".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tbz2", ".tar.xz", ".txz",
+        ];
+        if TARS.iter().any(|ext| url.ends_with(ext)) {
+            Kind::Tar
+        } else if url.ends_with(".zip") {
+            Kind::Zip
+        } else {
+            Kind::Git
+        }
+    }
+}
+
+/// Fetches the packages at `url` into ranch's cache and points 'DIR' at them. Git repositories
+/// are cloned the first time and fast-forwarded on later runs; archives, local or downloaded
+/// over HTTP(S), are verified and then extracted afresh every run.
+pub fn fetch(args: &mut Args, url: &str, stderr: &mut dyn io::Write) -> Result<(), String> {
+    let dir = cache_path("sources", url)?;
+    let dir = match Kind::of(url) {
+        Kind::Git if args.sha256.is_some() || args.lockfile.is_some() => {
+            return Err(tr!("git-source-pinned"));
+        }
+        Kind::Git => {
+            clone_or_pull(args, url, &dir, stderr)?;
+            dir
+        }
+        kind => {
+            let archive = match is_remote(url) {
+                true => download(args, url, |path| verify(args, url, path), stderr)?,
+                false => {
+                    let archive = PathBuf::from(paths::expand(url)?);
+                    verify(args, url, &archive)?;
+                    archive
+                }
+            };
+            if let Some(verifier) = Verifier::new(args) {
+                let signature = match is_remote(url) {
+                    true => {
+                        let url = format!("{}{}", url, verifier.extension());
+                        download(args, &url, |_| Ok(()), stderr)?
+                    }
+                    false => verifier.signature_path(&archive),
+                };
+                verifier.verify(&archive, &signature)?;
+                args.verified_source = true;
+            }
+            extract(args, &kind, &archive, &dir, stderr)?;
+            archive_root(args, dir)
+        }
+    };
+    args.dir = dir;
+    Ok(())
+}
+
+fn is_remote(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+/// Clones the git repository at `url` into `dir`, or fast-forwards it if it's already there.
+pub fn clone_or_pull(
+    args: &Args,
+    url: &str,
+    dir: &Path,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    if dir.join(".git").exists() {
+        if args.verbosity("source") >= LV_INFO {
+            _ = writeln!(stderr, "Updating {} from {}", dir.display(), url);
+        }
+        return git::pull(dir).map_err(|e| tr!("could-not-update", url = url, error = e));
+    }
+    if args.verbosity("source") >= LV_INFO {
+        _ = writeln!(stderr, "Cloning {} into {}", url, dir.display());
+    }
+    if let Some(parent) = dir.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| tr!("could-not-create-dir", path = parent.display(), error = e))?;
+    }
+    git::clone(url, dir).map_err(|e| tr!("could-not-clone", url = url, error = e))
+}
+
+/// Downloads the archive at `url` into the cache, skipping the transfer if the cached copy is
+/// already up to date. The download lands in a temporary file beside the cached copy and only
+/// replaces it once curl succeeds and `check` passes, so a failed transfer is never cached.
+fn download(
+    args: &Args,
+    url: &str,
+    check: impl Fn(&Path) -> Result<(), String>,
+    stderr: &mut dyn io::Write,
+) -> Result<PathBuf, String> {
+    let path = cache_path("downloads", url)?;
+    let parent = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)
+        .map_err(|e| tr!("could-not-create-dir", path = parent.display(), error = e))?;
+    let temp = tempfile::Builder::new()
+        .prefix(".download")
+        .tempfile_in(parent)
+        .map_err(|e| tr!("could-not-write", path = parent.display(), error = e))?
+        .into_temp_path();
+    if args.verbosity("source") >= LV_INFO {
+        _ = writeln!(stderr, "Downloading {}", url);
+    }
+    let mut cmd = Command::new("curl");
+    cmd.args([
+        "--silent",
+        "--show-error",
+        "--fail",
+        "--location",
+        "--output",
+    ])
+    .arg(&temp);
+    if path.exists() {
+        cmd.arg("--time-cond").arg(&path);
+    }
+    run(cmd.arg("--").arg(url))?;
+    // curl leaves the output untouched when the server reports the cached copy is current.
+    let fresh = fs::metadata(&temp).is_ok_and(|m| m.len() > 0);
+    if !fresh && path.exists() {
+        check(&path)?;
+        return Ok(path);
+    }
+    check(&temp)?;
+    temp.persist(&path)
+        .map_err(|e| tr!("could-not-write", path = path.display(), error = e))?;
+    Ok(path)
+}
+
+/// Checks `archive` against '--sha256', or the hash listed for `url` in '--lockfile'.
+fn verify(args: &Args, url: &str, archive: &Path) -> Result<(), String> {
+    let expected = match (&args.sha256, &args.lockfile) {
+        (Some(hash), _) => hash.to_lowercase(),
+        (None, Some(lockfile)) => locked_hash(lockfile, url)?,
+        (None, None) => return Ok(()),
+    };
+    let contents = fs::read(archive)
+        .map_err(|e| tr!("could-not-read", path = archive.display(), error = e))?;
+    let actual = sha256_hex(&contents);
+    if actual != expected {
+        return Err(tr!(
+            "checksum-mismatch",
+            url = url,
+            expected = expected,
+            actual = actual
+        ));
+    }
+    Ok(())
+}
+
+/// Looks up the SHA-256 of `url` in a lockfile of 'HASH  URL' lines, the format 'sha256sum'
+/// prints; blank lines and '#' comments are ignored.
+fn locked_hash(lockfile: &Path, url: &str) -> Result<String, String> {
+    let text = fs::read_to_string(lockfile)
+        .map_err(|e| tr!("could-not-read", path = lockfile.display(), error = e))?;
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| l.split_once(char::is_whitespace))
+        .find(|(_, u)| u.trim_start().trim_start_matches('*') == url)
+        .map(|(hash, _)| hash.to_lowercase())
+        .ok_or_else(|| tr!("no-checksum", lockfile = lockfile.display(), url = url))
+}
+
+/// Replaces `dir` with the contents of `archive`. The archive is unpacked beside `dir` first, so
+/// that a broken archive leaves the previous contents in place.
+fn extract(
+    args: &Args,
+    kind: &Kind,
+    archive: &Path,
+    dir: &Path,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    if args.verbosity("source") >= LV_INFO {
+        _ = writeln!(
+            stderr,
+            "Extracting {} into {}",
+            archive.display(),
+            dir.display()
+        );
+    }
+    let tmp = dir.with_extension("tmp");
+    _ = fs::remove_dir_all(&tmp);
+    fs::create_dir_all(&tmp)
+        .map_err(|e| tr!("could-not-create-dir", path = tmp.display(), error = e))?;
+    let mut cmd = match kind {
+        // Windows' bsdtar-based 'tar' reads zip files, and 'unzip' usually isn't installed.
+        Kind::Zip if !cfg!(windows) => {
+            let mut cmd = Command::new("unzip");
+            cmd.arg("-q").arg(archive).arg("-d").arg(&tmp);
+            cmd
+        }
+        _ => {
+            let mut cmd = Command::new("tar");
+            cmd.arg("-xf").arg(archive).arg("-C").arg(&tmp);
+            cmd
+        }
+    };
+    if let Err(e) = run(&mut cmd) {
+        _ = fs::remove_dir_all(&tmp);
+        return Err(tr!(
+            "could-not-extract",
+            path = archive.display(),
+            error = e
+        ));
+    }
+    if dir.exists() {
+        fs::remove_dir_all(dir)
+            .map_err(|e| tr!("could-not-remove", path = dir.display(), error = e))?;
+    }
+    fs::rename(&tmp, dir).map_err(|e| tr!("could-not-create", path = dir.display(), error = e))
+}
+
+/// Where the packages are in an extracted archive. Archives made from a whole repository, like
+/// GitHub's, wrap everything in one top-level directory, which is descended into unless it's
+/// the package being deployed.
+fn archive_root(args: &Args, dir: PathBuf) -> PathBuf {
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return dir;
+    };
+    let entries: Vec<_> = entries.filter_map(|r| r.ok()).collect();
+    let [entry] = entries.as_slice() else {
+        return dir;
+    };
+    let package = args
+        .package
+        .as_ref()
+        .or(args.delete.first())
+        .map(String::as_str)
+        .unwrap_or_default();
+    if !entry.path().is_dir() || entry.file_name() == package {
+        return dir;
+    }
+    entry.path()
+}
+
+/// Where `url` is cached: 'ranch/KIND/NAME-HASH' in the cache directory, where NAME is the last
+/// part of the URL, and HASH tells apart URLs that share it.
+fn cache_path(kind: &str, url: &str) -> Result<PathBuf, String> {
+    let cache = paths::cache_dir().ok_or_else(|| tr!("no-cache-dir"))?;
+    Ok(cache.join("ranch").join(kind).join(cache_name(url)))
+}
+
+fn run(cmd: &mut Command) -> Result<(), String> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let output = cmd
+        .output()
+        .map_err(|e| tr!("could-not-run", program = program, error = e))?;
+    if !output.status.success() {
+        return Err(tr!(
+            "program-failed",
+            program = program,
+            output = String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+fn cache_name(url: &str) -> String {
+    let name: String = url
+        .trim_end_matches('/')
+        .rsplit(['/', ':', '\\'])
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(".git")
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect();
+    format!("{}-{}", name, &sha256_hex(url.as_bytes())[..12])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::process::Command;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_cache_name() {
+        assert!(cache_name("https://github.com/alice/dots.git").starts_with("dots-"));
+        assert!(cache_name("git@github.com:alice/dotfiles/").starts_with("dotfiles-"));
+        assert!(cache_name("/srv/my repo").starts_with("my_repo-"));
+        assert_ne!(
+            cache_name("https://a.example/dots.git"),
+            cache_name("https://b.example/dots.git")
+        );
+    }
+
+    #[test]
+    fn test_extract() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        std::fs::create_dir_all(tmp_dir.path().join("dots-main/home")).unwrap();
+        std::fs::write(tmp_dir.path().join("dots-main/home/.vimrc"), "").unwrap();
+        let archive = tmp_dir.path().join("dots.tar.gz");
+        let tar = Command::new("tar")
+            .arg("-czf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(tmp_dir.path())
+            .arg("dots-main")
+            .status();
+        if !tar.is_ok_and(|s| s.success()) {
+            return; // tar isn't installed
+        }
+        let dir = tmp_dir.path().join("cache/dots");
+        std::fs::create_dir_all(dir.join("stale")).unwrap();
+
+        assert_eq!(Kind::of("https://example.com/dots.TGZ"), Kind::Tar);
+        let args = Args::parse_from(["ranch", "home"]);
+        extract(&args, &Kind::Tar, &archive, &dir, &mut io::sink()).unwrap();
+
+        assert!(!dir.join("stale").exists());
+        assert_eq!(archive_root(&args, dir.clone()), dir.join("dots-main"));
+        assert!(dir.join("dots-main/home/.vimrc").exists());
+        let args = Args::parse_from(["ranch", "dots-main"]);
+        assert_eq!(archive_root(&args, dir.clone()), dir);
+        assert!(extract(
+            &args,
+            &Kind::Tar,
+            &tmp_dir.path().join("nope.tar"),
+            &dir,
+            &mut io::sink()
+        )
+        .is_err());
+        assert!(dir.join("dots-main").exists());
+    }
+
+    #[test]
+    fn test_verify() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let archive = tmp_dir.path().join("dots.tar");
+        std::fs::write(&archive, "abc").unwrap();
+        let hash = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let lockfile = tmp_dir.path().join("ranch.lock");
+        std::fs::write(
+            &lockfile,
+            format!(
+                "# sources\n{}  https://a.example/dots.tar\n",
+                hash.to_uppercase()
+            ),
+        )
+        .unwrap();
+        let url = "https://a.example/dots.tar";
+
+        let args = Args::parse_from(["ranch", "--sha256", hash, "home"]);
+        assert!(verify(&args, url, &archive).is_ok());
+        let args = Args::parse_from(["ranch", "--sha256", &hash.replace('b', "c"), "home"]);
+        assert!(verify(&args, url, &archive)
+            .unwrap_err()
+            .starts_with("Checksum mismatch"));
+        let args = Args::parse_from(["ranch", "--lockfile", lockfile.to_str().unwrap(), "home"]);
+        assert!(verify(&args, url, &archive).is_ok());
+        assert!(verify(&args, "https://b.example/dots.tar", &archive)
+            .unwrap_err()
+            .contains("has no checksum"));
+    }
+
+    #[test]
+    fn test_clone_or_pull() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let upstream = tmp_dir.path().join("upstream");
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(&upstream)
+                .args(["-c", "user.name=a", "-c", "user.email=a@example.com"])
+                .args(args)
+                .output()
+                .is_ok_and(|o| o.status.success())
+        };
+        std::fs::create_dir_all(upstream.join("home")).unwrap();
+        if !git(&["init"]) {
+            return; // git isn't installed
+        }
+        std::fs::write(upstream.join("home/.vimrc"), "").unwrap();
+        assert!(git(&["add", "."]) && git(&["commit", "-m", "1"]));
+        let args = Args::parse_from(["ranch", "home"]);
+        let dir = tmp_dir.path().join("cache/dots");
+        let url = upstream.to_str().unwrap();
+
+        clone_or_pull(&args, url, &dir, &mut io::sink()).unwrap();
+        std::fs::write(upstream.join("home/.zshrc"), "").unwrap();
+        assert!(git(&["add", "."]) && git(&["commit", "-m", "2"]));
+        clone_or_pull(&args, url, &dir, &mut io::sink()).unwrap();
+
+        assert!(dir.join("home/.vimrc").exists());
+        assert!(dir.join("home/.zshrc").exists());
+    }
+}
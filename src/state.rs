@@ -0,0 +1,102 @@
+use crate::i18n::tr;
+use crate::status::PackageStatus;
+use crate::Args;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+/// What ranch remembers about this machine between runs, stored as JSON in '--state', or else
+/// '<state dir>/ranch/state.json'.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct State {
+    /// 'run_once_*' scripts that have already run, keyed by the SHA-256 of their contents, with
+    /// a label naming the script.
+    #[serde(default)]
+    pub run_once: BTreeMap<String, String>,
+
+    /// Target directories ranch created to hold links, which deleting packages removes again
+    /// once they're left empty. Directories that already existed are never removed.
+    #[serde(default)]
+    pub created_dirs: BTreeSet<PathBuf>,
+
+    /// Result of the daemon's most recent scheduled drift check.
+    pub last_check: Option<DriftCheck>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct DriftCheck {
+    /// Seconds since the Unix epoch.
+    pub checked_at: u64,
+    pub packages: Vec<PackageStatus>,
+}
+
+impl State {
+    /// Location of the state file, whether or not it exists.
+    pub fn path(args: &Args) -> Option<PathBuf> {
+        match &args.state {
+            Some(path) if !path.as_os_str().is_empty() => Some(path.clone()),
+            _ => default_path(),
+        }
+    }
+
+    /// Reads the state file, or returns an empty state if there isn't one yet.
+    pub fn load(args: &Args) -> Result<State, String> {
+        let Some(path) = State::path(args).filter(|p| p.exists()) else {
+            return Ok(State::default());
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| tr!("could-not-read", path = path.display(), error = e))?;
+        serde_json::from_str(&text)
+            .map_err(|e| tr!("invalid-state", path = path.display(), error = e))
+    }
+
+    /// Writes the state file, replacing it atomically so an interrupted run can't corrupt it.
+    pub fn save(&self, args: &Args) -> Result<(), String> {
+        let path = State::path(args).ok_or_else(|| tr!("no-state-path"))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| tr!("could-not-create-dir", path = parent.display(), error = e))?;
+        }
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|e| tr!("could-not-serialize-state", error = e))?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, text)
+            .and_then(|_| std::fs::rename(&tmp, &path))
+            .map_err(|e| tr!("could-not-write", path = path.display(), error = e))
+    }
+}
+
+#[cfg(not(test))]
+fn default_path() -> Option<PathBuf> {
+    Some(crate::paths::state_dir()?.join("ranch").join("state.json"))
+}
+
+/// Tests that don't pass '--state' get a file of their own, so that they neither touch the
+/// developer's state nor race each other on a shared one.
+#[cfg(test)]
+fn default_path() -> Option<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("ranch-tests-{}", std::process::id()));
+    Some(dir.join(format!("{:?}.json", std::thread::current().id())))
+}
+
+/// Lowercase hex SHA-256 digest of `data`.
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
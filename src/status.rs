@@ -0,0 +1,157 @@
+use crate::apply;
+use crate::pager::Pager;
+use crate::plan::{self, Action};
+use crate::{load_package, Args};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// How a planned target path compares with what's on disk.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkState {
+    /// The target holds what ranch would put there.
+    Deployed,
+    /// Nothing is at the target path yet.
+    Missing,
+    /// Something else occupies the target path, such as a real file replacing a managed link.
+    Conflict,
+}
+
+impl fmt::Display for LinkState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            LinkState::Deployed => "deployed",
+            LinkState::Missing => "missing",
+            LinkState::Conflict => "conflicting",
+        })
+    }
+}
+
+/// Classifies the target path of `action` without modifying anything. Generated files
+/// (secrets, templates) count as deployed when a regular file is present, since checking their
+/// contents would mean decrypting or rendering them.
+pub fn link_state(args: &Args, action: &Action) -> LinkState {
+    let dst = action.dst();
+    if !(dst.exists() || dst.is_symlink()) {
+        return LinkState::Missing;
+    }
+    let deployed = match action {
+        Action::Link { src, .. } => apply::is_linked(args, src, dst),
+        Action::Decrypt { .. } | Action::Render { .. } => !dst.is_symlink() && dst.is_file(),
+        Action::Mkdir { .. } => dst.is_dir(),
+    };
+    match deployed {
+        true => LinkState::Deployed,
+        false => LinkState::Conflict,
+    }
+}
+
+/// Summary of how far a package's target has drifted from its plan.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PackageStatus {
+    pub package: String,
+    pub deployed: usize,
+    pub missing: Vec<PathBuf>,
+    pub conflicts: Vec<PathBuf>,
+}
+
+impl PackageStatus {
+    pub fn new(args: &Args, package: &str, actions: &[Action]) -> PackageStatus {
+        let mut status = PackageStatus {
+            package: String::from(package),
+            ..Default::default()
+        };
+        for action in actions {
+            match link_state(args, action) {
+                LinkState::Deployed => status.deployed += 1,
+                LinkState::Missing => status.missing.push(action.dst().to_path_buf()),
+                LinkState::Conflict => status.conflicts.push(action.dst().to_path_buf()),
+            }
+        }
+        status
+    }
+}
+
+impl fmt::Display for PackageStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} deployed, {} missing, {} conflicting",
+            self.package,
+            self.deployed,
+            self.missing.len(),
+            self.conflicts.len()
+        )?;
+        for path in &self.missing {
+            writeln!(f, "  missing   {}", path.display())?;
+        }
+        for path in &self.conflicts {
+            writeln!(f, "  conflict  {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Plans `package` and compares the plan with its target.
+pub fn package_status(
+    args: &Args,
+    package: &str,
+    stderr: &mut dyn io::Write,
+) -> Result<PackageStatus, String> {
+    let (prefix_path, _, target_path) = load_package(args, package)?;
+    let actions = plan::plan(args, package, &prefix_path, &target_path, stderr)?;
+    Ok(PackageStatus::new(args, package, &actions))
+}
+
+/// Prints the status of `packages` (or every package in 'DIR') to stdout, through the pager.
+pub fn status(args: &Args, packages: &[String], stderr: &mut dyn io::Write) -> Result<(), String> {
+    let packages = match packages {
+        [] => plan::packages(&args.dir)?,
+        _ => packages.to_vec(),
+    };
+    let mut out = Pager::start(args);
+    for package in &packages {
+        _ = write!(out, "{}", package_status(args, package, stderr)?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_package_status() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let src = tmp_dir.path().join("src");
+        fs::write(&src, "").unwrap();
+        let link = |name: &str| Action::Link {
+            src: src.clone(),
+            dst: tmp_dir.path().join(name),
+        };
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&src, tmp_dir.path().join("deployed")).unwrap();
+        #[cfg(windows)]
+        std::os::windows::fs::symlink_file(&src, tmp_dir.path().join("deployed")).unwrap();
+        fs::write(tmp_dir.path().join("conflict"), "").unwrap();
+
+        let args = Args::parse_from(["ranch", "--link-mode", "symlink", "home"]);
+        let status = PackageStatus::new(
+            &args,
+            "home",
+            &[link("deployed"), link("missing"), link("conflict")],
+        );
+
+        assert_eq!(status.deployed, 1);
+        assert_eq!(status.missing, [tmp_dir.path().join("missing")]);
+        assert_eq!(status.conflicts, [tmp_dir.path().join("conflict")]);
+        assert!(status
+            .to_string()
+            .starts_with("home: 1 deployed, 1 missing, 1 conflicting\n"));
+    }
+}
@@ -0,0 +1,74 @@
+use crate::i18n::tr;
+use crate::{Args, LV_DEBUG, LV_WARN};
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The user and group that ran ranch through 'sudo', from 'SUDO_UID' and 'SUDO_GID'; None
+/// unless running as root that way.
+#[cfg(unix)]
+pub fn invoking_user() -> Option<(u32, u32)> {
+    // SAFETY: geteuid has no preconditions and can't fail.
+    if unsafe { libc::geteuid() } != 0 {
+        return None;
+    }
+    let uid = env::var("SUDO_UID").ok()?.parse().ok()?;
+    let gid = env::var("SUDO_GID").ok()?.parse().ok()?;
+    Some((uid, gid))
+}
+
+#[cfg(not(unix))]
+pub fn invoking_user() -> Option<(u32, u32)> {
+    None
+}
+
+/// Hands `paths` written into 'DIR' back to the user who ran ranch through 'sudo', so that files
+/// adopted into their repo aren't left owned by root. Directories are walked, and only what root
+/// owns is changed, so that a commit's new git objects are fixed without touching the rest.
+pub fn restore_ownership(args: &Args, paths: &[PathBuf], stderr: &mut dyn io::Write) {
+    let Some((uid, gid)) = invoking_user() else {
+        return;
+    };
+    if args.changes_nothing() {
+        return;
+    }
+    for path in paths {
+        for entry in walkdir::WalkDir::new(path).into_iter().flatten() {
+            if let Err(e) = chown(args, entry.path(), uid, gid, stderr) {
+                if args.verbosity("sudo") >= LV_WARN {
+                    let message = tr!(
+                        "could-not-chown",
+                        path = entry.path().display(),
+                        uid = uid,
+                        error = e
+                    );
+                    _ = writeln!(stderr, "{}", tr!("warning", message = message));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn chown(
+    args: &Args,
+    path: &Path,
+    uid: u32,
+    gid: u32,
+    stderr: &mut dyn io::Write,
+) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    if path.symlink_metadata()?.uid() != 0 {
+        return Ok(());
+    }
+    if args.verbosity("sudo") >= LV_DEBUG {
+        _ = writeln!(stderr, "chown {}:{} {}", uid, gid, path.display());
+    }
+    std::os::unix::fs::lchown(path, Some(uid), Some(gid))
+}
+
+#[cfg(not(unix))]
+fn chown(_: &Args, _: &Path, _: u32, _: u32, _: &mut dyn io::Write) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::secrets;
+use std::env;
+use std::path::Path;
+
+/// File extension marking a package file as a template, rendered into a real file at deploy time.
+pub const TEMPLATE_EXTENSION: &str = "tmpl";
+
+/// Renders the template at `src`.
+///
+/// Templates are plain text with `{{ function "argument" ... }}` tags, which are replaced with
+/// the function's result:
+///
+/// - `{{ env "NAME" }}`: the environment variable 'NAME'.
+/// - `{{ pass "github/token" }}`: the first line of a 'pass' entry.
+/// - `{{ secret "key" }}`: the output of the configured 'secret_command' for 'key'.
+/// - `{{ keyring "service" "user" }}`: a password from the OS keyring.
+pub fn render(src: &Path, config: &Config) -> Result<Vec<u8>, String> {
+    let text = std::fs::read_to_string(src)
+        .map_err(|e| tr!("could-not-read", path = src.display(), error = e))?;
+    render_str(&text, config)
+        .map(String::into_bytes)
+        .map_err(|e| format!("{}: {}", src.display(), e))
+}
+
+fn render_str(text: &str, config: &Config) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let line = text[..text.len() - rest.len() + start]
+            .matches('\n')
+            .count()
+            + 1;
+        let tag = &rest[start + 2..];
+        let end = tag.find("}}").ok_or_else(|| {
+            tr!(
+                "template-line",
+                line = line,
+                error = tr!("template-unterminated-tag")
+            )
+        })?;
+        let value =
+            call(&tag[..end], config).map_err(|e| tr!("template-line", line = line, error = e))?;
+        out.push_str(&value);
+        rest = &tag[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Evaluates the inside of a single `{{ ... }}` tag.
+fn call(tag: &str, config: &Config) -> Result<String, String> {
+    let mut words = parse_words(tag)?.into_iter();
+    let function = words.next().ok_or_else(|| tr!("template-empty-tag"))?;
+    let args: Vec<String> = words.collect();
+    let arity = |n: usize| {
+        if args.len() == n {
+            Ok(())
+        } else {
+            Err(tr!(
+                "template-arity",
+                function = function,
+                expected = n,
+                given = args.len()
+            ))
+        }
+    };
+    match function.as_str() {
+        "env" => {
+            arity(1)?;
+            env::var(&args[0]).map_err(|_| tr!("template-env-not-set", variable = &args[0]))
+        }
+        "pass" => {
+            arity(1)?;
+            secrets::pass(&args[0])
+        }
+        "secret" => {
+            arity(1)?;
+            secrets::lookup(config, &args[0])
+        }
+        "keyring" => {
+            arity(2)?;
+            secrets::keyring(&args[0], &args[1])
+        }
+        _ => Err(tr!("template-unknown-function", function = function)),
+    }
+}
+
+/// Splits a tag into a bare function name followed by double-quoted string arguments.
+fn parse_words(tag: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut chars = tag.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut word = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => word.extend(chars.next()),
+                    Some(c) => word.push(c),
+                    None => return Err(tr!("template-unterminated-string")),
+                }
+            }
+            words.push(word);
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            words.push(word);
+        }
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_str() {
+        let config = Config {
+            secret_command: Some(vec![String::from("echo"), String::from("secret:")]),
+            ..Default::default()
+        };
+        let path = env::var("PATH").unwrap_or_default();
+        assert_eq!(
+            render_str(
+                "path = {{ env \"PATH\" }}\ntoken = {{secret \"gh\"}}\n",
+                &config
+            )
+            .unwrap(),
+            format!("path = {}\ntoken = secret: gh\n", path)
+        );
+        assert_eq!(
+            render_str("a\n{{ nope \"x\" }}", &config).unwrap_err(),
+            "line 2: unknown template function 'nope'"
+        );
+        assert!(render_str("{{ env \"A\" \"B\" }}", &config)
+            .unwrap_err()
+            .contains("takes 1 argument(s)"));
+        assert!(render_str("{{ env \"A", &config).is_err());
+        assert!(render_str("{{ keyring \"github\" }}", &config)
+            .unwrap_err()
+            .contains("'keyring' takes 2 argument(s), but 1 were given"));
+    }
+}
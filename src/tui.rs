@@ -0,0 +1,318 @@
+use crate::apply::{self, Summary};
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::plan::{self, Action};
+use crate::status::{self, LinkState};
+use crate::{load_package, Args, ConflictResolution};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Focus {
+    Packages,
+    Files,
+}
+
+/// Browses packages and the status of each of their files, linking, adopting, or unlinking one
+/// file (or, from the package list, a whole package) at a time. Hooks aren't run.
+pub fn tui(args: &Args, packages: &[String]) -> Result<(), String> {
+    let mut app = App::new(args, packages)?;
+    let mut terminal =
+        ratatui::try_init().map_err(|e| tr!("could-not-set-up-terminal", error = e))?;
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+struct App<'a> {
+    args: &'a Args,
+    config: Config,
+    packages: Vec<String>,
+    package: ListState,
+    /// The selected package's plan, with the state of each target path.
+    files: Vec<(Action, LinkState)>,
+    file: ListState,
+    focus: Focus,
+    /// Outcome of the last command, shown in place of the key help.
+    message: String,
+    /// Set while asking whether to overwrite existing files; the next key answers.
+    confirming: bool,
+}
+
+impl<'a> App<'a> {
+    fn new(args: &'a Args, packages: &[String]) -> Result<App<'a>, String> {
+        let packages = match packages {
+            [] => plan::packages(&args.dir)?,
+            _ => packages.to_vec(),
+        };
+        let mut app = App {
+            args,
+            config: Config::load()?,
+            packages,
+            package: ListState::default().with_selected(Some(0)),
+            files: Vec::new(),
+            file: ListState::default(),
+            focus: Focus::Packages,
+            message: String::new(),
+            confirming: false,
+        };
+        app.refresh();
+        Ok(app)
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), String> {
+        loop {
+            terminal
+                .draw(|frame| self.draw(frame))
+                .map_err(|e| tr!("could-not-draw", error = e))?;
+            let event = event::read().map_err(|e| tr!("could-not-read-input", error = e))?;
+            if let Event::Key(key) = event {
+                if key.kind == KeyEventKind::Press && !self.handle(key.code) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Acts on a key press, returning false once the user quits.
+    fn handle(&mut self, code: KeyCode) -> bool {
+        if self.confirming {
+            self.confirming = false;
+            match code {
+                KeyCode::Char('y') => self.link(ConflictResolution::Overwrite),
+                _ => self.message = tr!("tui-cancelled"),
+            }
+            return true;
+        }
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.select(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.select(1),
+            // 'h' isn't bound alongside 'j' and 'k', since 'l' next to it links.
+            KeyCode::Tab | KeyCode::Left | KeyCode::Right => {
+                self.focus = match self.focus {
+                    Focus::Packages if !self.files.is_empty() => Focus::Files,
+                    _ => Focus::Packages,
+                };
+            }
+            KeyCode::Char('l') => self.link(ConflictResolution::Stop),
+            KeyCode::Char('o') => self.overwrite(),
+            KeyCode::Char('a') => self.link(ConflictResolution::Adopt),
+            KeyCode::Char('u') => self.unlink(),
+            KeyCode::Char('r') => {
+                self.message.clear();
+                self.refresh();
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn select(&mut self, delta: isize) {
+        let (state, len) = match self.focus {
+            Focus::Packages => (&mut self.package, self.packages.len()),
+            Focus::Files => (&mut self.file, self.files.len()),
+        };
+        if len == 0 {
+            return;
+        }
+        let i = state.selected().unwrap_or(0) as isize + delta;
+        state.select(Some(i.clamp(0, len as isize - 1) as usize));
+        if self.focus == Focus::Packages {
+            self.file.select(Some(0));
+            self.refresh();
+        }
+    }
+
+    /// Re-plans the selected package and re-reads the state of its files.
+    fn refresh(&mut self) {
+        self.files.clear();
+        let Some(package) = self.package.selected().and_then(|i| self.packages.get(i)) else {
+            return;
+        };
+        // Warnings would scribble over the screen; only errors are shown, in the message line.
+        let mut log = Vec::new();
+        let actions = load_package(self.args, package).and_then(|(prefix_path, _, target_path)| {
+            plan::plan(self.args, package, &prefix_path, &target_path, &mut log)
+        });
+        match actions {
+            Ok(actions) => {
+                self.files = actions
+                    .into_iter()
+                    .map(|a| {
+                        let state = status::link_state(self.args, &a);
+                        (a, state)
+                    })
+                    .collect();
+            }
+            Err(e) => self.message = e,
+        }
+        let last = self.files.len().saturating_sub(1);
+        self.file
+            .select(Some(self.file.selected().unwrap_or(0).min(last)));
+        if self.files.is_empty() {
+            self.focus = Focus::Packages;
+        }
+    }
+
+    /// The selected file's action, or every action of the selected package.
+    fn targets(&self) -> Vec<Action> {
+        match self.focus {
+            Focus::Packages => self.files.iter().map(|(a, _)| a.clone()).collect(),
+            Focus::Files => self
+                .file
+                .selected()
+                .and_then(|i| self.files.get(i))
+                .map(|(a, _)| a.clone())
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    fn link(&mut self, exists: ConflictResolution) {
+        let mut args = self.args.clone();
+        args.exists = exists;
+        // Pressing the key was the confirmation.
+        args.yes = true;
+        let mut summary = Summary::default();
+        let result = apply::apply(
+            &args,
+            &self.config,
+            &self.targets(),
+            &mut summary,
+            &mut Vec::new(),
+        );
+        self.finish(result, &summary);
+    }
+
+    /// Links the targets, replacing what's in their way once the user confirms it.
+    fn overwrite(&mut self) {
+        let count = self
+            .targets()
+            .iter()
+            .filter(|a| status::link_state(self.args, a) == LinkState::Conflict)
+            .count();
+        match count {
+            0 => self.link(ConflictResolution::Overwrite),
+            _ => {
+                self.confirming = true;
+                self.message = tr!("tui-confirm-overwrite", count = count);
+            }
+        }
+    }
+
+    fn unlink(&mut self) {
+        let mut summary = Summary::default();
+        let result = apply::unapply(self.args, &self.targets(), &mut summary, &mut Vec::new());
+        self.finish(result, &summary);
+    }
+
+    fn finish(&mut self, result: Result<(), String>, summary: &Summary) {
+        self.message = match result {
+            Ok(()) if self.args.dry_run => tr!("tui-nothing-changed"),
+            Ok(()) => tr!(
+                "tui-summary",
+                created = summary.created.len(),
+                removed = summary.removed.len(),
+                adopted = summary.adopted.len()
+            ),
+            Err(e) => e,
+        };
+        self.refresh();
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(25), Constraint::Percentage(75)])
+                .areas(main);
+        let block = |title: &str, focus: Focus| {
+            let style = match self.focus == focus {
+                true => Style::new().fg(Color::Cyan),
+                false => Style::new(),
+            };
+            Block::bordered()
+                .title(title.to_owned())
+                .border_style(style)
+        };
+        let highlight = Style::new().add_modifier(Modifier::REVERSED);
+
+        let packages = List::new(self.packages.iter().map(String::as_str))
+            .block(block(" Packages ", Focus::Packages))
+            .highlight_style(highlight);
+        frame.render_stateful_widget(packages, left, &mut self.package);
+
+        let files = List::new(self.files.iter().map(|(action, state)| {
+            let (label, color) = match state {
+                LinkState::Deployed => ("deployed", Color::Green),
+                LinkState::Missing => ("missing ", Color::Yellow),
+                LinkState::Conflict => ("conflict", Color::Red),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(label, Style::new().fg(color)),
+                Span::raw(format!("  {}", action.dst().display())),
+            ]))
+        }))
+        .block(block(" Files ", Focus::Files))
+        .highlight_style(highlight);
+        frame.render_stateful_widget(files, right, &mut self.file);
+
+        let text = match self.message.is_empty() {
+            true => tr!("tui-help"),
+            false => self.message.clone(),
+        };
+        frame.render_widget(Paragraph::new(text), footer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_handle() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        fs::create_dir_all(dir.join("home")).unwrap();
+        fs::write(dir.join("home/.vimrc"), "").unwrap();
+        fs::write(dir.join("home/.zshrc"), "repo").unwrap();
+        fs::write(tmp_dir.path().join(".zshrc"), "local").unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "tui"]);
+        let mut app = App::new(&args, &[]).unwrap();
+        assert_eq!(app.packages, ["home"]);
+        assert_eq!(app.files.len(), 2);
+
+        app.handle(KeyCode::Tab);
+        app.handle(KeyCode::Char('l'));
+        assert!(tmp_dir.path().join(".vimrc").is_symlink());
+        assert_eq!(app.files[0].1, LinkState::Deployed);
+
+        app.handle(KeyCode::Down);
+        app.handle(KeyCode::Char('l'));
+        assert!(app.message.contains("already exists"));
+        app.handle(KeyCode::Char('o'));
+        app.handle(KeyCode::Char('l'));
+        assert!(!tmp_dir.path().join(".zshrc").is_symlink());
+        assert_eq!(app.message, "Cancelled");
+        app.handle(KeyCode::Char('a'));
+        assert!(tmp_dir.path().join(".zshrc").is_symlink());
+        assert_eq!(
+            fs::read_to_string(dir.join("home/.zshrc")).unwrap(),
+            "local"
+        );
+
+        app.handle(KeyCode::Tab);
+        app.handle(KeyCode::Char('u'));
+        assert!(!tmp_dir.path().join(".vimrc").exists());
+        assert!(!tmp_dir.path().join(".zshrc").exists());
+        assert!(!app.handle(KeyCode::Char('q')));
+    }
+}
@@ -0,0 +1,48 @@
+use crate::i18n::tr;
+use crate::paths;
+use crate::{Args, LV_DEBUG, LV_INFO};
+use std::io;
+use std::path::Path;
+
+/// Deploys (or deletes) the packages for every user in '--for-users', in place of the current
+/// user: targets resolve against each user's home directory, and what ranch creates there is
+/// owned by that user. Only root may do this.
+pub fn for_users(args: &Args, stderr: &mut dyn io::Write) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        // SAFETY: geteuid has no preconditions and can't fail.
+        if unsafe { libc::geteuid() } != 0 {
+            return Err(tr!("for-users-needs-root"));
+        }
+        for user in &args.for_users {
+            let home = paths::user_home(user).ok_or_else(|| tr!("no-such-user", user = user))?;
+            let owner = paths::user_ids(user).ok_or_else(|| tr!("no-such-user", user = user))?;
+            if args.verbosity("users") >= LV_INFO {
+                _ = writeln!(stderr, "For {} in {}:", user, home.display());
+            }
+            let mut user_args = args.clone();
+            user_args.for_users.clear();
+            user_args.default_target = Some(home);
+            user_args.owner = Some(owner);
+            crate::run(&user_args, stderr).map_err(|e| tr!("for-user", user = user, error = e))?;
+        }
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        _ = (args, stderr);
+        Err(tr!("for-users-unix-only"))
+    }
+}
+
+/// Gives `path`, just created by ranch, to the user it's being deployed for with
+/// '--for-users'; links themselves are changed, not what they point to.
+pub fn chown(args: &Args, path: &Path, stderr: &mut dyn io::Write) -> io::Result<()> {
+    let Some((uid, gid)) = args.owner else {
+        return Ok(());
+    };
+    if args.verbosity("users") >= LV_DEBUG {
+        _ = writeln!(stderr, "chown {}:{} {}", uid, gid, path.display());
+    }
+    args.fs.chown(path, uid, gid)
+}
@@ -0,0 +1,191 @@
+use crate::apply::{self, Summary};
+use crate::config::Config;
+use crate::git;
+use crate::i18n::tr;
+use crate::notification::Notifier;
+use crate::paths;
+use crate::plan::{self, Action};
+use crate::{load_package, stow, Args, LV_INFO, LV_WARN};
+use notify::{RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use walkdir::WalkDir;
+
+/// How long to wait for a burst of changes (an editor's save, a 'git checkout') to settle.
+pub const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// A package whose changes are mirrored into its target.
+pub struct Watched {
+    pub package: String,
+    pub prefix_path: PathBuf,
+    pub target_path: PathBuf,
+}
+
+impl Watched {
+    pub fn new(args: &Args, package: &str) -> Result<Watched, String> {
+        let (prefix_path, _, target_path) = load_package(args, package)?;
+        Ok(Watched {
+            package: String::from(package),
+            prefix_path,
+            target_path,
+        })
+    }
+}
+
+/// Deploys `packages`, then keeps their targets in sync with the repo until interrupted: files
+/// added to a package are linked, and links to removed files are deleted.
+pub fn watch(args: &Args, packages: &[String], stderr: &mut dyn io::Write) -> Result<(), String> {
+    let config = Config::load()?;
+    let notifier = Notifier::new(args, &config);
+    let mut watched = Vec::new();
+    for package in packages {
+        stow(args, package, stderr)?;
+        watched.push(Watched::new(args, package)?);
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).map_err(|e| tr!("could-not-start-watching", error = e))?;
+    for w in &watched {
+        watcher
+            .watch(&w.prefix_path, RecursiveMode::Recursive)
+            .map_err(|e| tr!("could-not-watch", path = w.prefix_path.display(), error = e))?;
+        if args.verbosity("watch") >= LV_INFO {
+            _ = writeln!(stderr, "Watching {}", w.prefix_path.display());
+        }
+    }
+
+    loop {
+        let mut paths = BTreeSet::new();
+        let event = rx.recv().map_err(|_| tr!("notifications-stopped"))?;
+        collect(args, event, &mut paths, stderr);
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            collect(args, event, &mut paths, stderr);
+        }
+        for path in paths {
+            let Some(w) = watched.iter().find(|w| path.starts_with(&w.prefix_path)) else {
+                continue;
+            };
+            // One bad file shouldn't end the session; report it and keep watching.
+            if let Err(e) = sync_path(args, &config, w, &path, stderr) {
+                _ = writeln!(stderr, "{}", tr!("error", error = e));
+                notifier.failed(&e, stderr);
+            }
+        }
+    }
+}
+
+/// Adds the paths touched by a filesystem notification to `paths`.
+pub fn collect(
+    args: &Args,
+    event: notify::Result<notify::Event>,
+    paths: &mut BTreeSet<PathBuf>,
+    stderr: &mut dyn io::Write,
+) {
+    match event {
+        Ok(event) if !event.kind.is_access() => paths.extend(event.paths),
+        Ok(_) => {}
+        Err(e) => {
+            if args.verbosity("watch") >= LV_WARN {
+                _ = writeln!(stderr, "{}", tr!("notification-error", error = e));
+            }
+        }
+    }
+}
+
+/// Brings the target in line with the current state of `path`, a file or directory within the
+/// watched package that was just created, modified, or removed.
+pub fn sync_path(
+    args: &Args,
+    config: &Config,
+    w: &Watched,
+    path: &Path,
+    stderr: &mut dyn io::Write,
+) -> Result<(), String> {
+    if path.is_dir() && !path.is_symlink() {
+        // A directory copied or moved into the package; deploy everything in it.
+        let actions: Vec<Action> = WalkDir::new(path)
+            .into_iter()
+            .filter_map(|r| r.ok())
+            .filter(|e| !e.file_type().is_dir())
+            .filter(|e| !args.vcs_only || git::is_tracked(e.path()))
+            .filter_map(|e| {
+                plan::plan_path(args, &w.package, &w.target_path, e.into_path(), stderr)
+            })
+            .collect();
+        return apply::apply(args, config, &actions, &mut Summary::default(), stderr);
+    }
+    let Some(action) =
+        plan::plan_path(args, &w.package, &w.target_path, path.to_path_buf(), stderr)
+    else {
+        return Ok(());
+    };
+    if path.exists() || path.is_symlink() {
+        if args.vcs_only && !git::is_tracked(path) {
+            return Ok(());
+        }
+        return apply::apply(args, config, &[action], &mut Summary::default(), stderr);
+    }
+
+    // The path is gone. If it was a directory, its target counterpart may hold many links into
+    // it; otherwise there's at most the one link to remove.
+    let dst = action.dst();
+    if dst.is_dir() && !dst.is_symlink() {
+        let links: Vec<Action> = WalkDir::new(dst)
+            .into_iter()
+            .filter_map(|r| r.ok())
+            .filter(|e| e.path_is_symlink())
+            .filter_map(|e| {
+                let src = paths::resolve_link(e.path())?;
+                src.starts_with(path).then(|| Action::Link {
+                    src,
+                    dst: e.into_path(),
+                })
+            })
+            .collect();
+        return apply::unapply(args, &links, &mut Summary::default(), stderr);
+    }
+    apply::unapply(args, &[action], &mut Summary::default(), stderr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs::{create_dir_all, remove_dir_all, remove_file, write};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_sync_path() {
+        let tmp_dir = TempDir::new("alice").unwrap();
+        let dir = tmp_dir.path().join(".dotfiles");
+        create_dir_all(dir.join("home")).unwrap();
+        let args = Args::parse_from(["ranch", "-C", dir.to_str().unwrap(), "home"]);
+        let w = Watched::new(&args, "home").unwrap();
+        let config = Config::default();
+        let sync = |path: &Path| sync_path(&args, &config, &w, path, &mut io::sink()).unwrap();
+
+        write(dir.join("home/.vimrc"), "").unwrap();
+        sync(&dir.join("home/.vimrc"));
+        assert!(tmp_dir.path().join(".vimrc").is_symlink());
+
+        remove_file(dir.join("home/.vimrc")).unwrap();
+        sync(&dir.join("home/.vimrc"));
+        assert!(!tmp_dir.path().join(".vimrc").is_symlink());
+
+        create_dir_all(dir.join("home/.config/foo")).unwrap();
+        write(dir.join("home/.config/foo/a"), "").unwrap();
+        write(dir.join("home/.config/foo/b"), "").unwrap();
+        sync(&dir.join("home/.config"));
+        assert!(tmp_dir.path().join(".config/foo/a").is_symlink());
+        assert!(tmp_dir.path().join(".config/foo/b").is_symlink());
+
+        remove_dir_all(dir.join("home/.config/foo")).unwrap();
+        sync(&dir.join("home/.config/foo"));
+        assert!(!tmp_dir.path().join(".config/foo/a").is_symlink());
+        assert!(!tmp_dir.path().join(".config/foo/b").is_symlink());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::i18n::tr;
+use crate::{Args, LinkMode};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// Whether ranch is running under the Windows Subsystem for Linux; checked once per process.
+pub fn is_wsl() -> bool {
+    static WSL: OnceLock<bool> = OnceLock::new();
+    *WSL.get_or_init(|| {
+        env::var_os("WSL_DISTRO_NAME").is_some_and(|d| !d.is_empty())
+            || fs::read_to_string("/proc/sys/kernel/osrelease")
+                .is_ok_and(|r| r.to_lowercase().contains("microsoft"))
+    })
+}
+
+/// Whether `path` is on a Windows drive mounted into WSL, such as '/mnt/c/Users/alice'. Windows
+/// programs don't follow the Linux symlinks WSL creates there.
+pub fn is_windows_drive(path: &Path) -> bool {
+    let mut components = path.components();
+    let is_drive = |drive: &std::ffi::OsStr| {
+        drive.len() == 1 && drive.as_encoded_bytes()[0].is_ascii_alphabetic()
+    };
+    matches!(
+        (components.next(), components.next(), components.next()),
+        (Some(Component::RootDir), Some(Component::Normal(mnt)), Some(Component::Normal(drive)))
+            if mnt == "mnt" && is_drive(drive)
+    )
+}
+
+/// How the package file for `dst` is deployed: '--link-mode', where 'auto' picks Windows links
+/// for targets on a Windows drive under WSL if 'cmd.exe' is there to make them, else copies,
+/// and ordinary symlinks everywhere else.
+pub fn link_mode(args: &Args, dst: &Path) -> LinkMode {
+    match args.link_mode {
+        LinkMode::Auto if is_wsl() && is_windows_drive(dst) => match has_cmd() {
+            true => LinkMode::Windows,
+            false => LinkMode::Copy,
+        },
+        LinkMode::Auto => LinkMode::Symlink,
+        ref mode => mode.clone(),
+    }
+}
+
+/// Whether 'cmd.exe' can be run; checked once per process, since every link asks.
+fn has_cmd() -> bool {
+    static CMD: OnceLock<bool> = OnceLock::new();
+    *CMD.get_or_init(|| {
+        Command::new("cmd.exe")
+            .args(["/c", "exit"])
+            .output()
+            .is_ok_and(|o| o.status.success())
+    })
+}
+
+/// Creates a Windows symbolic link at `link` pointing to `target` with 'mklink', converting both
+/// paths with 'wslpath'. Windows only allows this with Developer Mode enabled, or as an
+/// administrator.
+pub fn mklink(target: &Path, link: &Path) -> io::Result<()> {
+    let output = Command::new("cmd.exe")
+        .args(["/c", "mklink"])
+        .arg(windows_path(target)?)
+        .arg(windows_path(link)?)
+        .output()?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(io::Error::other(tr!(
+            "mklink-failed",
+            output = String::from_utf8_lossy(&output.stdout).trim()
+        ))),
+    }
+}
+
+/// The Windows form of the WSL path `path`, from 'wslpath -w'. The link may not exist yet, so
+/// its directory is converted and its name appended.
+fn windows_path(path: &Path) -> io::Result<PathBuf> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(io::Error::other(tr!("invalid-path", path = path.display())));
+    };
+    let output = Command::new("wslpath").arg("-w").arg(dir).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(tr!(
+            "program-failed",
+            program = "wslpath",
+            output = String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let dir = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    Ok(PathBuf::from(format!(
+        "{}\\{}",
+        dir,
+        name.to_string_lossy()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_windows_drive() {
+        assert!(is_windows_drive(Path::new("/mnt/c/Users/alice/.gitconfig")));
+        assert!(is_windows_drive(Path::new("/mnt/D")));
+        assert!(!is_windows_drive(Path::new("/mnt/data/alice")));
+        assert!(!is_windows_drive(Path::new("/home/alice/mnt/c")));
+    }
+}